@@ -0,0 +1,170 @@
+//! Compares the insert and verify timings, and proof sizes, of `Trie` against results recorded
+//! from the TypeScript implementation of the Merkle Patricia Forestry, published by
+//! aiken-lang/merkle-patricia-forestry.
+//!
+//! The comparison only runs when `MUTREE_REFERENCE_RESULTS` points to the recorded results, and
+//! writes its report to `MUTREE_DIFFERENTIAL_REPORT`, or `target/differential.md` by default.
+//! The results are a JSON object naming the implementation, and one entry per dataset:
+//!
+//! ```json
+//! {
+//!   "implementation": "@aiken-lang/merkle-patricia-forestry 1.2.0",
+//!   "results": [
+//!     { "entries": 1000, "insert_ms": 512.0, "verify_ms": 24.0, "proof_bytes": 1024 }
+//!   ]
+//! }
+//! ```
+//!
+//! The dataset of `n` entries maps the decimal representation of each index below `n` to
+//! `value-` followed by the same index, inserted in increasing order and hashed with
+//! Blake2b-256, as done by the Forestry. `insert_ms` is the time taken to insert the whole
+//! dataset in an empty trie, `verify_ms` the time taken to verify the first
+//! [`VERIFIED_ENTRIES`] entries, and `proof_bytes` the size of the proof checked by a verifier
+//! of the first entry. A proof of the Forestry only covers the path of its key, while
+//! `Trie::verify` checks the whole proof of the Trie, so the sizes are expected to diverge with
+//! the number of entries.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use blake2::{digest::consts::U32, Blake2b};
+use mutree::prelude::*;
+use serde_json::Value;
+
+type Blake2b256 = Blake2b<U32>;
+
+/// The number of entries verified for each dataset, since `Trie::verify` scans the whole proof.
+const VERIFIED_ENTRIES: usize = 100;
+
+/// The timings and proof size of an implementation on a dataset.
+#[derive(Debug)]
+struct Measurement {
+    entries: usize,
+    insert: Duration,
+    verify: Duration,
+    proof_bytes: usize,
+}
+
+impl Measurement {
+    /// Reads a recorded result, failing with the name of the missing field.
+    fn from_json(result: &Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            result
+                .get(name)
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("recorded result is missing `{name}`: {result}"))
+        };
+
+        Ok(Self {
+            entries: field("entries")? as usize,
+            insert: Duration::from_secs_f64(field("insert_ms")? / 1000.0),
+            verify: Duration::from_secs_f64(field("verify_ms")? / 1000.0),
+            proof_bytes: field("proof_bytes")? as usize,
+        })
+    }
+
+    /// Inserts and verifies the dataset of `entries` entries with `Trie`.
+    fn measure(entries: usize) -> Self {
+        let mut trie = Trie::<Blake2b256>::empty();
+
+        let start = Instant::now();
+        for i in 0..entries {
+            trie.insert(i.to_string().as_bytes(), format!("value-{i}").as_bytes())
+                .unwrap();
+        }
+        let insert = start.elapsed();
+
+        let start = Instant::now();
+        for i in 0..entries.min(VERIFIED_ENTRIES) {
+            assert!(trie.verify(i.to_string().as_bytes(), format!("value-{i}").as_bytes()));
+        }
+        let verify = start.elapsed();
+
+        Self {
+            entries,
+            insert,
+            verify,
+            proof_bytes: trie.proof().to_bytes().len(),
+        }
+    }
+}
+
+/// The ratio of our measurement to the reference, below 1 when we are faster or smaller.
+fn ratio(ours: f64, reference: f64) -> String {
+    if reference > 0.0 {
+        format!("{:.2}x", ours / reference)
+    } else {
+        "-".to_string()
+    }
+}
+
+fn main() {
+    let Some(path) = env::var_os("MUTREE_REFERENCE_RESULTS") else {
+        println!("MUTREE_REFERENCE_RESULTS is not set, skipping the differential benchmark");
+        return;
+    };
+
+    let recorded: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    let implementation = recorded
+        .get("implementation")
+        .and_then(Value::as_str)
+        .unwrap_or("the reference implementation");
+    let references = recorded
+        .get("results")
+        .and_then(Value::as_array)
+        .expect("recorded results must have a `results` array")
+        .iter()
+        .map(Measurement::from_json)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut report = String::new();
+    writeln!(report, "# Mutree against {implementation}\n").unwrap();
+    writeln!(
+        report,
+        "| entries | insert (ms) | reference | ratio | verify (ms) | reference | ratio | proof (bytes) | reference | ratio |"
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "|---:|---:|---:|---:|---:|---:|---:|---:|---:|---:|"
+    )
+    .unwrap();
+
+    for reference in &references {
+        let ours = Measurement::measure(reference.entries);
+        let (insert, reference_insert) = (
+            ours.insert.as_secs_f64() * 1000.0,
+            reference.insert.as_secs_f64() * 1000.0,
+        );
+        let (verify, reference_verify) = (
+            ours.verify.as_secs_f64() * 1000.0,
+            reference.verify.as_secs_f64() * 1000.0,
+        );
+
+        writeln!(
+            report,
+            "| {} | {insert:.1} | {reference_insert:.1} | {} | {verify:.1} | {reference_verify:.1} | {} | {} | {} | {} |",
+            ours.entries,
+            ratio(insert, reference_insert),
+            ratio(verify, reference_verify),
+            ours.proof_bytes,
+            reference.proof_bytes,
+            ratio(ours.proof_bytes as f64, reference.proof_bytes as f64),
+        )
+        .unwrap();
+    }
+
+    let output = env::var_os("MUTREE_DIFFERENTIAL_REPORT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target/differential.md"));
+    fs::write(&output, &report).unwrap();
+
+    print!("{report}");
+    println!("\nreport written to {}", output.display());
+}
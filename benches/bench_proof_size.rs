@@ -0,0 +1,63 @@
+//! Records the size of the proofs of tries with 10² to 10⁴ entries, inserted one by one, and
+//! checks them against the bounds published in the documentation of `Trie`, failing if the
+//! encoding grows past them.
+//!
+//! The published bounds are ~130 bytes per branch step and ~130 · log₁₆(n) bytes per key.
+//! `Trie::verify` checks keys against the whole proof of the Trie, so the bytes it takes per key
+//! are checked against the bound per key, and its total against the size of its steps. Every
+//! insert calculates the root again, so larger tries take too long to build this way.
+
+use blake2::Blake2s256;
+use mutree::prelude::*;
+
+/// The encoded size of an untimestamped leaf step: its tag, skip, and key and value hashes.
+const LEAF_STEP_LEN: usize = 1 + 8 + 2 * 32;
+
+/// The bytes per branch step published in the documentation of `Trie`, with the tag and skip of
+/// the step on top of its hashes.
+const DOCUMENTED_BRANCH_STEP_LEN: usize = 130 + 1 + 8;
+
+fn main() {
+    let branch = Step::Branch {
+        skip: 0,
+        neighbors: [Hash::zero(); 4],
+    };
+    let branch_len = branch.to_bytes().len();
+    assert!(
+        branch_len <= DOCUMENTED_BRANCH_STEP_LEN,
+        "branch steps take {branch_len} bytes, more than the documented ~130 bytes of hashes"
+    );
+
+    println!(
+        "{:>9} {:>14} {:>16} {:>18}",
+        "entries", "proof size", "bytes per key", "documented bound"
+    );
+
+    let (mut trie, mut inserted) = (Trie::<Blake2s256>::empty(), 0);
+    for exponent in 2..=4 {
+        let len = 10usize.pow(exponent);
+        for i in inserted..len as u64 {
+            trie.insert(&i.to_be_bytes(), &b"value"[..])
+                .expect("keys are never empty");
+        }
+        inserted = len as u64;
+
+        let size = trie.proof().to_bytes().len();
+        let per_key = size as f64 / len as f64;
+        let documented = 130.0 * (len as f64).log(16.0);
+        println!("{len:>9} {size:>14} {per_key:>16.1} {documented:>18.1}");
+
+        assert!(
+            per_key <= documented,
+            "the proof of {len} entries takes {per_key:.1} bytes per key, more than the \
+             documented {documented:.1} bytes"
+        );
+        // A step count, then each step prefixed by its length
+        let bound = 4 + len * (4 + LEAF_STEP_LEN);
+        assert!(
+            size <= bound,
+            "the proof of {len} entries takes {size} bytes, more than the {bound} bytes of its steps"
+        );
+        assert!(trie.verify(&0u64.to_be_bytes(), b"value"));
+    }
+}
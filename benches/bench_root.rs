@@ -0,0 +1,97 @@
+#![allow(unused)]
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mutree::{prelude::*, TrieHasher};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+/// A proof with as many branch steps as leaves, like the ones of merged replicas, skipping at
+/// most [`MAX_SKIP`] nibbles so it would also decode.
+fn proof(len: usize) -> Proof {
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let mut hash = || Hash::from_array(rng.gen());
+
+    Proof::from(
+        (0..len)
+            .map(|i| match i % 2 {
+                0 => Step::Branch {
+                    skip: i % (MAX_SKIP + 1),
+                    neighbors: [hash(), Hash::zero(), hash(), Hash::zero()],
+                },
+                _ => Step::Leaf {
+                    skip: i % (MAX_SKIP + 1),
+                    key: hash(),
+                    value: hash(),
+                    timestamp: None,
+                },
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn bench_root<D: TrieHasher + 'static>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group(format!("root/{}", name));
+
+    for len in [1_000, 10_000, 100_000] {
+        let proof = proof(len);
+        assert!(Proof::from_bytes(&proof.to_bytes()).is_ok());
+
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new("from_proof", len), &proof, |b, proof| {
+            b.iter(|| black_box(Trie::<D>::from_proof(proof.clone())).root());
+        });
+    }
+
+    group.finish();
+}
+
+/// Filtering the empty neighbors of branch steps, as done for every step of a root.
+fn bench_is_zero(c: &mut Criterion) {
+    let mut group = c.benchmark_group("root/zero_neighbors");
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+    let neighbors: Vec<Hash> = (0..4096)
+        .map(|_| match rng.gen_bool(0.5) {
+            true => Hash::zero(),
+            false => Hash::from_array(rng.gen()),
+        })
+        .collect();
+
+    group.throughput(Throughput::Elements(neighbors.len() as u64));
+    group.bench_function("eq_zero", |b| {
+        b.iter(|| {
+            black_box(&neighbors)
+                .iter()
+                .filter(|&&n| n != Hash::zero())
+                .count()
+        });
+    });
+    group.bench_function("is_zero", |b| {
+        b.iter(|| {
+            black_box(&neighbors)
+                .iter()
+                .filter(|n| !n.is_zero())
+                .count()
+        });
+    });
+
+    group.finish();
+}
+
+fn root_benchmark(c: &mut Criterion) {
+    bench_root::<blake2::Blake2s256>(c, "blake2s");
+
+    #[cfg(feature = "blake3")]
+    bench_root::<blake3::Hasher>(c, "blake3");
+
+    #[cfg(feature = "sha2")]
+    bench_root::<sha2::Sha256>(c, "sha256");
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(5));
+    targets = root_benchmark, bench_is_zero
+);
+
+criterion_main!(benches);
@@ -8,26 +8,26 @@ use criterion::{
     measurement::{Measurement, WallTime},
     BenchmarkId,
     Criterion,
+    Throughput,
 };
 use criterion_cycles_per_byte::CyclesPerByte;
-use digest::Digest;
-use mutree::prelude::*;
+use mutree::{prelude::*, TrieHasher};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 
-struct BenchData<D: Digest + 'static> {
+struct BenchData<D: TrieHasher + 'static> {
     trie: Trie<D>,
     insert_key: Vec<u8>,
     insert_value: Vec<u8>,
     rng: ChaCha8Rng,
 }
 
-impl<D: Digest + 'static> BenchData<D> {
+impl<D: TrieHasher + 'static> BenchData<D> {
     fn new(size: usize) -> Self {
         let mut rng = ChaCha8Rng::seed_from_u64(42);
         let mut trie = Trie::<D>::empty();
 
-        // Pre-populate the Forestry
+        // Pre-populate the Trie
         for _ in 0..size {
             let key_len = rng.gen_range(1..100);
             let value_len = rng.gen_range(100..10000);
@@ -51,7 +51,7 @@ impl<D: Digest + 'static> BenchData<D> {
     }
 }
 
-fn bench_insert<D: Digest + 'static, T: Measurement>(c: &mut Criterion<T>, name: &str) {
+fn bench_insert<D: TrieHasher + 'static, T: Measurement>(c: &mut Criterion<T>, name: &str) {
     let type_name = type_name::<T>().split(":").take(1).collect::<Vec<_>>()[0];
     let mut group = c.benchmark_group(format!("trie/{}/{}", name, type_name));
 
@@ -64,6 +64,36 @@ fn bench_insert<D: Digest + 'static, T: Measurement>(c: &mut Criterion<T>, name:
                 black_box(trie.insert(&data.insert_key, &*data.insert_value)).unwrap();
             });
         });
+
+        // Overwriting the same key keeps the size of the trie, so this measures the insertion
+        // alone, without cloning the trie on every iteration.
+        let mut trie = bench_data.trie.clone();
+        group.bench_function(BenchmarkId::new("insert_in_place", size), |b| {
+            b.iter(|| {
+                black_box(trie.insert(&bench_data.insert_key, &*bench_data.insert_value)).unwrap();
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_large_value<D: TrieHasher + 'static, T: Measurement>(c: &mut Criterion<T>, name: &str) {
+    let type_name = type_name::<T>().split(":").take(1).collect::<Vec<_>>()[0];
+    let mut group = c.benchmark_group(format!("trie/{}/{}", name, type_name));
+    let mut rng = ChaCha8Rng::seed_from_u64(42);
+
+    // Multi-megabyte values, where BLAKE3 hashes in parallel with the `rayon` feature
+    for size in [1 << 20, 16 << 20, 64 << 20].iter() {
+        let value: Vec<u8> = (0..*size).map(|_| rng.gen()).collect();
+
+        group.throughput(Throughput::Bytes(*size as u64));
+        group.bench_with_input(BenchmarkId::new("large_value", size), &value, |b, value| {
+            b.iter(|| {
+                let mut trie = Trie::<D>::empty();
+                black_box(trie.insert(b"key", &value[..])).unwrap();
+            });
+        });
     }
 
     group.finish();
@@ -71,16 +101,17 @@ fn bench_insert<D: Digest + 'static, T: Measurement>(c: &mut Criterion<T>, name:
 
 fn trie_benchmark<T: Measurement>(c: &mut Criterion<T>) {
     // Blake2s-256
-    #[cfg(feature = "blake2")]
     bench_insert::<blake2::Blake2s256, T>(c, "blake2s");
+    bench_large_value::<blake2::Blake2s256, T>(c, "blake2s");
 
     // Blake2b-256
-    #[cfg(feature = "blake2")]
     bench_insert::<blake2::Blake2b<digest::consts::U32>, T>(c, "blake2b");
 
     // Blake3
     #[cfg(feature = "blake3")]
     bench_insert::<blake3::Hasher, T>(c, "blake3");
+    #[cfg(feature = "blake3")]
+    bench_large_value::<blake3::Hasher, T>(c, "blake3");
 
     // SHA2
     #[cfg(feature = "sha2")]
@@ -89,6 +120,14 @@ fn trie_benchmark<T: Measurement>(c: &mut Criterion<T>) {
     // SHA3
     #[cfg(feature = "sha3")]
     bench_insert::<sha3::Sha3_256, T>(c, "sha3_256");
+
+    // Keccak-256
+    #[cfg(feature = "keccak")]
+    bench_insert::<sha3::Keccak256, T>(c, "keccak256");
+
+    // RIPEMD-160
+    #[cfg(feature = "ripemd")]
+    bench_insert::<ripemd::Ripemd160, T>(c, "ripemd160");
 }
 
 fn cycles_per_byte_bench(c: &mut Criterion<CyclesPerByte>) {
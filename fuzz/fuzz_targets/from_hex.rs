@@ -0,0 +1,19 @@
+//! Parses hex strings as hashes, steps and proofs, checking that the values accepted encode back
+//! to the same bytes.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mutree::prelude::*;
+
+fuzz_target!(|hex: &str| {
+    if let Ok(hash) = Hash::from_hex(hex) {
+        assert_eq!(Hash::from_hex(&hash.to_hex()).unwrap(), hash);
+    }
+    if let Ok(step) = Step::from_hex(hex) {
+        assert_eq!(Step::from_hex(&step.to_hex()).unwrap(), step);
+    }
+    if let Ok(proof) = Proof::from_hex(hex) {
+        assert!(proof.to_hex().eq_ignore_ascii_case(hex));
+    }
+});
@@ -0,0 +1,15 @@
+//! Decodes neighbors, checking that the ones accepted encode back to an equal neighbor.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mutree::prelude::*;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(neighbor) = Neighbor::from_bytes(data) {
+        assert_eq!(
+            Neighbor::from_bytes(&neighbor.to_bytes()).unwrap(),
+            neighbor
+        );
+    }
+});
@@ -0,0 +1,20 @@
+//! Decodes proofs, checking that the ones accepted are exactly their encoding, and that tries
+//! can be built from them.
+
+#![no_main]
+
+use blake2::Blake2s256;
+use libfuzzer_sys::fuzz_target;
+use mutree::prelude::*;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(proof) = Proof::from_bytes(data) {
+        assert_eq!(Proof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+
+        let mut normalized = proof.clone();
+        normalized.normalize();
+        assert!(normalized.len() <= proof.len());
+
+        Trie::<Blake2s256>::from_proof(proof);
+    }
+});
@@ -0,0 +1,16 @@
+//! Calculates roots while reading proofs, checking that they match the roots of the tries built
+//! from the same proofs when both accept them.
+
+#![no_main]
+
+use blake2::Blake2s256;
+use libfuzzer_sys::fuzz_target;
+use mutree::prelude::*;
+
+fuzz_target!(|data: &[u8]| {
+    let streamed = calculate_root_from_reader::<Blake2s256, _>(data);
+
+    if let (Ok(root), Ok(proof)) = (streamed, Proof::from_bytes(data)) {
+        assert_eq!(root, Trie::<Blake2s256>::from_proof(proof).root());
+    }
+});
@@ -0,0 +1,13 @@
+//! Decodes steps, checking that the ones accepted are valid and encode back to an equal step.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mutree::prelude::*;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(step) = Step::from_bytes(data) {
+        assert!(step.validate().is_ok());
+        assert_eq!(Step::from_bytes(&step.to_bytes()).unwrap(), step);
+    }
+});
@@ -0,0 +1,101 @@
+//! Derive macros for the CRDT traits of [mutree](https://github.com/mugraph-payments/mutree),
+//! re-exported by it with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Index, LitStr, Path};
+
+/// Derives `CvRDT` for a struct whose fields are all CvRDTs, merging each field with the same
+/// field of the other state.
+///
+/// A product of CvRDTs is a CvRDT, so the merge is commutative, associative and idempotent
+/// whenever the merges of the fields are. Fields are merged into a copy of the state, in
+/// declaration order, so a failing merge leaves the state unchanged. Type parameters are
+/// required to implement `CvRDT` themselves.
+///
+/// Like the trait, the struct must also implement `Default`, `Clone` and `PartialEq`.
+///
+/// The generated code refers to the trait as `::mutree::CvRDT`. Crates using mutree under
+/// another name set its path with `#[cvrdt(crate = "path::to::mutree")]`.
+#[proc_macro_derive(CvRDT, attributes(cvrdt))]
+pub fn derive_cvrdt(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    cvrdt(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn cvrdt(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "CvRDT can only be derived for structs",
+        ));
+    };
+
+    let fields: Vec<TokenStream2> = match &data.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = &field.ident;
+                quote!(#name)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|index| {
+                let index = Index::from(index);
+                quote!(#index)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let krate = crate_path(&input)?;
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#krate::CvRDT));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::CvRDT for #name #type_generics #where_clause {
+            #[inline]
+            fn merge(
+                &mut self,
+                other: &Self,
+            ) -> ::core::result::Result<(), #krate::prelude::Error> {
+                let mut merged = ::core::clone::Clone::clone(self);
+                #(#krate::CvRDT::merge(&mut merged.#fields, &other.#fields)?;)*
+                *self = merged;
+
+                ::core::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// The path to the mutree crate, `::mutree` unless set by a `#[cvrdt(crate = "...")]` attribute.
+fn crate_path(input: &DeriveInput) -> syn::Result<Path> {
+    let mut path = parse_quote!(::mutree);
+
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cvrdt"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                path = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported cvrdt attribute"))
+            }
+        })?;
+    }
+
+    Ok(path)
+}
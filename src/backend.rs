@@ -0,0 +1,112 @@
+use core::fmt::{self, Display};
+
+/// The implementation of the SHA-256 compression function used by tries hashed with
+/// [`sha2::Sha256`], as reported by [`hash_backend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashBackend {
+    /// The Intel SHA extensions, on x86 and x86-64.
+    ShaNi,
+    /// The ARMv8 cryptography extensions, on AArch64.
+    ArmSha2,
+    /// The assembly implementation of the `sha2-asm` crate.
+    Asm,
+    /// The portable Rust implementation.
+    Software,
+}
+
+impl Display for HashBackend {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HashBackend::ShaNi => "sha-ni",
+            HashBackend::ArmSha2 => "armv8-sha2",
+            HashBackend::Asm => "asm",
+            HashBackend::Software => "software",
+        })
+    }
+}
+
+/// The same detection `sha2` runs before picking an implementation.
+#[allow(deprecated)] // `cpufeatures::new!` expands to `u8::max_value()`
+mod detect {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    cpufeatures::new!(shani_cpuid, "sha", "sse2", "ssse3", "sse4.1");
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub(super) fn shani() -> bool {
+        shani_cpuid::get()
+    }
+
+    #[cfg(all(feature = "sha2-asm", target_arch = "aarch64"))]
+    cpufeatures::new!(sha2_hwcap, "sha2");
+
+    #[cfg(all(feature = "sha2-asm", target_arch = "aarch64"))]
+    pub(super) fn arm_sha2() -> bool {
+        sha2_hwcap::get()
+    }
+}
+
+/// Reports which implementation of SHA-256 is used on this machine.
+///
+/// The `sha2` crate picks it at runtime, checking the CPU for hardware support on the first
+/// hash. SHA-NI is always used when available on x86 and x86-64, falling back to assembly with
+/// the `sha2-asm` feature, or to the portable implementation otherwise. The ARMv8 extensions
+/// are only used with the `sha2-asm` feature, since `sha2` doesn't detect them without it.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::{hash_backend, HashBackend};
+///
+/// println!("hashing with {}", hash_backend());
+/// assert!(matches!(
+///     hash_backend(),
+///     HashBackend::ShaNi | HashBackend::ArmSha2 | HashBackend::Asm | HashBackend::Software
+/// ));
+/// ```
+#[inline]
+pub fn hash_backend() -> HashBackend {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if detect::shani() {
+        return HashBackend::ShaNi;
+    }
+
+    #[cfg(all(feature = "sha2-asm", any(target_arch = "x86", target_arch = "x86_64")))]
+    return HashBackend::Asm;
+
+    #[cfg(all(feature = "sha2-asm", target_arch = "aarch64"))]
+    if detect::arm_sha2() {
+        return HashBackend::ArmSha2;
+    }
+
+    #[allow(unreachable_code)]
+    HashBackend::Software
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn test_detects_sha_ni() {
+        let detected = std::is_x86_feature_detected!("sha")
+            && std::is_x86_feature_detected!("sse2")
+            && std::is_x86_feature_detected!("ssse3")
+            && std::is_x86_feature_detected!("sse4.1");
+
+        assert_eq!(hash_backend() == HashBackend::ShaNi, detected);
+    }
+
+    #[test]
+    fn test_backend_matches_reference() {
+        // The SHA-256 test vector for "abc", from FIPS 180-2.
+        assert_eq!(
+            hex::encode(Sha256::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(!hash_backend().to_string().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! Emits JSON test vectors, for checking other implementations against this one.
+//!
+//! Every vector is generated from a deterministic seed, so the output is stable across runs and
+//! platforms. For each seed and digest it lists the inserted entries, the root after every
+//! insertion, and the final proof in its serialized form.
+//!
+//! ```sh
+//! cargo run --features vectors --bin mutree-vectors -- [seeds]
+//! ```
+
+use std::{env, fmt::Write, process::ExitCode};
+
+use blake2::{digest::consts::U32, Blake2b, Blake2s256};
+use mutree::{prelude::*, TrieHasher};
+use ripemd::Ripemd160;
+use sha2::Sha256;
+use sha3::{Keccak256, Sha3_256};
+
+const DEFAULT_SEEDS: u64 = 8;
+
+/// SplitMix64, which is small enough to be reimplemented by anyone replaying the vectors.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> usize {
+        (self.next() % bound) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// The entries for a seed, shared by every digest so vectors can be compared across them.
+fn entries(seed: u64) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut rng = SplitMix64(seed);
+    let count = 1 + rng.below(16);
+
+    (0..count)
+        .map(|_| {
+            let key_len = 1 + rng.below(32);
+            let key = rng.bytes(key_len);
+            let value_len = rng.below(65);
+            let value = rng.bytes(value_len);
+            (key, value)
+        })
+        .collect()
+}
+
+fn vector<D: TrieHasher + 'static>(name: &str, seed: u64) -> Result<String> {
+    let entries = entries(seed);
+    let mut trie = Trie::<D>::empty();
+    let mut roots = Vec::with_capacity(entries.len());
+
+    for (key, value) in entries.iter() {
+        trie.insert(key, &value[..])?;
+        roots.push(trie.root());
+    }
+
+    let entries = entries
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                r#"{{"key":"{}","value":"{}"}}"#,
+                hex::encode(key),
+                hex::encode(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let roots = roots
+        .iter()
+        .map(|root| format!(r#""{}""#, root.to_hex()))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Ok(format!(
+        r#"{{"digest":"{name}","seed":{seed},"entries":[{entries}],"roots":[{roots}],"root":"{}","proof":"{}"}}"#,
+        trie.root().to_hex(),
+        trie.proof().to_hex(),
+    ))
+}
+
+fn vectors(seeds: u64) -> Result<String> {
+    let mut out = String::from("{\"version\":1,\"vectors\":[");
+
+    for seed in 0..seeds {
+        for vector in [
+            vector::<Blake2b<U32>>("blake2b-256", seed)?,
+            vector::<Blake2s256>("blake2s-256", seed)?,
+            vector::<blake3::Hasher>("blake3", seed)?,
+            vector::<Sha256>("sha2-256", seed)?,
+            vector::<Sha3_256>("sha3-256", seed)?,
+            vector::<Keccak256>("keccak-256", seed)?,
+            vector::<Ripemd160>("ripemd-160", seed)?,
+        ] {
+            if !out.ends_with('[') {
+                out.push(',');
+            }
+            out.push_str(&vector);
+        }
+    }
+
+    writeln!(out, "]}}").expect("writing to a String never fails");
+    Ok(out)
+}
+
+fn main() -> ExitCode {
+    let seeds = match env::args().nth(1).map(|arg| arg.parse()) {
+        None => DEFAULT_SEEDS,
+        Some(Ok(seeds)) => seeds,
+        Some(Err(error)) => {
+            eprintln!("usage: mutree-vectors [seeds]: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match vectors(seeds) {
+        Ok(out) => {
+            print!("{out}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
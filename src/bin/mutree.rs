@@ -0,0 +1,191 @@
+//! Inspects tries and proofs from the command line.
+//!
+//! ```text
+//! mutree [--digest NAME] root FILE
+//! mutree [--digest NAME] prove FILE [KEY]
+//! mutree [--digest NAME] verify ROOT KEY VALUE PROOF
+//! mutree show PROOF
+//! ```
+//!
+//! `FILE` holds one entry per line, either as `key,value` (CSV, split at the first comma) or as
+//! `{"key": "...", "value": "..."}` (JSONL, for files ending in `.jsonl` or `.json`). Roots and
+//! proofs are given and printed in hex, proofs in the format of `Proof::to_hex`.
+//!
+//! Since the proof of a trie authenticates every entry it holds, `prove` prints the same proof
+//! for every key. Given a `KEY`, it only checks that the key is present before printing it.
+
+use std::{env, fs, path::Path, process::ExitCode};
+
+use blake2::{digest::consts::U32, Blake2b, Blake2s256};
+use mutree::{prelude::*, TrieHasher};
+use ripemd::Ripemd160;
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::{Keccak256, Sha3_256};
+
+const USAGE: &str = "usage:
+  mutree [--digest NAME] root FILE
+  mutree [--digest NAME] prove FILE [KEY]
+  mutree [--digest NAME] verify ROOT KEY VALUE PROOF
+  mutree show PROOF
+
+digests: blake2b-256 (default), blake2s-256, blake3, sha2-256, sha3-256, keccak-256, ripemd-160";
+
+fn read_entries(path: &str) -> Result<Vec<(String, String)>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| Error::InvalidOperation(format!("can't read {path}: {error}")))?;
+    let jsonl = matches!(
+        Path::new(path).extension().and_then(|ext| ext.to_str()),
+        Some("jsonl" | "json")
+    );
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let entry = if jsonl {
+                parse_json_entry(line)
+            } else {
+                line.split_once(',')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            };
+
+            entry.ok_or_else(|| {
+                Error::Deserialization(format!("{path}:{}: invalid entry", number + 1))
+            })
+        })
+        .collect()
+}
+
+fn parse_json_entry(line: &str) -> Option<(String, String)> {
+    let entry: Value = serde_json::from_str(line).ok()?;
+    let key = entry.get("key")?.as_str()?;
+    let value = entry.get("value")?.as_str()?;
+
+    Some((key.to_string(), value.to_string()))
+}
+
+fn build<D: TrieHasher + 'static>(entries: &[(String, String)]) -> Result<Trie<D>> {
+    let mut trie = Trie::empty();
+
+    for (key, value) in entries {
+        trie.insert(key.as_bytes(), value.as_bytes())?;
+    }
+
+    Ok(trie)
+}
+
+fn run<D: TrieHasher + 'static>(args: &[String]) -> Result<String> {
+    match args {
+        [command, file] if command == "root" => {
+            let trie = build::<D>(&read_entries(file)?)?;
+            Ok(trie.root().to_hex())
+        }
+        [command, file] if command == "prove" => {
+            let entries = read_entries(file)?;
+            let proof = build::<D>(&entries)?.proof().to_hex();
+
+            Ok(entries
+                .iter()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value, "proof": proof }))
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+        [command, file, key] if command == "prove" => {
+            let entries = read_entries(file)?;
+
+            if !entries.iter().any(|(k, _)| k == key) {
+                return Err(Error::ElementNotExists);
+            }
+
+            Ok(build::<D>(&entries)?.proof().to_hex())
+        }
+        [command, root, key, value, proof] if command == "verify" => {
+            let root = Hash::from_hex(root)?;
+            let trie = Trie::<D>::from_proof(Proof::from_hex(proof)?);
+
+            if trie.root() == root && trie.verify(key.as_bytes(), value.as_bytes()) {
+                Ok("valid".to_string())
+            } else {
+                Err(Error::InvalidProof(format!(
+                    "{key:?} is not committed under {root}"
+                )))
+            }
+        }
+        [command, proof] if command == "show" => show(&Proof::from_hex(proof)?),
+        _ => Err(Error::InvalidOperation(USAGE.to_string())),
+    }
+}
+
+fn show(proof: &Proof) -> Result<String> {
+    let lines = proof.iter().enumerate().map(|(i, step)| match step {
+        Step::Branch { skip, neighbors } => {
+            let neighbors = neighbors
+                .iter()
+                .map(|n| format!("\n    {n}"))
+                .collect::<String>();
+            format!("{i}: Branch skip={skip}{neighbors}")
+        }
+        Step::Fork { skip, neighbor } => format!(
+            "{i}: Fork skip={skip}\n    nibble={:x} prefix={}\n    root={}",
+            neighbor.nibble,
+            hex::encode(&neighbor.prefix),
+            neighbor.root
+        ),
+        Step::Leaf {
+            skip,
+            key,
+            value,
+            timestamp,
+        } => {
+            let timestamp = timestamp
+                .map(|ts| format!("\n    timestamp={}.{}", ts.wall, ts.logical))
+                .unwrap_or_default();
+            format!("{i}: Leaf skip={skip}\n    key={key}\n    value={value}{timestamp}")
+        }
+    });
+
+    Ok(lines.collect::<Vec<_>>().join("\n"))
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let digest = match args.iter().position(|arg| arg == "--digest") {
+        Some(i) if i + 1 < args.len() => {
+            let digest = args.remove(i + 1);
+            args.remove(i);
+            digest
+        }
+        Some(_) => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+        None => "blake2b-256".to_string(),
+    };
+
+    let result = match digest.as_str() {
+        "blake2b-256" => run::<Blake2b<U32>>(&args),
+        "blake2s-256" => run::<Blake2s256>(&args),
+        "blake3" => run::<blake3::Hasher>(&args),
+        "sha2-256" => run::<Sha256>(&args),
+        "sha3-256" => run::<Sha3_256>(&args),
+        "keccak-256" => run::<Keccak256>(&args),
+        "ripemd-160" => run::<Ripemd160>(&args),
+        _ => Err(Error::InvalidOperation(format!(
+            "unknown digest {digest:?}\n{USAGE}"
+        ))),
+    };
+
+    match result {
+        Ok(out) => {
+            println!("{out}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
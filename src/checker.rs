@@ -0,0 +1,278 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::fmt;
+
+use crate::prelude::*;
+
+/// An algebraic law every [`CvRDT`] merge must satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Law {
+    /// Merging the same state twice has no further effect.
+    Idempotence,
+    /// Merging `a` into `b` gives the same state as merging `b` into `a`.
+    Commutativity,
+    /// Merging `c` into the merge of `a` and `b` gives the same state as merging the merge of `b`
+    /// and `c` into `a`.
+    Associativity,
+}
+
+impl fmt::Display for Law {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Law::Idempotence => write!(f, "idempotence"),
+            Law::Commutativity => write!(f, "commutativity"),
+            Law::Associativity => write!(f, "associativity"),
+        }
+    }
+}
+
+/// A law that was found not to hold for a merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The law that did not hold
+    pub law: Law,
+    /// The sequence number of the offending merge, starting at zero
+    pub merge: u64,
+}
+
+/// Checks the algebraic laws of a [`CvRDT`] on live traffic.
+///
+/// The checker wraps a state and runs every merge through it. On sampled merges, it also replays
+/// the merge on copies of the states involved and checks that it is idempotent, commutative, and
+/// associative with respect to recently merged states. The checks run in shadow mode: they never
+/// affect the wrapped state, and violations are only recorded for later reporting.
+///
+/// This is mostly useful for custom merge policies, which are easy to get subtly wrong in ways
+/// that only show up under real-world traffic.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut checker = CrdtChecker::new(Trie::<Blake2s256>::empty());
+///
+///     let mut other = Trie::<Blake2s256>::empty();
+///     other.insert(b"key", &b"value"[..])?;
+///     checker.merge(&other)?;
+///
+///     assert!(checker.violations().is_empty());
+///     assert!(checker.inner().verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CrdtChecker<T> {
+    inner: T,
+    sample_every: u64,
+    merges: u64,
+    recent: VecDeque<T>,
+    violations: Vec<Violation>,
+}
+
+impl<T: CvRDT> CrdtChecker<T> {
+    /// How many recently merged states are kept for checking associativity.
+    pub const HISTORY: usize = 8;
+
+    /// Wraps a state, checking every merge.
+    #[inline]
+    pub fn new(inner: T) -> Self {
+        Self::with_sampling(inner, 1)
+    }
+
+    /// Wraps a state, checking one in every `sample_every` merges.
+    ///
+    /// Checking a merge costs a handful of extra merges and clones, so busy replicas should only
+    /// check a sample of their traffic.
+    #[inline]
+    pub fn with_sampling(inner: T, sample_every: u64) -> Self {
+        Self {
+            inner,
+            sample_every: sample_every.max(1),
+            merges: 0,
+            recent: VecDeque::with_capacity(Self::HISTORY),
+            violations: Vec::new(),
+        }
+    }
+
+    /// The wrapped state.
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwraps the state, discarding the recorded violations.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// The number of merges performed so far.
+    #[inline]
+    pub fn merges(&self) -> u64 {
+        self.merges
+    }
+
+    /// The violations recorded so far.
+    #[inline]
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Returns the violations recorded so far, clearing them.
+    #[inline]
+    pub fn take_violations(&mut self) -> Vec<Violation> {
+        core::mem::take(&mut self.violations)
+    }
+
+    /// Merges `other` into the wrapped state, checking the laws if the merge is sampled.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the merge of the wrapped state. Errors from the shadow merges are
+    /// not reported, as they don't affect the wrapped state.
+    #[inline]
+    pub fn merge(&mut self, other: &T) -> Result<()> {
+        let merge = self.merges;
+        self.merges += 1;
+
+        if !merge.is_multiple_of(self.sample_every) {
+            return self.inner.merge(other);
+        }
+
+        let before = self.inner.clone();
+        self.inner.merge(other)?;
+
+        for law in self.check(&before, other) {
+            self.violations.push(Violation { law, merge });
+        }
+
+        if self.recent.len() == Self::HISTORY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(other.clone());
+
+        Ok(())
+    }
+
+    fn check(&self, before: &T, other: &T) -> Vec<Law> {
+        let merged = |a: &T, b: &T| -> Option<T> {
+            let mut a = a.clone();
+            a.merge(b).ok()?;
+            Some(a)
+        };
+
+        let mut violations = Vec::new();
+
+        if merged(&self.inner, other).as_ref() != Some(&self.inner) {
+            violations.push(Law::Idempotence);
+        }
+
+        if merged(other, before).as_ref() != Some(&self.inner) {
+            violations.push(Law::Commutativity);
+        }
+
+        let associative = self.recent.iter().all(|previous| {
+            let left = merged(&self.inner, previous);
+            let right = merged(other, previous).and_then(|bc| merged(before, &bc));
+            left.is_some() && left == right
+        });
+
+        if !associative {
+            violations.push(Law::Associativity);
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use test_strategy::Arbitrary;
+
+    use super::*;
+
+    /// Not a CRDT: the last merged state always wins.
+    #[derive(Debug, Clone, PartialEq, Default, Arbitrary)]
+    struct LastWriter(u64);
+
+    impl CvRDT for LastWriter {
+        fn merge(&mut self, other: &Self) -> Result<(), Error> {
+            self.0 = other.0;
+            Ok(())
+        }
+    }
+
+    /// Not a CRDT: the order in which states are averaged changes the result.
+    #[derive(Debug, Clone, PartialEq, Default, Arbitrary)]
+    struct Midpoint(u64);
+
+    impl CvRDT for Midpoint {
+        fn merge(&mut self, other: &Self) -> Result<(), Error> {
+            self.0 = (self.0 + other.0) / 2;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trie_merges_satisfy_laws() -> Result<()> {
+        let mut checker = CrdtChecker::new(Trie::<Blake2s256>::empty());
+
+        for i in 0..16u8 {
+            let mut other = Trie::<Blake2s256>::empty();
+            other.insert(&[i % 4 + 1], &[i][..])?;
+            checker.merge(&other)?;
+        }
+
+        assert_eq!(checker.merges(), 16);
+        assert_eq!(checker.violations(), &[]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_violations() -> Result<()> {
+        let mut checker = CrdtChecker::new(LastWriter(1));
+        checker.merge(&LastWriter(2))?;
+
+        assert_eq!(
+            checker.take_violations(),
+            vec![Violation {
+                law: Law::Commutativity,
+                merge: 0
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_associativity_violations() -> Result<()> {
+        let mut checker = CrdtChecker::new(Midpoint(0));
+        checker.merge(&Midpoint(8))?;
+        checker.merge(&Midpoint(0))?;
+
+        assert!(checker
+            .violations()
+            .iter()
+            .any(|v| v.law == Law::Associativity && v.merge == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sampling_skips_checks() -> Result<()> {
+        let mut checker = CrdtChecker::with_sampling(LastWriter(1), 2);
+        checker.merge(&LastWriter(2))?;
+        checker.merge(&LastWriter(3))?;
+
+        assert_eq!(checker.inner(), &LastWriter(3));
+        assert!(checker.violations().iter().all(|v| v.merge == 0));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use test_strategy::Arbitrary;
+
+use crate::prelude::*;
+
+/// A Hybrid Logical Clock timestamp.
+///
+/// Timestamps combine the physical time, in milliseconds since the Unix epoch, with a logical
+/// counter that orders events happening within the same millisecond, or while the physical
+/// clock lags behind a timestamp received from another replica. They are ordered by physical
+/// time first, and logical counter second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "std", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Timestamp {
+    /// Physical time, in milliseconds since the Unix epoch
+    pub wall: u64,
+    /// Logical counter, for events not ordered by physical time
+    pub logical: u32,
+}
+
+impl Timestamp {
+    /// The length of the byte representation of a timestamp.
+    pub const LEN: usize = 12;
+
+    #[inline]
+    pub const fn new(wall: u64, logical: u32) -> Self {
+        Self { wall, logical }
+    }
+}
+
+impl ToBytes for Timestamp {
+    type Output = [u8; Self::LEN];
+
+    /// Encodes the wall time as a big-endian `u64`, followed by the logical counter as a
+    /// big-endian `u32`, so byte order matches timestamp order.
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[..8].copy_from_slice(&self.wall.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.logical.to_be_bytes());
+        bytes
+    }
+}
+
+impl FromBytes for Timestamp {
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LEN {
+            return Err(Error::InvalidLength);
+        }
+
+        Ok(Self {
+            wall: u64::from_be_bytes(bytes[..8].try_into()?),
+            logical: u32::from_be_bytes(bytes[8..].try_into()?),
+        })
+    }
+}
+
+crate::impl_hex_via_bytes!(Timestamp);
+
+/// A Hybrid Logical Clock, producing timestamps for last-writer-wins writes.
+///
+/// The clock never goes backwards, even if the physical clock does, and timestamps it produces
+/// after observing a remote timestamp are always greater than it. This keeps causally related
+/// writes ordered across replicas, while staying close to physical time.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+///
+/// let mut clock = HybridClock::new();
+///
+/// let a = clock.tick_at(100);
+/// let b = clock.tick_at(100);
+/// assert!(b > a);
+///
+/// // A remote timestamp from the future pushes the clock forward
+/// let c = clock.observe_at(Timestamp::new(200, 5), 101);
+/// assert_eq!(c, Timestamp::new(200, 6));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HybridClock {
+    last: Timestamp,
+}
+
+impl HybridClock {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last timestamp produced or observed by the clock.
+    #[inline]
+    pub fn last(&self) -> Timestamp {
+        self.last
+    }
+
+    /// Produces a timestamp for a local event, using the system clock.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn tick(&mut self) -> Timestamp {
+        self.tick_at(Self::physical_now())
+    }
+
+    /// Produces a timestamp for a local event happening at `physical` milliseconds.
+    #[inline]
+    pub fn tick_at(&mut self, physical: u64) -> Timestamp {
+        self.last = if physical > self.last.wall {
+            Timestamp::new(physical, 0)
+        } else {
+            Timestamp::new(self.last.wall, self.last.logical.saturating_add(1))
+        };
+
+        self.last
+    }
+
+    /// Merges a timestamp received from another replica, using the system clock.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn observe(&mut self, remote: Timestamp) -> Timestamp {
+        self.observe_at(remote, Self::physical_now())
+    }
+
+    /// Merges a timestamp received from another replica at `physical` milliseconds, returning a
+    /// timestamp greater than both the remote one and any produced so far.
+    #[inline]
+    pub fn observe_at(&mut self, remote: Timestamp, physical: u64) -> Timestamp {
+        let wall = physical.max(self.last.wall).max(remote.wall);
+
+        let logical = match (wall == self.last.wall, wall == remote.wall) {
+            (true, true) => self.last.logical.max(remote.logical).saturating_add(1),
+            (true, false) => self.last.logical.saturating_add(1),
+            (false, true) => remote.logical.saturating_add(1),
+            (false, false) => 0,
+        };
+
+        self.last = Timestamp::new(wall, logical);
+        self.last
+    }
+
+    #[cfg(feature = "std")]
+    fn physical_now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    crate::test_to_bytes!(Timestamp);
+
+    #[proptest]
+    fn test_byte_order_matches_timestamp_order(a: Timestamp, b: Timestamp) {
+        prop_assert_eq!(a.cmp(&b), a.to_bytes().cmp(&b.to_bytes()));
+    }
+
+    #[proptest]
+    fn test_ticks_are_monotonic(
+        #[strategy(proptest::collection::vec(any::<u32>(), 1..16))] physical: Vec<u32>,
+    ) {
+        let mut clock = HybridClock::new();
+        let mut last = clock.last();
+
+        for physical in physical {
+            let next = clock.tick_at(physical.into());
+            prop_assert!(next > last);
+            last = next;
+        }
+    }
+
+    #[proptest]
+    fn test_observe_is_after_remote(
+        #[strategy(0..u32::MAX - 1)] local: u32,
+        #[strategy((0..u64::MAX / 2, 0..u32::MAX - 1))] remote: (u64, u32),
+        #[strategy(0..u64::MAX / 2)] physical: u64,
+    ) {
+        let mut clock = HybridClock::new();
+        let before = clock.tick_at(local.into());
+        let remote = Timestamp::new(remote.0, remote.1);
+        let after = clock.observe_at(remote, physical);
+
+        prop_assert!(after > remote);
+        prop_assert!(after > before);
+    }
+}
@@ -0,0 +1,220 @@
+//! Adapters between the CRDT traits of this crate and the ones of the [`crdts`] crate.
+//!
+//! [`Crdts`] wraps a type from the `crdts` ecosystem, implementing [`CvRDT`], [`CmRDT`] and
+//! [`ToBytes`] for it, so existing application state can be merged by the replication engine,
+//! or committed as a value of a [`CrdtMap`], without rewriting its merge logic. [`Native`] goes
+//! the other way around, exposing a type of this crate, like a [`Trie`], to code written against
+//! [`crdts::CvRDT`].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use blake2::Blake2s256;
+//! use crdts::{CmRDT as _, GCounter};
+//! use mutree::{compat::Crdts, prelude::*};
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut counter = GCounter::new();
+//!     counter.apply(counter.inc("alice"));
+//!
+//!     let mut map = CrdtMap::<String, Crdts<GCounter<&str>>, Blake2s256>::new();
+//!     map.merge_value("visits".to_string(), &Crdts::from(counter))?;
+//!     assert!(map.verify(&"visits".to_string()));
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use serde::Serialize;
+
+use crate::prelude::*;
+
+/// A CRDT from the [`crdts`] crate, usable wherever this crate expects a [`CvRDT`] or [`CmRDT`].
+///
+/// Merges and operations are validated with `validate_merge` and `validate_op` before being
+/// applied, and validation failures are returned as [`Error::InvalidOperation`].
+///
+/// The byte representation is the postcard encoding of the wrapped state. It is only
+/// deterministic if the serialization of the state is: CRDTs backed by a `HashMap`, like
+/// `Orswot` and `Map`, serialize their entries in an arbitrary order, so replicas holding the
+/// same state can disagree on its bytes, and on the root of a [`CrdtMap`] holding it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Crdts<T>(pub T);
+
+impl<T> Crdts<T> {
+    /// Unwraps the CRDT.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Crdts<T> {
+    #[coverage(off)]
+    #[inline]
+    fn from(inner: T) -> Self {
+        Crdts(inner)
+    }
+}
+
+impl<T> CvRDT for Crdts<T>
+where
+    T: crdts::CvRDT + Default + Clone + PartialEq,
+{
+    #[inline]
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        self.0
+            .validate_merge(&other.0)
+            .map_err(|error| Error::InvalidOperation(error.to_string()))?;
+        self.0.merge(other.0.clone());
+
+        Ok(())
+    }
+}
+
+impl<T> CmRDT<T::Op> for Crdts<T>
+where
+    T: crdts::CmRDT + Default + Clone + PartialEq,
+    T::Op: Clone,
+{
+    #[inline]
+    fn apply(&mut self, op: &T::Op) -> Result<(), Error> {
+        self.0
+            .validate_op(op)
+            .map_err(|error| Error::InvalidOperation(error.to_string()))?;
+        self.0.apply(op.clone());
+
+        Ok(())
+    }
+}
+
+impl<T: Serialize> ToBytes for Crdts<T> {
+    type Output = Vec<u8>;
+
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        postcard::to_allocvec(&self.0).expect("serializing to a vector never fails")
+    }
+}
+
+/// A CRDT of this crate, usable wherever the [`crdts`] crate expects a [`crdts::CvRDT`].
+///
+/// `crdts` merges can't fail, so failed merges leave the state unchanged. Call
+/// [`crdts::CvRDT::validate_merge`] first to find out whether a merge will fail.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Native<T>(pub T);
+
+impl<T> Native<T> {
+    /// Unwraps the CRDT.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Native<T> {
+    #[coverage(off)]
+    #[inline]
+    fn from(inner: T) -> Self {
+        Native(inner)
+    }
+}
+
+impl<T: CvRDT> crdts::CvRDT for Native<T> {
+    type Validation = Error;
+
+    #[inline]
+    fn validate_merge(&self, other: &Self) -> Result<(), Self::Validation> {
+        self.0.clone().merge(&other.0)
+    }
+
+    #[inline]
+    fn merge(&mut self, other: Self) {
+        let mut merged = self.0.clone();
+
+        if merged.merge(&other.0).is_ok() {
+            self.0 = merged;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use crdts::{CmRDT as _, CvRDT as _, GCounter};
+
+    use super::*;
+
+    type Counter = Crdts<GCounter<u8>>;
+
+    fn counter(actor: u8, steps: u64) -> Counter {
+        let mut counter = GCounter::new();
+        counter.apply(counter.inc_many(actor, steps));
+        Crdts(counter)
+    }
+
+    #[test]
+    fn test_merge_matches_crdts() -> Result<()> {
+        let (a, b) = (counter(1, 3), counter(2, 4));
+
+        let mut merged = a.clone();
+        CvRDT::merge(&mut merged, &b)?;
+
+        let mut expected = a.into_inner();
+        expected.merge(b.into_inner());
+
+        assert_eq!(merged.0, expected);
+        assert_eq!(merged.0.read(), 7u8.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_matches_crdts() -> Result<()> {
+        let mut counter = Counter::default();
+        let op = counter.0.inc(1);
+
+        CmRDT::apply(&mut counter, &op)?;
+        CmRDT::apply(&mut counter, &op)?;
+
+        assert_eq!(counter.0.read(), 1u8.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_committed_into_map() -> Result<()> {
+        let mut a = CrdtMap::<String, Counter, Blake2s256>::new();
+        a.merge_value("visits".to_string(), &counter(1, 2))?;
+        let mut b = CrdtMap::new();
+        b.merge_value("visits".to_string(), &counter(2, 5))?;
+
+        let mut ab = a.clone();
+        CvRDT::merge(&mut ab, &b)?;
+        let mut ba = b.clone();
+        CvRDT::merge(&mut ba, &a)?;
+
+        assert_eq!(ab.root(), ba.root());
+        assert_eq!(ab.get(&"visits".to_string()).unwrap().0.read(), 7u8.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_trie() -> Result<()> {
+        let mut a = Trie::<Blake2s256>::empty();
+        a.insert(b"a", &b"1"[..])?;
+        let mut b = Trie::<Blake2s256>::empty();
+        b.insert(b"b", &b"2"[..])?;
+
+        let mut native = Native::from(a);
+        native.validate_merge(&Native(b.clone()))?;
+        native.merge(Native(b));
+
+        let trie = native.into_inner();
+        assert!(trie.verify(b"a", b"1"));
+        assert!(trie.verify(b"b", b"2"));
+
+        Ok(())
+    }
+}
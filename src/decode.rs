@@ -0,0 +1,177 @@
+use alloc::format;
+
+use crate::prelude::*;
+
+/// Bounds on the inputs decoded with [`FromBytes::from_bytes_with`].
+///
+/// Decoders already reject malformed inputs without panicking, but a well-formed input can
+/// still be large enough to exhaust the memory of the replica decoding it. Every decoder checks
+/// the same context, so untrusted inputs are bounded consistently, wherever they come from.
+/// [`FromBytes::from_bytes`] decodes with [`DecodeContext::UNBOUNDED`], for trusted inputs.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"a", &b"1"[..])?;
+///     trie.insert(b"b", &b"2"[..])?;
+///     let bytes = trie.proof().to_bytes();
+///
+///     let context = DecodeContext {
+///         max_steps: 1,
+///         ..DecodeContext::default()
+///     };
+///     assert!(Proof::from_bytes_with(&bytes, &context).is_err());
+///     assert_eq!(Proof::from_bytes_with(&bytes, &DecodeContext::default())?, *trie.proof());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeContext {
+    /// The length of the longest input accepted, in bytes
+    pub max_bytes: usize,
+    /// The most steps accepted in a proof
+    pub max_steps: usize,
+    /// The length of the longest neighbor prefix accepted, in bytes
+    pub max_prefix_len: usize,
+}
+
+impl DecodeContext {
+    /// The longest input accepted by default, 64 MiB.
+    pub const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+    /// The most steps accepted in a proof by default.
+    pub const DEFAULT_MAX_STEPS: usize = 1 << 20;
+
+    /// The longest neighbor prefix accepted by default, which is also the longest a valid Trie
+    /// holds.
+    pub const DEFAULT_MAX_PREFIX_LEN: usize = MAX_SKIP / 2;
+
+    /// A context accepting inputs of any size.
+    pub const UNBOUNDED: Self = Self {
+        max_bytes: usize::MAX,
+        max_steps: usize::MAX,
+        max_prefix_len: usize::MAX,
+    };
+
+    /// Checks that an input of `len` bytes is not longer than the limit.
+    pub(crate) fn check_bytes(&self, len: usize) -> Result<()> {
+        if len > self.max_bytes {
+            return Err(Error::Deserialization(format!(
+                "Input of {len} bytes exceeds the maximum of {}",
+                self.max_bytes
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a proof of `count` steps does not hold more than the limit.
+    pub(crate) fn check_steps(&self, count: usize) -> Result<()> {
+        if count > self.max_steps {
+            return Err(Error::Deserialization(format!(
+                "Proof of {count} steps exceeds the maximum of {}",
+                self.max_steps
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a neighbor prefix of `len` bytes is not longer than the limit.
+    pub(crate) fn check_prefix(&self, len: usize) -> Result<()> {
+        if len > self.max_prefix_len {
+            return Err(Error::Deserialization(format!(
+                "Neighbor prefix of {len} bytes exceeds the maximum of {}",
+                self.max_prefix_len
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DecodeContext {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+            max_prefix_len: Self::DEFAULT_MAX_PREFIX_LEN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+    use crate::wire::WireFormat;
+
+    #[proptest]
+    fn test_default_bounds_accept_tries(trie: Trie<Blake2s256>) {
+        let bytes = trie.proof().to_bytes();
+        let context = DecodeContext::default();
+
+        prop_assert_eq!(
+            Proof::from_bytes_with(&bytes, &context)?,
+            trie.proof().clone()
+        );
+        prop_assert_eq!(Trie::<Blake2s256>::from_bytes_with(&bytes, &context)?, trie);
+    }
+
+    #[proptest]
+    fn test_bounds_are_checked_everywhere(#[any(8usize)] proof: Proof) {
+        let bytes = proof.to_bytes();
+        let wire = proof.to_wire(1)?;
+        let longest_prefix = proof
+            .iter()
+            .filter_map(|step| match step {
+                Step::Fork { neighbor, .. } => Some(neighbor.prefix.len()),
+                _ => None,
+            })
+            .max();
+
+        let exact = DecodeContext {
+            max_bytes: bytes.len(),
+            max_steps: proof.len(),
+            max_prefix_len: longest_prefix.unwrap_or(0),
+        };
+        prop_assert_eq!(Proof::from_bytes_with(&bytes, &exact)?, proof.clone());
+        prop_assert_eq!(Proof::from_wire_with(&wire, &exact)?, proof.clone());
+
+        let mut short = vec![DecodeContext {
+            max_bytes: bytes.len() - 1,
+            ..exact
+        }];
+        if let Some(max_steps) = proof.len().checked_sub(1) {
+            short.push(DecodeContext { max_steps, ..exact });
+        }
+        for context in &short {
+            prop_assert!(matches!(
+                Proof::from_bytes_with(&bytes, context),
+                Err(Error::Deserialization(_))
+            ));
+            prop_assert!(Proof::from_wire_with(&wire, context).is_err());
+        }
+
+        if let Some(len) = longest_prefix.filter(|len| *len > 0) {
+            let context = DecodeContext {
+                max_prefix_len: len - 1,
+                ..exact
+            };
+            prop_assert!(matches!(
+                Proof::from_bytes_with(&bytes, &context),
+                Err(Error::Deserialization(_))
+            ));
+        }
+    }
+}
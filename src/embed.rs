@@ -0,0 +1,261 @@
+//! Compile-time checks backing [`include_proof!`](crate::include_proof).
+//!
+//! These functions mirror the decoders of [`Proof`](crate::prelude::Proof) in `const` context,
+//! so malformed embedded proofs fail the build instead of the program.
+
+use crate::trie::MAX_SKIP;
+
+/// The length of the `skip` of a step, a big-endian `u64` on every platform.
+const SKIP_LEN: usize = 8;
+
+/// The number of bytes encoded by a hex string.
+///
+/// # Panics
+///
+/// Panics, failing compilation in `const` context, if the string has an odd length.
+#[inline]
+pub const fn hex_len(hex: &str) -> usize {
+    assert!(
+        hex.len() % 2 == 0,
+        "include_proof!: hex string has an odd length"
+    );
+    hex.len() / 2
+}
+
+/// Decodes a hex string of `N` bytes.
+///
+/// # Panics
+///
+/// Panics, failing compilation in `const` context, if the string is not `N` bytes of hex.
+#[inline]
+pub const fn decode_hex<const N: usize>(hex: &str) -> [u8; N] {
+    let hex = hex.as_bytes();
+    assert!(
+        hex.len() == 2 * N,
+        "include_proof!: hex string has the wrong length"
+    );
+
+    let mut bytes = [0u8; N];
+    let mut i = 0;
+    while i < N {
+        bytes[i] = (nibble(hex[2 * i]) << 4) | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+    bytes
+}
+
+const fn nibble(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => panic!("include_proof!: invalid hex digit"),
+    }
+}
+
+const fn be_u32(bytes: &[u8], at: usize) -> usize {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize
+}
+
+const fn be_u64(bytes: &[u8], at: usize) -> u64 {
+    let mut value = 0;
+    let mut i = 0;
+    while i < SKIP_LEN {
+        value = (value << 8) | bytes[at + i] as u64;
+        i += 1;
+    }
+    value
+}
+
+/// Checks that `FromBytes::from_bytes` accepts a step spanning `bytes[start..end]`.
+const fn is_valid_step(bytes: &[u8], start: usize, end: usize) -> bool {
+    let len = end - start;
+
+    if len == 0 {
+        return false;
+    }
+
+    let valid_len = match bytes[start] {
+        0 => len == 1 + SKIP_LEN + 4 * 32,
+        1 => len >= 1 + SKIP_LEN + 33,
+        2 => len == 1 + SKIP_LEN + 64 || len == 1 + SKIP_LEN + 64 + 12,
+        _ => false,
+    };
+
+    if !valid_len {
+        return false;
+    }
+
+    let skip = be_u64(bytes, start + 1);
+    if skip > MAX_SKIP as u64 {
+        return false;
+    }
+
+    // The neighbor of a fork sits at a nibble, and its prefix fits in a key after the skip
+    bytes[start] != 1
+        || (bytes[start + 1 + SKIP_LEN] <= 0xf
+            && skip as usize + 1 + 2 * (len - (1 + SKIP_LEN + 33)) <= MAX_SKIP)
+}
+
+/// Checks that `Proof::from_bytes` accepts `bytes`.
+#[inline]
+pub const fn is_valid_proof(bytes: &[u8]) -> bool {
+    if bytes.len() < 4 {
+        return false;
+    }
+
+    let count = be_u32(bytes, 0);
+    let mut at = 4;
+    let mut i = 0;
+
+    while i < count {
+        if bytes.len() - at < 4 {
+            return false;
+        }
+
+        let len = be_u32(bytes, at);
+        at += 4;
+
+        if bytes.len() - at < len || !is_valid_step(bytes, at, at + len) {
+            return false;
+        }
+
+        at += len;
+        i += 1;
+    }
+
+    at == bytes.len()
+}
+
+/// Embeds a proof known at compile time, checking that it is well-formed during the build.
+///
+/// The proof is given either as a hex string literal, or with `bytes =` as a `const` byte
+/// slice, like the output of [`include_bytes!`], in the format of its `ToBytes`
+/// implementation. Malformed proofs fail compilation, so the macro expands to a [`Proof`]
+/// without any error to handle. Decoding still allocates the steps when the expression is
+/// evaluated, so proofs needed as a `static` can be wrapped in a `LazyLock`.
+///
+/// [`Proof`]: crate::prelude::Proof
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::LazyLock;
+///
+/// use mutree::{include_proof, prelude::*};
+///
+/// static EMPTY: LazyLock<Proof> = LazyLock::new(|| include_proof!("00000000"));
+///
+/// let leaf = include_proof!(
+///     "0000000100000049020000000000000000557eb63353d68c62ae2f59f8e2c82b07ffff936fe594a000dfaf0d50015930d8d9ca0be5fe2bac780b5de4601bc9fcb2a7c8c3d9d3a5b285bc05f255881ea9c9"
+/// );
+///
+/// assert!(EMPTY.is_empty());
+/// assert_eq!(leaf.len(), 1);
+/// ```
+///
+/// Invalid proofs are rejected by the compiler:
+///
+/// ```rust,compile_fail
+/// let proof = mutree::include_proof!("0000000100000001ff");
+/// ```
+#[macro_export]
+macro_rules! include_proof {
+    (bytes = $bytes:expr) => {{
+        const __INCLUDED_PROOF: &[u8] = $bytes;
+        const _: () = assert!(
+            $crate::__private::is_valid_proof(__INCLUDED_PROOF),
+            "include_proof!: malformed proof"
+        );
+
+        <$crate::prelude::Proof as $crate::FromBytes>::from_bytes(__INCLUDED_PROOF)
+            .expect("the proof was checked at compile time")
+    }};
+    ($hex:literal) => {
+        $crate::include_proof!(
+            bytes = &$crate::__private::decode_hex::<{ $crate::__private::hex_len($hex) }>($hex)
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[proptest]
+    fn test_hex_matches_hex_crate(bytes: Vec<u8>) {
+        let hex = hex::encode(&bytes);
+        let mut decoded = vec![0u8; hex_len(&hex)];
+
+        for (i, byte) in decoded.iter_mut().enumerate() {
+            *byte = decode_hex::<1>(&hex[2 * i..2 * i + 2])[0];
+        }
+
+        prop_assert_eq!(decoded, bytes);
+    }
+
+    #[proptest]
+    fn test_accepts_encoded_proofs(#[any(8usize)] proof: Proof) {
+        prop_assert!(is_valid_proof(&proof.to_bytes()));
+    }
+
+    #[proptest]
+    fn test_matches_decoder(#[any(8usize)] proof: Proof, index: usize, byte: u8, cut: usize) {
+        let mut bytes = proof.to_bytes();
+        let index = index % bytes.len();
+        bytes[index] = byte;
+        bytes.truncate(bytes.len() - cut % 3);
+
+        prop_assert_eq!(is_valid_proof(&bytes), Proof::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_matches_decoder_on_skip_bounds() {
+        let proof = Proof::from(vec![Step::Fork {
+            skip: 0,
+            neighbor: Neighbor {
+                nibble: 0,
+                prefix: vec![0; 21],
+                root: Hash::default(),
+            },
+        }]);
+        let mut bytes = proof.to_bytes();
+
+        // Overwrites the last byte of the skip: with a prefix of 21 bytes, forks skip at most
+        // 21 nibbles
+        for (skip, valid) in [(21, true), (22, false), (MAX_SKIP as u8 + 1, false)] {
+            bytes[4 + 4 + SKIP_LEN] = skip;
+            assert_eq!(is_valid_proof(&bytes), valid);
+            assert_eq!(Proof::from_bytes(&bytes).is_ok(), valid);
+        }
+    }
+
+    #[proptest]
+    fn test_matches_decoder_on_noise(bytes: Vec<u8>) {
+        prop_assert_eq!(is_valid_proof(&bytes), Proof::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_include_proof() -> Result<()> {
+        let mut trie = Trie::<blake2::Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+        assert_eq!(
+            trie.proof().to_hex(),
+            "0000000100000049020000000000000000557eb63353d68c62ae2f59f8e2c82b07ffff936fe594a000dfaf0d50015930d8d9ca0be5fe2bac780b5de4601bc9fcb2a7c8c3d9d3a5b285bc05f255881ea9c9"
+        );
+
+        let proof = include_proof!(
+            "0000000100000049020000000000000000557eb63353d68c62ae2f59f8e2c82b07ffff936fe594a000dfaf0d50015930d8d9ca0be5fe2bac780b5de4601bc9fcb2a7c8c3d9d3a5b285bc05f255881ea9c9"
+        );
+        assert_eq!(&proof, trie.proof());
+
+        const EMPTY: [u8; 4] = [0, 0, 0, 0];
+        assert!(include_proof!(bytes = &EMPTY).is_empty());
+
+        Ok(())
+    }
+}
@@ -1,13 +1,20 @@
-use std::{
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::{
     array::TryFromSliceError,
     num::{ParseIntError, TryFromIntError},
 };
 
 use thiserror::Error as ThisError;
 
-pub type Result<T, E = Error> = std::result::Result<T, E>;
+use crate::hash::Hash;
+
+pub type Result<T, E = Error> = core::result::Result<T, E>;
 
 #[derive(Debug, ThisError)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error), uniffi(flat_error))]
 pub enum Error {
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
@@ -21,6 +28,9 @@ pub enum Error {
     #[error("Invalid proof: {0}")]
     InvalidProof(String),
 
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
     #[error("Element already exists")]
     ElementExists,
 
@@ -41,6 +51,167 @@ pub enum Error {
 
     #[error("Database error: {0}")]
     DatabaseError(String),
+
+    #[error("Transport error: {0}")]
+    Transport(String),
+
+    #[error("Unsupported wire version: {0}")]
+    UnsupportedWireVersion(u16),
+
+    #[error("Memory budget exceeded: {used} bytes needed, {budget} allowed")]
+    MemoryBudgetExceeded { used: usize, budget: usize },
+}
+
+impl Error {
+    /// The stable number of the variant of the error, for conveying it across the FFI and HTTP
+    /// boundaries without parsing its message.
+    ///
+    /// Codes start at 1, so 0 can stand for success, and are never reused. They are mapped back
+    /// to the variant with [`ErrorCode::from_u32`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    ///
+    /// let error = Error::InvalidProof("root mismatch".to_string());
+    /// assert_eq!(error.code(), 4);
+    /// assert_eq!(ErrorCode::from_u32(error.code()), Some(ErrorCode::InvalidProof));
+    /// ```
+    #[inline]
+    pub fn code(&self) -> u32 {
+        ErrorCode::from(self) as u32
+    }
+}
+
+/// The variants of [`Error`], without their details, numbered by [`Error::code`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// [`Error::InvalidOperation`]
+    InvalidOperation = 1,
+    /// [`Error::InvalidState`]
+    InvalidState = 2,
+    /// [`Error::EmptyKeyOrValue`]
+    EmptyKeyOrValue = 3,
+    /// [`Error::InvalidProof`]
+    InvalidProof = 4,
+    /// [`Error::InvalidSignature`]
+    InvalidSignature = 5,
+    /// [`Error::ElementExists`]
+    ElementExists = 6,
+    /// [`Error::ElementNotExists`]
+    ElementNotExists = 7,
+    /// [`Error::Deserialization`]
+    Deserialization = 8,
+    /// [`Error::Serialization`]
+    Serialization = 9,
+    /// [`Error::Unknown`]
+    Unknown = 10,
+    /// [`Error::InvalidLength`]
+    InvalidLength = 11,
+    /// [`Error::DatabaseError`]
+    DatabaseError = 12,
+    /// [`Error::Transport`]
+    Transport = 13,
+    /// [`Error::UnsupportedWireVersion`]
+    UnsupportedWireVersion = 14,
+    /// [`Error::MemoryBudgetExceeded`]
+    MemoryBudgetExceeded = 15,
+}
+
+impl ErrorCode {
+    /// Every code, in increasing order.
+    pub const ALL: [Self; 15] = [
+        Self::InvalidOperation,
+        Self::InvalidState,
+        Self::EmptyKeyOrValue,
+        Self::InvalidProof,
+        Self::InvalidSignature,
+        Self::ElementExists,
+        Self::ElementNotExists,
+        Self::Deserialization,
+        Self::Serialization,
+        Self::Unknown,
+        Self::InvalidLength,
+        Self::DatabaseError,
+        Self::Transport,
+        Self::UnsupportedWireVersion,
+        Self::MemoryBudgetExceeded,
+    ];
+
+    /// The variant numbered `code` by [`Error::code`], or `None` if no variant has that number.
+    #[inline]
+    pub fn from_u32(code: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|variant| *variant as u32 == code)
+    }
+}
+
+impl From<&Error> for ErrorCode {
+    #[inline]
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::InvalidOperation(_) => Self::InvalidOperation,
+            Error::InvalidState(_) => Self::InvalidState,
+            Error::EmptyKeyOrValue => Self::EmptyKeyOrValue,
+            Error::InvalidProof(_) => Self::InvalidProof,
+            Error::InvalidSignature(_) => Self::InvalidSignature,
+            Error::ElementExists => Self::ElementExists,
+            Error::ElementNotExists => Self::ElementNotExists,
+            Error::Deserialization(_) => Self::Deserialization,
+            Error::Serialization(_) => Self::Serialization,
+            Error::Unknown(_) => Self::Unknown,
+            Error::InvalidLength => Self::InvalidLength,
+            Error::DatabaseError(_) => Self::DatabaseError,
+            Error::Transport(_) => Self::Transport,
+            Error::UnsupportedWireVersion(_) => Self::UnsupportedWireVersion,
+            Error::MemoryBudgetExceeded { .. } => Self::MemoryBudgetExceeded,
+        }
+    }
+}
+
+impl TryFrom<u32> for ErrorCode {
+    type Error = Error;
+
+    /// Maps a code back to its variant, like [`ErrorCode::from_u32`].
+    #[inline]
+    fn try_from(code: u32) -> Result<Self> {
+        Self::from_u32(code)
+            .ok_or_else(|| Error::Deserialization(format!("unknown error code: {code}")))
+    }
+}
+
+/// Why a key-value pair failed to verify, as reported by
+/// [`Trie::verify_detailed`](crate::prelude::Trie::verify_detailed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+pub enum VerifyError {
+    /// A step of the proof is out of the bounds of a valid Trie.
+    #[error("Malformed step at index {index}")]
+    MalformedStep { index: usize },
+
+    /// No leaf of the proof holds the pair.
+    #[error("Leaf not found")]
+    LeafNotFound,
+
+    /// The proof holds the pair, but also a repeated step or another leaf for the key, which
+    /// the [`VerifyMode::Strict`](crate::prelude::VerifyMode::Strict) verification of the
+    /// profile of the Trie rejects.
+    #[error("Proof rejected by strict verification")]
+    NotStrict,
+
+    /// The proof holds the pair, but doesn't hash to the root.
+    #[error("Root mismatch: expected {expected}, computed {computed}")]
+    RootMismatch { expected: Hash, computed: Hash },
+}
+
+impl From<VerifyError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(error: VerifyError) -> Self {
+        Error::InvalidProof(error.to_string())
+    }
 }
 
 impl From<hex::FromHexError> for Error {
@@ -75,6 +246,7 @@ impl From<TryFromSliceError> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<redb::Error> for Error {
     #[coverage(off)]
     #[inline]
@@ -83,6 +255,7 @@ impl From<redb::Error> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<redb::DatabaseError> for Error {
     #[coverage(off)]
     #[inline]
@@ -90,3 +263,81 @@ impl From<redb::DatabaseError> for Error {
         Error::DatabaseError(value.to_string())
     }
 }
+
+#[cfg(feature = "std")]
+impl From<redb::TransactionError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(value: redb::TransactionError) -> Self {
+        Error::DatabaseError(value.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<redb::TableError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(value: redb::TableError) -> Self {
+        Error::DatabaseError(value.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<redb::StorageError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(value: redb::StorageError) -> Self {
+        Error::DatabaseError(value.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<redb::CommitError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(value: redb::CommitError) -> Self {
+        Error::DatabaseError(value.to_string())
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<prost::DecodeError> for Error {
+    #[coverage(off)]
+    #[inline]
+    fn from(error: prost::DecodeError) -> Self {
+        Error::Deserialization(format!("protobuf error: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable() {
+        let errors = [
+            Error::InvalidOperation(String::new()),
+            Error::InvalidState(String::new()),
+            Error::EmptyKeyOrValue,
+            Error::InvalidProof(String::new()),
+            Error::InvalidSignature(String::new()),
+            Error::ElementExists,
+            Error::ElementNotExists,
+            Error::Deserialization(String::new()),
+            Error::Serialization(String::new()),
+            Error::Unknown(String::new()),
+            Error::InvalidLength,
+            Error::DatabaseError(String::new()),
+            Error::Transport(String::new()),
+            Error::UnsupportedWireVersion(0),
+            Error::MemoryBudgetExceeded { used: 0, budget: 0 },
+        ];
+
+        for (error, code) in errors.iter().zip(1..) {
+            assert_eq!(error.code(), code, "{error:?}");
+            assert_eq!(ErrorCode::from_u32(code), Some(ErrorCode::from(error)));
+        }
+        assert_eq!(ErrorCode::from_u32(0), None);
+        assert!(ErrorCode::try_from(16).is_err());
+    }
+}
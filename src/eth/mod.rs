@@ -0,0 +1,592 @@
+//! Ethereum-compatible Merkle Patricia Trie.
+//!
+//! Ethereum commits to its state, storage and receipts with a hexary Merkle Patricia Trie whose
+//! nodes are RLP-encoded and hashed with Keccak-256. Its layout differs from [`Trie`], so it is
+//! provided as a separate type, [`EthTrie`], with the same shape of API. [`verify_proof`] checks
+//! the proofs returned by `eth_getProof`, or by [`EthTrie::prove`].
+//!
+//! State and storage tries are "secure" tries: their keys are the Keccak-256 hashes of the
+//! account address or storage slot, and their values are RLP-encoded. Receipt and transaction
+//! tries are keyed by the RLP encoding of the index. Keys and values are used here as given, so
+//! callers hash or encode them as the trie they are verifying expects.
+//!
+//! [`Trie`]: crate::prelude::Trie
+
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+
+use sha3::Keccak256;
+
+use crate::prelude::*;
+
+pub mod rlp;
+
+use self::rlp::Item;
+
+/// The root of an empty trie, `keccak256(rlp(""))`.
+pub const EMPTY_ROOT: Hash = Hash::from_array([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Box<[Node; 16]>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl Node {
+    fn branch() -> Self {
+        Node::Branch {
+            children: Box::default(),
+            value: None,
+        }
+    }
+
+    fn insert(self, path: &[u8], value: Vec<u8>) -> Self {
+        match self {
+            Node::Empty => Node::Leaf {
+                path: path.to_vec(),
+                value,
+            },
+            Node::Leaf {
+                path: leaf_path,
+                value: leaf_value,
+            } => {
+                if leaf_path == path {
+                    return Node::Leaf {
+                        path: leaf_path,
+                        value,
+                    };
+                }
+
+                let common = common_prefix(&leaf_path, path);
+                let branch = Node::branch()
+                    .insert(&leaf_path[common..], leaf_value)
+                    .insert(&path[common..], value);
+
+                Self::extend(&path[..common], branch)
+            }
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => {
+                let common = common_prefix(&extension_path, path);
+
+                if common == extension_path.len() {
+                    return Node::Extension {
+                        child: Box::new(child.insert(&path[common..], value)),
+                        path: extension_path,
+                    };
+                }
+
+                let mut children: Box<[Node; 16]> = Box::default();
+                children[usize::from(extension_path[common])] =
+                    Self::extend(&extension_path[common + 1..], *child);
+
+                let branch = Node::Branch {
+                    children,
+                    value: None,
+                }
+                .insert(&path[common..], value);
+
+                Self::extend(&path[..common], branch)
+            }
+            Node::Branch {
+                mut children,
+                value: branch_value,
+            } => match path.split_first() {
+                None => Node::Branch {
+                    children,
+                    value: Some(value),
+                },
+                Some((&nibble, rest)) => {
+                    let child = &mut children[usize::from(nibble)];
+                    *child = core::mem::take(child).insert(rest, value);
+
+                    Node::Branch {
+                        children,
+                        value: branch_value,
+                    }
+                }
+            },
+        }
+    }
+
+    /// Prefixes `node` with an extension, unless the path is empty.
+    fn extend(path: &[u8], node: Node) -> Node {
+        if path.is_empty() {
+            return node;
+        }
+
+        Node::Extension {
+            path: path.to_vec(),
+            child: Box::new(node),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            Node::Empty => rlp::encode_bytes(&[]),
+            Node::Leaf { path, value } => {
+                let mut payload = rlp::encode_bytes(&hex_prefix(path, true));
+                payload.extend(rlp::encode_bytes(value));
+                rlp::encode_list(&payload)
+            }
+            Node::Extension { path, child } => {
+                let mut payload = rlp::encode_bytes(&hex_prefix(path, false));
+                payload.extend(child.reference());
+                rlp::encode_list(&payload)
+            }
+            Node::Branch { children, value } => {
+                let mut payload = Vec::new();
+                for child in children.iter() {
+                    payload.extend(child.reference());
+                }
+                payload.extend(rlp::encode_bytes(value.as_deref().unwrap_or_default()));
+                rlp::encode_list(&payload)
+            }
+        }
+    }
+
+    /// How a parent refers to this node: inline if its encoding is shorter than a hash, or by
+    /// the hash of its encoding otherwise.
+    fn reference(&self) -> Vec<u8> {
+        let encoded = self.encode();
+
+        if encoded.len() < 32 {
+            encoded
+        } else {
+            rlp::encode_bytes(Hash::digest::<Keccak256>(&encoded).as_ref())
+        }
+    }
+
+    fn get(&self, path: &[u8]) -> Option<&[u8]> {
+        match self {
+            Node::Empty => None,
+            Node::Leaf {
+                path: leaf_path,
+                value,
+            } => (leaf_path == path).then_some(value.as_slice()),
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => child.get(path.strip_prefix(extension_path.as_slice())?),
+            Node::Branch { children, value } => match path.split_first() {
+                None => value.as_deref(),
+                Some((&nibble, rest)) => children[usize::from(nibble)].get(rest),
+            },
+        }
+    }
+
+    /// Collects the encoding of every node on the path that is referenced by hash.
+    fn prove(&self, path: &[u8], proof: &mut Vec<Vec<u8>>) {
+        let encoded = self.encode();
+        if encoded.len() >= 32 || proof.is_empty() {
+            proof.push(encoded);
+        }
+
+        match self {
+            Node::Empty | Node::Leaf { .. } => {}
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => {
+                if let Some(rest) = path.strip_prefix(extension_path.as_slice()) {
+                    child.prove(rest, proof);
+                }
+            }
+            Node::Branch { children, .. } => {
+                if let Some((&nibble, rest)) = path.split_first() {
+                    children[usize::from(nibble)].prove(rest, proof);
+                }
+            }
+        }
+    }
+}
+
+/// A hexary Merkle Patricia Trie, hashed the same way as Ethereum's.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::{eth::{verify_proof, EthTrie}, prelude::*};
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = EthTrie::empty();
+///     trie.insert(b"doe", b"reindeer")?;
+///     trie.insert(b"dog", b"puppy")?;
+///     trie.insert(b"dogglesworth", b"cat")?;
+///
+///     assert_eq!(
+///         trie.root.to_hex(),
+///         "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+///     );
+///
+///     let proof = trie.prove(b"dog");
+///     assert_eq!(verify_proof(&trie.root, b"dog", &proof)?, Some(b"puppy".to_vec()));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthTrie {
+    pub root: Hash,
+    node: Node,
+}
+
+impl EthTrie {
+    /// Constructs a new empty trie.
+    #[inline]
+    pub fn empty() -> Self {
+        Self {
+            root: EMPTY_ROOT,
+            node: Node::Empty,
+        }
+    }
+
+    /// Checks if the trie is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.node == Node::Empty
+    }
+
+    /// Inserts a key-value pair, replacing any previous value for the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the value is empty, as Ethereum treats empty
+    /// values as deletions.
+    #[inline]
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if value.is_empty() {
+            return Err(Error::EmptyKeyOrValue);
+        }
+
+        self.node = core::mem::take(&mut self.node).insert(&nibbles(key), value.to_vec());
+        self.root = Hash::digest::<Keccak256>(&self.node.encode());
+
+        Ok(())
+    }
+
+    /// Returns the value for the given key, if any.
+    #[inline]
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.node.get(&nibbles(key))
+    }
+
+    /// Verifies if a key-value pair exists in the trie.
+    #[inline]
+    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
+        self.get(key) == Some(value)
+    }
+
+    /// Builds a proof for `key`, in the format returned by `eth_getProof`: the encoding of
+    /// every node from the root down to the key, skipping nodes inlined in their parent.
+    ///
+    /// Proofs for absent keys prove their absence.
+    #[inline]
+    pub fn prove(&self, key: &[u8]) -> Vec<Vec<u8>> {
+        let mut proof = Vec::new();
+        self.node.prove(&nibbles(key), &mut proof);
+        proof
+    }
+}
+
+impl Default for EthTrie {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// Verifies a proof for `key` against `root`, returning the value it proves, or `None` if it
+/// proves that the key is absent.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidProof`] if a node doesn't match the hash its parent commits to, or
+/// the proof ends before reaching the key, and [`Error::Deserialization`] if a node is not valid
+/// RLP.
+#[inline]
+pub fn verify_proof(root: &Hash, key: &[u8], proof: &[Vec<u8>]) -> Result<Option<Vec<u8>>> {
+    if proof.is_empty() && *root == EMPTY_ROOT {
+        return Ok(None);
+    }
+
+    let path = nibbles(key);
+    let mut path = path.as_slice();
+    let mut proof = proof.iter();
+    let mut next = Reference::Hash(*root);
+
+    loop {
+        let node = match next {
+            Reference::Hash(hash) => {
+                let node = proof
+                    .next()
+                    .ok_or_else(|| Error::InvalidProof("proof ends before the key".to_string()))?;
+
+                if Hash::digest::<Keccak256>(node) != hash {
+                    return Err(Error::InvalidProof(
+                        "node does not match the hash committed by its parent".to_string(),
+                    ));
+                }
+
+                node.as_slice()
+            }
+            Reference::Inline(node) => node,
+        };
+
+        let (node, _) = rlp::decode(node)?;
+        if node == Item::Bytes(&[]) {
+            return Ok(None);
+        }
+
+        let items = node.items()?;
+        next = match items.as_slice() {
+            [children @ .., value] if children.len() == 16 => match path.split_first() {
+                None => {
+                    let value = value.bytes()?;
+                    return Ok((!value.is_empty()).then(|| value.to_vec()));
+                }
+                Some((&nibble, rest)) => {
+                    path = rest;
+                    match Reference::from_item(children[usize::from(nibble)])? {
+                        Some(reference) => reference,
+                        None => return Ok(None),
+                    }
+                }
+            },
+            [encoded_path, item] => {
+                let (node_path, is_leaf) = decode_hex_prefix(encoded_path.bytes()?)?;
+
+                if is_leaf {
+                    return (node_path == path)
+                        .then(|| item.bytes().map(<[u8]>::to_vec))
+                        .transpose();
+                }
+
+                match path.strip_prefix(node_path.as_slice()) {
+                    Some(rest) => path = rest,
+                    None => return Ok(None),
+                }
+
+                Reference::from_item(*item)?.ok_or_else(|| {
+                    Error::InvalidProof("extension node without a child".to_string())
+                })?
+            }
+            _ => {
+                return Err(Error::InvalidProof(
+                    "trie nodes must have 2 or 17 items".to_string(),
+                ))
+            }
+        };
+    }
+}
+
+/// How a node refers to one of its children.
+#[derive(Clone, Copy)]
+enum Reference<'a> {
+    Hash(Hash),
+    Inline(&'a [u8]),
+}
+
+impl<'a> Reference<'a> {
+    fn from_item(item: Item<'a>) -> Result<Option<Self>> {
+        match item {
+            Item::Bytes([]) => Ok(None),
+            Item::Bytes(hash) => Ok(Some(Reference::Hash(Hash::from_bytes(hash)?))),
+            Item::List(node) => Ok(Some(Reference::Inline(node))),
+        }
+    }
+}
+
+fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Packs a path of nibbles into bytes, flagging whether it has an odd length and whether it
+/// belongs to a leaf.
+fn hex_prefix(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 0x20 } else { 0x00 };
+    let mut out = Vec::with_capacity(path.len() / 2 + 1);
+
+    let rest = if path.len() % 2 == 1 {
+        out.push(flag | 0x10 | path[0]);
+        &path[1..]
+    } else {
+        out.push(flag);
+        path
+    };
+
+    out.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    out
+}
+
+fn decode_hex_prefix(bytes: &[u8]) -> Result<(Vec<u8>, bool)> {
+    let (&first, rest) = bytes
+        .split_first()
+        .ok_or_else(|| Error::InvalidProof("empty node path".to_string()))?;
+
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(Error::InvalidProof("invalid node path flag".to_string()));
+    }
+
+    let mut path = Vec::with_capacity(bytes.len() * 2);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(nibbles(rest));
+
+    Ok((path, flag & 2 == 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    fn build(entries: &[(&[u8], &[u8])]) -> Result<EthTrie> {
+        let mut trie = EthTrie::empty();
+        for (key, value) in entries {
+            trie.insert(key, value)?;
+        }
+        Ok(trie)
+    }
+
+    fn entries() -> impl Strategy<Value = Vec<(Vec<u8>, Vec<u8>)>> {
+        vec((vec(0..4u8, 0..6), vec(any::<u8>(), 1..48)), 1..16)
+    }
+
+    #[test]
+    fn test_empty_root() {
+        assert_eq!(
+            EMPTY_ROOT,
+            Hash::digest::<Keccak256>(&rlp::encode_bytes(&[]))
+        );
+        assert_eq!(EthTrie::empty().root, EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_known_roots() -> Result<()> {
+        // Vectors from the Patricia Tree wiki page and the `trieanyorder` suite of ethereum/tests
+        let puppy = build(&[
+            (b"do", b"verb"),
+            (b"dog", b"puppy"),
+            (b"doge", b"coin"),
+            (b"horse", b"stallion"),
+        ])?;
+        assert_eq!(
+            puppy.root.to_hex(),
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+
+        let foo = build(&[(b"foo", b"bar"), (b"food", b"bass")])?;
+        assert_eq!(
+            foo.root.to_hex(),
+            "17beaa1648bafa633cda809c90c04af50fc8aed3cb40d16efbddee6fdf63c4c3"
+        );
+
+        let dogs = build(&[
+            (b"doe", b"reindeer"),
+            (b"dog", b"puppy"),
+            (b"dogglesworth", b"cat"),
+        ])?;
+        assert_eq!(
+            dogs.root.to_hex(),
+            "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_empty_values() {
+        assert!(matches!(
+            EthTrie::empty().insert(b"key", b""),
+            Err(Error::EmptyKeyOrValue)
+        ));
+    }
+
+    #[proptest]
+    fn test_root_is_independent_of_insertion_order(
+        #[strategy(entries())] entries: Vec<(Vec<u8>, Vec<u8>)>,
+    ) {
+        let mut forward = EthTrie::empty();
+        let mut latest = alloc::collections::BTreeMap::new();
+        for (key, value) in entries.iter() {
+            forward.insert(key, value)?;
+            latest.insert(key.clone(), value.clone());
+        }
+
+        let mut backward = EthTrie::empty();
+        for (key, value) in latest.iter().rev() {
+            backward.insert(key, value)?;
+        }
+
+        prop_assert_eq!(forward.root, backward.root);
+    }
+
+    #[proptest]
+    fn test_proofs_verify(
+        #[strategy(entries())] entries: Vec<(Vec<u8>, Vec<u8>)>,
+        #[strategy(vec(0..4u8, 0..6))] absent: Vec<u8>,
+    ) {
+        let mut trie = EthTrie::empty();
+        for (key, value) in entries.iter() {
+            trie.insert(key, value)?;
+        }
+
+        for (key, _) in entries.iter() {
+            let proof = trie.prove(key);
+            let value = verify_proof(&trie.root, key, &proof)?;
+            prop_assert_eq!(value.as_deref(), trie.get(key));
+        }
+
+        let proof = trie.prove(&absent);
+        let value = verify_proof(&trie.root, &absent, &proof)?;
+        prop_assert_eq!(value.as_deref(), trie.get(&absent));
+    }
+
+    #[proptest]
+    fn test_tampered_proofs_are_rejected(
+        #[strategy(entries())] entries: Vec<(Vec<u8>, Vec<u8>)>,
+        index: prop::sample::Index,
+        flip: u8,
+    ) {
+        prop_assume!(flip != 0);
+
+        let mut trie = EthTrie::empty();
+        for (key, value) in entries.iter() {
+            trie.insert(key, value)?;
+        }
+
+        let key = &entries[0].0;
+        let mut proof = trie.prove(key);
+        let node = &mut proof[0];
+        let byte = index.index(node.len());
+        node[byte] ^= flip;
+
+        prop_assert!(verify_proof(&trie.root, key, &proof).is_err());
+    }
+}
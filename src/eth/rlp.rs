@@ -0,0 +1,197 @@
+//! Recursive Length Prefix encoding, as used to serialize Ethereum trie nodes.
+//!
+//! Only the subset needed for trie nodes is implemented: byte strings, and lists of already
+//! encoded items.
+
+use alloc::{string::ToString, vec::Vec};
+
+use crate::prelude::*;
+
+/// A decoded RLP item, borrowing from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item<'a> {
+    /// A byte string, without its header.
+    Bytes(&'a [u8]),
+    /// A list, as its full encoding including the header.
+    List(&'a [u8]),
+}
+
+impl<'a> Item<'a> {
+    /// Returns the contents of a byte string.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the item is a list.
+    #[inline]
+    pub fn bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            Item::Bytes(bytes) => Ok(bytes),
+            Item::List(_) => Err(Error::Deserialization(
+                "expected an RLP string, found a list".to_string(),
+            )),
+        }
+    }
+
+    /// Decodes the items of a list.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the item is a byte string, or any of the list items
+    /// is malformed.
+    #[inline]
+    pub fn items(&self) -> Result<Vec<Item<'a>>> {
+        let Item::List(raw) = self else {
+            return Err(Error::Deserialization(
+                "expected an RLP list, found a string".to_string(),
+            ));
+        };
+
+        let (offset, len) = header(raw)?;
+        let mut payload = &raw[offset..offset + len];
+        let mut items = Vec::new();
+
+        while !payload.is_empty() {
+            let (item, rest) = decode(payload)?;
+            items.push(item);
+            payload = rest;
+        }
+
+        Ok(items)
+    }
+}
+
+/// Encodes a byte string.
+#[inline]
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if let [byte @ 0x00..=0x7f] = bytes {
+        return Vec::from([*byte]);
+    }
+
+    let mut out = encode_header(0x80, bytes.len());
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Encodes a list, from the concatenated encodings of its items.
+#[inline]
+pub fn encode_list(payload: &[u8]) -> Vec<u8> {
+    let mut out = encode_header(0xc0, payload.len());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Decodes the first item of `input`, returning it along with the remaining bytes.
+///
+/// # Errors
+///
+/// Returns [`Error::Deserialization`] if the input is empty or truncated.
+#[inline]
+pub fn decode(input: &[u8]) -> Result<(Item<'_>, &[u8])> {
+    let (offset, len) = header(input)?;
+    let (raw, rest) = input.split_at(offset + len);
+
+    let item = match input[0] {
+        0x00..=0x7f => Item::Bytes(raw),
+        0x80..=0xbf => Item::Bytes(&raw[offset..]),
+        _ => Item::List(raw),
+    };
+
+    Ok((item, rest))
+}
+
+fn encode_header(base: u8, len: usize) -> Vec<u8> {
+    if len <= 55 {
+        return Vec::from([base + len as u8]);
+    }
+
+    let len = len.to_be_bytes();
+    let len = &len[len.iter().take_while(|&&byte| byte == 0).count()..];
+
+    let mut out = Vec::with_capacity(1 + len.len());
+    out.push(base + 55 + len.len() as u8);
+    out.extend_from_slice(len);
+    out
+}
+
+/// Returns the length of the header of the first item, and the length of its payload.
+fn header(input: &[u8]) -> Result<(usize, usize)> {
+    let truncated = || Error::Deserialization("truncated RLP item".to_string());
+    let prefix = *input.first().ok_or_else(truncated)?;
+
+    let (offset, len) = match prefix {
+        0x00..=0x7f => (0, 1),
+        0x80..=0xb7 => (1, usize::from(prefix - 0x80)),
+        0xc0..=0xf7 => (1, usize::from(prefix - 0xc0)),
+        0xb8..=0xbf | 0xf8..=0xff => {
+            let len_of_len = usize::from(prefix - if prefix < 0xc0 { 0xb7 } else { 0xf7 });
+            let bytes = input.get(1..1 + len_of_len).ok_or_else(truncated)?;
+
+            if bytes.len() > core::mem::size_of::<usize>() {
+                return Err(Error::Deserialization("RLP item is too long".to_string()));
+            }
+
+            let len = bytes
+                .iter()
+                .fold(0usize, |len, &byte| (len << 8) | usize::from(byte));
+            (1 + len_of_len, len)
+        }
+    };
+
+    if input.len() - offset < len {
+        return Err(truncated());
+    }
+
+    Ok((offset, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[test]
+    fn test_known_encodings() {
+        assert_eq!(encode_bytes(b""), [0x80]);
+        assert_eq!(encode_bytes(&[0x0f]), [0x0f]);
+        assert_eq!(encode_bytes(&[0x80]), [0x81, 0x80]);
+        assert_eq!(encode_bytes(b"dog"), [0x83, b'd', b'o', b'g']);
+        assert_eq!(encode_list(&[]), [0xc0]);
+
+        let long = [b'a'; 56];
+        assert_eq!(encode_bytes(&long)[..2], [0xb8, 56]);
+    }
+
+    #[proptest]
+    fn test_bytes_roundtrip(#[strategy(vec(any::<u8>(), 0..300))] bytes: Vec<u8>) {
+        let encoded = encode_bytes(&bytes);
+        let (item, rest) = decode(&encoded)?;
+
+        prop_assert_eq!(item, Item::Bytes(&bytes));
+        prop_assert!(rest.is_empty());
+    }
+
+    #[proptest]
+    fn test_list_roundtrip(#[strategy(vec(vec(any::<u8>(), 0..80), 0..8))] items: Vec<Vec<u8>>) {
+        let payload: Vec<u8> = items.iter().flat_map(|item| encode_bytes(item)).collect();
+        let encoded = encode_list(&payload);
+        let (list, rest) = decode(&encoded)?;
+
+        prop_assert!(rest.is_empty());
+        let decoded = list.items()?;
+        prop_assert_eq!(decoded.len(), items.len());
+        for (item, expected) in decoded.iter().zip(items.iter()) {
+            prop_assert_eq!(item.bytes()?, &expected[..]);
+        }
+    }
+
+    #[proptest]
+    fn test_truncated_input_is_rejected(
+        #[strategy(vec(any::<u8>(), 2..80))] bytes: Vec<u8>,
+        #[strategy(1..#bytes.len())] cut: usize,
+    ) {
+        let encoded = encode_bytes(&bytes);
+        prop_assert!(decode(&encoded[..cut]).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+pragma solidity ^0.8.12;
+
+/// @title MutreeVerifier
+/// @notice Verifies that a key-value pair is committed under the root of a mutree trie hashed with
+///         Keccak-256.
+/// @dev Proofs are produced by `Proof::to_evm_calldata`, as the ABI encoding of a `bytes[]`
+///      holding the bytes every step contributes to the root, in the order they are hashed:
+///
+///      - Branch: the number of non-zero neighbors, followed by each of them.
+///      - Fork: `0xff`, the neighbor nibble, its prefix and its root.
+///      - Leaf: `0x00`, the key hash, the value hash and, if present, the 12-byte timestamp.
+library MutreeVerifier {
+    /// @notice Checks that `key` maps to `value` in the trie with the given `root`.
+    /// @param root The root of the trie.
+    /// @param key The key, as given to `Trie::insert`.
+    /// @param value The value, as given to `Trie::insert`.
+    /// @param proof The output of `Proof::to_evm_calldata`.
+    function verify(bytes32 root, bytes memory key, bytes memory value, bytes memory proof)
+        internal
+        pure
+        returns (bool)
+    {
+        bytes[] memory steps = abi.decode(proof, (bytes[]));
+        if (steps.length == 0) {
+            return false;
+        }
+
+        bytes32 keyHash = keccak256(key);
+        bytes32 valueHash = keccak256(value);
+        bool found = false;
+        bytes memory preimage;
+
+        for (uint256 i = 0; i < steps.length; i++) {
+            bytes memory step = steps[i];
+            if (!isWellFormed(step)) {
+                return false;
+            }
+
+            found = found || isLeaf(step, keyHash, valueHash);
+            preimage = bytes.concat(preimage, step);
+        }
+
+        return found && keccak256(preimage) == root;
+    }
+
+    function isWellFormed(bytes memory step) private pure returns (bool) {
+        if (step.length == 0) {
+            return false;
+        }
+
+        uint8 marker = uint8(step[0]);
+
+        if (marker == 0xff) {
+            return step.length >= 34;
+        }
+
+        if (marker == 0x00 && step.length > 1) {
+            return step.length == 65 || step.length == 77;
+        }
+
+        return marker <= 4 && step.length == 1 + 32 * uint256(marker);
+    }
+
+    function isLeaf(bytes memory step, bytes32 keyHash, bytes32 valueHash) private pure returns (bool) {
+        if (step.length < 65 || uint8(step[0]) != 0x00) {
+            return false;
+        }
+
+        bytes32 stepKey;
+        bytes32 stepValue;
+        assembly {
+            stepKey := mload(add(step, 33))
+            stepValue := mload(add(step, 65))
+        }
+
+        return stepKey == keyHash && stepValue == valueHash;
+    }
+}
@@ -0,0 +1,215 @@
+//! Verification of proofs on EVM chains.
+//!
+//! Bridges anchored on a trie root check memberships on-chain with a Solidity library, generated
+//! by [`solidity_verifier`], against proofs encoded with [`Proof::to_evm_calldata`]. The library
+//! uses the `keccak256` precompile, so only tries built with [`Keccak256`] can be verified.
+//! It hashes steps in [`RootFormat::V1`], so the tries must keep the default format.
+//!
+//! [`Keccak256`]: sha3::Keccak256
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::prelude::*;
+
+const VERIFIER: &str = include_str!("MutreeVerifier.sol");
+const VERIFIER_NAME: &str = "MutreeVerifier";
+
+/// Emits the source of a Solidity library verifying proofs of a `Trie<Keccak256>`.
+///
+/// The library exposes a single function, to be called with the output of
+/// [`Proof::to_evm_calldata`]:
+///
+/// ```solidity
+/// function verify(bytes32 root, bytes memory key, bytes memory value, bytes memory proof)
+///     internal pure returns (bool);
+/// ```
+///
+/// # Arguments
+///
+/// * `name` - The name of the library, so several versions can live in the same project
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidOperation`] if `name` is not a valid Solidity identifier.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::evm::solidity_verifier;
+///
+/// let source = solidity_verifier("BridgeVerifier").unwrap();
+/// assert!(source.contains("library BridgeVerifier {"));
+/// assert!(solidity_verifier("0Bridge").is_err());
+/// ```
+#[inline]
+pub fn solidity_verifier(name: &str) -> Result<String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+
+    if !valid {
+        return Err(Error::InvalidOperation(format!(
+            "{name:?} is not a valid Solidity identifier"
+        )));
+    }
+
+    Ok(VERIFIER.replace(VERIFIER_NAME, name))
+}
+
+impl Proof {
+    /// Encodes the proof for the Solidity library emitted by [`solidity_verifier`].
+    ///
+    /// The result is the ABI encoding of a `bytes[]`, with one element per step holding the bytes
+    /// it contributes to the root, in the order in which they are hashed. The verifier hashes
+    /// their concatenation, so it doesn't need to reconstruct the steps themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use sha3::Keccak256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Keccak256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let calldata = trie.proof().to_evm_calldata();
+    ///     assert_eq!(calldata.len() % 32, 0);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn to_evm_calldata(&self) -> Vec<u8> {
+        let segments: Vec<Vec<u8>> = self
+            .canonical_steps()
+            .into_iter()
+            .map(|step| {
+                let mut segment = Vec::new();
+                step.commit(RootFormat::V1, |bytes| segment.extend_from_slice(bytes));
+                segment
+            })
+            .collect();
+
+        let mut out = Vec::new();
+        write_word(&mut out, 32);
+        write_word(&mut out, segments.len());
+
+        // Offsets are relative to the first word after the length of the array
+        let mut offset = 32 * segments.len();
+        for segment in segments.iter() {
+            write_word(&mut out, offset);
+            offset += 32 + padded_len(segment.len());
+        }
+
+        for segment in segments.iter() {
+            write_word(&mut out, segment.len());
+            out.extend_from_slice(segment);
+            out.resize(out.len() + padded_len(segment.len()) - segment.len(), 0);
+        }
+
+        out
+    }
+}
+
+/// Writes `value` as a big-endian, 32-byte ABI word.
+fn write_word(out: &mut Vec<u8>, value: usize) {
+    let mut word = [0u8; 32];
+    word[32 - core::mem::size_of::<usize>()..].copy_from_slice(&value.to_be_bytes());
+    out.extend_from_slice(&word);
+}
+
+fn padded_len(len: usize) -> usize {
+    len.div_ceil(32) * 32
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use sha3::{Digest, Keccak256};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    fn read_word(bytes: &[u8], at: usize) -> usize {
+        usize::from_be_bytes(bytes[at + 24..at + 32].try_into().unwrap())
+    }
+
+    /// Decodes the calldata as `abi.decode(proof, (bytes[]))` does.
+    fn decode(calldata: &[u8]) -> Vec<Vec<u8>> {
+        let array = read_word(calldata, 0);
+        let len = read_word(calldata, array);
+        let base = array + 32;
+
+        (0..len)
+            .map(|i| {
+                let at = base + read_word(calldata, base + 32 * i);
+                let size = read_word(calldata, at);
+                calldata[at + 32..at + 32 + size].to_vec()
+            })
+            .collect()
+    }
+
+    /// Mirrors `MutreeVerifier.verify`.
+    fn verify(root: Hash, key: &[u8], value: &[u8], calldata: &[u8]) -> bool {
+        let steps = decode(calldata);
+        let key = Keccak256::digest(key);
+        let value = Keccak256::digest(value);
+
+        let found = steps.iter().any(|step| {
+            step.len() >= 65 && step[0] == 0 && step[1..33] == key[..] && step[33..65] == value[..]
+        });
+
+        found && Hash::from_digest(&Keccak256::digest(steps.concat())) == root
+    }
+
+    #[test]
+    fn test_single_leaf_layout() -> Result<()> {
+        let mut trie = Trie::<Keccak256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+
+        let calldata = trie.proof().to_evm_calldata();
+
+        assert_eq!(read_word(&calldata, 0), 32);
+        assert_eq!(read_word(&calldata, 32), 1);
+        assert_eq!(read_word(&calldata, 64), 32);
+        assert_eq!(read_word(&calldata, 96), 65);
+        assert_eq!(calldata[128], 0);
+        assert_eq!(&calldata[129..161], &Keccak256::digest(b"key")[..]);
+        assert_eq!(&calldata[161..193], &Keccak256::digest(b"value")[..]);
+        assert_eq!(calldata.len(), 128 + 96);
+
+        Ok(())
+    }
+
+    #[proptest]
+    fn test_calldata_verifies_against_root(
+        #[strategy(vec((vec(any::<u8>(), 1..16), vec(any::<u8>(), 1..16)), 1..8))] entries: Vec<(
+            Vec<u8>,
+            Vec<u8>,
+        )>,
+    ) {
+        let mut trie = Trie::<Keccak256>::empty();
+        for (key, value) in entries.iter() {
+            trie.insert(key, &value[..])?;
+        }
+
+        let calldata = trie.proof().to_evm_calldata();
+        let (key, value) = entries.last().unwrap();
+
+        prop_assert!(verify(trie.root(), key, value, &calldata));
+        prop_assert!(!verify(trie.root(), key, b"wrong value", &calldata));
+    }
+
+    #[test]
+    fn test_verifier_name() {
+        let source = solidity_verifier("Bridge_V2").unwrap();
+
+        assert!(source.contains("library Bridge_V2 {"));
+        assert!(!source.contains(VERIFIER_NAME));
+        assert!(solidity_verifier("").is_err());
+        assert!(solidity_verifier("with space").is_err());
+    }
+}
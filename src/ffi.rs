@@ -0,0 +1,255 @@
+//! UniFFI bindings, for verifying proofs in iOS and Android wallets with the same code as the
+//! backend.
+//!
+//! Like the WebAssembly bindings, they fix the digest to Blake2b-256, and exchange roots, hashes
+//! and proofs in their byte encoding. Errors are raised as a flat `Error` enum, whose variants
+//! carry the message of the Rust error. They are declared in the order of their
+//! [`Error::code`](crate::prelude::Error::code), so bindings forwarding failures elsewhere
+//! can number them the same way.
+//!
+//! Build the library with the `uniffi` feature as a `cdylib` or `staticlib`, and generate the
+//! Swift or Kotlin sources from it with `uniffi-bindgen`:
+//!
+//! ```sh
+//! cargo rustc --lib --release --features uniffi --crate-type cdylib
+//! uniffi-bindgen generate --library target/release/libmutree.so --language kotlin --out-dir out
+//! ```
+//!
+//! ```kotlin
+//! val trie = Trie()
+//! trie.insert(key, value)
+//!
+//! verify(trie.root(), key, value, trie.proof())
+//! ```
+
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use blake2::{digest::consts::U32, Blake2b};
+
+use crate::prelude::*;
+
+/// The digest used by the bindings.
+pub type Blake2b256 = Blake2b<U32>;
+
+type Inner = crate::prelude::Trie<Blake2b256>;
+
+/// Checks that `proof` commits to `root`, and proves that `key` is set to `value`.
+///
+/// # Errors
+///
+/// Fails if `root` is not 32 bytes long, or if `proof` can't be decoded.
+#[uniffi::export]
+#[inline]
+pub fn verify(root: Vec<u8>, key: Vec<u8>, value: Vec<u8>, proof: Vec<u8>) -> Result<bool> {
+    verify_with(&root, &key, &value, &proof, VerifyMode::Lenient)
+}
+
+/// Checks like [`verify`], also rejecting proofs that repeat a step or hold more than one leaf
+/// for `key`.
+///
+/// # Errors
+///
+/// Fails if `root` is not 32 bytes long, or if `proof` can't be decoded.
+#[uniffi::export]
+#[inline]
+pub fn verify_strict(root: Vec<u8>, key: Vec<u8>, value: Vec<u8>, proof: Vec<u8>) -> Result<bool> {
+    verify_with(&root, &key, &value, &proof, VerifyMode::Strict)
+}
+
+fn verify_with(
+    root: &[u8],
+    key: &[u8],
+    value: &[u8],
+    proof: &[u8],
+    mode: VerifyMode,
+) -> Result<bool> {
+    let root = Hash::from_bytes(root)?;
+    let trie = Inner::from_proof(Proof::from_bytes(proof)?);
+
+    Ok(trie.root() == root && trie.verify_with(key, value, mode))
+}
+
+/// A Trie handle for Swift and Kotlin.
+///
+/// Handles are shared between threads by the generated code, so the Trie is kept behind a lock.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct Trie {
+    inner: RwLock<Inner>,
+}
+
+impl Trie {
+    fn read(&self) -> RwLockReadGuard<'_, Inner> {
+        self.inner
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Inner> {
+        self.inner
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[uniffi::export]
+impl Trie {
+    /// Creates an empty Trie.
+    #[uniffi::constructor]
+    #[inline]
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// Decodes a Trie from the bytes returned by [`Trie::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the bytes are not a valid proof.
+    #[uniffi::constructor]
+    #[inline]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            inner: RwLock::new(Inner::from_bytes(&bytes)?),
+        }))
+    }
+
+    /// Encodes the Trie, as its proof.
+    #[inline]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.read().to_bytes()
+    }
+
+    /// Inserts a key-value pair, returning the hash of the value.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key is empty.
+    #[inline]
+    pub fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(self.write().insert(&key, &value[..])?.to_bytes_vec())
+    }
+
+    /// Checks if the Trie proves that `key` is set to `value`.
+    #[inline]
+    pub fn verify(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.read().verify(&key, &value)
+    }
+
+    /// Merges another Trie into this one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the merge itself fails.
+    #[inline]
+    pub fn merge(&self, other: Arc<Trie>) -> Result<()> {
+        let other = other.read().clone();
+        self.write().merge(&other)
+    }
+
+    /// The root of the Trie.
+    #[inline]
+    pub fn root(&self) -> Vec<u8> {
+        self.read().root().to_bytes_vec()
+    }
+
+    /// The proof of the Trie, in the format expected by [`verify`].
+    #[inline]
+    pub fn proof(&self) -> Vec<u8> {
+        self.read().proof().to_bytes()
+    }
+
+    /// Checks if the Trie has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_verify_matches_trie(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+        other: Vec<u8>,
+    ) {
+        let trie = Trie::new();
+        trie.insert(key.clone(), value.clone())?;
+
+        prop_assert!(verify(
+            trie.root(),
+            key.clone(),
+            value.clone(),
+            trie.proof()
+        )?);
+        prop_assert_eq!(
+            verify(trie.root(), key, other.clone(), trie.proof())?,
+            value == other
+        );
+    }
+
+    #[proptest]
+    fn test_verify_strict_rejects_repeated_steps(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        let trie = Trie::new();
+        trie.insert(key.clone(), value.clone())?;
+        prop_assert!(verify_strict(
+            trie.root(),
+            key.clone(),
+            value.clone(),
+            trie.proof()
+        )?);
+
+        let mut repeated = trie.read().clone();
+        let first = repeated.proof()[0].clone();
+        repeated.proof_mut().push(first);
+        repeated.refresh_root();
+
+        let (root, proof) = (
+            repeated.root().to_bytes_vec(),
+            repeated.proof().to_bytes_vec(),
+        );
+        prop_assert!(verify(
+            root.clone(),
+            key.clone(),
+            value.clone(),
+            proof.clone()
+        )?);
+        prop_assert!(!verify_strict(root, key, value, proof)?);
+    }
+
+    #[test]
+    fn test_roundtrip_and_merge() -> Result<()> {
+        let a = Trie::new();
+        a.insert(b"a".to_vec(), b"1".to_vec())?;
+        let b = Trie::from_bytes(a.to_bytes())?;
+        assert_eq!(a.root(), b.root());
+
+        b.insert(b"b".to_vec(), b"2".to_vec())?;
+        a.merge(b.clone())?;
+        assert!(a.verify(b"b".to_vec(), b"2".to_vec()));
+        assert_eq!(a.root(), b.root());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        assert!(verify(
+            vec![0; 31],
+            b"key".to_vec(),
+            b"value".to_vec(),
+            Proof::new().to_bytes()
+        )
+        .is_err());
+        assert!(Trie::new().insert(Vec::new(), b"value".to_vec()).is_err());
+        assert!(Trie::from_bytes(vec![1, 2, 3]).is_err());
+    }
+}
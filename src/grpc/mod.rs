@@ -0,0 +1,514 @@
+//! Protobuf messages for replication, as defined in `proto/mutree.proto`.
+//!
+//! The messages mirror the internal types one to one, so services written in other languages
+//! can take part in replication using only the `.proto` file. Conversions into the internal
+//! types validate hash lengths and required fields, but do not verify roots: use
+//! [`StateBundle::verify`] for that, as with any other bundle.
+//!
+//! Sync requests list the [`wire`](crate::wire) versions the requester decodes, and answers carry
+//! the bundle as a versioned wire message at the newest version both sides support. Requesters
+//! listing no version in common, such as those written from the `.proto` file alone, get the
+//! bundle as a protobuf message instead.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use blake2::Blake2s256;
+//! use mutree::{grpc::proto, prelude::*};
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut trie = Trie::<Blake2s256>::empty();
+//!     let request = proto::SyncRequest::for_trie(&trie);
+//!     trie.insert(b"key", &b"value"[..])?;
+//!
+//!     let response = proto::SyncResponse::answer(&trie, &request)?;
+//!     let bundle = response.into_bundle(&DecodeContext::default())?.unwrap();
+//!     assert_eq!(bundle.verify::<Blake2s256>()?, trie);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+pub use prost::Message;
+
+use crate::{
+    prelude::*,
+    replication::StateBundle,
+    wire::{negotiate, supported_versions, WireFormat},
+    TrieHasher,
+};
+
+pub mod proto;
+
+fn hash_field(bytes: &[u8], field: &str) -> Result<Hash> {
+    Hash::from_bytes(bytes)
+        .map_err(|_| Error::Deserialization(format!("invalid hash length for `{field}`")))
+}
+
+fn missing_field(field: &str) -> Error {
+    Error::Deserialization(format!("missing required field `{field}`"))
+}
+
+impl From<&Neighbor> for proto::Neighbor {
+    #[inline]
+    fn from(neighbor: &Neighbor) -> Self {
+        Self {
+            nibble: neighbor.nibble.into(),
+            prefix: neighbor.prefix.clone(),
+            root: neighbor.root.to_bytes_vec(),
+        }
+    }
+}
+
+impl TryFrom<proto::Neighbor> for Neighbor {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(neighbor: proto::Neighbor) -> Result<Self> {
+        Self {
+            nibble: neighbor.nibble.try_into()?,
+            prefix: neighbor.prefix,
+            root: hash_field(&neighbor.root, "Neighbor.root")?,
+        }
+        .checked()
+    }
+}
+
+impl From<Timestamp> for proto::Timestamp {
+    #[inline]
+    fn from(timestamp: Timestamp) -> Self {
+        Self {
+            wall: timestamp.wall,
+            logical: timestamp.logical,
+        }
+    }
+}
+
+impl From<proto::Timestamp> for Timestamp {
+    #[inline]
+    fn from(timestamp: proto::Timestamp) -> Self {
+        Self::new(timestamp.wall, timestamp.logical)
+    }
+}
+
+impl From<&Step> for proto::Step {
+    #[inline]
+    fn from(step: &Step) -> Self {
+        let step = match step {
+            Step::Branch { skip, neighbors } => proto::step::Step::Branch(proto::BranchStep {
+                skip: *skip as u64,
+                neighbors: neighbors.iter().map(ToBytes::to_bytes_vec).collect(),
+            }),
+            Step::Fork { skip, neighbor } => proto::step::Step::Fork(proto::ForkStep {
+                skip: *skip as u64,
+                neighbor: Some(neighbor.into()),
+            }),
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => proto::step::Step::Leaf(proto::LeafStep {
+                skip: *skip as u64,
+                key: key.to_bytes_vec(),
+                value: value.to_bytes_vec(),
+                timestamp: timestamp.map(Into::into),
+            }),
+        };
+
+        Self { step: Some(step) }
+    }
+}
+
+impl TryFrom<proto::Step> for Step {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(step: proto::Step) -> Result<Self> {
+        let step = match step.step.ok_or_else(|| missing_field("Step.step"))? {
+            proto::step::Step::Branch(branch) => {
+                if branch.neighbors.len() != 4 {
+                    return Err(Error::Deserialization(
+                        "Branch steps must have exactly 4 neighbors".to_string(),
+                    ));
+                }
+
+                let mut neighbors = [Hash::zero(); 4];
+                for (neighbor, bytes) in neighbors.iter_mut().zip(&branch.neighbors) {
+                    *neighbor = hash_field(bytes, "BranchStep.neighbors")?;
+                }
+
+                Step::Branch {
+                    skip: Step::decode_skip(branch.skip)?,
+                    neighbors,
+                }
+            }
+            proto::step::Step::Fork(fork) => Step::Fork {
+                skip: Step::decode_skip(fork.skip)?,
+                neighbor: fork
+                    .neighbor
+                    .ok_or_else(|| missing_field("ForkStep.neighbor"))?
+                    .try_into()?,
+            },
+            proto::step::Step::Leaf(leaf) => Step::Leaf {
+                skip: Step::decode_skip(leaf.skip)?,
+                key: hash_field(&leaf.key, "LeafStep.key")?,
+                value: hash_field(&leaf.value, "LeafStep.value")?,
+                timestamp: leaf.timestamp.map(Into::into),
+            },
+        };
+
+        step.checked()
+    }
+}
+
+impl From<&Proof> for proto::Proof {
+    #[inline]
+    fn from(proof: &Proof) -> Self {
+        Self {
+            steps: proof.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::Proof> for Proof {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(proof: proto::Proof) -> Result<Self> {
+        proof
+            .steps
+            .into_iter()
+            .map(Step::try_from)
+            .collect::<Result<Vec<_>>>()
+            .map(Proof::from)
+    }
+}
+
+impl From<&StateBundle> for proto::StateBundle {
+    #[inline]
+    fn from(bundle: &StateBundle) -> Self {
+        Self {
+            root: bundle.root.to_bytes_vec(),
+            proof: Some((&bundle.proof).into()),
+        }
+    }
+}
+
+impl TryFrom<proto::StateBundle> for StateBundle {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(bundle: proto::StateBundle) -> Result<Self> {
+        Ok(Self {
+            root: hash_field(&bundle.root, "StateBundle.root")?,
+            proof: bundle.proof.unwrap_or_default().try_into()?,
+        })
+    }
+}
+
+impl proto::SyncRequest {
+    /// Builds a request advertising the root of the given Trie, and the wire versions this
+    /// release decodes.
+    #[inline]
+    pub fn for_trie<D: TrieHasher + 'static>(trie: &Trie<D>) -> Self {
+        Self {
+            root: trie.root().to_bytes_vec(),
+            wire_versions: supported_versions()
+                .iter()
+                .copied()
+                .map(u32::from)
+                .collect(),
+        }
+    }
+}
+
+impl proto::SyncResponse {
+    /// Answers a request with the state of the given Trie, omitting the bundle when the
+    /// requester is already in sync.
+    ///
+    /// The bundle is encoded with [`WireFormat::to_wire`] at the newest version listed by the
+    /// requester that this release supports, and sent as a protobuf message if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the request carries an invalid root.
+    #[inline]
+    pub fn answer<D: TrieHasher + 'static>(
+        trie: &Trie<D>,
+        request: &proto::SyncRequest,
+    ) -> Result<Self> {
+        if hash_field(&request.root, "SyncRequest.root")? == trie.root() {
+            return Ok(Self {
+                in_sync: true,
+                ..Self::default()
+            });
+        }
+
+        let bundle = StateBundle::from_trie(trie);
+        let versions = request
+            .wire_versions
+            .iter()
+            .filter_map(|&version| u16::try_from(version).ok())
+            .collect::<Vec<_>>();
+
+        match negotiate(&versions) {
+            Some(version) => Ok(Self {
+                wire_version: version.into(),
+                wire_bundle: bundle.to_wire(version)?,
+                ..Self::default()
+            }),
+            None => Ok(Self {
+                bundle: Some((&bundle).into()),
+                ..Self::default()
+            }),
+        }
+    }
+
+    /// Extracts the bundle of the answer, decoding it within the bounds of `context`, or `None`
+    /// if the requester is in sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`WireFormat::from_wire_with`] for wire bundles, including
+    /// [`Error::UnsupportedWireVersion`] if the version they carry doesn't match
+    /// `wire_version`, and [`Error::Deserialization`] for malformed protobuf bundles.
+    #[inline]
+    pub fn into_bundle(self, context: &DecodeContext) -> Result<Option<StateBundle>> {
+        if self.in_sync {
+            return Ok(None);
+        }
+
+        if self.wire_bundle.is_empty() {
+            return self.bundle.map(StateBundle::try_from).transpose();
+        }
+
+        let version = u16::try_from(self.wire_version).unwrap_or(u16::MAX);
+        if self.wire_bundle.get(..2) != Some(&version.to_be_bytes()[..]) {
+            return Err(Error::UnsupportedWireVersion(version));
+        }
+
+        StateBundle::from_wire_with(&self.wire_bundle, context).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+    use crate::wire::WIRE_VERSION;
+
+    #[proptest]
+    fn test_step_roundtrip(step: Step) {
+        let encoded = proto::Step::from(&step).encode_to_vec();
+        prop_assert_eq!(Step::try_from(proto::Step::decode(&encoded[..])?)?, step);
+    }
+
+    #[proptest]
+    fn test_bundle_roundtrip(root: Hash, #[any(8usize)] proof: Proof) {
+        let bundle = StateBundle { root, proof };
+        let encoded = proto::StateBundle::from(&bundle).encode_to_vec();
+        let decoded = proto::StateBundle::decode(&encoded[..])?;
+        prop_assert_eq!(StateBundle::try_from(decoded)?, bundle);
+    }
+
+    /// The fields of every message, as `(message, field, type, tag, repeated)`.
+    type Fields = std::collections::BTreeSet<(String, String, String, i32, bool)>;
+
+    /// Compiles `proto/mutree.proto` and lists the fields of its messages.
+    fn proto_fields() -> Fields {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/proto");
+        let parsed = protobuf_parse::Parser::new()
+            .pure()
+            .include(dir)
+            .input(format!("{dir}/mutree.proto"))
+            .parse_and_typecheck()
+            .unwrap();
+
+        let mut fields = Fields::new();
+        for message in parsed.file_descriptors.iter().flat_map(|f| &f.message_type) {
+            for field in &message.field {
+                // Types are named like `TYPE_UINT64`, and messages after their package
+                let kind = format!("{:?}", field.type_())
+                    .replace("TYPE_", "")
+                    .to_lowercase();
+                let kind = match field.type_name().rsplit('.').next() {
+                    Some(name) if !name.is_empty() => format!("{kind} {name}"),
+                    _ => kind,
+                };
+                let repeated = format!("{:?}", field.label()) == "LABEL_REPEATED";
+                fields.insert((
+                    message.name().to_string(),
+                    field.name().to_string(),
+                    kind,
+                    field.number(),
+                    repeated,
+                ));
+            }
+        }
+        fields
+    }
+
+    /// Lists the fields of the messages of `proto.rs` from their `#[prost]` attributes.
+    fn rust_fields() -> Fields {
+        let mut fields = Fields::new();
+        let (mut message, mut attribute) = (String::new(), None);
+
+        for line in include_str!("proto.rs").lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("pub struct ") {
+                message = name.trim_end_matches(" {").to_string();
+            } else if let Some(module) = line.strip_prefix("pub mod ") {
+                // Oneofs are enums in a module named after their message
+                let module = module.trim_end_matches(" {");
+                message = module[..1].to_uppercase() + &module[1..];
+            } else if let Some(args) = line.strip_prefix("#[prost(") {
+                let args = args.trim_end_matches(")]");
+                if let Some((_, tag)) = args.split_once("tag = \"") {
+                    let kind = args.split([',', ' ']).next().unwrap().to_string();
+                    let tag = tag.trim_end_matches('"').parse::<i32>().unwrap();
+                    attribute = Some((kind, tag, args.contains("repeated")));
+                }
+            } else if let Some((kind, tag, repeated)) = attribute.take() {
+                // The field, `pub name: Type,`, or the variant, `Name(Type),`
+                let (name, ty) = line.split_once([':', '(']).unwrap();
+                let name = name.trim_start_matches("pub ").to_lowercase();
+                let ty = ty.trim_end_matches([',', '>', ')']);
+                let ty = ty.rsplit([':', '<']).next().unwrap();
+                let kind = match kind.as_str() {
+                    "message" => format!("message {ty}"),
+                    _ => kind,
+                };
+                fields.insert((message.clone(), name, kind, tag, repeated));
+            }
+        }
+        fields
+    }
+
+    #[test]
+    fn test_messages_match_proto_file() {
+        let fields = rust_fields();
+        assert!(fields.contains(&(
+            "Step".into(),
+            "fork".into(),
+            "message ForkStep".into(),
+            2,
+            false
+        )));
+        assert_eq!(fields, proto_fields());
+    }
+
+    #[test]
+    fn test_rejects_short_hashes() {
+        let bundle = proto::StateBundle {
+            root: vec![0; 31],
+            proof: None,
+        };
+        assert!(matches!(
+            StateBundle::try_from(bundle),
+            Err(Error::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_forks() {
+        let fork = |nibble, prefix| Step::Fork {
+            skip: 0,
+            neighbor: Neighbor {
+                nibble,
+                prefix,
+                root: Hash::zero(),
+            },
+        };
+
+        for step in [fork(0x10, vec![]), fork(0, vec![0; 32])] {
+            assert!(matches!(
+                Step::try_from(proto::Step::from(&step)),
+                Err(Error::Deserialization(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rejects_skips_past_the_maximum() {
+        let leaf = |skip| proto::Step {
+            step: Some(proto::step::Step::Leaf(proto::LeafStep {
+                skip,
+                key: vec![0; 32],
+                value: vec![0; 32],
+                timestamp: None,
+            })),
+        };
+
+        assert!(Step::try_from(leaf(MAX_SKIP as u64)).is_ok());
+        assert!(matches!(
+            Step::try_from(leaf(MAX_SKIP as u64 + 1)),
+            Err(Error::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_sync_answer() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        let request = proto::SyncRequest::for_trie(&trie);
+        trie.insert(b"key", &b"value"[..])?;
+
+        let response = proto::SyncResponse::answer(&trie, &request)?;
+        assert!(!response.in_sync);
+        assert_eq!(response.bundle, None);
+        assert_eq!(response.wire_version, u32::from(WIRE_VERSION));
+        let bundle = response.into_bundle(&DecodeContext::default())?.unwrap();
+        assert_eq!(bundle.verify::<Blake2s256>()?, trie);
+
+        let response = proto::SyncResponse::answer(&trie, &proto::SyncRequest::for_trie(&trie))?;
+        assert!(response.in_sync);
+        assert_eq!(response.bundle, None);
+        assert_eq!(response.into_bundle(&DecodeContext::default())?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_answer_negotiates_wire_versions() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+        let request = |wire_versions| proto::SyncRequest {
+            root: Hash::zero().to_bytes_vec(),
+            wire_versions,
+        };
+
+        let newest = u32::from(WIRE_VERSION);
+        let response = proto::SyncResponse::answer(&trie, &request(vec![newest, newest + 1]))?;
+        assert_eq!(response.wire_version, newest);
+        assert_eq!(
+            response.wire_bundle,
+            StateBundle::from_trie(&trie).to_wire(WIRE_VERSION)?
+        );
+
+        // Requesters sharing no version get a protobuf bundle
+        for versions in [vec![], vec![newest + 1, u32::MAX]] {
+            let response = proto::SyncResponse::answer(&trie, &request(versions))?;
+            assert!(response.wire_bundle.is_empty());
+            let bundle = response.into_bundle(&DecodeContext::default())?.unwrap();
+            assert_eq!(bundle.verify::<Blake2s256>()?, trie);
+        }
+
+        let mut response = proto::SyncResponse::answer(&trie, &request(vec![newest]))?;
+        response.wire_version = newest + 1;
+        assert!(matches!(
+            response.into_bundle(&DecodeContext::default()),
+            Err(Error::UnsupportedWireVersion(_))
+        ));
+
+        let response = proto::SyncResponse::answer(&trie, &request(vec![newest]))?;
+        let context = DecodeContext {
+            max_steps: 0,
+            ..DecodeContext::default()
+        };
+        assert!(response.into_bundle(&context).is_err());
+
+        Ok(())
+    }
+}
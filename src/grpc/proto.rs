@@ -0,0 +1,120 @@
+//! Message types for `proto/mutree.proto`, in the layout produced by `prost-build`.
+//!
+//! These are kept in-tree so building the crate doesn't require `protoc`. Any change to the
+//! `.proto` file must be mirrored here: a test compiles it, and checks that these messages have
+//! the same fields, with the same tags and types.
+
+/// A neighbor of a Fork step.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct Neighbor {
+    /// The 4-bit position (0-15) of this neighbor in its parent branch.
+    #[prost(uint32, tag = "1")]
+    pub nibble: u32,
+    /// The common prefix shared with its siblings.
+    #[prost(bytes = "vec", tag = "2")]
+    pub prefix: ::prost::alloc::vec::Vec<u8>,
+    /// The root hash of this neighbor's subtree.
+    #[prost(bytes = "vec", tag = "3")]
+    pub root: ::prost::alloc::vec::Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct BranchStep {
+    #[prost(uint64, tag = "1")]
+    pub skip: u64,
+    /// Exactly 4 hashes, the authentication path in the branch's Sparse-Merkle Tree.
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub neighbors: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct ForkStep {
+    #[prost(uint64, tag = "1")]
+    pub skip: u64,
+    #[prost(message, optional, tag = "2")]
+    pub neighbor: ::core::option::Option<Neighbor>,
+}
+
+/// A Hybrid Logical Clock timestamp, ordered by wall time first and logical counter second.
+#[derive(Clone, Copy, PartialEq, Eq, ::prost::Message)]
+pub struct Timestamp {
+    #[prost(uint64, tag = "1")]
+    pub wall: u64,
+    #[prost(uint32, tag = "2")]
+    pub logical: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct LeafStep {
+    #[prost(uint64, tag = "1")]
+    pub skip: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+    /// Absent for leaves written without a timestamp.
+    #[prost(message, optional, tag = "4")]
+    pub timestamp: ::core::option::Option<Timestamp>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct Step {
+    #[prost(oneof = "step::Step", tags = "1, 2, 3")]
+    pub step: ::core::option::Option<step::Step>,
+}
+
+/// Nested message and enum types in `Step`.
+pub mod step {
+    #[derive(Clone, PartialEq, Eq, ::prost::Oneof)]
+    pub enum Step {
+        #[prost(message, tag = "1")]
+        Branch(super::BranchStep),
+        #[prost(message, tag = "2")]
+        Fork(super::ForkStep),
+        #[prost(message, tag = "3")]
+        Leaf(super::LeafStep),
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct Proof {
+    #[prost(message, repeated, tag = "1")]
+    pub steps: ::prost::alloc::vec::Vec<Step>,
+}
+
+/// The full state of a replica, along with the root it claims the proof commits to.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct StateBundle {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub proof: ::core::option::Option<Proof>,
+}
+
+/// Asks a peer for its state, advertising the root of the requesting replica.
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct SyncRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root: ::prost::alloc::vec::Vec<u8>,
+    /// The versions of the mutree wire format the requester decodes. Empty for requesters that
+    /// only read the `bundle` message.
+    #[prost(uint32, repeated, tag = "2")]
+    pub wire_versions: ::prost::alloc::vec::Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Eq, ::prost::Message)]
+pub struct SyncResponse {
+    /// Set when the peer has the same root as the requester, in which case no bundle is sent.
+    #[prost(bool, tag = "1")]
+    pub in_sync: bool,
+    /// The state of the peer, sent when the requester shares no wire version with it.
+    #[prost(message, optional, tag = "2")]
+    pub bundle: ::core::option::Option<StateBundle>,
+    /// The wire version `wire_bundle` is encoded at.
+    #[prost(uint32, tag = "3")]
+    pub wire_version: u32,
+    /// The state of the peer as a versioned mutree wire message, sent instead of `bundle` at the
+    /// newest version listed in `wire_versions` that the peer supports.
+    #[prost(bytes = "vec", tag = "4")]
+    pub wire_bundle: ::prost::alloc::vec::Vec<u8>,
+}
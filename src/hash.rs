@@ -1,9 +1,23 @@
-use std::fmt::{self, Display, Formatter, LowerHex, UpperHex};
-
-use digest::Digest;
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unimplemented
+    )
+)]
+
+#[cfg(feature = "serde")]
+use alloc::string::String;
+use core::fmt::{self, Display, Formatter};
+
+#[cfg(feature = "std")]
 use proptest::{prelude::*, strategy::BoxedStrategy};
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::prelude::*;
+use crate::{prelude::*, TrieHasher};
 
 /// A 32-byte cryptographic hash used throughout the Merkle-Patricia Trie.
 ///
@@ -16,6 +30,7 @@ use crate::prelude::*;
 /// constant-time operations where possible. It supports common traits like
 /// Display, Debug, and various conversion traits for flexibility.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 #[repr(transparent)]
 pub struct Hash([u8; 32]);
 
@@ -33,6 +48,7 @@ impl fmt::Debug for Hash {
     }
 }
 
+#[cfg(feature = "std")]
 impl Arbitrary for Hash {
     type Parameters = [u8; 32];
     type Strategy = BoxedStrategy<Self>;
@@ -48,16 +64,71 @@ impl Arbitrary for Hash {
     }
 }
 
+/// Hashes serialize as hex strings in human-readable formats, and as 32-byte tuples otherwise.
+#[cfg(feature = "serde")]
+impl Serialize for Hash {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_hex())
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Hash {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            Hash::from_hex(&hex).map_err(de::Error::custom)
+        } else {
+            <[u8; 32]>::deserialize(deserializer).map(Hash)
+        }
+    }
+}
+
 impl Hash {
     #[inline]
     pub fn new<T: Into<[u8; 32]>>(data: T) -> Self {
         Hash(data.into())
     }
 
+    /// Wraps an array, in `const` contexts.
+    #[inline]
+    pub const fn from_array(bytes: [u8; 32]) -> Self {
+        Hash(bytes)
+    }
+
+    /// Copies a slice of 32 bytes into a Hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLength`] if the slice is not exactly 32 bytes long.
+    #[inline]
+    pub fn from_slice(slice: &[u8]) -> Result<Self> {
+        slice.try_into().map(Hash).map_err(|_| Error::InvalidLength)
+    }
+
+    /// Converts the output of a digest into a Hash.
+    ///
+    /// Outputs shorter than 32 bytes, like the ones of RIPEMD-160, are left-padded with zeros.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the output is longer than 32 bytes. Digests with longer outputs don't implement
+    /// [`TrieHasher`], so tries never pass them here.
     #[inline]
-    pub fn from_slice(slice: &[u8]) -> Self {
+    pub fn from_digest(output: &[u8]) -> Self {
+        assert!(
+            output.len() <= 32,
+            "digest outputs must be at most 32 bytes"
+        );
+
         let mut inner = [0u8; 32];
-        inner.copy_from_slice(slice);
+        inner[32 - output.len()..].copy_from_slice(output);
         Hash(inner)
     }
 
@@ -66,19 +137,73 @@ impl Hash {
         Self([0u8; 32])
     }
 
+    /// Checks if this is the zero hash, which marks empty neighbors.
+    ///
+    /// The bytes are checked as four 64-bit words, since roots check every neighbor of every
+    /// branch step. The `zero_neighbors` group of the `root` benchmark compares it with `==`.
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        let (words, _) = self.0.as_chunks::<8>();
+        words
+            .iter()
+            .fold(0, |acc, word| acc | u64::from_ne_bytes(*word))
+            == 0
+    }
+
+    /// Compares two hashes in constant time.
+    ///
+    /// Every byte of both hashes is read, whatever the position of the first difference, unlike
+    /// `==` which may return as soon as it finds one.
     #[inline]
-    pub fn digest<D: Digest>(data: &[u8]) -> Self {
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        let (ours, _) = self.0.as_chunks::<8>();
+        let (theirs, _) = other.0.as_chunks::<8>();
+        let difference = ours.iter().zip(theirs).fold(0, |acc, (a, b)| {
+            acc | (u64::from_ne_bytes(*a) ^ u64::from_ne_bytes(*b))
+        });
+
+        core::hint::black_box(difference) == 0
+    }
+
+    #[inline]
+    pub fn digest<D: TrieHasher>(data: &[u8]) -> Self {
         let mut hasher = D::new();
         hasher.update(data);
-        Hash::from_slice(&hasher.finalize())
+        hasher.finalize32()
     }
 
     #[inline]
-    pub fn combine<D: Digest>(left: &Hash, right: &Hash) -> Self {
+    pub fn combine<D: TrieHasher>(left: &Hash, right: &Hash) -> Self {
         let mut hasher = D::new();
         hasher.update(left.as_ref());
         hasher.update(right.as_ref());
-        Hash::from_slice(&hasher.finalize())
+        hasher.finalize32()
+    }
+
+    /// Displays the first bytes of the hash, enough to tell the hashes of a Trie apart in trees
+    /// and logs.
+    #[cfg_attr(feature = "redact", allow(dead_code))]
+    pub(crate) fn short(&self) -> ShortHash<'_> {
+        ShortHash(self)
+    }
+}
+
+/// A hash displayed by its first bytes, returned by [`Hash::short`].
+#[cfg_attr(feature = "redact", allow(dead_code))]
+pub(crate) struct ShortHash<'a>(&'a Hash);
+
+#[cfg_attr(feature = "redact", allow(dead_code))]
+impl ShortHash<'_> {
+    /// The number of bytes displayed.
+    const LEN: usize = 4;
+}
+
+impl Display for ShortHash<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 .0[..Self::LEN] {
+            write!(f, "{byte:02x}")?;
+        }
+        f.write_str("…")
     }
 }
 
@@ -117,7 +242,7 @@ impl From<Hash> for [u8; 32] {
     }
 }
 
-impl std::ops::Index<usize> for Hash {
+impl core::ops::Index<usize> for Hash {
     type Output = u8;
 
     #[inline]
@@ -126,7 +251,7 @@ impl std::ops::Index<usize> for Hash {
     }
 }
 
-impl std::ops::IndexMut<usize> for Hash {
+impl core::ops::IndexMut<usize> for Hash {
     #[inline]
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.0[index]
@@ -136,12 +261,7 @@ impl std::ops::IndexMut<usize> for Hash {
 impl FromBytes for Hash {
     #[inline]
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() != 32 {
-            return Err(Error::InvalidLength);
-        }
-        let mut array = [0u8; 32];
-        array.copy_from_slice(bytes);
-        Ok(Hash(array))
+        Hash::from_slice(bytes)
     }
 }
 
@@ -152,43 +272,21 @@ impl ToBytes for Hash {
     fn to_bytes(&self) -> Self::Output {
         self.0
     }
-}
-
-impl std::hash::Hash for Hash {
-    #[inline]
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.to_bytes().hash(state);
-    }
-}
-
-impl FromHex for Hash {
-    #[inline]
-    fn from_hex(input: &str) -> Result<Self> {
-        let bytes = hex::decode(input)?;
-        Self::from_bytes(&bytes)
-    }
-}
 
-impl ToHex for Hash {
     #[inline]
-    fn to_hex(&self) -> String {
-        hex::encode(ToBytes::to_bytes(self))
+    fn is_zero(&self) -> bool {
+        Hash::is_zero(self)
     }
 }
 
-impl LowerHex for Hash {
+impl core::hash::Hash for Hash {
     #[inline]
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", hex::encode(self.to_bytes()))
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.to_bytes().hash(state);
     }
 }
 
-impl UpperHex for Hash {
-    #[inline]
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", hex::encode_upper(self.to_bytes()))
-    }
-}
+crate::impl_hex_via_bytes!(Hash);
 
 #[cfg(test)]
 mod tests {
@@ -196,6 +294,27 @@ mod tests {
 
     use super::*;
 
+    #[proptest]
+    fn test_is_zero(#[strategy(0..32usize)] index: usize, #[strategy(1..=255u8)] byte: u8) {
+        let mut bytes = [0u8; 32];
+        prop_assert!(Hash::new(bytes).is_zero());
+
+        bytes[index] = byte;
+        let hash = Hash::new(bytes);
+        prop_assert!(!hash.is_zero());
+        prop_assert!(!ToBytes::is_zero(&hash));
+    }
+
+    #[proptest]
+    fn test_ct_eq_matches_eq(a: Hash, #[strategy(0..32usize)] index: usize, byte: u8) {
+        let mut bytes = a.0;
+        bytes[index] ^= byte;
+        let b = Hash::new(bytes);
+
+        prop_assert!(a.ct_eq(&a));
+        prop_assert_eq!(a.ct_eq(&b), a == b);
+    }
+
     #[proptest]
     fn test_hash_indexing(#[strategy(any::<[u8; 32]>())] data: [u8; 32]) {
         let hash = Hash::new(data);
@@ -223,6 +342,17 @@ mod tests {
         hash[32] = 0;
     }
 
+    #[proptest]
+    fn test_from_digest_left_pads_short_outputs(
+        #[strategy(proptest::collection::vec(any::<u8>(), 0..=32))] output: Vec<u8>,
+    ) {
+        let hash = Hash::from_digest(&output);
+        let padding = 32 - output.len();
+
+        prop_assert!(hash.as_ref()[..padding].iter().all(|&byte| byte == 0));
+        prop_assert_eq!(&hash.as_ref()[padding..], &output[..]);
+    }
+
     crate::test_to_bytes!(Hash);
     crate::test_to_hex!(Hash);
 }
@@ -0,0 +1,131 @@
+use digest::{
+    typenum::{IsLessOrEqual, True, U32},
+    Digest,
+};
+
+use crate::hash::Hash;
+
+/// The hash function backing a [`Trie`](crate::prelude::Trie).
+///
+/// This is the minimal interface the trie needs: an incremental hasher producing at most 32
+/// bytes. Every [`Digest`] implements it, so the RustCrypto hashes work out of the box, while
+/// hardware-accelerated or FFI hashers (OpenSSL, HSMs, SGX enclaves) only need to implement these
+/// three methods, instead of the whole RustCrypto trait stack.
+///
+/// The trait is exported from the crate root, but not from the prelude, since its methods share
+/// their names with the ones of [`Digest`], and calling them on a type implementing both would
+/// be ambiguous.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::{prelude::*, TrieHasher};
+///
+/// /// An FNV-1a hasher, standing in for a hasher from a C library.
+/// struct Fnv(u64);
+///
+/// impl TrieHasher for Fnv {
+///     fn new() -> Self {
+///         Fnv(0xcbf29ce484222325)
+///     }
+///
+///     fn update(&mut self, data: &[u8]) {
+///         for byte in data {
+///             self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+///         }
+///     }
+///
+///     fn finalize32(self) -> Hash {
+///         Hash::from_digest(&self.0.to_be_bytes())
+///     }
+/// }
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Fnv>::empty();
+///     trie.insert(b"key", &b"value"[..])?;
+///     assert!(trie.verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+pub trait TrieHasher {
+    /// Creates a hasher with an empty state.
+    fn new() -> Self;
+
+    /// Feeds `data` into the hasher.
+    fn update(&mut self, data: &[u8]);
+
+    /// Consumes the hasher, returning its output as a [`Hash`]. Outputs shorter than 32 bytes are
+    /// left-padded with zeros, as done by [`Hash::from_digest`].
+    fn finalize32(self) -> Hash;
+}
+
+/// Digests of at most 32 bytes are hashers. Longer ones, like Blake2b512, are rejected when
+/// compiling, instead of panicking in [`Hash::from_digest`] once they are used:
+///
+/// ```rust,compile_fail
+/// use blake2::Blake2b512;
+/// use mutree::prelude::*;
+///
+/// let trie = Trie::<Blake2b512>::empty();
+/// ```
+impl<D: Digest> TrieHasher for D
+where
+    D::OutputSize: IsLessOrEqual<U32, Output = True>,
+{
+    #[inline]
+    fn new() -> Self {
+        Digest::new()
+    }
+
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    #[inline]
+    fn finalize32(self) -> Hash {
+        Hash::from_digest(&Digest::finalize(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+    use crate::prelude::Trie;
+
+    /// A hasher that doesn't implement [`Digest`], delegating to Blake2s.
+    struct Wrapped(Blake2s256);
+
+    impl TrieHasher for Wrapped {
+        fn new() -> Self {
+            Wrapped(Digest::new())
+        }
+
+        fn update(&mut self, data: &[u8]) {
+            Digest::update(&mut self.0, data);
+        }
+
+        fn finalize32(self) -> Hash {
+            Hash::from_digest(&Digest::finalize(self.0))
+        }
+    }
+
+    #[proptest]
+    fn test_custom_hasher_matches_digest(entries: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut custom = Trie::<Wrapped>::empty();
+        let mut digest = Trie::<Blake2s256>::empty();
+
+        for (key, value) in entries.iter().filter(|(key, _)| !key.is_empty()) {
+            custom.insert(key, &value[..])?;
+            digest.insert(key, &value[..])?;
+        }
+
+        prop_assert_eq!(custom.root(), digest.root());
+        prop_assert_eq!(custom.proof(), digest.proof());
+    }
+}
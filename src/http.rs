@@ -0,0 +1,469 @@
+//! Ready-made HTTP handlers for replicating a [`Trie`] over axum.
+//!
+//! [`router`] serves a [`SharedTrie`] on three routes:
+//!
+//! - `GET /root` returns the current root, in hex.
+//! - `GET /state/{key}` returns the full state of the Trie, as a [`StateBundle`], if it holds
+//!   `key`, given in hex.
+//! - `POST /merge` verifies a [`StateBundle`] and merges it, returning the new root in hex.
+//!
+//! There is no `GET /proof/{key}`: the root hashes every step of the Trie, so no proof smaller
+//! than the whole state shows that it holds a key. The route is named after what it returns, so
+//! callers don't mistake the response for a proof of bounded size.
+//!
+//! Bundles posted to `/merge` come from untrusted peers, and are decoded within the bounds of
+//! the [`DecodeContext`] in the [`Config`] given to the router.
+//!
+//! Bundles are exchanged as versioned [`wire`](crate::wire) messages, as
+//! `application/octet-stream`. Clients list the versions they decode in the
+//! [`WIRE_VERSIONS_HEADER`], and `GET /state/{key}` answers at the newest one the server also
+//! supports, or at [`WIRE_VERSION`] if the header is missing. Every response lists the versions of
+//! the server in the same header, so clients can pick the version to post bundles at.
+//!
+//! Errors are returned as plain text, with `400 Bad Request` for undecodable bundles and version
+//! lists, `404 Not Found` for unknown keys, `406 Not Acceptable` when client and server share no
+//! wire version, and `422 Unprocessable Entity` for bundles that fail verification. Their
+//! [`Error::code`] is sent in the [`ERROR_CODE_HEADER`], so clients can tell failures apart
+//! without parsing the message.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//!
+//! use blake2::Blake2s256;
+//! use mutree::{
+//!     http::{router, Config},
+//!     prelude::*,
+//!     shared::SharedTrie,
+//! };
+//!
+//! # async fn serve() -> std::io::Result<()> {
+//! let trie = Arc::new(SharedTrie::new(Trie::<Blake2s256>::empty()));
+//! let config = Config {
+//!     decode: DecodeContext {
+//!         max_bytes: 1024 * 1024,
+//!         ..DecodeContext::default()
+//!     },
+//! };
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+//! axum::serve(listener, router(trie, config)).await
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    prelude::*,
+    replication::StateBundle,
+    shared::SharedTrie,
+    wire::{negotiate, supported_versions, WireFormat, WIRE_VERSION},
+    TrieHasher,
+};
+
+/// The header holding the [`Error::code`] of failed requests.
+pub const ERROR_CODE_HEADER: &str = "x-mutree-error-code";
+
+/// The header listing the wire versions a client or server decodes, separated by commas.
+pub const WIRE_VERSIONS_HEADER: &str = "x-mutree-wire-versions";
+
+/// Configuration for [`router`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The bounds bundles posted to `/merge` are decoded within
+    pub decode: DecodeContext,
+}
+
+/// The state of the handlers: the Trie they serve, and the [`Config`] they serve it with.
+#[derive(Debug)]
+pub struct Server<D: TrieHasher> {
+    pub trie: Arc<SharedTrie<D>>,
+    pub config: Config,
+}
+
+impl<D: TrieHasher> Clone for Server<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            trie: Arc::clone(&self.trie),
+            config: self.config,
+        }
+    }
+}
+
+/// Builds a router serving `GET /root`, `GET /state/{key}` and `POST /merge` for `trie`.
+#[inline]
+pub fn router<D>(trie: Arc<SharedTrie<D>>, config: Config) -> Router
+where
+    D: TrieHasher + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/root", get(root::<D>))
+        .route("/state/{key}", get(state::<D>))
+        .route("/merge", post(merge::<D>))
+        .layer(axum::middleware::map_response(advertise))
+        .with_state(Server { trie, config })
+}
+
+/// Handles `GET /root`, returning the root of the Trie in hex.
+#[inline]
+pub async fn root<D: TrieHasher + 'static>(State(server): State<Server<D>>) -> String {
+    server.trie.root().to_hex()
+}
+
+/// Handles `GET /state/{key}`, returning the full state of the Trie as a [`StateBundle`] if it
+/// holds `key`, encoded at the newest wire version listed in the [`WIRE_VERSIONS_HEADER`].
+///
+/// The root hashes every step of the Trie, so no smaller bundle proves a single key: the
+/// response holds every entry of the Trie, and grows with it. Only serve it to replicas allowed
+/// to read the whole Trie.
+///
+/// # Errors
+///
+/// Responds with `400 Bad Request` if the key is not valid hex or the version list can't be
+/// parsed, `404 Not Found` if the Trie doesn't hold the key, and `406 Not Acceptable` if none of
+/// the versions listed is supported.
+#[inline]
+pub async fn state<D: TrieHasher + 'static>(
+    State(server): State<Server<D>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let version = match version(&headers) {
+        Ok(version) => version,
+        Err(error @ Error::UnsupportedWireVersion(_)) => {
+            return failure(StatusCode::NOT_ACCEPTABLE, &error)
+        }
+        Err(error) => return failure(StatusCode::BAD_REQUEST, &error),
+    };
+    let key = match hex::decode(&key) {
+        Ok(key) => key,
+        Err(error) => return failure(StatusCode::BAD_REQUEST, &error.into()),
+    };
+    let key = Hash::digest::<D>(&key);
+    let trie = server.trie.snapshot();
+
+    if !trie
+        .proof()
+        .iter()
+        .any(|step| matches!(step, Step::Leaf { key: leaf, .. } if *leaf == key))
+    {
+        return failure(StatusCode::NOT_FOUND, &Error::ElementNotExists);
+    }
+
+    match StateBundle::from_trie(&trie).to_wire(version) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "application/octet-stream")], bytes).into_response(),
+        Err(error) => failure(StatusCode::NOT_ACCEPTABLE, &error),
+    }
+}
+
+/// Handles `POST /merge`, merging the [`StateBundle`] in the body and returning the new root
+/// in hex.
+///
+/// # Errors
+///
+/// Responds with `400 Bad Request` if the body is not a wire message of a supported version,
+/// holding a bundle within the bounds of the [`DecodeContext`] in the [`Config`], and `422 Unprocessable Entity` if it fails verification or can't be
+/// merged.
+#[inline]
+pub async fn merge<D: TrieHasher + 'static>(
+    State(server): State<Server<D>>,
+    body: Bytes,
+) -> Response {
+    let remote = match StateBundle::from_wire_with(&body, &server.config.decode) {
+        Ok(bundle) => bundle,
+        Err(error) => return failure(StatusCode::BAD_REQUEST, &error),
+    };
+
+    let result = remote.verify::<D>().and_then(|remote| {
+        server.trie.update(|trie| {
+            trie.merge(&remote)?;
+            Ok(trie.root())
+        })
+    });
+
+    match result {
+        Ok(root) => root.to_hex().into_response(),
+        Err(error) => failure(StatusCode::UNPROCESSABLE_ENTITY, &error),
+    }
+}
+
+fn failure(status: StatusCode, error: &Error) -> Response {
+    (
+        status,
+        [(ERROR_CODE_HEADER, error.code().to_string())],
+        error.to_string(),
+    )
+        .into_response()
+}
+
+/// Picks the newest version listed in the [`WIRE_VERSIONS_HEADER`] that this release supports.
+fn version(headers: &HeaderMap) -> Result<u16> {
+    let Some(list) = headers.get(WIRE_VERSIONS_HEADER) else {
+        return Ok(WIRE_VERSION);
+    };
+
+    let versions = list
+        .to_str()
+        .ok()
+        .and_then(|list| {
+            list.split(',')
+                .map(|version| version.trim().parse().ok())
+                .collect::<Option<Vec<u16>>>()
+        })
+        .ok_or_else(|| Error::Deserialization(format!("Invalid wire versions: {list:?}")))?;
+
+    negotiate(&versions).ok_or_else(|| {
+        Error::UnsupportedWireVersion(versions.iter().max().copied().unwrap_or_default())
+    })
+}
+
+/// Lists the wire versions of the server in the [`WIRE_VERSIONS_HEADER`] of `response`.
+async fn advertise(mut response: Response) -> Response {
+    let versions = supported_versions()
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+
+    if let Ok(value) = versions.parse() {
+        response.headers_mut().insert(WIRE_VERSIONS_HEADER, value);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use blake2::Blake2s256;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    type TrieT = Trie<Blake2s256>;
+
+    fn request(
+        trie: &Arc<SharedTrie<Blake2s256>>,
+        request: axum::http::Request<Body>,
+    ) -> (StatusCode, Vec<u8>) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let response = router(trie.clone(), Config::default())
+                .oneshot(request)
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, body.to_vec())
+        })
+    }
+
+    fn error_code(
+        trie: &Arc<SharedTrie<Blake2s256>>,
+        request: axum::http::Request<Body>,
+    ) -> Option<ErrorCode> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let response = runtime
+            .block_on(router(trie.clone(), Config::default()).oneshot(request))
+            .unwrap();
+        let code = response.headers().get(ERROR_CODE_HEADER)?;
+        ErrorCode::from_u32(code.to_str().ok()?.parse().ok()?)
+    }
+
+    fn get(uri: &str) -> axum::http::Request<Body> {
+        axum::http::Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn versioned(uri: &str, versions: &str) -> axum::http::Request<Body> {
+        axum::http::Request::get(uri)
+            .header(WIRE_VERSIONS_HEADER, versions)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn wire(bundle: &StateBundle) -> Vec<u8> {
+        bundle.to_wire(WIRE_VERSION).unwrap()
+    }
+
+    fn post(body: Vec<u8>) -> axum::http::Request<Body> {
+        axum::http::Request::post("/merge")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn shared() -> Result<Arc<SharedTrie<Blake2s256>>> {
+        let mut trie = TrieT::empty();
+        trie.insert(b"key", &b"value"[..])?;
+        Ok(Arc::new(SharedTrie::new(trie)))
+    }
+
+    #[test]
+    fn test_root() -> Result<()> {
+        let trie = shared()?;
+        let (status, body) = request(&trie, get("/root"));
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(String::from_utf8(body).unwrap(), trie.root().to_hex());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_state() -> Result<()> {
+        let trie = shared()?;
+
+        let (status, body) = request(&trie, get(&format!("/state/{}", hex::encode("key"))));
+        assert_eq!(status, StatusCode::OK);
+        let bundle = StateBundle::from_wire(&body)?;
+        assert!(bundle.verify::<Blake2s256>()?.verify(b"key", b"value"));
+
+        let (status, _) = request(&trie, get(&format!("/state/{}", hex::encode("missing"))));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = request(&trie, get("/state/not-hex"));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let trie = shared()?;
+        let mut remote = TrieT::empty();
+        remote.insert(b"other", &b"value"[..])?;
+
+        let (status, body) = request(&trie, post(wire(&StateBundle::from_trie(&remote))));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(String::from_utf8(body).unwrap(), trie.root().to_hex());
+        assert!(trie.verify(b"other", b"value"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_rejects_invalid_bundles() -> Result<()> {
+        let trie = shared()?;
+        let before = trie.root();
+
+        let (status, _) = request(&trie, post(vec![1, 2, 3]));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut forged = StateBundle::from_trie(&trie.snapshot());
+        forged.root = Hash::zero();
+        let (status, _) = request(&trie, post(wire(&forged)));
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        assert_eq!(trie.root(), before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiates_wire_versions() -> Result<()> {
+        let trie = shared()?;
+        let uri = format!("/state/{}", hex::encode("key"));
+        let newer = format!("{WIRE_VERSION}, {}", WIRE_VERSION + 1);
+
+        let (status, body) = request(&trie, versioned(&uri, &newer));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&body[..2], &WIRE_VERSION.to_be_bytes()[..]);
+        assert_eq!(
+            StateBundle::from_wire(&body)?,
+            StateBundle::from_trie(&trie.snapshot())
+        );
+
+        let (status, _) = request(&trie, versioned(&uri, &(WIRE_VERSION + 1).to_string()));
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+
+        let (status, _) = request(&trie, versioned(&uri, "one"));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let mut unsupported = wire(&StateBundle::from_trie(&trie.snapshot()));
+        unsupported[..2].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert_eq!(
+            error_code(&trie, post(unsupported)),
+            Some(ErrorCode::UnsupportedWireVersion)
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let response = runtime
+            .block_on(router(trie.clone(), Config::default()).oneshot(get("/root")))
+            .unwrap();
+        assert_eq!(
+            response.headers()[WIRE_VERSIONS_HEADER],
+            WIRE_VERSION.to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_decodes_within_the_config() -> Result<()> {
+        let trie = shared()?;
+        let before = trie.root();
+        let mut remote = TrieT::empty();
+        remote.insert(b"other", &b"value"[..])?;
+        remote.insert(b"more", &b"value"[..])?;
+
+        let config = Config {
+            decode: DecodeContext {
+                max_steps: 1,
+                ..DecodeContext::default()
+            },
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let response = runtime
+            .block_on(
+                router(trie.clone(), config).oneshot(post(wire(&StateBundle::from_trie(&remote)))),
+            )
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(trie.root(), before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_codes() -> Result<()> {
+        let trie = shared()?;
+
+        assert_eq!(error_code(&trie, get("/root")), None);
+        assert_eq!(
+            error_code(&trie, get(&format!("/state/{}", hex::encode("missing")))),
+            Some(ErrorCode::ElementNotExists)
+        );
+        assert_eq!(
+            error_code(&trie, get("/state/not-hex")),
+            Some(ErrorCode::Deserialization)
+        );
+        let mut malformed = WIRE_VERSION.to_be_bytes().to_vec();
+        malformed.extend([1, 2, 3]);
+        assert_eq!(
+            error_code(&trie, post(malformed)),
+            Some(ErrorCode::Deserialization)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! Instrumentation of the main operations of [`Trie`] and [`Mutree`], through the first of
+//! `tracing`, `log` or `defmt` enabled as a feature.
+//!
+//! With `tracing`, every operation runs in a `DEBUG` span named after it, with the target
+//! `mutree`, and ends with an event carrying its duration and the number of steps of the proof
+//! afterwards. Without it, `log` and `defmt`, which also work without `std`, get a single
+//! `DEBUG` message at the end of every operation, with the same outcome. Operations returning an
+//! error, and verifications rejecting their pair, end as failed. Keys are only recorded by the
+//! first bytes of their hash, so logs don't leak them, and not at all with the `redact` feature.
+//! Without any of these features, operations are not instrumented, and the calls below compile
+//! to nothing.
+//!
+//! [`Mutree`]: crate::prelude::Mutree
+
+#[cfg(not(any(feature = "tracing", feature = "log", feature = "defmt")))]
+pub(crate) use self::disabled::Operation;
+#[cfg(all(not(feature = "tracing"), any(feature = "log", feature = "defmt")))]
+pub(crate) use self::messages::Operation;
+#[cfg(feature = "tracing")]
+pub(crate) use self::spans::Operation;
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// The target of the spans, events and messages, to filter them from the rest of the
+/// application.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) const TARGET: &str = "mutree";
+
+/// Starts an operation named `$name`, declaring the fields of its span, which are either
+/// recorded later, or set to a value when the operation starts.
+macro_rules! operation {
+    ($name:literal $(, $field:ident $(= $value:expr)?)*) => {{
+        #[cfg(feature = "tracing")]
+        let operation = $crate::instrument::Operation::start(tracing::debug_span!(
+            target: $crate::instrument::TARGET,
+            $name,
+            $($field = $crate::instrument::field!($($value)?)),*
+        ));
+        #[cfg(not(feature = "tracing"))]
+        let operation = $crate::instrument::Operation::start($name);
+        operation
+    }};
+}
+
+/// The value of a field of a span, empty until it is recorded if none is given.
+#[cfg(feature = "tracing")]
+macro_rules! field {
+    () => {
+        tracing::field::Empty
+    };
+    ($value:expr) => {
+        $value
+    };
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) use field;
+pub(crate) use operation;
+
+#[cfg(feature = "tracing")]
+mod spans {
+    use std::time::Instant;
+
+    use tracing::span::EnteredSpan;
+
+    use super::*;
+
+    /// An operation in progress, within its span until it is dropped.
+    pub(crate) struct Operation {
+        span: EnteredSpan,
+        start: Instant,
+        steps: Option<usize>,
+    }
+
+    impl Operation {
+        /// Enters `span` for the duration of the operation.
+        pub(crate) fn start(span: tracing::Span) -> Self {
+            Self {
+                span: span.entered(),
+                start: Instant::now(),
+                steps: None,
+            }
+        }
+
+        /// Records the hash of the key the operation is about, in the `key` field of its span,
+        /// unless the `redact` feature is enabled.
+        pub(crate) fn record_key(&mut self, key: &Hash) {
+            #[cfg(not(feature = "redact"))]
+            self.span
+                .record("key", tracing::field::display(key.short()));
+            #[cfg(feature = "redact")]
+            let _ = key;
+        }
+
+        /// Records the outcome of the operation, in the `field` declared by its span.
+        pub(crate) fn record(&mut self, field: &'static str, value: bool) {
+            self.span.record(field, value);
+        }
+
+        /// Marks the operation as successful, leaving a proof of `steps` steps.
+        pub(crate) fn succeed(&mut self, steps: usize) {
+            self.steps = Some(steps);
+        }
+    }
+
+    impl Drop for Operation {
+        fn drop(&mut self) {
+            let elapsed_us = u64::try_from(self.start.elapsed().as_micros()).unwrap_or(u64::MAX);
+
+            match self.steps {
+                Some(steps) => tracing::debug!(target: TARGET, steps, elapsed_us, "finished"),
+                None => tracing::debug!(target: TARGET, elapsed_us, "failed"),
+            }
+        }
+    }
+}
+
+#[cfg(all(not(feature = "tracing"), any(feature = "log", feature = "defmt")))]
+mod messages {
+    use super::*;
+
+    /// An operation in progress, logged when it is dropped.
+    pub(crate) struct Operation {
+        name: &'static str,
+        key: Option<Hash>,
+        outcome: Option<(&'static str, bool)>,
+        steps: Option<usize>,
+    }
+
+    impl Operation {
+        /// Starts the operation called `name`.
+        pub(crate) fn start(name: &'static str) -> Self {
+            Self {
+                name,
+                key: None,
+                outcome: None,
+                steps: None,
+            }
+        }
+
+        /// Records the hash of the key the operation is about, unless the `redact` feature is
+        /// enabled.
+        pub(crate) fn record_key(&mut self, key: &Hash) {
+            if cfg!(not(feature = "redact")) {
+                self.key = Some(*key);
+            }
+        }
+
+        /// Records the outcome of the operation, as `field`.
+        pub(crate) fn record(&mut self, field: &'static str, value: bool) {
+            self.outcome = Some((field, value));
+        }
+
+        /// Marks the operation as successful, leaving a proof of `steps` steps.
+        pub(crate) fn succeed(&mut self, steps: usize) {
+            self.steps = Some(steps);
+        }
+    }
+
+    #[cfg(feature = "log")]
+    impl Drop for Operation {
+        fn drop(&mut self) {
+            use core::fmt::{self, Display, Formatter};
+
+            /// The key and outcome of the operation, when they were recorded.
+            struct Details<'a>(&'a Operation);
+
+            impl Display for Details<'_> {
+                fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                    if let Some(key) = &self.0.key {
+                        write!(f, " key={}", key.short())?;
+                    }
+                    if let Some((field, value)) = self.0.outcome {
+                        write!(f, " {field}={value}")?;
+                    }
+                    Ok(())
+                }
+            }
+
+            match self.steps {
+                Some(steps) => log::debug!(
+                    target: TARGET,
+                    "{} finished steps={steps}{}",
+                    self.name,
+                    Details(self)
+                ),
+                None => log::debug!(target: TARGET, "{} failed{}", self.name, Details(self)),
+            }
+        }
+    }
+
+    #[cfg(all(feature = "defmt", not(feature = "log")))]
+    impl Drop for Operation {
+        fn drop(&mut self) {
+            /// The key and outcome of the operation, when they were recorded.
+            struct Details<'a>(&'a Operation);
+
+            impl defmt::Format for Details<'_> {
+                fn format(&self, f: defmt::Formatter<'_>) {
+                    if let Some(key) = &self.0.key {
+                        defmt::write!(f, " key={=[u8]:x}", &key.as_ref()[..4]);
+                    }
+                    if let Some((field, value)) = self.0.outcome {
+                        defmt::write!(f, " {=str}={=bool}", field, value);
+                    }
+                }
+            }
+
+            match self.steps {
+                Some(steps) => defmt::debug!(
+                    "mutree: {=str} finished steps={=usize}{}",
+                    self.name,
+                    steps,
+                    Details(self)
+                ),
+                None => defmt::debug!("mutree: {=str} failed{}", self.name, Details(self)),
+            }
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tracing", feature = "log", feature = "defmt")))]
+mod disabled {
+    use super::*;
+
+    /// An operation nobody listens to.
+    pub(crate) struct Operation;
+
+    impl Operation {
+        #[inline(always)]
+        pub(crate) fn start(_: &'static str) -> Self {
+            Self
+        }
+
+        #[inline(always)]
+        pub(crate) fn record_key(&mut self, _: &Hash) {}
+
+        #[inline(always)]
+        pub(crate) fn record(&mut self, _: &'static str, _: bool) {}
+
+        #[inline(always)]
+        pub(crate) fn succeed(&mut self, _: usize) {}
+    }
+}
+
+#[cfg(all(test, feature = "tracing", not(feature = "redact")))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use blake2::Blake2s256;
+    use tracing::{
+        field::{Field, Visit},
+        span,
+        subscriber::with_default,
+        Event,
+        Metadata,
+        Subscriber,
+    };
+
+    use super::*;
+
+    /// Records the names of the spans entered, with their fields, and the messages of events.
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(Vec<String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            self.0.push(format!("{}={value:?}", field.name()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == TARGET
+        }
+
+        fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+            let mut lines = self.0.lock().unwrap();
+            lines.push(attributes.metadata().name().to_string());
+            span::Id::from_u64(lines.len() as u64)
+        }
+
+        fn record(&self, _: &span::Id, values: &span::Record<'_>) {
+            let mut fields = Fields(Vec::new());
+            values.record(&mut fields);
+            self.0.lock().unwrap().extend(fields.0);
+        }
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(Vec::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0.join(" "));
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn test_traces_operations() -> Result<()> {
+        let recorder = Recorder::default();
+
+        with_default(recorder.clone(), || {
+            let mut trie = Trie::<Blake2s256>::empty();
+            trie.insert(b"key", &b"value"[..])?;
+            assert!(trie.insert(b"", &b"value"[..]).is_err());
+            trie.merge(&trie.clone())?;
+            assert!(trie.verify(b"key", b"value"));
+            assert!(!trie.verify(b"key", b"other"));
+            assert!(!Trie::<Blake2s256>::empty().verify(b"key", b"value"));
+            Ok::<_, Error>(())
+        })?;
+
+        let key = format!("key={}", Hash::digest::<Blake2s256>(b"key").short());
+        let lines = recorder.0.lock().unwrap();
+        let expected = [
+            "insert",
+            &key,
+            "message=finished steps=1",
+            "insert",
+            "message=failed",
+            "merge",
+            "message=finished steps=1",
+            "verify",
+            &key,
+            "verified=true",
+            "message=finished steps=1",
+            "verify",
+            &key,
+            "verified=false",
+            "message=failed",
+            "verify",
+            "verified=false",
+            "message=failed",
+        ];
+
+        assert_eq!(lines.len(), expected.len(), "{lines:?}");
+        for (line, expected) in lines.iter().zip(expected) {
+            assert!(line.starts_with(expected), "{line} is not {expected}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_traces_mutree_operations() -> Result<()> {
+        let recorder = Recorder::default();
+
+        with_default(recorder.clone(), || {
+            let mut mutree = Mutree::<Blake2s256>::new_in_memory()?;
+            let entries = (0..3u8).map(|i| (vec![i], vec![i]));
+            mutree.bulk_load(entries, &BulkLoadConfig::default())
+        })?;
+
+        let lines = recorder.0.lock().unwrap();
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.split(' ').next().unwrap())
+                .collect::<Vec<_>>(),
+            [
+                "bulk_load",
+                "commit",
+                "message=finished",
+                "message=finished"
+            ]
+        );
+        assert!(lines[3].starts_with("message=finished steps=3"));
+
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "log",
+    not(any(feature = "tracing", feature = "redact"))
+))]
+mod log_tests {
+    use std::{
+        sync::Mutex,
+        thread::{self, ThreadId},
+    };
+
+    use blake2::Blake2s256;
+
+    use super::*;
+
+    /// Keeps the messages logged with the target of this crate, with the thread logging them,
+    /// so tests running in parallel can be told apart.
+    struct Recorder(Mutex<Vec<(ThreadId, String)>>);
+
+    impl log::Log for Recorder {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            metadata.target() == TARGET
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            if self.enabled(record.metadata()) {
+                let thread = thread::current().id();
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((thread, record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static RECORDER: Recorder = Recorder(Mutex::new(Vec::new()));
+
+    #[test]
+    fn test_logs_operations() -> Result<()> {
+        log::set_logger(&RECORDER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+        assert!(trie.insert(b"", &b"value"[..]).is_err());
+        assert!(trie.verify(b"key", b"value"));
+        assert!(!trie.verify(b"key", b"other"));
+
+        let key = Hash::digest::<Blake2s256>(b"key").short().to_string();
+        let thread = thread::current().id();
+        let lines: Vec<_> = RECORDER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(logged_by, _)| *logged_by == thread)
+            .map(|(_, line)| line.clone())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                format!("insert finished steps=1 key={key}"),
+                "insert failed".to_string(),
+                format!("verify finished steps=1 key={key} verified=true"),
+                format!("verify failed key={key} verified=false"),
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Invariants every [`Trie`] upholds, whatever its digest, for downstream test suites.
+//!
+//! [`test_trie_invariants!`](crate::test_trie_invariants) generates a property test for each
+//! check of this module, so crates plugging in their own digest, or storing tries in their own
+//! backend, can assert that their configuration still behaves like the ones tested here. The
+//! checks can also be called directly from custom property tests, with entries from
+//! [`entries`].
+
+use std::collections::BTreeMap;
+
+use proptest::{collection::vec, prelude::*};
+
+use crate::{prelude::*, TrieHasher};
+
+/// Key-value pairs drawn from a small key space, so the same keys are often written several
+/// times, and by several replicas.
+#[inline]
+pub fn entries() -> impl Strategy<Value = Vec<(Vec<u8>, Vec<u8>)>> + Clone {
+    vec((vec(0..4u8, 1..=3), vec(any::<u8>(), 0..8)), 0..8)
+}
+
+/// Builds a Trie by inserting `entries` in order.
+///
+/// # Errors
+///
+/// Returns the errors of [`Trie::insert`].
+#[inline]
+pub fn build<D: TrieHasher + 'static>(entries: &[(Vec<u8>, Vec<u8>)]) -> Result<Trie<D>> {
+    let mut trie = Trie::empty();
+    for (key, value) in entries {
+        trie.insert(key, &value[..])?;
+    }
+    Ok(trie)
+}
+
+/// The value each key is left with after inserting `entries` in order.
+fn latest(entries: &[(Vec<u8>, Vec<u8>)]) -> BTreeMap<&[u8], &[u8]> {
+    entries
+        .iter()
+        .map(|(key, value)| (&key[..], &value[..]))
+        .collect()
+}
+
+fn fail(error: Error) -> TestCaseError {
+    TestCaseError::fail(error.to_string())
+}
+
+/// Whether `a` and `b` hold the same state.
+///
+/// An empty Trie has a zero root, while tries rebuilt from an empty proof, by merges or
+/// [`Trie::from_proof`], have the digest of no input as their root. Both are empty, so their
+/// roots are not compared.
+fn same_state<D: TrieHasher + 'static>(a: &Trie<D>, b: &Trie<D>) -> bool {
+    a.root() == b.root() || (a.is_empty() && b.is_empty())
+}
+
+/// Checks that the root only depends on the contents of the Trie: not on the order of the
+/// insertions, nor on whether the Trie was rebuilt from its proof or its bytes.
+///
+/// # Errors
+///
+/// Returns a [`TestCaseError`] describing the first invariant that doesn't hold.
+#[inline]
+pub fn check_root_determinism<D: TrieHasher + 'static>(
+    entries: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), TestCaseError> {
+    let trie = build::<D>(entries).map_err(fail)?;
+
+    let contents: Vec<_> = latest(entries)
+        .into_iter()
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect();
+    let forward = build::<D>(&contents).map_err(fail)?;
+    let backward = build::<D>(&contents.iter().rev().cloned().collect::<Vec<_>>()).map_err(fail)?;
+
+    prop_assert_eq!(
+        forward.root(),
+        trie.root(),
+        "root depends on overwritten values"
+    );
+    prop_assert_eq!(
+        backward.root(),
+        trie.root(),
+        "root depends on insertion order"
+    );
+
+    let rebuilt = Trie::<D>::from_proof(trie.proof().clone());
+    prop_assert!(
+        same_state(&rebuilt, &trie),
+        "root changes through the proof"
+    );
+
+    let decoded = Trie::<D>::from_bytes(&trie.to_bytes()).map_err(fail)?;
+    prop_assert!(
+        same_state(&decoded, &trie),
+        "root changes through the byte encoding"
+    );
+
+    let mut refreshed = trie.clone();
+    refreshed.refresh_root();
+    prop_assert!(
+        same_state(&refreshed, &trie),
+        "root is not recalculated identically"
+    );
+
+    Ok(())
+}
+
+/// Checks that exactly the pairs held by the Trie verify, and that their proofs hold no issue.
+///
+/// # Errors
+///
+/// Returns a [`TestCaseError`] describing the first invariant that doesn't hold.
+#[inline]
+pub fn check_verification<D: TrieHasher + 'static>(
+    entries: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), TestCaseError> {
+    let trie = build::<D>(entries).map_err(fail)?;
+    let contents = latest(entries);
+
+    for (key, value) in &contents {
+        prop_assert!(trie.verify(key, value), "{key:?} doesn't verify");
+        prop_assert!(trie.verify_detailed(key, value).is_ok());
+
+        let mut other = value.to_vec();
+        other.push(0);
+        prop_assert!(!trie.verify(key, &other), "{key:?} verifies another value");
+    }
+
+    // Keys are drawn from nibbles below 4, so this one is never inserted
+    prop_assert!(!trie.verify(&[0xff], &[]), "a missing key verifies");
+
+    if !trie.is_empty() {
+        let report = trie.proof().check_soundness::<D>(trie.root());
+        prop_assert!(report.is_sound(), "{}", report);
+    }
+
+    Ok(())
+}
+
+/// Checks that merges are idempotent, commutative and associative, so replicas converge
+/// whatever the order in which they exchange their states.
+///
+/// # Errors
+///
+/// Returns a [`TestCaseError`] describing the first invariant that doesn't hold.
+#[inline]
+pub fn check_merge_laws<D: TrieHasher + 'static>(
+    a: &[(Vec<u8>, Vec<u8>)],
+    b: &[(Vec<u8>, Vec<u8>)],
+    c: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), TestCaseError> {
+    let (a, b, c) = (
+        build::<D>(a).map_err(fail)?,
+        build::<D>(b).map_err(fail)?,
+        build::<D>(c).map_err(fail)?,
+    );
+    let merged = |x: &Trie<D>, y: &Trie<D>| {
+        let mut x = x.clone();
+        x.merge(y).map(|()| x).map_err(fail)
+    };
+
+    prop_assert!(
+        same_state(&merged(&a, &a)?, &a),
+        "merges are not idempotent"
+    );
+    prop_assert!(
+        same_state(&merged(&a, &b)?, &merged(&b, &a)?),
+        "merges are not commutative"
+    );
+    prop_assert!(
+        same_state(
+            &merged(&merged(&a, &b)?, &c)?,
+            &merged(&a, &merged(&b, &c)?)?
+        ),
+        "merges are not associative"
+    );
+
+    Ok(())
+}
+
+/// Generates property tests checking the invariants of [`Trie`] for a digest: the root is
+/// deterministic, exactly the inserted pairs verify, and merges follow the CRDT laws.
+///
+/// The tests are generated in a module named after the digest, so the macro can be called once
+/// per digest in the same scope. They only need `mutree` itself as a dependency.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+///
+/// // In the tests of a crate using its own digest
+/// mutree::test_trie_invariants!(Blake2s256);
+/// ```
+#[macro_export]
+macro_rules! test_trie_invariants {
+    ($digest:ty) => {
+        $crate::__dependencies::paste::paste! {
+            mod [<test_trie_invariants_ $digest:snake>] {
+                use $crate::invariants;
+
+                use super::*;
+
+                $crate::__dependencies::proptest::proptest! {
+                    #[test]
+                    fn test_root_determinism(entries in invariants::entries()) {
+                        invariants::check_root_determinism::<$digest>(&entries)?;
+                    }
+
+                    #[test]
+                    fn test_verification(entries in invariants::entries()) {
+                        invariants::check_verification::<$digest>(&entries)?;
+                    }
+
+                    #[test]
+                    fn test_merge_laws(
+                        a in invariants::entries(),
+                        b in invariants::entries(),
+                        c in invariants::entries(),
+                    ) {
+                        invariants::check_merge_laws::<$digest>(&a, &b, &c)?;
+                    }
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,45 @@
+//! Streaming input for values inserted into a [`Trie`](crate::prelude::Trie).
+//!
+//! With the `std` feature, this is [`std::io::Read`]. Without it, a minimal trait with the same
+//! shape is provided instead, implemented for byte slices, so values can still be inserted in
+//! `no_std` environments.
+
+#[cfg(feature = "std")]
+pub use std::io::Read;
+
+/// A source of bytes, read in chunks.
+///
+/// This mirrors the `read` method of `std::io::Read`, for environments without `std`.
+#[cfg(not(feature = "std"))]
+pub trait Read {
+    /// The error returned when the source fails.
+    type Error: core::fmt::Display;
+
+    /// Pulls some bytes into `buf`, returning how many were read. Returning zero means the source
+    /// is exhausted.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+#[cfg(not(feature = "std"))]
+impl Read for &[u8] {
+    type Error = core::convert::Infallible;
+
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let len = buf.len().min(self.len());
+        let (head, tail) = self.split_at(len);
+        buf[..len].copy_from_slice(head);
+        *self = tail;
+        Ok(len)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<R: Read + ?Sized> Read for &mut R {
+    type Error = R::Error;
+
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        (**self).read(buf)
+    }
+}
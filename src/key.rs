@@ -0,0 +1,210 @@
+use alloc::{string::String, vec::Vec};
+
+/// Encodes structured keys as bytes, preserving their order.
+///
+/// Keys of a [`Trie`](crate::prelude::Trie) are bytes, so applications with integer, string or
+/// composite keys need an encoding for them. The encodings of this trait compare like the keys
+/// they encode, so ranges of keys stay ranges of bytes, and tuples encode each of their fields
+/// in turn, so `(tenant, id)` keys sort by tenant, then by id:
+///
+/// - Unsigned integers are encoded in big-endian, and `usize` as a `u64`, so keys are the same on
+///   every platform.
+/// - Signed integers are encoded in big-endian with their sign bit flipped, so negative numbers
+///   sort before positive ones.
+/// - Strings and byte slices are terminated by `00 00`, with their zero bytes escaped as
+///   `00 ff`, so no encoding is the prefix of another and shorter keys sort first.
+/// - UUIDs, with the `uuid` feature, are encoded as their 16 bytes.
+///
+/// Encodings are never empty, except for `()`, which Tries refuse as a key.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// fn main() -> Result<(), Error> {
+///     assert!((1u32, "b").encode_key() < (2u32, "a").encode_key());
+///     assert!((-1i64).encode_key() < 0i64.encode_key());
+///
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert_encoded(&(7u32, "alice"), &b"balance"[..])?;
+///     assert!(trie.verify_encoded(&(7u32, "alice"), b"balance"));
+///
+///     Ok(())
+/// }
+/// ```
+pub trait KeyEncode {
+    /// Appends the encoding of the key to `key`.
+    fn encode_key_into(&self, key: &mut Vec<u8>);
+
+    /// Encodes the key as bytes.
+    ///
+    /// This is a convenience method, and automatically derived from `encode_key_into`.
+    #[inline]
+    fn encode_key(&self) -> Vec<u8> {
+        let mut key = Vec::new();
+        self.encode_key_into(&mut key);
+        key
+    }
+}
+
+impl<T: KeyEncode + ?Sized> KeyEncode for &T {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        (**self).encode_key_into(key);
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($int:ty),*) => {
+        $(
+            impl KeyEncode for $int {
+                #[inline]
+                fn encode_key_into(&self, key: &mut Vec<u8>) {
+                    key.extend_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($int:ty => $unsigned:ty),*) => {
+        $(
+            impl KeyEncode for $int {
+                #[inline]
+                fn encode_key_into(&self, key: &mut Vec<u8>) {
+                    let flipped = (*self as $unsigned) ^ (1 << (<$unsigned>::BITS - 1));
+                    key.extend_from_slice(&flipped.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl KeyEncode for usize {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        (*self as u64).encode_key_into(key);
+    }
+}
+
+impl KeyEncode for bool {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        key.push(u8::from(*self));
+    }
+}
+
+impl KeyEncode for [u8] {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        for byte in self {
+            key.push(*byte);
+            if *byte == 0 {
+                key.push(0xff);
+            }
+        }
+        key.extend_from_slice(&[0, 0]);
+    }
+}
+
+impl KeyEncode for Vec<u8> {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        self[..].encode_key_into(key);
+    }
+}
+
+impl KeyEncode for str {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        self.as_bytes().encode_key_into(key);
+    }
+}
+
+impl KeyEncode for String {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        self.as_bytes().encode_key_into(key);
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl KeyEncode for uuid::Uuid {
+    #[inline]
+    fn encode_key_into(&self, key: &mut Vec<u8>) {
+        key.extend_from_slice(self.as_bytes());
+    }
+}
+
+macro_rules! impl_tuple {
+    ($(($($field:ident),*)),*) => {
+        $(
+            impl<$($field: KeyEncode),*> KeyEncode for ($($field,)*) {
+                #[inline]
+                #[allow(non_snake_case)]
+                fn encode_key_into(&self, key: &mut Vec<u8>) {
+                    let ($($field,)*) = self;
+                    $($field.encode_key_into(key);)*
+                }
+            }
+        )*
+    };
+}
+
+impl KeyEncode for () {
+    #[inline]
+    fn encode_key_into(&self, _: &mut Vec<u8>) {}
+}
+
+impl_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_integers_keep_their_order(a: i64, b: i64, c: u32, d: u32, e: i8, f: i8) {
+        prop_assert_eq!(a.cmp(&b), a.encode_key().cmp(&b.encode_key()));
+        prop_assert_eq!(c.cmp(&d), c.encode_key().cmp(&d.encode_key()));
+        prop_assert_eq!(e.cmp(&f), e.encode_key().cmp(&f.encode_key()));
+    }
+
+    #[proptest]
+    fn test_tuples_keep_their_order(a: (u16, Vec<u8>, String, i32), b: (u16, Vec<u8>, String, i32)) {
+        prop_assert_eq!(a.cmp(&b), a.encode_key().cmp(&b.encode_key()));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[proptest]
+    fn test_uuids_keep_their_order(a: u128, b: u128) {
+        let (a, b) = (uuid::Uuid::from_u128(a), uuid::Uuid::from_u128(b));
+        prop_assert_eq!(a.cmp(&b), a.encode_key().cmp(&b.encode_key()));
+    }
+
+    #[test]
+    fn test_escapes_zero_bytes() {
+        let keys: [&[u8]; 5] = [b"", b"\0", b"\0\0", b"\0\x01", b"\x01"];
+        for pair in keys.windows(2) {
+            assert!(pair[0].encode_key() < pair[1].encode_key());
+        }
+
+        assert_eq!("a\0".encode_key(), [b'a', 0, 0xff, 0, 0]);
+        assert_eq!((1u8, -1i8).encode_key(), [1, 0x7f]);
+    }
+}
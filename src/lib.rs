@@ -1,3 +1,4 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![feature(coverage_attribute)]
 #![deny(
     clippy::correctness,
@@ -6,35 +7,187 @@
     clippy::missing_inline_in_public_items
 )]
 
+extern crate alloc;
+
+#[cfg(feature = "sha2")]
+mod backend;
+mod checker;
+mod clock;
+#[cfg(feature = "crdts")]
+pub mod compat;
+mod decode;
+mod embed;
 mod error;
+#[cfg(feature = "eth")]
+pub mod eth;
+#[cfg(feature = "evm")]
+pub mod evm;
+#[cfg(feature = "uniffi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 mod hash;
+mod hasher;
+#[cfg(feature = "http")]
+pub mod http;
+mod instrument;
+#[cfg(feature = "std")]
+pub mod invariants;
+pub mod io;
+mod key;
+#[cfg(feature = "std")]
+pub mod locked;
+mod map;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "std")]
 mod mutree;
+#[cfg(feature = "libp2p")]
+pub mod p2p;
+mod plutus;
+#[cfg(all(
+    feature = "std",
+    any(
+        feature = "blake2",
+        feature = "blake3",
+        feature = "keccak",
+        feature = "ripemd",
+        feature = "sha2",
+        feature = "sha3"
+    )
+))]
+mod profile;
+#[cfg(feature = "replication")]
+pub mod replication;
+#[cfg(feature = "shared")]
+pub mod shared;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "ssz")]
+pub mod ssz;
 mod trie;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
 pub mod testing;
 
-pub mod prelude {
-    pub use digest::Digest;
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
 
+/// Everything needed to use the crate, in a single import.
+///
+/// Libraries importing only part of it can use its [`traits`] and
+/// [`trie`](crate::prelude::trie) modules instead, so their other names don't collide with
+/// their own.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::{traits::*, trie::Trie};
+///
+/// let trie = Trie::<Blake2s256>::empty();
+/// assert!(Trie::<Blake2s256>::from_bytes(&trie.to_bytes()).is_ok());
+/// ```
+pub mod prelude {
+    pub use self::{traits::*, trie::*};
+    #[cfg(feature = "metrics")]
+    pub use crate::metrics::MergeMetrics;
     pub use crate::{
-        error::{Error, Result},
-        hash::Hash,
-        mutree::Mutree,
-        trie::{Neighbor, Proof, Step, Trie},
-        CmRDT,
-        CvRDT,
-        FromBytes,
-        FromHex,
-        ToBytes,
-        ToHex,
+        checker::{CrdtChecker, Law, Violation},
+        clock::HybridClock,
+        diverged,
+        map::CrdtMap,
+        plutus::PlutusData,
     };
+
+    /// The traits of the crate, and the [`Digest`] trait of the digests tries are hashed with.
+    pub mod traits {
+        pub use digest::Digest;
+
+        pub use crate::{
+            key::KeyEncode,
+            trie::ValueHasher,
+            CmRDT,
+            CvRDT,
+            DynCvRDT,
+            FromBytes,
+            FromHex,
+            ToBytes,
+            ToHex,
+        };
+    }
+
+    /// The Trie, its proofs and their configuration, and the errors of its operations.
+    pub mod trie {
+        pub use crate::{
+            clock::Timestamp,
+            decode::DecodeContext,
+            error::{Error, ErrorCode, Result, VerifyError},
+            hash::Hash,
+            trie::{
+                calculate_root_from_reader,
+                calculate_root_from_reader_with_format,
+                ChunkedMerkle,
+                DuplicateLeaves,
+                EntryLimits,
+                Epoch,
+                InternedProof,
+                LengthPrefixed,
+                MemoryUsage,
+                Neighbor,
+                PlainValues,
+                Profile,
+                Proof,
+                Redacted,
+                RootFormat,
+                SoundnessReport,
+                Step,
+                StepIssue,
+                StepReport,
+                Trie,
+                TrieBuilder,
+                VerifyMode,
+                MAX_SKIP,
+                MAX_STREAMED_STEP_LEN,
+            },
+        };
+        #[cfg(feature = "std")]
+        pub use crate::{
+            mutree::{BulkLoadConfig, Mutree},
+            trie::EpochAcks,
+        };
+    }
 }
 
-use digest::Digest;
-use proptest::prelude::*;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::any::{type_name, Any};
 
+#[cfg(feature = "derive")]
+pub use mutree_derive::CvRDT;
+
+#[cfg(feature = "sha2")]
+pub use self::backend::{hash_backend, HashBackend};
+pub use self::hasher::TrieHasher;
 use self::prelude::*;
+#[cfg(all(
+    feature = "std",
+    any(
+        feature = "blake2",
+        feature = "blake3",
+        feature = "keccak",
+        feature = "ripemd",
+        feature = "sha2",
+        feature = "sha3"
+    )
+))]
+pub use self::profile::{benchmark_digests, DigestBenchmark};
 
 /// A Conflict-free Replicated Data Type (CRDT) that supports state-based replication.
 ///
@@ -61,7 +214,7 @@ use self::prelude::*;
 ///     }
 /// }
 /// ```
-pub trait CvRDT: Sized + Arbitrary + Default + Clone + PartialEq {
+pub trait CvRDT: Sized + Default + Clone + PartialEq {
     /// Merges another CRDT state into this one.
     ///
     /// The merge operation combines the states of two replicas in a way that:
@@ -77,6 +230,47 @@ pub trait CvRDT: Sized + Arbitrary + Default + Clone + PartialEq {
     ///
     /// Returns `Ok(())` if the merge was successful, or an error if the merge failed
     fn merge(&mut self, other: &Self) -> Result<(), Error>;
+
+    /// A 32-byte digest of the state, for cheaply detecting replicas that are out of sync.
+    ///
+    /// Two replicas with the same fingerprint have the same state, so gossip rounds can skip
+    /// exchanging full states with them. The default implementation hashes the byte
+    /// representation of the state with `D`. Types that already carry a commitment to their state,
+    /// like the root of a [`Trie`], should return it instead.
+    #[inline]
+    fn fingerprint<D: TrieHasher>(&self) -> Hash
+    where
+        Self: ToBytes,
+    {
+        self.hash_bytes::<D>()
+    }
+}
+
+/// Checks whether two replicas are out of sync, by comparing their fingerprints.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut a = Trie::<Blake2s256>::empty();
+///     let mut b = Trie::<Blake2s256>::empty();
+///     assert!(!diverged::<Blake2s256, _>(&a, &b));
+///
+///     a.insert(b"key", &b"value"[..])?;
+///     assert!(diverged::<Blake2s256, _>(&a, &b));
+///
+///     b.merge(&a)?;
+///     assert!(!diverged::<Blake2s256, _>(&a, &b));
+///
+///     Ok(())
+/// }
+/// ```
+#[inline]
+pub fn diverged<D: TrieHasher, T: CvRDT + ToBytes>(a: &T, b: &T) -> bool {
+    a.fingerprint::<D>() != b.fingerprint::<D>()
 }
 
 /// A Conflict-free Replicated Data Type (CRDT) that supports operation-based replication.
@@ -85,10 +279,79 @@ pub trait CvRDT: Sized + Arbitrary + Default + Clone + PartialEq {
 /// Operations must be:
 /// - Commutative: order of operations doesn't matter
 /// - Idempotent: applying same operation multiple times has no effect
-pub trait CmRDT<T>: Sized + Arbitrary + Default + Clone + PartialEq {
+pub trait CmRDT<T>: Sized + Default + Clone + PartialEq {
     fn apply(&mut self, other: &T) -> Result<(), Error>;
 }
 
+/// An object-safe counterpart to [`CvRDT`], for managing heterogeneous CRDTs at runtime.
+///
+/// [`CvRDT`] can't be used as a trait object, since `merge` takes `&Self`. This trait erases the
+/// concrete type, either by downcasting another state with [`DynCvRDT::merge_dyn`], or by
+/// decoding it from bytes with [`DynCvRDT::merge_bytes`]. It is implemented automatically for
+/// every [`CvRDT`] that can be converted to and from bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::{Blake2b, Blake2s256, digest::consts::U32};
+///
+/// fn main() -> Result<(), Error> {
+///     let mut states: Vec<Box<dyn DynCvRDT>> = vec![
+///         Box::new(Trie::<Blake2s256>::empty()),
+///         Box::new(Trie::<Blake2b<U32>>::empty()),
+///     ];
+///
+///     let mut other = Trie::<Blake2s256>::empty();
+///     other.insert(b"key", &b"value"[..])?;
+///
+///     states[0].merge_dyn(&other)?;
+///     assert!(states[1].merge_dyn(&other).is_err());
+///     states[1].merge_bytes(&other.to_bytes())?;
+///
+///     Ok(())
+/// }
+/// ```
+pub trait DynCvRDT: Any {
+    /// Merges another state into this one, failing if it is not of the same concrete type.
+    fn merge_dyn(&mut self, other: &dyn Any) -> Result<(), Error>;
+
+    /// Decodes a state of the same concrete type from bytes, and merges it into this one.
+    fn merge_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Encodes this state, in the format expected by [`DynCvRDT::merge_bytes`].
+    fn state_bytes(&self) -> Vec<u8>;
+
+    /// Upcasts to [`Any`], for downcasting back to the concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: CvRDT + ToBytes + FromBytes + 'static> DynCvRDT for T {
+    #[inline]
+    fn merge_dyn(&mut self, other: &dyn Any) -> Result<(), Error> {
+        let other = other.downcast_ref::<T>().ok_or_else(|| {
+            Error::InvalidOperation(format!("can only merge {} states", type_name::<T>()))
+        })?;
+
+        self.merge(other)
+    }
+
+    #[inline]
+    fn merge_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.merge(&T::from_bytes(bytes)?)
+    }
+
+    #[inline]
+    fn state_bytes(&self) -> Vec<u8> {
+        self.to_bytes_vec()
+    }
+
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
 /// Provides conversion from a byte array representation.
 ///
 /// This trait allows types to be reconstructed from their serialized byte form.
@@ -99,6 +362,22 @@ where
     Self: Sized,
 {
     fn from_bytes(bytes: &[u8]) -> Result<Self>;
+
+    /// Converts a representation in bytes back to a value, rejecting inputs past the bounds of
+    /// `context`.
+    ///
+    /// The default implementation only bounds the length of the input. Types holding other
+    /// values decode them with the same context, so they are bounded too.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the input exceeds a bound of `context`, and the
+    /// errors of [`FromBytes::from_bytes`] otherwise.
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+        Self::from_bytes(bytes)
+    }
 }
 
 /// Provides conversion to a byte array representation.
@@ -122,10 +401,19 @@ pub trait ToBytes {
         self.to_bytes().as_ref().to_vec()
     }
 
+    /// Appends the representation in bytes to `bytes`.
+    ///
+    /// The default implementation goes through `to_bytes`. Types building their bytes
+    /// piecewise override it, so encoders of larger values can write them into a single buffer.
+    #[inline]
+    fn to_bytes_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.to_bytes().as_ref());
+    }
+
     ///
     /// This is a convenience method, and automatically derived from `to_bytes`.
     #[inline]
-    fn hash_bytes<D: Digest>(&self) -> crate::hash::Hash {
+    fn hash_bytes<D: TrieHasher>(&self) -> crate::hash::Hash {
         crate::hash::Hash::digest::<D>(self.to_bytes().as_ref())
     }
 
@@ -133,18 +421,22 @@ pub trait ToBytes {
     /// This is useful for checking if a value is empty.
     #[inline]
     fn is_zero(&self) -> bool {
-        let len = self.to_bytes().as_ref().len();
-        self.to_bytes_vec() == vec![0; len]
+        self.to_bytes().as_ref().iter().all(|&byte| byte == 0)
     }
 
     /// Provides mutable access to the bytes representation.
     ///
     /// This is an optional method that types can implement if they can
     /// safely provide mutable access to their byte representation.
-    /// The default implementation panics.
+    ///
+    /// # Errors
+    ///
+    /// The default implementation returns [`Error::InvalidOperation`].
     #[inline]
-    fn to_bytes_mut(&mut self) -> &mut [u8] {
-        unimplemented!("to_bytes_mut is not implemented for this type")
+    fn to_bytes_mut(&mut self) -> Result<&mut [u8]> {
+        Err(Error::InvalidOperation(
+            "to_bytes_mut is not implemented for this type".to_string(),
+        ))
     }
 }
 
@@ -159,9 +451,128 @@ pub trait ToHex {
     fn to_hex(&self) -> String;
 }
 
-#[cfg(test)]
+/// Implements [`FromHex`], [`ToHex`], [`LowerHex`](core::fmt::LowerHex) and
+/// [`UpperHex`](core::fmt::UpperHex) for types implementing [`ToBytes`] and [`FromBytes`], as the
+/// hex encoding of their bytes.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Nonce([u8; 4]);
+///
+/// impl ToBytes for Nonce {
+///     type Output = [u8; 4];
+///
+///     fn to_bytes(&self) -> Self::Output {
+///         self.0
+///     }
+/// }
+///
+/// impl FromBytes for Nonce {
+///     fn from_bytes(bytes: &[u8]) -> Result<Self> {
+///         Ok(Nonce(bytes.try_into()?))
+///     }
+/// }
+///
+/// mutree::impl_hex_via_bytes!(Nonce);
+///
+/// let nonce = Nonce([0xde, 0xad, 0xbe, 0xef]);
+/// assert_eq!(nonce.to_hex(), "deadbeef");
+/// assert_eq!(format!("{nonce:X}"), "DEADBEEF");
+/// assert_eq!(Nonce::from_hex("deadbeef").unwrap(), nonce);
+/// ```
+#[macro_export]
+macro_rules! impl_hex_via_bytes {
+    ($($type:ty),+ $(,)?) => {
+        $(
+            impl $crate::FromHex for $type {
+                #[inline]
+                fn from_hex(input: &str) -> $crate::prelude::Result<Self> {
+                    let bytes = $crate::__private::hex::decode(input)?;
+                    <Self as $crate::FromBytes>::from_bytes(&bytes)
+                }
+            }
+
+            impl $crate::ToHex for $type {
+                #[inline]
+                fn to_hex(&self) -> $crate::__private::String {
+                    $crate::__private::hex::encode($crate::ToBytes::to_bytes(self))
+                }
+            }
+
+            impl ::core::fmt::LowerHex for $type {
+                #[inline]
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(&$crate::ToHex::to_hex(self))
+                }
+            }
+
+            impl ::core::fmt::UpperHex for $type {
+                #[inline]
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    let bytes = $crate::ToBytes::to_bytes(self);
+                    f.write_str(&$crate::__private::hex::encode_upper(bytes))
+                }
+            }
+        )+
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::String;
+
+    pub use hex;
+
+    pub use crate::embed::{decode_hex, hex_len, is_valid_proof};
+}
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
 pub mod __dependencies {
     pub use paste;
     pub use proptest;
     pub use test_strategy;
 }
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use blake2::Blake2s256;
+    use test_strategy::Arbitrary;
+
+    use crate::prelude::*;
+
+    /// The state of a wallet replica, merged field by field.
+    #[derive(Debug, Clone, Default, PartialEq, Arbitrary, CvRDT)]
+    #[cvrdt(crate = "crate")]
+    struct Wallet {
+        balances: Trie<Blake2s256>,
+        spent: Trie<Blake2s256>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Arbitrary, CvRDT)]
+    #[cvrdt(crate = "crate")]
+    struct Pair<T: core::fmt::Debug + proptest::arbitrary::Arbitrary>(T, Wallet);
+
+    type WalletPair = Pair<Trie<Blake2s256>>;
+
+    crate::test_state_crdt_properties!(Wallet);
+    crate::test_state_crdt_properties!(WalletPair);
+
+    #[test]
+    fn test_merges_every_field() -> Result<()> {
+        let mut a = Wallet::default();
+        a.balances.insert(b"alice", &b"10"[..])?;
+        let mut b = Wallet::default();
+        b.spent.insert(b"note", &b"1"[..])?;
+
+        a.merge(&b)?;
+        assert!(a.balances.verify(b"alice", b"10"));
+        assert!(a.spent.verify(b"note", b"1"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! A Trie shared between threads behind a read-write lock.
+//!
+//! [`Trie`], [`Proof`] and [`Mutree`](crate::prelude::Mutree) are `Send` and `Sync` for every
+//! digest of this crate, so they can be moved between threads and read from many of them at
+//! once. Writes take `&mut self`, so sharing a Trie that is also written needs a lock.
+//! [`LockedTrie`] is that lock, with writes applied atomically: readers see the Trie either
+//! before or after a write, never in between. [`SharedTrie`](crate::shared::SharedTrie) serves
+//! readers without locking them, at the cost of a dependency on `arc-swap`.
+//!
+//! Building with `RUSTFLAGS="--cfg mutree_loom"` swaps the lock for the one of [loom], whose tests
+//! check every interleaving of a few readers and writers.
+//!
+//! [loom]: https://docs.rs/loom
+
+#[cfg(not(mutree_loom))]
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(mutree_loom)]
+use loom::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{prelude::*, TrieHasher};
+
+/// A Trie readable by many threads at once, and written by one at a time.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+///
+/// use blake2::Blake2s256;
+/// use mutree::{locked::LockedTrie, prelude::*};
+///
+/// fn main() -> Result<(), Error> {
+///     let locked = LockedTrie::<Blake2s256>::default();
+///
+///     thread::scope(|scope| {
+///         for i in 0u8..4 {
+///             let locked = &locked;
+///             scope.spawn(move || locked.update(|trie| trie.insert(&[i], &b"value"[..])));
+///         }
+///     });
+///
+///     assert!(locked.verify(&[3], b"value"));
+///     assert_eq!(locked.read(|trie| trie.proof().len()), 4);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LockedTrie<D: TrieHasher> {
+    trie: RwLock<Trie<D>>,
+}
+
+impl<D: TrieHasher + 'static> LockedTrie<D> {
+    /// Locks a Trie.
+    #[inline]
+    pub fn new(trie: Trie<D>) -> Self {
+        Self {
+            trie: RwLock::new(trie),
+        }
+    }
+
+    /// Calls `read` with the Trie, which no write changes meanwhile.
+    #[inline]
+    pub fn read<T>(&self, read: impl FnOnce(&Trie<D>) -> T) -> T {
+        read(&self.read_lock())
+    }
+
+    /// The root of the Trie.
+    #[inline]
+    pub fn root(&self) -> Hash {
+        self.read_lock().root()
+    }
+
+    /// Checks if the Trie proves that `key` is set to `value`.
+    #[inline]
+    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
+        self.read_lock().verify(key, value)
+    }
+
+    /// Applies `update` to a copy of the Trie, and replaces the Trie with it if it succeeds.
+    ///
+    /// Cloning the Trie is O(1), but the steps are copied on the first change, since the Trie
+    /// being replaced still shares them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `update`, in which case the Trie is left unchanged.
+    #[inline]
+    pub fn update<T>(&self, update: impl FnOnce(&mut Trie<D>) -> Result<T>) -> Result<T> {
+        let mut trie = self.write_lock();
+
+        let mut next = trie.clone();
+        let output = update(&mut next)?;
+        *trie = next;
+
+        Ok(output)
+    }
+
+    /// Replaces the Trie with `trie`, returning the one it replaced.
+    #[inline]
+    pub fn replace(&self, trie: Trie<D>) -> Trie<D> {
+        core::mem::replace(&mut self.write_lock(), trie)
+    }
+
+    /// Unlocks the Trie.
+    #[inline]
+    pub fn into_inner(self) -> Trie<D> {
+        self.trie
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes the lock for reading. Updates panicking in `update` leave the Trie unchanged, so
+    /// it is still usable after the lock is poisoned.
+    fn read_lock(&self) -> RwLockReadGuard<'_, Trie<D>> {
+        self.trie
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes the lock for writing, recovering from poisoning like [`LockedTrie::read_lock`].
+    fn write_lock(&self) -> RwLockWriteGuard<'_, Trie<D>> {
+        self.trie
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<D: TrieHasher + 'static> Default for LockedTrie<D> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Trie::empty())
+    }
+}
+
+impl<D: TrieHasher + 'static> From<Trie<D>> for LockedTrie<D> {
+    #[inline]
+    fn from(trie: Trie<D>) -> Self {
+        Self::new(trie)
+    }
+}
+
+#[cfg(all(test, not(mutree_loom)))]
+mod tests {
+    use std::thread;
+
+    use proptest::{collection::vec, prelude::*};
+    use static_assertions::assert_impl_all;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    type Blake2s = blake2::Blake2s256;
+
+    assert_impl_all!(Hash: Send, Sync);
+    assert_impl_all!(Step: Send, Sync);
+    assert_impl_all!(Proof: Send, Sync);
+    assert_impl_all!(Trie<Blake2s>: Send, Sync);
+    assert_impl_all!(Mutree<Blake2s>: Send, Sync);
+    assert_impl_all!(LockedTrie<Blake2s>: Send, Sync);
+    #[cfg(feature = "shared")]
+    assert_impl_all!(crate::shared::SharedTrie<Blake2s>: Send, Sync);
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let locked = LockedTrie::<Blake2s>::default();
+
+        thread::scope(|scope| {
+            for writer in 0u8..4 {
+                let locked = &locked;
+                scope.spawn(move || {
+                    for i in 0u8..16 {
+                        locked
+                            .update(|trie| trie.insert(&[writer, i], &b"value"[..]))
+                            .unwrap();
+                    }
+                });
+            }
+
+            scope.spawn(|| {
+                while locked.read(|trie| trie.proof().len()) < 64 {
+                    locked.read(|trie| {
+                        assert_eq!(Trie::<Blake2s>::from_proof(trie.proof().clone()), *trie)
+                    });
+                }
+            });
+        });
+
+        assert_eq!(locked.into_inner().proof().len(), 64);
+    }
+
+    #[proptest]
+    fn test_failed_updates_change_nothing(
+        #[strategy(vec(any::<u8>(), 1..8))] key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        prop_assume!(key != b"other");
+
+        let locked = LockedTrie::<Blake2s>::default();
+        locked.update(|trie| trie.insert(&key, &value[..]))?;
+        let root = locked.root();
+
+        let result = locked.update(|trie| {
+            trie.insert(b"other", &b"value"[..])?;
+            trie.insert(b"", &b"value"[..])
+        });
+
+        prop_assert!(result.is_err());
+        prop_assert_eq!(locked.root(), root);
+        prop_assert!(!locked.verify(b"other", b"value"));
+
+        let previous = locked.replace(Trie::empty());
+        prop_assert!(previous.verify(&key, &value));
+        prop_assert!(locked.read(Trie::is_empty));
+    }
+}
+
+#[cfg(all(test, mutree_loom))]
+mod loom_tests {
+    use loom::{sync::Arc, thread};
+
+    use super::*;
+
+    type Blake2s = blake2::Blake2s256;
+
+    #[test]
+    fn test_readers_never_see_partial_updates() {
+        loom::model(|| {
+            let locked = Arc::new(LockedTrie::<Blake2s>::default());
+
+            let writers: Vec<_> = (0u8..2)
+                .map(|writer| {
+                    let locked = locked.clone();
+                    thread::spawn(move || {
+                        locked
+                            .update(|trie| {
+                                trie.insert(&[writer], &b"value"[..])?;
+                                trie.insert(&[writer, writer], &b"value"[..])
+                            })
+                            .unwrap();
+                    })
+                })
+                .collect();
+
+            // Each update inserts two keys, so readers see both or neither
+            locked.read(|trie| {
+                for writer in 0u8..2 {
+                    assert_eq!(
+                        trie.verify(&[writer], b"value"),
+                        trie.verify(&[writer, writer], b"value")
+                    );
+                }
+            });
+
+            for writer in writers {
+                writer.join().unwrap();
+            }
+
+            let trie = Arc::try_unwrap(locked).unwrap().into_inner();
+            assert_eq!(trie.proof.iter().filter(|step| step.is_leaf()).count(), 4);
+        });
+    }
+}
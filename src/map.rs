@@ -0,0 +1,294 @@
+use alloc::collections::BTreeMap;
+use core::fmt::Debug;
+
+#[cfg(feature = "std")]
+use proptest::{collection::btree_map, prelude::*};
+
+use crate::{prelude::*, TrieHasher};
+
+/// A map of replicated values, committed into a [`Trie`].
+///
+/// Each value is itself a [`CvRDT`], and merging two maps merges the values of every key they
+/// share, so nested replicated documents converge. The state of every value is committed as the
+/// leaf value of its key in a Trie, so the whole map remains provable under a single root.
+///
+/// The Trie is rebuilt in key order after every change, so two maps holding the same entries
+/// always have the same root, regardless of the order in which they were built.
+///
+/// # Type Parameters
+///
+/// * `K` - The application key, used as the key of the Trie leaf
+/// * `V` - The replicated value, whose serialized state is used as the value of the Trie leaf
+/// * `D` - The digest algorithm of the Trie
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// type Document = Trie<Blake2s256>;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut document = Document::empty();
+///     document.insert(b"title", &b"Hello"[..])?;
+///
+///     let mut a = CrdtMap::<String, Document, Blake2s256>::new();
+///     a.merge_value("doc-1".to_string(), &document)?;
+///
+///     let mut b = CrdtMap::new();
+///     b.merge(&a)?;
+///
+///     assert_eq!(a.root(), b.root());
+///     assert!(b.verify(&"doc-1".to_string()));
+///
+///     Ok(())
+/// }
+/// ```
+pub struct CrdtMap<K, V, D: TrieHasher> {
+    entries: BTreeMap<K, V>,
+    trie: Trie<D>,
+}
+
+impl<K, V, D> CrdtMap<K, V, D>
+where
+    K: AsRef<[u8]> + Ord + Clone,
+    V: CvRDT + ToBytes,
+    D: TrieHasher + 'static,
+{
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            trie: Trie::empty(),
+        }
+    }
+
+    /// Returns the value for the given key, if any.
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    /// Checks if the map has a value for the given key.
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Returns the number of keys in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the entries of the map, in key order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter()
+    }
+
+    /// The Trie committing to the state of every value.
+    #[inline]
+    pub fn trie(&self) -> &Trie<D> {
+        &self.trie
+    }
+
+    /// The root of the Trie committing to the state of every value.
+    #[inline]
+    pub fn root(&self) -> Hash {
+        self.trie.root()
+    }
+
+    /// Checks that the current state of the value for `key` is committed under the root.
+    #[inline]
+    pub fn verify(&self, key: &K) -> bool {
+        self.entries
+            .get(key)
+            .is_some_and(|value| self.trie.verify(key.as_ref(), value.to_bytes().as_ref()))
+    }
+
+    /// Merges `value` into the value for `key`, starting from the default state if the key is
+    /// not present yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the key is empty, or any error from merging the
+    /// value itself.
+    #[inline]
+    pub fn merge_value(&mut self, key: K, value: &V) -> Result<()> {
+        if key.as_ref().is_empty() {
+            return Err(Error::EmptyKeyOrValue);
+        }
+
+        self.entries.entry(key).or_default().merge(value)?;
+        self.commit()
+    }
+
+    /// Rebuilds the Trie from the entries, in key order.
+    fn commit(&mut self) -> Result<()> {
+        let mut trie = Trie::empty();
+
+        for (key, value) in self.entries.iter() {
+            trie.insert(key.as_ref(), value.to_bytes().as_ref())?;
+        }
+
+        self.trie = trie;
+        Ok(())
+    }
+}
+
+impl<K, V, D> CvRDT for CrdtMap<K, V, D>
+where
+    K: AsRef<[u8]> + Ord + Clone + 'static,
+    V: CvRDT + ToBytes + 'static,
+    D: TrieHasher + 'static,
+{
+    #[inline]
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        for (key, value) in other.entries.iter() {
+            self.entries.entry(key.clone()).or_default().merge(value)?;
+        }
+
+        self.commit()
+    }
+}
+
+impl<K, V, D> Default for CrdtMap<K, V, D>
+where
+    K: AsRef<[u8]> + Ord + Clone,
+    V: CvRDT + ToBytes,
+    D: TrieHasher + 'static,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Clone, V: Clone, D: TrieHasher> Clone for CrdtMap<K, V, D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            trie: self.trie.clone(),
+        }
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, D: TrieHasher> PartialEq for CrdtMap<K, V, D> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl<K: Debug, V: Debug, D: TrieHasher> Debug for CrdtMap<K, V, D> {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CrdtMap")
+            .field("entries", &self.entries)
+            .field("root", &self.trie.root())
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, D> Arbitrary for CrdtMap<K, V, D>
+where
+    K: AsRef<[u8]> + Ord + Clone + Arbitrary + Debug + 'static,
+    V: CvRDT + ToBytes + Arbitrary + 'static,
+    D: TrieHasher + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    #[inline]
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        btree_map(
+            any::<K>().prop_filter("must not be empty", |k| !k.as_ref().is_empty()),
+            any::<V>(),
+            0..8,
+        )
+        .prop_map(|entries| {
+            let mut map = Self {
+                entries,
+                trie: Trie::empty(),
+            };
+            map.commit().expect("keys are never empty");
+            map
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    type Document = Trie<Blake2s256>;
+    type Map = CrdtMap<String, Document, Blake2s256>;
+
+    crate::test_state_crdt_properties!(Map);
+    crate::test_convergence!(Map);
+
+    #[proptest]
+    fn test_root_is_independent_of_merge_order(a: Map, b: Map) {
+        let mut ab = a.clone();
+        ab.merge(&b)?;
+
+        let mut ba = b.clone();
+        ba.merge(&a)?;
+
+        prop_assume!(ab == ba);
+        prop_assert_eq!(ab.root(), ba.root());
+    }
+
+    #[proptest]
+    fn test_every_entry_is_verified(map: Map) {
+        for (key, _) in map.iter() {
+            prop_assert!(map.verify(key));
+        }
+    }
+
+    #[test]
+    fn test_merge_recurses_into_values() -> Result<()> {
+        let mut left = Document::empty();
+        left.insert(b"a", &b"1"[..])?;
+        let mut right = Document::empty();
+        right.insert(b"b", &b"2"[..])?;
+
+        let mut a = Map::new();
+        a.merge_value("doc".to_string(), &left)?;
+        let mut b = Map::new();
+        b.merge_value("doc".to_string(), &right)?;
+
+        a.merge(&b)?;
+
+        let document = a.get(&"doc".to_string()).unwrap();
+        assert!(document.verify(b"a", b"1"));
+        assert!(document.verify(b"b", b"2"));
+        assert!(a.verify(&"doc".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_empty_keys() {
+        let mut map = Map::new();
+        assert!(matches!(
+            map.merge_value(String::new(), &Document::empty()),
+            Err(Error::EmptyKeyOrValue)
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use crate::prelude::*;
+
+/// Counters accumulated over every merge performed on a replica.
+///
+/// Replicas that keep receiving steps they cannot incorporate, or whose roots change on almost
+/// every merge, are likely diverging from their peers. These counters make that visible without
+/// having to diff proofs by hand.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut a = Trie::<Blake2s256>::empty();
+///     let mut b = Trie::<Blake2s256>::empty();
+///     b.insert(b"key", &b"value"[..])?;
+///
+///     a.merge(&b)?;
+///
+///     let metrics = a.merge_metrics();
+///     assert_eq!(metrics.merges, 1);
+///     assert_eq!(metrics.steps_received, 1);
+///     assert_eq!(metrics.steps_incorporated, 1);
+///     assert_eq!(metrics.root_changes, 1);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeMetrics {
+    /// Number of merges performed
+    pub merges: u64,
+    /// Total number of steps received from other replicas
+    pub steps_received: u64,
+    /// Number of received steps that were not already present locally, and were kept after
+    /// resolving conflicting writes
+    pub steps_incorporated: u64,
+    /// Number of received leaves committing a different value for a key already present
+    pub conflicts: u64,
+    /// Number of merges that resulted in a different root
+    pub root_changes: u64,
+    /// Total time spent merging
+    pub elapsed: Duration,
+}
+
+impl MergeMetrics {
+    /// Ratio of merges that changed the root, between 0.0 and 1.0.
+    ///
+    /// A replica that is in sync with its peers should see this approach zero over time.
+    #[inline]
+    pub fn root_change_ratio(&self) -> f64 {
+        if self.merges == 0 {
+            return 0.0;
+        }
+
+        self.root_changes as f64 / self.merges as f64
+    }
+
+    /// Resets all counters to zero.
+    #[inline]
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn record(&mut self, observation: MergeObservation, proof: &Proof, root: Hash) {
+        self.merges += 1;
+        self.steps_received += observation.steps_received;
+        self.steps_incorporated += proof
+            .iter()
+            .filter(|step| !observation.previous.contains(step))
+            .count() as u64;
+        self.conflicts += observation.conflicts;
+        self.root_changes += u64::from(observation.previous_root != root);
+        self.elapsed += observation.started.elapsed();
+    }
+}
+
+/// State captured right before a merge, used to derive the counters once it is done.
+pub(crate) struct MergeObservation {
+    started: Instant,
+    previous: HashSet<Step>,
+    previous_root: Hash,
+    steps_received: u64,
+    conflicts: u64,
+}
+
+impl MergeObservation {
+    pub(crate) fn start(local: &Proof, root: Hash, remote: &Proof) -> Self {
+        let leaves: HashMap<Hash, Hash> = local
+            .iter()
+            .filter_map(|step| match step {
+                Step::Leaf { key, value, .. } => Some((*key, *value)),
+                _ => None,
+            })
+            .collect();
+
+        let conflicts = remote
+            .iter()
+            .filter(|step| {
+                matches!(step, Step::Leaf { key, value, .. }
+                    if leaves.get(key).is_some_and(|local| local != value))
+            })
+            .count() as u64;
+
+        Self {
+            started: Instant::now(),
+            previous: local.iter().cloned().collect(),
+            previous_root: root,
+            steps_received: remote.len() as u64,
+            conflicts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+
+    use super::*;
+
+    #[test]
+    fn test_merge_counts_conflicts() -> Result<()> {
+        let mut a = Trie::<Blake2s256>::empty();
+        let mut b = Trie::<Blake2s256>::empty();
+
+        a.insert_at(b"key", &b"left"[..], Timestamp::new(1, 0))?;
+        b.insert_at(b"key", &b"right"[..], Timestamp::new(2, 0))?;
+        b.insert(b"other", &b"value"[..])?;
+
+        a.merge(&b)?;
+
+        let metrics = a.merge_metrics();
+        assert_eq!(metrics.merges, 1);
+        assert_eq!(metrics.steps_received, 2);
+        assert_eq!(metrics.steps_incorporated, 2);
+        assert_eq!(metrics.conflicts, 1);
+        assert_eq!(metrics.root_changes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_with_self_changes_nothing() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+
+        let other = trie.clone();
+        trie.merge(&other)?;
+
+        let metrics = trie.merge_metrics();
+        assert_eq!(metrics.steps_incorporated, 0);
+        assert_eq!(metrics.conflicts, 0);
+        assert_eq!(metrics.root_changes, 0);
+        assert_eq!(metrics.root_change_ratio(), 0.0);
+
+        trie.reset_merge_metrics();
+        assert_eq!(*trie.merge_metrics(), MergeMetrics::default());
+
+        Ok(())
+    }
+}
@@ -1,14 +1,41 @@
-use redb::{backends::InMemoryBackend, Database};
+use std::{fs::File, io::Read, num::NonZeroUsize, path::Path, sync::mpsc, thread};
 
-use crate::prelude::*;
+use redb::{backends::InMemoryBackend, Database, ReadableTable, TableDefinition};
+
+use crate::{instrument::operation, prelude::*, TrieHasher};
+
+/// The table holding the values of the entries, by key.
+const ENTRIES: TableDefinition<&[u8], &[u8]> = TableDefinition::new("entries");
+
+/// An entry being loaded, with the key and value hashes once computed.
+type Hashed = (Vec<u8>, Vec<u8>, Hash, Hash);
 
 #[derive(Debug)]
-pub struct Mutree<D: Digest> {
+pub struct Mutree<D: TrieHasher> {
     pub trie: Trie<D>,
     pub database: Database,
 }
 
-impl<D: Digest + 'static> Mutree<D> {
+/// Configuration for [`Mutree::bulk_load`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkLoadConfig {
+    /// The number of threads hashing keys and values
+    pub concurrency: NonZeroUsize,
+    /// The number of entries committed to the database and the Trie at once
+    pub batch_size: NonZeroUsize,
+}
+
+impl Default for BulkLoadConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            concurrency: thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            batch_size: NonZeroUsize::new(1024).unwrap(),
+        }
+    }
+}
+
+impl<D: TrieHasher + 'static> Mutree<D> {
     #[inline]
     pub fn new_in_memory() -> Result<Self, Error> {
         Ok(Self {
@@ -16,4 +43,479 @@ impl<D: Digest + 'static> Mutree<D> {
             database: Database::builder().create_with_backend(InMemoryBackend::new())?,
         })
     }
+
+    /// Returns the value stored for `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DatabaseError`] if reading from the database fails.
+    #[inline]
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let transaction = self.database.begin_read()?;
+        let table = match transaction.open_table(ENTRIES) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(table.get(key)?.map(|value| value.value().to_vec()))
+    }
+
+    /// Returns the value stored for a structured key, encoded with [`KeyEncode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DatabaseError`] if reading from the database fails.
+    #[inline]
+    pub fn get_encoded<K: KeyEncode + ?Sized>(&self, key: &K) -> Result<Option<Vec<u8>>> {
+        self.get(&key.encode_key())
+    }
+
+    /// Imports many entries, storing their values in the database and inserting them in the
+    /// Trie.
+    ///
+    /// Hashing keys and values is the expensive part of an import, so entries are hashed on
+    /// `config.concurrency` threads, a batch ahead of the current thread, which commits each
+    /// batch to the database and the Trie in the order of `entries`. The root is recalculated
+    /// once per batch instead of once per entry. The resulting Trie is the same as the one
+    /// built by inserting the entries one by one.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of entries loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if a key is empty, [`Error::InvalidOperation`] if an
+    /// entry exceeds the [entry limits](Trie::set_entry_limits) of the Trie,
+    /// [`Error::MemoryBudgetExceeded`] if a batch would exceed its memory budget, or
+    /// [`Error::DatabaseError`] if writing to the database fails. Batches before the failing one stay loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let entries = (0u32..100).map(|i| (i.to_be_bytes().to_vec(), b"value".to_vec()));
+    ///
+    ///     let mut mutree = Mutree::<Blake2s256>::new_in_memory()?;
+    ///     assert_eq!(mutree.bulk_load(entries, &BulkLoadConfig::default())?, 100);
+    ///
+    ///     assert!(mutree.trie.verify(&7u32.to_be_bytes(), b"value"));
+    ///     assert_eq!(mutree.get(&7u32.to_be_bytes())?, Some(b"value".to_vec()));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn bulk_load<I>(&mut self, entries: I, config: &BulkLoadConfig) -> Result<usize>
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        let mut operation = operation!("bulk_load");
+
+        let mut entries = entries.into_iter();
+        let mut next_batch =
+            || -> Vec<_> { entries.by_ref().take(config.batch_size.get()).collect() };
+        let limits = self.trie.entry_limits();
+        let value_hasher = self.trie.shared_value_hasher();
+        let values = value_hasher.as_deref();
+
+        let loaded = thread::scope(|scope| {
+            let (sender, receiver) = mpsc::sync_channel(1);
+            let hash_in_background = |batch| {
+                let sender = sender.clone();
+                let concurrency = config.concurrency;
+                scope
+                    .spawn(move || sender.send(Self::hash_batch(batch, concurrency, limits, values)));
+            };
+
+            let mut loaded = 0;
+            let mut batch = next_batch();
+            let mut hashing = !batch.is_empty();
+            if hashing {
+                hash_in_background(batch);
+            }
+
+            while hashing {
+                let hashed = receiver
+                    .recv()
+                    .expect("hashing threads always send their batch")?;
+
+                // Hash the following batch while this one is committed
+                batch = next_batch();
+                hashing = !batch.is_empty();
+                if hashing {
+                    hash_in_background(batch);
+                }
+
+                loaded += self.commit_batch(&hashed)?;
+            }
+
+            Ok::<_, Error>(loaded)
+        })?;
+
+        operation.succeed(self.trie.proof().len());
+
+        Ok(loaded)
+    }
+
+    /// Hashes the entries of a batch on `concurrency` threads, keeping their order.
+    fn hash_batch(
+        batch: Vec<(Vec<u8>, Vec<u8>)>,
+        concurrency: NonZeroUsize,
+        limits: EntryLimits,
+        values: Option<&dyn ValueHasher>,
+    ) -> Result<Vec<Hashed>> {
+        let chunk_size = batch.len().div_ceil(concurrency.get()).max(1);
+        let hash = |chunk: &[(Vec<u8>, Vec<u8>)]| {
+            let mut scratch = Vec::new();
+            chunk
+                .iter()
+                .map(|(key, value)| {
+                    Trie::<D>::hash_entry(key, &value[..], &mut scratch, limits, values)
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        let hashes = thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || hash(chunk)))
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("hashing threads don't panic"))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        Ok(batch
+            .into_iter()
+            .zip(hashes.into_iter().flatten())
+            .map(|((key, value), (key_hash, value_hash))| (key, value, key_hash, value_hash))
+            .collect())
+    }
+
+    /// Inserts a key-value pair whose value is the content of the file at `path`, storing it in
+    /// the database and inserting it in the Trie.
+    ///
+    /// With the `mmap` feature, the file is memory-mapped, then hashed and written to the
+    /// database from the map, without reading it into a buffer first. The file must not be
+    /// modified meanwhile. Otherwise, or if the file can't be mapped, it is read into memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the key is empty, [`Error::InvalidOperation`] if
+    /// the key or the file exceeds the entry limits of the Trie, [`Error::Unknown`] if the file
+    /// can't be read, [`Error::MemoryBudgetExceeded`] if the insertion would exceed the memory
+    /// budget of the Trie, or [`Error::DatabaseError`] if writing to the database fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let path = std::env::temp_dir().join("mutree-mutree-insert-file-example");
+    ///     std::fs::write(&path, b"value").unwrap();
+    ///
+    ///     let mut mutree = Mutree::<Blake2s256>::new_in_memory()?;
+    ///     mutree.insert_file(b"key", &path)?;
+    ///     assert!(mutree.trie.verify(b"key", b"value"));
+    ///     assert_eq!(mutree.get(b"key")?, Some(b"value".to_vec()));
+    ///
+    ///     std::fs::remove_file(&path).unwrap();
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn insert_file<P: AsRef<Path>>(&mut self, key: &[u8], path: P) -> Result<Hash> {
+        let file = File::open(path).map_err(|e| Error::Unknown(e.to_string()))?;
+
+        #[cfg(all(feature = "mmap", any(unix, windows)))]
+        if let Some(map) = Trie::<D>::map_file(&file) {
+            let hashes = Trie::<D>::hash_entry_slice(
+                key,
+                &map,
+                self.trie.entry_limits(),
+                self.trie.value_hasher(),
+            )?;
+            self.commit([(key, &map[..])], &[hashes])?;
+
+            return Ok(hashes.1);
+        }
+
+        // Read one byte past the limit, so that longer files are refused without reading them
+        let limits = self.trie.entry_limits();
+        let mut value = Vec::new();
+        (&file)
+            .take(limits.max_value_len.saturating_add(1))
+            .read_to_end(&mut value)
+            .map_err(|e| Error::Unknown(e.to_string()))?;
+
+        let hashes = Trie::<D>::hash_entry(
+            key,
+            &value[..],
+            &mut Vec::new(),
+            limits,
+            self.trie.value_hasher(),
+        )?;
+        self.commit([(key, &value[..])], &[hashes])?;
+
+        Ok(hashes.1)
+    }
+
+    /// Rewrites the proofs stored as the values of `table`, in the database, from the encoding
+    /// of [`Proof::migrate_from_v0`] to the current one, in place.
+    ///
+    /// The table is rewritten in a single transaction, so it is either fully migrated or left
+    /// unchanged. Proofs already in the current encoding are left as they are, so a migration
+    /// interrupted and run again, or run on a table written by 64-bit targets, does nothing more.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of proofs rewritten.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if a value is not a proof in either encoding, or
+    /// [`Error::DatabaseError`] if reading or writing the database fails, in which case no proof
+    /// is rewritten.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    /// use redb::TableDefinition;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mutree = Mutree::<Blake2s256>::new_in_memory()?;
+    ///     let proofs = TableDefinition::<&[u8], &[u8]>::new("proofs");
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///     let transaction = mutree.database.begin_write()?;
+    ///     transaction
+    ///         .open_table(proofs)?
+    ///         .insert(&b"key"[..], &trie.proof().to_bytes()[..])?;
+    ///     transaction.commit()?;
+    ///
+    ///     // Written in the current encoding already
+    ///     assert_eq!(mutree.migrate_proofs_from_v0("proofs")?, 0);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn migrate_proofs_from_v0(&self, table: &str) -> Result<usize> {
+        let definition = TableDefinition::<&[u8], &[u8]>::new(table);
+
+        let transaction = self.database.begin_write()?;
+        let migrated = {
+            let mut table = transaction.open_table(definition)?;
+            let mut upgrades = Vec::new();
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                let bytes = Proof::migrate_from_v0(value.value())?.to_bytes();
+                if bytes != value.value() {
+                    upgrades.push((key.value().to_vec(), bytes));
+                }
+            }
+
+            for (key, bytes) in &upgrades {
+                table.insert(&key[..], &bytes[..])?;
+            }
+            upgrades.len()
+        };
+        transaction.commit()?;
+
+        Ok(migrated)
+    }
+
+    /// Writes a hashed batch to the database, then inserts it in the Trie.
+    fn commit_batch(&mut self, batch: &[Hashed]) -> Result<usize> {
+        let hashes: Vec<_> = batch
+            .iter()
+            .map(|(_, _, key_hash, value_hash)| (*key_hash, *value_hash))
+            .collect();
+
+        self.commit(
+            batch.iter().map(|(key, value, ..)| (&key[..], &value[..])),
+            &hashes,
+        )?;
+
+        Ok(batch.len())
+    }
+
+    /// Writes entries to the database, then inserts their `hashes` in the Trie.
+    fn commit<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'a [u8], &'a [u8])>,
+        hashes: &[(Hash, Hash)],
+    ) -> Result<()> {
+        let mut operation = operation!("commit", entries = hashes.len());
+
+        let transaction = self.database.begin_write()?;
+        {
+            let mut table = transaction.open_table(ENTRIES)?;
+            for (key, value) in entries {
+                table.insert(key, value)?;
+            }
+        }
+
+        // Checking the memory budget first leaves nothing to fail in the Trie once the values
+        // are committed
+        self.trie.reserve_steps(hashes.len())?;
+        transaction.commit()?;
+        self.trie.insert_hashed(hashes)?;
+
+        operation.succeed(self.trie.proof().len());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    type Blake2s = blake2::Blake2s256;
+
+    #[proptest(cases = 32)]
+    fn test_bulk_load_matches_inserts(
+        #[strategy(vec((vec(any::<u8>(), 1..4), vec(any::<u8>(), 0..8)), 0..64))] entries: Vec<(
+            Vec<u8>,
+            Vec<u8>,
+        )>,
+        #[strategy(1..4usize)] concurrency: usize,
+        #[strategy(1..16usize)] batch_size: usize,
+    ) {
+        let config = BulkLoadConfig {
+            concurrency: NonZeroUsize::new(concurrency).unwrap(),
+            batch_size: NonZeroUsize::new(batch_size).unwrap(),
+        };
+
+        let mut mutree = Mutree::<Blake2s>::new_in_memory()?;
+        prop_assert_eq!(mutree.bulk_load(entries.clone(), &config)?, entries.len());
+
+        let mut expected = Trie::<Blake2s>::empty();
+        for (key, value) in &entries {
+            expected.insert(key, &value[..])?;
+        }
+        prop_assert_eq!(&mutree.trie.proof(), &expected.proof());
+        prop_assert_eq!(mutree.trie.root(), expected.root());
+
+        for (key, _) in &entries {
+            let (_, latest) = entries.iter().rfind(|(other, _)| other == key).unwrap();
+            prop_assert_eq!(mutree.get(key)?, Some(latest.clone()));
+            prop_assert!(mutree.trie.verify(key, latest));
+        }
+    }
+
+    #[proptest(cases = 32)]
+    fn test_insert_file_stores_value(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        let path = std::env::temp_dir().join(format!(
+            "mutree-test-mutree-insert-file-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, &value).unwrap();
+
+        let mut mutree = Mutree::<Blake2s>::new_in_memory()?;
+        let inserted = mutree.insert_file(&key, &path);
+        std::fs::remove_file(&path).unwrap();
+
+        prop_assert_eq!(inserted?, Hash::digest::<Blake2s>(&value));
+        prop_assert_eq!(mutree.get(&key)?, Some(value.clone()));
+        prop_assert!(mutree.trie.verify(&key, &value));
+        prop_assert!(mutree.insert_file(&key, "/nonexistent/mutree").is_err());
+    }
+
+    #[test]
+    fn test_bulk_load_stops_at_empty_key() -> Result<()> {
+        let config = BulkLoadConfig {
+            concurrency: NonZeroUsize::new(2).unwrap(),
+            batch_size: NonZeroUsize::new(2).unwrap(),
+        };
+        let entries = [&b"a"[..], b"b", b"c", b"", b"d"].map(|key| (key.to_vec(), b"value".to_vec()));
+
+        let mut mutree = Mutree::<Blake2s>::new_in_memory()?;
+        assert!(matches!(
+            mutree.bulk_load(entries, &config),
+            Err(Error::EmptyKeyOrValue)
+        ));
+
+        // The first batch was committed before the second one failed to hash
+        assert!(mutree.trie.verify(b"b", b"value"));
+        assert_eq!(mutree.get(b"b")?, Some(b"value".to_vec()));
+        assert!(!mutree.trie.verify(b"c", b"value"));
+        assert_eq!(mutree.get(b"c")?, None);
+        assert_eq!(mutree.get(b"d")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_load_stops_at_oversized_value() -> Result<()> {
+        let mut mutree = Mutree::<Blake2s>::new_in_memory()?;
+        mutree.trie.set_entry_limits(EntryLimits {
+            max_value_len: 4,
+            ..EntryLimits::default()
+        });
+        let entries = [&b"1234"[..], b"12345"].map(|value| (value.to_vec(), value.to_vec()));
+
+        assert!(matches!(
+            mutree.bulk_load(entries, &BulkLoadConfig::default()),
+            Err(Error::InvalidOperation(_))
+        ));
+        assert!(mutree.trie.is_empty());
+        assert_eq!(mutree.get(b"1234")?, None);
+
+        Ok(())
+    }
+
+    #[proptest(cases = 32)]
+    fn test_migrates_proofs_in_place(
+        #[strategy(vec(crate::testing::strategies::realistic_proof::<Blake2s>(4), 0..8))] proofs: Vec<
+            Proof,
+        >,
+        #[strategy(vec(any::<bool>(), #proofs.len()))] narrow: Vec<bool>,
+    ) {
+        let mutree = Mutree::<Blake2s>::new_in_memory()?;
+        let table = TableDefinition::<&[u8], &[u8]>::new("proofs");
+
+        let transaction = mutree.database.begin_write()?;
+        {
+            let mut table = transaction.open_table(table)?;
+            for (i, (proof, &narrow)) in proofs.iter().zip(&narrow).enumerate() {
+                let bytes = match narrow {
+                    true => proof.to_narrow_v0_bytes(),
+                    false => proof.to_bytes(),
+                };
+                table.insert(&(i as u32).to_be_bytes()[..], &bytes[..])?;
+            }
+        }
+        transaction.commit()?;
+
+        let expected = narrow.iter().filter(|&&narrow| narrow).count();
+        prop_assert_eq!(mutree.migrate_proofs_from_v0("proofs")?, expected);
+        prop_assert_eq!(mutree.migrate_proofs_from_v0("proofs")?, 0);
+
+        let transaction = mutree.database.begin_read()?;
+        let table = transaction.open_table(table)?;
+        for (i, proof) in proofs.iter().enumerate() {
+            let bytes = table.get(&(i as u32).to_be_bytes()[..])?.unwrap();
+            prop_assert_eq!(&Proof::from_bytes(bytes.value())?, proof);
+        }
+    }
 }
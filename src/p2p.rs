@@ -0,0 +1,428 @@
+//! Replicating a [`Trie`] over libp2p, with gossipsub and request-response.
+//!
+//! Replicas announce their root on the [`ROOTS_TOPIC`] gossipsub topic whenever it changes, and
+//! whenever a new peer subscribes to it. A replica receiving a root it doesn't hold asks the
+//! announcer for its state over the [`SYNC_PROTOCOL`] request-response protocol, sending its own
+//! root and the [`wire`](crate::wire) versions it decodes along: the announcer answers with
+//! nothing if both roots match, and with a [`StateBundle`] encoded at the newest version both
+//! support otherwise. Bundles are verified before being merged, and merges changing the
+//! root are announced in turn, so every connected replica eventually converges.
+//!
+//! [`Behaviour`] is the network behaviour to build a swarm with, and [`Replica`] holds the Trie,
+//! reacting to the events of the behaviour.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use blake2::Blake2s256;
+//! use libp2p::{
+//!     futures::StreamExt, identity::Keypair, noise, swarm::SwarmEvent, tcp, yamux, SwarmBuilder,
+//! };
+//! use mutree::{
+//!     p2p::{Behaviour, Replica},
+//!     prelude::*,
+//! };
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let keypair = Keypair::generate_ed25519();
+//! let behaviour = Behaviour::new(&keypair)?;
+//! let mut swarm = SwarmBuilder::with_existing_identity(keypair)
+//!     .with_tokio()
+//!     .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+//!     .with_behaviour(|_| behaviour)?
+//!     .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+//!     .build();
+//! swarm.listen_on("/ip4/0.0.0.0/tcp/4001".parse()?)?;
+//!
+//! let mut replica = Replica::new(Trie::<Blake2s256>::empty());
+//! replica.trie_mut().insert(b"key", &b"value"[..])?;
+//! swarm.behaviour_mut().subscribe()?;
+//!
+//! loop {
+//!     if let SwarmEvent::Behaviour(event) = swarm.select_next_some().await {
+//!         if let Err(error) = replica.handle(swarm.behaviour_mut(), event) {
+//!             eprintln!("sync failed: {error}");
+//!         }
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use libp2p::{
+    gossipsub::{self, IdentTopic, MessageAuthenticity},
+    identity::Keypair,
+    request_response::{self, ProtocolSupport},
+    swarm::NetworkBehaviour,
+    PeerId,
+    StreamProtocol,
+};
+
+use crate::{
+    prelude::*,
+    replication::StateBundle,
+    wire::{negotiate, supported_versions, WireFormat},
+    TrieHasher,
+};
+
+/// The gossipsub topic replicas announce their roots on.
+pub const ROOTS_TOPIC: &str = "/mutree/roots/1";
+
+/// The request-response protocol replicas exchange their state with.
+pub const SYNC_PROTOCOL: &str = "/mutree/sync/2";
+
+/// The network behaviour of a replica: gossipsub for root announcements, and request-response
+/// for state exchanges.
+///
+/// Requests hold the 32-byte root of the requester, followed by the wire versions it decodes, as
+/// big-endian `u16`s. Responses are empty if the responder holds the same root, and hold a
+/// [`StateBundle`] encoded with [`WireFormat::to_wire`] at the newest version both support
+/// otherwise. Both are framed with CBOR.
+#[derive(NetworkBehaviour)]
+pub struct Behaviour {
+    pub gossipsub: gossipsub::Behaviour,
+    pub sync: request_response::cbor::Behaviour<Vec<u8>, Vec<u8>>,
+}
+
+impl Behaviour {
+    /// Creates a behaviour signing its announcements with `keypair`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Transport`] if gossipsub rejects its configuration.
+    #[inline]
+    pub fn new(keypair: &Keypair) -> Result<Self> {
+        let config = gossipsub::ConfigBuilder::default()
+            .heartbeat_interval(Duration::from_secs(1))
+            .build()
+            .map_err(|error| Error::Transport(error.to_string()))?;
+        let gossipsub =
+            gossipsub::Behaviour::new(MessageAuthenticity::Signed(keypair.clone()), config)
+                .map_err(|error| Error::Transport(error.to_string()))?;
+        let sync = request_response::cbor::Behaviour::new(
+            [(StreamProtocol::new(SYNC_PROTOCOL), ProtocolSupport::Full)],
+            request_response::Config::default(),
+        );
+
+        Ok(Self { gossipsub, sync })
+    }
+
+    /// Subscribes to [`ROOTS_TOPIC`], to start receiving announcements.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Transport`] if gossipsub fails to subscribe.
+    #[inline]
+    pub fn subscribe(&mut self) -> Result<()> {
+        self.gossipsub
+            .subscribe(&IdentTopic::new(ROOTS_TOPIC))
+            .map_err(|error| Error::Transport(error.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Announces `root` on [`ROOTS_TOPIC`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Transport`] if the announcement can't be published, including when no
+    /// peer is subscribed to the topic yet.
+    #[inline]
+    pub fn announce(&mut self, root: Hash) -> Result<()> {
+        self.gossipsub
+            .publish(IdentTopic::new(ROOTS_TOPIC), root.to_bytes_vec())
+            .map_err(|error| Error::Transport(error.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Asks `peer` for its state, sending `root` along so it can skip answering if it holds it,
+    /// and the wire versions this release decodes so it can pick one for its answer.
+    #[inline]
+    pub fn request(&mut self, peer: &PeerId, root: Hash) {
+        let mut request = root.to_bytes_vec();
+        for version in supported_versions() {
+            request.extend_from_slice(&version.to_be_bytes());
+        }
+
+        self.sync.send_request(peer, request);
+    }
+}
+
+/// A replica of a [`Trie`], kept in sync with its peers by a [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Replica<D: TrieHasher> {
+    trie: Trie<D>,
+}
+
+impl<D: TrieHasher + 'static> Replica<D> {
+    /// Creates a replica holding `trie`.
+    #[inline]
+    pub fn new(trie: Trie<D>) -> Self {
+        Self { trie }
+    }
+
+    /// The Trie held by this replica.
+    #[inline]
+    pub fn trie(&self) -> &Trie<D> {
+        &self.trie
+    }
+
+    /// Mutable access to the Trie held by this replica.
+    ///
+    /// Local changes are not announced automatically: call [`Behaviour::announce`] with the new
+    /// root to let peers know about them.
+    #[inline]
+    pub fn trie_mut(&mut self) -> &mut Trie<D> {
+        &mut self.trie
+    }
+
+    /// Unwraps the Trie held by this replica.
+    #[inline]
+    pub fn into_trie(self) -> Trie<D> {
+        self.trie
+    }
+
+    /// Reacts to an event of `behaviour`, returning the new root if it changed.
+    ///
+    /// - New subscribers to [`ROOTS_TOPIC`] are asked for their state.
+    /// - Announcements of a different root are answered with a request for the announcer state.
+    /// - Requests are answered with the state of this replica, at the newest wire version both
+    ///   sides support, unless the requester is in sync. Requesters sharing no version with this
+    ///   replica are left unanswered.
+    /// - Responses are verified and merged, and the new root is announced if it changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for failed requests and malformed messages, and the errors
+    /// of [`StateBundle::verify`] and [`Trie::merge`] for bundles that can't be merged, in which
+    /// case the Trie is left unchanged.
+    #[inline]
+    pub fn handle(
+        &mut self,
+        behaviour: &mut Behaviour,
+        event: BehaviourEvent,
+    ) -> Result<Option<Hash>> {
+        match event {
+            BehaviourEvent::Gossipsub(gossipsub::Event::Subscribed { peer_id, topic, .. })
+                if topic == IdentTopic::new(ROOTS_TOPIC).hash() =>
+            {
+                behaviour.request(&peer_id, self.trie.root());
+            }
+            BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source,
+                message,
+                ..
+            }) => {
+                let root = Hash::from_bytes(&message.data)?;
+
+                if root != self.trie.root() {
+                    let peer = message.source.unwrap_or(propagation_source);
+                    behaviour.request(&peer, self.trie.root());
+                }
+            }
+            BehaviourEvent::Sync(request_response::Event::Message { message, .. }) => match message {
+                request_response::Message::Request {
+                    request, channel, ..
+                } => {
+                    let (root, versions) = parse_request(&request)?;
+                    let response = if root == self.trie.root() {
+                        Vec::new()
+                    } else {
+                        let version = negotiate(&versions).ok_or_else(|| {
+                            Error::UnsupportedWireVersion(
+                                versions.iter().max().copied().unwrap_or_default(),
+                            )
+                        })?;
+                        StateBundle::from_trie(&self.trie).to_wire(version)?
+                    };
+
+                    behaviour
+                        .sync
+                        .send_response(channel, response)
+                        .map_err(|_| Error::Transport("the requester is gone".to_string()))?;
+                }
+                request_response::Message::Response { response, .. } => {
+                    if response.is_empty() {
+                        return Ok(None);
+                    }
+
+                    let remote = StateBundle::from_wire(&response)?.verify::<D>()?;
+                    let before = self.trie.root();
+                    let mut merged = self.trie.clone();
+                    merged.merge(&remote)?;
+                    self.trie = merged;
+
+                    if self.trie.root() != before {
+                        // Peers that haven't subscribed yet will ask for the state themselves.
+                        match behaviour.announce(self.trie.root()) {
+                            Ok(()) | Err(Error::Transport(_)) => {}
+                            Err(error) => return Err(error),
+                        }
+
+                        return Ok(Some(self.trie.root()));
+                    }
+                }
+            },
+            BehaviourEvent::Sync(request_response::Event::OutboundFailure {
+                peer, error, ..
+            }) => {
+                return Err(Error::Transport(format!(
+                    "request to {peer} failed: {error}"
+                )));
+            }
+            _ => {}
+        }
+
+        Ok(None)
+    }
+}
+
+/// Splits a sync request into the root of the requester and the wire versions it decodes.
+fn parse_request(request: &[u8]) -> Result<(Hash, Vec<u16>)> {
+    let (root, versions) = request
+        .split_first_chunk::<32>()
+        .ok_or_else(|| Error::Deserialization("Sync request too short".to_string()))?;
+    let (versions, []) = versions.as_chunks::<2>() else {
+        return Err(Error::Deserialization(
+            "Sync request has a truncated version".to_string(),
+        ));
+    };
+
+    Ok((
+        Hash::from_bytes(root)?,
+        versions.iter().copied().map(u16::from_be_bytes).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use libp2p::{
+        core::{transport::MemoryTransport, upgrade::Version, Multiaddr, Transport},
+        futures::StreamExt,
+        noise,
+        swarm::{Swarm, SwarmEvent},
+        yamux,
+        SwarmBuilder,
+    };
+
+    use super::*;
+
+    type TrieT = Trie<Blake2s256>;
+
+    fn swarm() -> Swarm<Behaviour> {
+        let keypair = Keypair::generate_ed25519();
+        let behaviour = Behaviour::new(&keypair).unwrap();
+
+        SwarmBuilder::with_existing_identity(keypair)
+            .with_tokio()
+            .with_other_transport(|key| {
+                MemoryTransport::default()
+                    .upgrade(Version::V1)
+                    .authenticate(noise::Config::new(key).unwrap())
+                    .multiplex(yamux::Config::default())
+                    .boxed()
+            })
+            .unwrap()
+            .with_behaviour(|_| behaviour)
+            .unwrap()
+            .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(60)))
+            .build()
+    }
+
+    fn replica(entries: &[(&[u8], &[u8])]) -> Result<Replica<Blake2s256>> {
+        let mut trie = TrieT::empty();
+
+        for (key, value) in entries {
+            trie.insert(key, *value)?;
+        }
+
+        Ok(Replica::new(trie))
+    }
+
+    #[test]
+    fn test_replicas_converge() -> Result<()> {
+        let mut a = replica(&[(b"a", b"1"), (b"shared", b"x")])?;
+        let mut b = replica(&[(b"b", b"2")])?;
+
+        let mut expected = a.trie().clone();
+        expected.merge(b.trie())?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let (mut sa, mut sb) = (swarm(), swarm());
+            let address: Multiaddr = "/memory/4669".parse().unwrap();
+
+            sa.listen_on(address.clone()).unwrap();
+            sb.dial(address).unwrap();
+            sa.behaviour_mut().subscribe()?;
+            sb.behaviour_mut().subscribe()?;
+
+            while a.trie().root() != expected.root() || b.trie().root() != expected.root() {
+                tokio::select! {
+                    event = sa.select_next_some() => {
+                        if let SwarmEvent::Behaviour(event) = event {
+                            a.handle(sa.behaviour_mut(), event)?;
+                        }
+                    }
+                    event = sb.select_next_some() => {
+                        if let SwarmEvent::Behaviour(event) = event {
+                            b.handle(sb.behaviour_mut(), event)?;
+                        }
+                    }
+                }
+            }
+
+            Ok::<_, Error>(())
+        })?;
+
+        assert!(a.trie().verify(b"b", b"2"));
+        assert!(b.trie().verify(b"a", b"1"));
+        assert!(b.trie().verify(b"shared", b"x"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_request() -> Result<()> {
+        let root = Hash::digest::<Blake2s256>(b"root");
+        let mut request = root.to_bytes_vec();
+        request.extend([0, 1, 0, 2]);
+
+        assert_eq!(parse_request(&request)?, (root, vec![1, 2]));
+        assert!(parse_request(&request[..35]).is_err());
+        assert!(parse_request(&request[..31]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_rejects_malformed_announcements() -> Result<()> {
+        let mut a = replica(&[(b"a", b"1")])?;
+        let mut behaviour = Behaviour::new(&Keypair::generate_ed25519())?;
+        let peer = PeerId::random();
+
+        let event = BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+            propagation_source: peer,
+            message_id: gossipsub::MessageId::new(b"id"),
+            message: gossipsub::Message {
+                source: Some(peer),
+                data: vec![1, 2, 3],
+                sequence_number: None,
+                topic: IdentTopic::new(ROOTS_TOPIC).hash(),
+            },
+        });
+
+        assert!(a.handle(&mut behaviour, event).is_err());
+
+        Ok(())
+    }
+}
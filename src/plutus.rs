@@ -0,0 +1,380 @@
+//! Plutus Data, the format of datums and redeemers on Cardano.
+//!
+//! Proofs are converted to the layout expected by the Aiken Merkle Patricia Forestry validator,
+//! so off-chain code can build redeemers directly:
+//!
+//! ```aiken
+//! pub type Proof = List<ProofStep>
+//!
+//! pub type ProofStep {
+//!   Branch { skip: Int, neighbors: ByteArray }
+//!   Fork { skip: Int, neighbor: Neighbor }
+//!   Leaf { skip: Int, key: ByteArray, value: ByteArray }
+//! }
+//!
+//! pub type Neighbor {
+//!   nibble: Int,
+//!   prefix: ByteArray,
+//!   root: ByteArray,
+//! }
+//! ```
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::prelude::*;
+
+/// A Plutus Data value.
+///
+/// Its byte representation is the CBOR encoding used by the Cardano ledger, with non-empty
+/// lists encoded as indefinite-length arrays and byte strings longer than 64 bytes split in
+/// 64-byte chunks, as done by the Haskell and Aiken implementations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlutusData {
+    /// A constructor of a sum type, identified by its index, along with its fields.
+    Constr {
+        tag: u64,
+        fields: Vec<PlutusData>,
+    },
+    Map(Vec<(PlutusData, PlutusData)>),
+    List(Vec<PlutusData>),
+    Integer(i128),
+    Bytes(Vec<u8>),
+}
+
+impl PlutusData {
+    #[inline]
+    pub fn constr(tag: u64, fields: impl IntoIterator<Item = PlutusData>) -> Self {
+        PlutusData::Constr {
+            tag,
+            fields: fields.into_iter().collect(),
+        }
+    }
+
+    #[inline]
+    pub fn bytes(bytes: impl AsRef<[u8]>) -> Self {
+        PlutusData::Bytes(bytes.as_ref().to_vec())
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            PlutusData::Constr { tag, fields } => {
+                match tag {
+                    0..=6 => write_head(out, 6, 121 + tag),
+                    7..=127 => write_head(out, 6, 1280 + tag - 7),
+                    _ => {
+                        write_head(out, 6, 102);
+                        write_head(out, 4, 2);
+                        write_head(out, 0, *tag);
+                    }
+                }
+                write_list(out, fields);
+            }
+            PlutusData::Map(entries) => {
+                write_head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode(out);
+                    value.encode(out);
+                }
+            }
+            PlutusData::List(items) => write_list(out, items),
+            PlutusData::Integer(n) => match (u64::try_from(*n), u64::try_from(-1 - *n)) {
+                (Ok(n), _) => write_head(out, 0, n),
+                (_, Ok(n)) => write_head(out, 1, n),
+                _ => {
+                    // Bignums are tagged byte strings holding the big-endian magnitude
+                    let (tag, magnitude) = if *n >= 0 {
+                        (2, *n as u128)
+                    } else {
+                        (3, (-1 - *n) as u128)
+                    };
+                    let bytes = magnitude.to_be_bytes();
+                    let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+
+                    write_head(out, 6, tag);
+                    write_bytes(out, &bytes[skip..]);
+                }
+            },
+            PlutusData::Bytes(bytes) => write_bytes(out, bytes),
+        }
+    }
+}
+
+impl ToBytes for PlutusData {
+    type Output = Vec<u8>;
+
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+impl ToHex for PlutusData {
+    #[inline]
+    fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend([major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(value.to_be_bytes());
+        }
+    }
+}
+
+fn write_list(out: &mut Vec<u8>, items: &[PlutusData]) {
+    if items.is_empty() {
+        write_head(out, 4, 0);
+        return;
+    }
+
+    out.push(0x9f);
+    for item in items {
+        item.encode(out);
+    }
+    out.push(0xff);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() <= 64 {
+        write_head(out, 2, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+        return;
+    }
+
+    out.push(0x5f);
+    for chunk in bytes.chunks(64) {
+        write_head(out, 2, chunk.len() as u64);
+        out.extend_from_slice(chunk);
+    }
+    out.push(0xff);
+}
+
+impl Neighbor {
+    /// Converts the neighbor to the `Neighbor` type of the Aiken validator.
+    #[inline]
+    pub fn to_plutus_data(&self) -> PlutusData {
+        PlutusData::constr(
+            0,
+            [
+                PlutusData::Integer(self.nibble.into()),
+                PlutusData::bytes(&self.prefix),
+                PlutusData::bytes(self.root),
+            ],
+        )
+    }
+}
+
+impl Step {
+    /// Converts the step to the `ProofStep` type of the Aiken validator.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] for leaves with a timestamp, which the validator can't
+    /// represent.
+    #[inline]
+    pub fn to_plutus_data(&self) -> Result<PlutusData> {
+        Ok(match self {
+            Step::Branch { skip, neighbors } => PlutusData::constr(
+                0,
+                [
+                    PlutusData::Integer(*skip as i128),
+                    PlutusData::Bytes(neighbors.iter().flat_map(|n| n.to_bytes()).collect()),
+                ],
+            ),
+            Step::Fork { skip, neighbor } => PlutusData::constr(
+                1,
+                [
+                    PlutusData::Integer(*skip as i128),
+                    neighbor.to_plutus_data(),
+                ],
+            ),
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp: None,
+            } => PlutusData::constr(
+                2,
+                [
+                    PlutusData::Integer(*skip as i128),
+                    PlutusData::bytes(key),
+                    PlutusData::bytes(value),
+                ],
+            ),
+            Step::Leaf { .. } => {
+                return Err(Error::Serialization(
+                    "timestamped leaves have no Plutus Data representation".to_string(),
+                ))
+            }
+        })
+    }
+}
+
+impl Proof {
+    /// Converts the proof to the `Proof` type of the Aiken validator, ready to be used in a
+    /// redeemer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the proof has leaves with a timestamp, which the
+    /// validator can't represent.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::{digest::consts::U32, Blake2b};
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2b<U32>>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let redeemer = trie.proof().to_plutus_data()?;
+    ///     assert!(redeemer.to_hex().starts_with("9fd87b9f00"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn to_plutus_data(&self) -> Result<PlutusData> {
+        Ok(PlutusData::List(
+            self.iter()
+                .map(Step::to_plutus_data)
+                .collect::<Result<_>>()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    fn hex(data: PlutusData) -> String {
+        data.to_hex()
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(hex(PlutusData::Integer(0)), "00");
+        assert_eq!(hex(PlutusData::Integer(23)), "17");
+        assert_eq!(hex(PlutusData::Integer(24)), "1818");
+        assert_eq!(hex(PlutusData::Integer(1000)), "1903e8");
+        assert_eq!(hex(PlutusData::Integer(-1)), "20");
+        assert_eq!(hex(PlutusData::Integer(-1000)), "3903e7");
+        assert_eq!(
+            hex(PlutusData::Integer(u64::MAX as i128 + 1)),
+            "c249010000000000000000"
+        );
+        assert_eq!(
+            hex(PlutusData::Integer(-(u64::MAX as i128) - 2)),
+            "c349010000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(hex(PlutusData::constr(0, [])), "d87980");
+        assert_eq!(hex(PlutusData::constr(6, [])), "d87f80");
+        assert_eq!(hex(PlutusData::constr(7, [])), "d9050080");
+        assert_eq!(hex(PlutusData::constr(127, [])), "d9057880");
+        assert_eq!(hex(PlutusData::constr(128, [])), "d86682188080");
+        assert_eq!(
+            hex(PlutusData::constr(1, [PlutusData::Integer(1)])),
+            "d87a9f01ff"
+        );
+    }
+
+    #[test]
+    fn test_long_bytes_are_chunked() {
+        let encoded = PlutusData::Bytes(vec![0xab; 100]).to_bytes();
+
+        assert_eq!(encoded[0], 0x5f);
+        assert_eq!(&encoded[1..3], &[0x58, 64]);
+        assert_eq!(&encoded[67..69], &[0x58, 36]);
+        assert_eq!(encoded.last(), Some(&0xff));
+        assert_eq!(encoded.len(), 1 + 2 + 64 + 2 + 36 + 1);
+    }
+
+    #[test]
+    fn test_leaf_layout() -> Result<()> {
+        let key = Hash::from_array([1; 32]);
+        let value = Hash::from_array([2; 32]);
+        let step = Step::Leaf {
+            skip: 0,
+            key,
+            value,
+            timestamp: None,
+        };
+
+        assert_eq!(
+            step.to_plutus_data()?,
+            PlutusData::constr(
+                2,
+                [
+                    PlutusData::Integer(0),
+                    PlutusData::bytes(key),
+                    PlutusData::bytes(value)
+                ]
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamped_leaves_are_rejected() {
+        let step = Step::Leaf {
+            skip: 0,
+            key: Hash::zero(),
+            value: Hash::zero(),
+            timestamp: Some(Timestamp::new(1, 0)),
+        };
+
+        assert!(matches!(
+            step.to_plutus_data(),
+            Err(Error::Serialization(_))
+        ));
+    }
+
+    #[proptest]
+    fn test_proof_is_a_list_of_steps(#[any(8usize)] proof: Proof) {
+        let untimestamped = proof.iter().all(|step| {
+            !matches!(
+                step,
+                Step::Leaf {
+                    timestamp: Some(_),
+                    ..
+                }
+            )
+        });
+
+        match proof.to_plutus_data() {
+            Ok(PlutusData::List(steps)) => prop_assert_eq!(steps.len(), proof.len()),
+            Ok(other) => prop_assert!(false, "expected a list, got {:?}", other),
+            Err(_) => prop_assert!(!untimestamped),
+        }
+    }
+}
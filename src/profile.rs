@@ -0,0 +1,182 @@
+use std::{
+    fmt::{self, Display},
+    time::{Duration, Instant},
+};
+
+use crate::{prelude::*, TrieHasher};
+
+/// The insert and verify throughput of a digest on this machine, as measured by
+/// [`benchmark_digests`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DigestBenchmark {
+    /// The name of the digest, as used by the test vectors.
+    pub name: &'static str,
+    /// The time taken to insert every entry in an empty Trie.
+    pub insert: Duration,
+    /// The time taken to verify every entry against the resulting Trie.
+    pub verify: Duration,
+    /// The number of entries inserted and verified.
+    pub entries: usize,
+}
+
+impl DigestBenchmark {
+    /// The number of entries inserted per second.
+    #[inline]
+    pub fn inserts_per_sec(&self) -> f64 {
+        self.entries as f64 / self.insert.as_secs_f64()
+    }
+
+    /// The number of entries verified per second.
+    #[inline]
+    pub fn verifies_per_sec(&self) -> f64 {
+        self.entries as f64 / self.verify.as_secs_f64()
+    }
+
+    /// The time taken to insert and verify every entry, by which digests are ranked.
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.insert + self.verify
+    }
+
+    /// Inserts `entries` entries in a Trie hashed with `D`, and then verifies them.
+    fn measure<D: TrieHasher + 'static>(name: &'static str, entries: usize) -> Result<Self> {
+        let mut trie = Trie::<D>::empty();
+        trie.reserve(entries)?;
+
+        let start = Instant::now();
+        for i in 0..entries as u64 {
+            trie.insert(&i.to_be_bytes(), &i.to_le_bytes()[..])?;
+        }
+        let insert = start.elapsed();
+
+        let start = Instant::now();
+        for i in 0..entries as u64 {
+            if !trie.verify(&i.to_be_bytes(), &i.to_le_bytes()) {
+                return Err(Error::InvalidOperation(format!(
+                    "{name} failed to verify entry {i}"
+                )));
+            }
+        }
+        let verify = start.elapsed();
+
+        Ok(Self {
+            name,
+            insert,
+            verify,
+            entries,
+        })
+    }
+}
+
+impl Display for DigestBenchmark {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.0} inserts/s, {:.0} verifies/s",
+            self.name,
+            self.inserts_per_sec(),
+            self.verifies_per_sec()
+        )
+    }
+}
+
+/// Measures the insert and verify throughput of every digest enabled by the hash features, and
+/// ranks them from the fastest to the slowest.
+///
+/// Each digest inserts `entries` entries in an empty Trie, one by one, and then verifies each of
+/// them. Verification scans the whole proof, so the time taken grows with the square of
+/// `entries`, and a few thousand entries are enough to rank the digests. The ranking depends on
+/// the CPU, since some digests use instructions only available on some of them, as reported by
+/// [`hash_backend`](crate::hash_backend) for SHA-256.
+///
+/// # Errors
+///
+/// Returns an error if an entry fails to insert or to verify.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::benchmark_digests;
+///
+/// fn main() -> Result<(), mutree::prelude::Error> {
+///     let ranking = benchmark_digests(64)?;
+///
+///     for benchmark in &ranking {
+///         println!("{benchmark}");
+///     }
+///     assert!(ranking.windows(2).all(|pair| pair[0].total() <= pair[1].total()));
+///
+///     Ok(())
+/// }
+/// ```
+#[inline]
+#[allow(clippy::vec_init_then_push)] // Each push depends on a different feature
+pub fn benchmark_digests(entries: usize) -> Result<Vec<DigestBenchmark>> {
+    let mut ranking = Vec::new();
+
+    #[cfg(feature = "blake2")]
+    {
+        use blake2::{digest::consts::U32, Blake2b, Blake2s256};
+
+        ranking.push(DigestBenchmark::measure::<Blake2b<U32>>(
+            "blake2b-256",
+            entries,
+        )?);
+        ranking.push(DigestBenchmark::measure::<Blake2s256>(
+            "blake2s-256",
+            entries,
+        )?);
+    }
+    #[cfg(feature = "blake3")]
+    ranking.push(DigestBenchmark::measure::<blake3::Hasher>(
+        "blake3", entries,
+    )?);
+    #[cfg(feature = "sha2")]
+    ranking.push(DigestBenchmark::measure::<sha2::Sha256>(
+        "sha2-256", entries,
+    )?);
+    #[cfg(feature = "sha3")]
+    ranking.push(DigestBenchmark::measure::<sha3::Sha3_256>(
+        "sha3-256", entries,
+    )?);
+    #[cfg(feature = "keccak")]
+    ranking.push(DigestBenchmark::measure::<sha3::Keccak256>(
+        "keccak-256",
+        entries,
+    )?);
+    #[cfg(feature = "ripemd")]
+    ranking.push(DigestBenchmark::measure::<ripemd::Ripemd160>(
+        "ripemd-160",
+        entries,
+    )?);
+
+    ranking.sort_by_key(DigestBenchmark::total);
+
+    Ok(ranking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranks_enabled_digests() {
+        let ranking = benchmark_digests(16).unwrap();
+
+        let enabled = [
+            cfg!(feature = "blake2"),
+            cfg!(feature = "blake2"),
+            cfg!(feature = "blake3"),
+            cfg!(feature = "sha2"),
+            cfg!(feature = "sha3"),
+            cfg!(feature = "keccak"),
+            cfg!(feature = "ripemd"),
+        ];
+        assert_eq!(ranking.len(), enabled.iter().filter(|&&on| on).count());
+        assert!(ranking
+            .windows(2)
+            .all(|pair| pair[0].total() <= pair[1].total()));
+        assert!(ranking.iter().all(|benchmark| benchmark.entries == 16));
+    }
+}
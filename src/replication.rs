@@ -0,0 +1,634 @@
+//! A reference gossip replication engine.
+//!
+//! [`Node`] is transport-agnostic: it never performs any I/O by itself. Instead, the embedding
+//! application calls [`Node::tick`] periodically to get the bundles that should be sent to each
+//! peer, and hands every bundle it receives to [`Node::receive`], which verifies and merges it.
+//! This keeps the engine usable with any transport and any async runtime, and makes it a
+//! blueprint that can be copied and adapted when more control is needed.
+//!
+//! Transports that can be polled without blocking can implement [`SyncTransport`] instead, and
+//! let [`Node::sync`] drive the exchange. [`LoopbackNetwork`] is an in-memory implementation,
+//! for testing replication without any network. It carries bundles as versioned messages, like a
+//! network would: each endpoint advertises the [`wire`](crate::wire) versions it decodes, and
+//! senders encode at the newest version their peer supports.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Display},
+    hash::Hash as StdHash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    prelude::*,
+    wire::{negotiate, supported_versions, WireFormat},
+    TrieHasher,
+};
+
+/// The unit of state exchanged between replicas.
+///
+/// A bundle carries the full proof of the sending replica, along with the root it claims the
+/// proof commits to. Receivers must recompute the root before trusting the proof.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StateBundle {
+    /// The root the sender claims for `proof`
+    pub root: Hash,
+    /// The complete proof of the sender
+    pub proof: Proof,
+}
+
+impl StateBundle {
+    /// Captures the current state of a Trie.
+    #[inline]
+    pub fn from_trie<D: TrieHasher + 'static>(trie: &Trie<D>) -> Self {
+        Self {
+            root: trie.root(),
+            proof: trie.proof().clone(),
+        }
+    }
+
+    /// Rebuilds a Trie from the bundle, checking that the proof commits to the claimed root.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] if the recomputed root differs from the claimed one.
+    #[inline]
+    pub fn verify<D: TrieHasher + 'static>(&self) -> Result<Trie<D>> {
+        let trie = Trie::<D>::from_proof(self.proof.clone());
+
+        if trie.root() != self.root {
+            return Err(Error::InvalidProof(format!(
+                "bundle claims root {} but its proof commits to {}",
+                self.root,
+                trie.root()
+            )));
+        }
+
+        Ok(trie)
+    }
+}
+
+impl ToBytes for StateBundle {
+    type Output = Vec<u8>;
+
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = self.root.to_bytes_vec();
+        bytes.extend(self.proof.to_bytes());
+        bytes
+    }
+}
+
+impl FromBytes for StateBundle {
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+
+        if bytes.len() < 32 {
+            return Err(Error::Deserialization(
+                "Invalid length for StateBundle".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            root: Hash::from_bytes(&bytes[..32])?,
+            proof: Proof::from_bytes_with(&bytes[32..], context)?,
+        })
+    }
+}
+
+crate::impl_hex_via_bytes!(StateBundle);
+
+/// A transport carrying [`StateBundle`]s between replicas, like QUIC streams, WebSockets or
+/// libp2p.
+///
+/// Both methods must return immediately: asynchronous transports usually implement them on top
+/// of channels filled and drained by a background task.
+pub trait SyncTransport<P> {
+    /// The error returned when the transport fails.
+    type Error: Display;
+
+    /// Queues `bundle` to be sent to `peer`.
+    fn send(&mut self, peer: &P, bundle: &StateBundle) -> Result<(), Self::Error>;
+
+    /// Returns the next bundle received, along with its sender, or `None` if there is none yet.
+    fn receive(&mut self) -> Result<Option<(P, StateBundle)>, Self::Error>;
+}
+
+/// An in-memory network, delivering bundles between the [`Loopback`] endpoints created from it.
+///
+/// Bundles are encoded with [`WireFormat::to_wire`] at the newest version supported by both
+/// endpoints, and decoded with [`WireFormat::from_wire`] on receipt.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Instant;
+///
+/// use blake2::Blake2s256;
+/// use mutree::{prelude::*, replication::*};
+///
+/// fn main() -> Result<(), Error> {
+///     let network = LoopbackNetwork::new();
+///     let (mut to_a, mut to_b) = (network.endpoint("a"), network.endpoint("b"));
+///
+///     let config = |peer| NodeConfig { peers: vec![peer], ..Default::default() };
+///     let mut a = Node::<Blake2s256, &str>::new(Trie::empty(), config("b"));
+///     let mut b = Node::<Blake2s256, &str>::new(Trie::empty(), config("a"));
+///     a.trie_mut().insert(b"key", &b"value"[..])?;
+///
+///     a.sync(&mut to_a, Instant::now())?;
+///     b.sync(&mut to_b, Instant::now())?;
+///     assert_eq!(a.trie().root(), b.trie().root());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoopbackNetwork<P> {
+    inboxes: Arc<Mutex<Inboxes<P>>>,
+}
+
+/// The inbox of each peer.
+type Inboxes<P> = HashMap<P, Inbox<P>>;
+
+/// The wire versions a peer decodes, and the messages waiting to be received by it, along with
+/// their senders.
+#[derive(Debug)]
+struct Inbox<P> {
+    versions: Vec<u16>,
+    messages: VecDeque<(P, Vec<u8>)>,
+}
+
+impl<P: Clone + Eq + StdHash> LoopbackNetwork<P> {
+    /// Creates a network without any endpoint.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            inboxes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Creates the endpoint of the peer addressed by `address`, decoding every wire version this
+    /// release supports.
+    #[inline]
+    pub fn endpoint(&self, address: P) -> Loopback<P> {
+        self.endpoint_with_versions(address, supported_versions())
+    }
+
+    /// Creates the endpoint of the peer addressed by `address`, advertising that it only decodes
+    /// the wire versions in `versions`, as an older or newer release would.
+    #[inline]
+    pub fn endpoint_with_versions(&self, address: P, versions: &[u16]) -> Loopback<P> {
+        self.lock()
+            .entry(address.clone())
+            .or_insert_with(|| Inbox {
+                versions: Vec::new(),
+                messages: VecDeque::new(),
+            })
+            .versions = versions.to_vec();
+
+        Loopback {
+            address,
+            network: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inboxes<P>> {
+        self.inboxes
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<P: Clone + Eq + StdHash> Default for LoopbackNetwork<P> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The endpoint of a peer in a [`LoopbackNetwork`].
+#[derive(Debug, Clone)]
+pub struct Loopback<P> {
+    address: P,
+    network: LoopbackNetwork<P>,
+}
+
+impl<P: Clone + Eq + StdHash + Debug> SyncTransport<P> for Loopback<P> {
+    type Error = Error;
+
+    /// Queues `bundle` to be sent to `peer`, encoded at the newest wire version it supports.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Transport`] if `peer` has no endpoint, and
+    /// [`Error::UnsupportedWireVersion`] if it supports none of the versions of this release.
+    #[inline]
+    fn send(&mut self, peer: &P, bundle: &StateBundle) -> Result<(), Self::Error> {
+        let mut inboxes = self.network.lock();
+        let inbox = inboxes
+            .get_mut(peer)
+            .ok_or_else(|| Error::Transport(format!("unknown peer {peer:?}")))?;
+        let version = negotiate(&inbox.versions).ok_or_else(|| {
+            Error::UnsupportedWireVersion(inbox.versions.last().copied().unwrap_or_default())
+        })?;
+
+        inbox
+            .messages
+            .push_back((self.address.clone(), bundle.to_wire(version)?));
+
+        Ok(())
+    }
+
+    /// Returns the next bundle received, decoded from its wire message.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`WireFormat::from_wire`] if the message can't be decoded.
+    #[inline]
+    fn receive(&mut self) -> Result<Option<(P, StateBundle)>, Self::Error> {
+        let message = self
+            .network
+            .lock()
+            .get_mut(&self.address)
+            .and_then(|inbox| inbox.messages.pop_front());
+
+        message
+            .map(|(peer, bytes)| Ok((peer, StateBundle::from_wire(&bytes)?)))
+            .transpose()
+    }
+}
+
+/// Something observable that happened while processing a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<P> {
+    /// The bundle was verified and merged, changing the local root.
+    Merged {
+        peer: P,
+        previous_root: Hash,
+        root: Hash,
+    },
+    /// The local replica has the same root as the peer.
+    Converged { peer: P, root: Hash },
+    /// The bundle failed verification and was discarded.
+    Rejected { peer: P, reason: String },
+}
+
+/// Configuration for a replication [`Node`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeConfig<P> {
+    /// The peers this node pushes its state to
+    pub peers: Vec<P>,
+    /// How often the state is pushed to the peers
+    pub interval: Duration,
+}
+
+impl<P> Default for NodeConfig<P> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A replica that periodically gossips its state to a fixed set of peers.
+///
+/// # Type Parameters
+///
+/// * `D` - The digest algorithm of the replicated Trie
+/// * `P` - Whatever the transport uses to address peers
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Instant;
+///
+/// use blake2::Blake2s256;
+/// use mutree::{prelude::*, replication::*};
+///
+/// fn main() -> Result<(), Error> {
+///     let config = |peer| NodeConfig { peers: vec![peer], ..Default::default() };
+///     let mut a = Node::<Blake2s256, &str>::new(Trie::empty(), config("b"));
+///     let mut b = Node::<Blake2s256, &str>::new(Trie::empty(), config("a"));
+///
+///     a.trie_mut().insert(b"key", &b"value"[..])?;
+///
+///     for (_, bundle) in a.tick(Instant::now()) {
+///         b.receive("a", &bundle);
+///     }
+///
+///     assert_eq!(a.trie().root(), b.trie().root());
+///     assert!(b.is_converged_with(&"a"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Node<D: TrieHasher, P> {
+    trie: Trie<D>,
+    config: NodeConfig<P>,
+    last_exchange: Option<Instant>,
+    peer_roots: HashMap<P, Hash>,
+}
+
+impl<D: TrieHasher + 'static, P: Clone + Eq + StdHash + Debug> Node<D, P> {
+    /// Creates a node replicating the given Trie.
+    #[inline]
+    pub fn new(trie: Trie<D>, config: NodeConfig<P>) -> Self {
+        Self {
+            trie,
+            config,
+            last_exchange: None,
+            peer_roots: HashMap::new(),
+        }
+    }
+
+    /// The local replica.
+    #[inline]
+    pub fn trie(&self) -> &Trie<D> {
+        &self.trie
+    }
+
+    /// Mutable access to the local replica, for applying local writes.
+    #[inline]
+    pub fn trie_mut(&mut self) -> &mut Trie<D> {
+        &mut self.trie
+    }
+
+    /// Consumes the node, returning the local replica.
+    #[inline]
+    pub fn into_trie(self) -> Trie<D> {
+        self.trie
+    }
+
+    /// The configured peers.
+    #[inline]
+    pub fn peers(&self) -> &[P] {
+        &self.config.peers
+    }
+
+    /// Returns the last root received from `peer`, if any.
+    #[inline]
+    pub fn peer_root(&self, peer: &P) -> Option<Hash> {
+        self.peer_roots.get(peer).copied()
+    }
+
+    /// Checks whether the last root received from `peer` matches the local root.
+    #[inline]
+    pub fn is_converged_with(&self, peer: &P) -> bool {
+        self.peer_root(peer) == Some(self.trie.root())
+    }
+
+    /// Returns the bundles to send if the exchange interval elapsed since the last exchange.
+    ///
+    /// The first call always produces bundles. Peers whose last known root already matches the
+    /// local root are skipped, since they have nothing to learn from us.
+    #[inline]
+    pub fn tick(&mut self, now: Instant) -> Vec<(P, StateBundle)> {
+        if let Some(last) = self.last_exchange {
+            if now.saturating_duration_since(last) < self.config.interval {
+                return Vec::new();
+            }
+        }
+
+        self.last_exchange = Some(now);
+        let bundle = StateBundle::from_trie(&self.trie);
+
+        self.config
+            .peers
+            .iter()
+            .filter(|peer| !self.is_converged_with(peer))
+            .map(|peer| (peer.clone(), bundle.clone()))
+            .collect()
+    }
+
+    /// Verifies a bundle received from `peer` and merges it into the local replica.
+    #[inline]
+    pub fn receive(&mut self, peer: P, bundle: &StateBundle) -> Vec<Event<P>> {
+        let remote = match bundle.verify::<D>() {
+            Ok(remote) => remote,
+            Err(e) => {
+                return vec![Event::Rejected {
+                    peer,
+                    reason: e.to_string(),
+                }]
+            }
+        };
+
+        let mut events = Vec::new();
+        let previous_root = self.trie.root();
+
+        if previous_root != remote.root() {
+            if let Err(e) = self.trie.merge(&remote) {
+                return vec![Event::Rejected {
+                    peer,
+                    reason: e.to_string(),
+                }];
+            }
+
+            if self.trie.root() != previous_root {
+                events.push(Event::Merged {
+                    peer: peer.clone(),
+                    previous_root,
+                    root: self.trie.root(),
+                });
+            }
+        }
+
+        if self.trie.root() == remote.root() {
+            events.push(Event::Converged {
+                peer: peer.clone(),
+                root: self.trie.root(),
+            });
+        }
+
+        self.peer_roots.insert(peer, remote.root());
+        events
+    }
+
+    /// Runs one round of replication over `transport`: sends the bundles produced by
+    /// [`Node::tick`], then receives every pending bundle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Transport`] if the transport fails. Bundles received before the failure
+    /// are merged anyway.
+    #[inline]
+    pub fn sync<T: SyncTransport<P>>(
+        &mut self,
+        transport: &mut T,
+        now: Instant,
+    ) -> Result<Vec<Event<P>>> {
+        let transport_error = |e: T::Error| Error::Transport(e.to_string());
+
+        for (peer, bundle) in self.tick(now) {
+            transport.send(&peer, &bundle).map_err(transport_error)?;
+        }
+
+        let mut events = Vec::new();
+        while let Some((peer, bundle)) = transport.receive().map_err(transport_error)? {
+            events.extend(self.receive(peer, &bundle));
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+
+    use super::*;
+    use crate::wire::WIRE_VERSION;
+
+    type TestNode = Node<Blake2s256, u8>;
+
+    fn node(id: u8, peers: u8) -> TestNode {
+        let config = NodeConfig {
+            peers: (0..peers).filter(|&p| p != id).collect(),
+            interval: Duration::from_secs(1),
+        };
+        Node::new(Trie::empty(), config)
+    }
+
+    #[test]
+    fn test_tick_respects_interval() {
+        let mut a = node(0, 2);
+        let now = Instant::now();
+
+        assert_eq!(a.tick(now).len(), 1);
+        assert!(a.tick(now + Duration::from_millis(500)).is_empty());
+        assert_eq!(a.tick(now + Duration::from_secs(1)).len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_tampered_bundle() -> Result<()> {
+        let mut a = node(0, 2);
+        let mut b = node(1, 2);
+        a.trie_mut().insert(b"key", &b"value"[..])?;
+
+        let (_, mut bundle) = a.tick(Instant::now()).remove(0);
+        bundle.root = Hash::zero();
+
+        assert!(matches!(
+            b.receive(0, &bundle)[..],
+            [Event::Rejected { peer: 0, .. }]
+        ));
+        assert!(b.trie().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_writes_propagate_through_relays() -> Result<()> {
+        // A chain topology: 0 -> 1 -> 2
+        let mut nodes: Vec<TestNode> = (0..3u8)
+            .map(|id| {
+                let config = NodeConfig {
+                    peers: vec![id + 1].into_iter().filter(|&p| p < 3).collect(),
+                    interval: Duration::from_secs(1),
+                };
+                Node::new(Trie::empty(), config)
+            })
+            .collect();
+
+        nodes[0].trie_mut().insert(b"key", &b"value"[..])?;
+
+        let mut now = Instant::now();
+        for _ in 0..2 {
+            for from in 0..nodes.len() {
+                for (to, bundle) in nodes[from].tick(now) {
+                    nodes[to as usize].receive(from as u8, &bundle);
+                }
+            }
+
+            now += Duration::from_secs(1);
+        }
+
+        let root = nodes[0].trie().root();
+        assert!(nodes.iter().all(|node| node.trie().root() == root));
+        assert!(nodes[2].is_converged_with(&1));
+        assert!(nodes[2].trie().verify(b"key", b"value"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_over_loopback() -> Result<()> {
+        let network = LoopbackNetwork::new();
+        let mut endpoints: Vec<_> = (0..3u8).map(|id| network.endpoint(id)).collect();
+        let mut nodes: Vec<TestNode> = (0..3u8).map(|id| node(id, 3)).collect();
+
+        nodes[0].trie_mut().insert(b"a", &b"1"[..])?;
+        nodes[2].trie_mut().insert(b"b", &b"2"[..])?;
+
+        let mut now = Instant::now();
+        for _ in 0..3 {
+            for (node, endpoint) in nodes.iter_mut().zip(endpoints.iter_mut()) {
+                node.sync(endpoint, now)?;
+            }
+
+            now += Duration::from_secs(1);
+        }
+
+        let root = nodes[0].trie().root();
+        assert!(nodes.iter().all(|node| node.trie().root() == root));
+        assert!(nodes[1].trie().verify(b"a", b"1"));
+        assert!(nodes[1].trie().verify(b"b", b"2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_to_unknown_peer_fails() {
+        let network = LoopbackNetwork::new();
+        let mut endpoint = network.endpoint(0);
+        let mut a = node(0, 2);
+
+        assert!(matches!(
+            a.sync(&mut endpoint, Instant::now()),
+            Err(Error::Transport(_))
+        ));
+    }
+
+    #[test]
+    fn test_sync_negotiates_wire_versions() {
+        let network = LoopbackNetwork::new();
+        let mut to_b = network.endpoint(0);
+        network.endpoint_with_versions(1, &[0, WIRE_VERSION]);
+        network.endpoint_with_versions(2, &[WIRE_VERSION + 1]);
+
+        let bundle = StateBundle::default();
+        assert!(to_b.send(&1, &bundle).is_ok());
+        assert!(matches!(
+            to_b.send(&2, &bundle),
+            Err(Error::UnsupportedWireVersion(version)) if version == WIRE_VERSION + 1
+        ));
+
+        let message = network.lock().get_mut(&1).unwrap().messages.pop_front();
+        let (sender, bytes) = message.unwrap();
+        assert_eq!(sender, 0);
+        assert_eq!(bytes, bundle.to_wire(WIRE_VERSION).unwrap());
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+
+        let bundle = StateBundle::from_trie(&trie);
+        assert_eq!(StateBundle::from_bytes(&bundle.to_bytes())?, bundle);
+        assert_eq!(StateBundle::from_hex(&bundle.to_hex())?, bundle);
+
+        Ok(())
+    }
+}
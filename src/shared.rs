@@ -0,0 +1,220 @@
+//! A Trie shared between many readers and a writer, without locking the readers.
+//!
+//! Servers embedding a Trie usually answer many more verifications than they perform writes.
+//! [`SharedTrie`] keeps the current version of the Trie behind an [`ArcSwap`], so readers load a
+//! consistent snapshot without taking any lock, while writers build the next version on a copy
+//! and publish it atomically. Readers holding an older snapshot keep using it until they drop
+//! it.
+
+use std::sync::{Arc, Mutex};
+
+use arc_swap::ArcSwap;
+
+use crate::{prelude::*, TrieHasher};
+
+/// A Trie readable without locks, and updated by publishing new versions.
+///
+/// Writes are serialized by a lock only taken by writers, so concurrent updates are applied one
+/// after the other instead of overwriting each other. Each update starts from a clone of the
+/// current version, which is O(1), but copies its steps on the first change, since the
+/// published version still shares them.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+///
+/// use blake2::Blake2s256;
+/// use mutree::{prelude::*, shared::SharedTrie};
+///
+/// fn main() -> Result<(), Error> {
+///     let shared = SharedTrie::<Blake2s256>::default();
+///
+///     thread::scope(|scope| {
+///         scope.spawn(|| shared.update(|trie| trie.insert(b"key", &b"value"[..])));
+///     });
+///
+///     let snapshot = shared.snapshot();
+///     assert!(snapshot.verify(b"key", b"value"));
+///
+///     // Snapshots don't see later writes
+///     shared.update(|trie| trie.insert(b"other", &b"value"[..]))?;
+///     assert!(!snapshot.verify(b"other", b"value"));
+///     assert!(shared.verify(b"other", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SharedTrie<D: TrieHasher> {
+    current: ArcSwap<Trie<D>>,
+    writer: Mutex<()>,
+}
+
+impl<D: TrieHasher + 'static> SharedTrie<D> {
+    /// Shares a Trie.
+    #[inline]
+    pub fn new(trie: Trie<D>) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(trie),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current version of the Trie, which stays the same while it is held.
+    #[inline]
+    pub fn snapshot(&self) -> Arc<Trie<D>> {
+        self.current.load_full()
+    }
+
+    /// The root of the current version.
+    #[inline]
+    pub fn root(&self) -> Hash {
+        self.current.load().root()
+    }
+
+    /// Checks if the current version proves that `key` is set to `value`.
+    #[inline]
+    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
+        self.current.load().verify(key, value)
+    }
+
+    /// Applies `update` to a copy of the current version, and publishes the copy if it succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `update`, in which case nothing is published.
+    #[inline]
+    pub fn update<T>(&self, update: impl FnOnce(&mut Trie<D>) -> Result<T>) -> Result<T> {
+        let _writer = self.lock();
+
+        let mut next = Trie::clone(&self.current.load());
+        let output = update(&mut next)?;
+        self.current.store(Arc::new(next));
+
+        Ok(output)
+    }
+
+    /// Replaces the current version with `trie`, returning the version it replaced.
+    #[inline]
+    pub fn replace(&self, trie: Trie<D>) -> Arc<Trie<D>> {
+        let _writer = self.lock();
+        self.current.swap(Arc::new(trie))
+    }
+
+    /// Takes the writer lock. A writer panicking in `update` publishes nothing, so the lock is
+    /// still usable afterwards.
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<D: TrieHasher + 'static> Default for SharedTrie<D> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Trie::empty())
+    }
+}
+
+impl<D: TrieHasher + 'static> From<Trie<D>> for SharedTrie<D> {
+    #[inline]
+    fn from(trie: Trie<D>) -> Self {
+        Self::new(trie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    type Blake2s = blake2::Blake2s256;
+
+    #[test]
+    fn test_readers_see_consistent_snapshots() {
+        let shared = SharedTrie::<Blake2s>::default();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0u32..64 {
+                    shared
+                        .update(|trie| trie.insert(&i.to_be_bytes(), &b"value"[..]))
+                        .unwrap();
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let mut seen = 0;
+                    while seen < 64 {
+                        let snapshot = shared.snapshot();
+                        assert_eq!(
+                            Trie::<Blake2s>::from_proof(snapshot.proof().clone()).root(),
+                            snapshot.root()
+                        );
+
+                        // Versions only grow, and hold every key written before them
+                        assert!(snapshot.proof().len() >= seen);
+                        seen = snapshot.proof().len();
+                        for i in 0..seen as u32 {
+                            assert!(snapshot.verify(&i.to_be_bytes(), b"value"));
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(shared.snapshot().proof().len(), 64);
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let shared = SharedTrie::<Blake2s>::default();
+
+        thread::scope(|scope| {
+            for writer in 0u8..4 {
+                let shared = &shared;
+                scope.spawn(move || {
+                    for i in 0u8..16 {
+                        shared
+                            .update(|trie| trie.insert(&[writer, i], &b"value"[..]))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(shared.snapshot().proof().len(), 64);
+    }
+
+    #[proptest]
+    fn test_failed_updates_publish_nothing(
+        #[strategy(vec(any::<u8>(), 1..8))] key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        prop_assume!(key != b"other");
+
+        let shared = SharedTrie::<Blake2s>::default();
+        shared.update(|trie| trie.insert(&key, &value[..]))?;
+        let root = shared.root();
+
+        let result = shared.update(|trie| {
+            trie.insert(b"other", &b"value"[..])?;
+            trie.insert(b"", &b"value"[..])
+        });
+
+        prop_assert!(result.is_err());
+        prop_assert_eq!(shared.root(), root);
+        prop_assert!(!shared.verify(b"other", b"value"));
+
+        let previous = shared.replace(Trie::empty());
+        prop_assert!(previous.verify(&key, &value));
+        prop_assert!(shared.snapshot().is_empty());
+    }
+}
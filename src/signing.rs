@@ -0,0 +1,450 @@
+//! Signed operations, for provenance in federated deployments.
+//!
+//! An [`AuthenticatedOp`] wraps a [`Proof`] operation with the id of the replica that authored
+//! it and an ed25519 signature over both. Replicas only apply operations whose signature
+//! verifies against the key registered for their author in a [`KeySet`].
+//!
+//! A [`Checkpoint`] is a root signed by the replica that computed it, so light clients can trust
+//! roots distributed out-of-band, and verify proofs against them without holding the Trie.
+
+use std::collections::HashMap;
+
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use ed25519_dalek::{Signer, SIGNATURE_LENGTH};
+
+use crate::{prelude::*, TrieHasher};
+
+/// Identifies the replica that authored an operation.
+pub type ReplicaId = u64;
+
+/// Domain separation tag for operation signatures, so they can't be replayed in another context.
+const OP_DOMAIN: &[u8] = b"mutree/op/v1";
+
+/// Domain separation tag for checkpoint signatures, so they can't be mistaken for operations.
+const CHECKPOINT_DOMAIN: &[u8] = b"mutree/checkpoint/v1";
+
+/// A [`Proof`] operation signed by the replica that authored it.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::{prelude::*, signing::*};
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let key = SigningKey::from_bytes(&[7; 32]);
+///     let mut keys = KeySet::new();
+///     keys.insert(1, key.verifying_key());
+///
+///     let mut source = Trie::<Blake2s256>::empty();
+///     source.insert(b"key", &b"value"[..])?;
+///     let op = AuthenticatedOp::sign(1, source.into_proof(), &key);
+///
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.apply_authenticated(&op, &keys)?;
+///     assert!(trie.verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedOp {
+    /// The replica that authored the operation
+    pub replica: ReplicaId,
+    /// The operation itself
+    pub op: Proof,
+    /// The signature of the author over the replica id and the operation
+    pub signature: Signature,
+}
+
+impl AuthenticatedOp {
+    /// Signs an operation on behalf of `replica`.
+    #[inline]
+    pub fn sign(replica: ReplicaId, op: Proof, key: &SigningKey) -> Self {
+        let signature = key.sign(&Self::message(replica, &op));
+
+        Self {
+            replica,
+            op,
+            signature,
+        }
+    }
+
+    /// Checks the signature against the given key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] if the signature doesn't verify.
+    #[inline]
+    pub fn verify(&self, key: &VerifyingKey) -> Result<()> {
+        key.verify_strict(&Self::message(self.replica, &self.op), &self.signature)
+            .map_err(|e| Error::InvalidSignature(e.to_string()))
+    }
+
+    fn message(replica: ReplicaId, op: &Proof) -> Vec<u8> {
+        let mut message = OP_DOMAIN.to_vec();
+        message.extend_from_slice(&replica.to_be_bytes());
+        message.extend(op.to_bytes());
+        message
+    }
+}
+
+impl ToBytes for AuthenticatedOp {
+    type Output = Vec<u8>;
+
+    /// Encodes the replica id as a big-endian `u64`, followed by the signature and the operation.
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = self.replica.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes.extend(self.op.to_bytes());
+        bytes
+    }
+}
+
+impl FromBytes for AuthenticatedOp {
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+
+        if bytes.len() < 8 + SIGNATURE_LENGTH {
+            return Err(Error::Deserialization(
+                "Invalid length for AuthenticatedOp".to_string(),
+            ));
+        }
+
+        let (replica, rest) = bytes.split_at(8);
+        let (signature, op) = rest.split_at(SIGNATURE_LENGTH);
+
+        Ok(Self {
+            replica: u64::from_be_bytes(replica.try_into()?),
+            signature: Signature::from_slice(signature)
+                .map_err(|e| Error::Deserialization(e.to_string()))?,
+            op: Proof::from_bytes_with(op, context)?,
+        })
+    }
+}
+
+/// A root signed by the replica that computed it, taken with [`Trie::checkpoint`].
+///
+/// The sequence number orders the checkpoints of a signer: light clients keep the newest one
+/// they verified, and refuse any with a lower sequence number, so old roots can't be replayed to
+/// them.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::{prelude::*, signing::*};
+///
+/// fn main() -> Result<(), Error> {
+///     let key = SigningKey::from_bytes(&[7; 32]);
+///
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"key", &b"value"[..])?;
+///     let checkpoint = trie.checkpoint(1, &key);
+///
+///     // A light client, given the checkpoint and the public key of the signer
+///     let checkpoint = Checkpoint::from_bytes(&checkpoint.to_bytes())?;
+///     checkpoint.verify(&key.verifying_key())?;
+///     assert_eq!(checkpoint.root, trie.root());
+///
+///     trie.verify_checkpoint(&checkpoint, &key.verifying_key())?;
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The root of the Trie
+    pub root: Hash,
+    /// The position of the checkpoint among those of its signer
+    pub sequence: u64,
+    /// When the checkpoint was taken
+    pub timestamp: Timestamp,
+    /// The signature of the signer over the root, sequence number and timestamp
+    pub signature: Signature,
+}
+
+impl Checkpoint {
+    /// The length of the byte representation of a checkpoint.
+    pub const LEN: usize = 32 + 8 + Timestamp::LEN + SIGNATURE_LENGTH;
+
+    /// Signs `root` as the checkpoint `sequence`, taken at `timestamp`.
+    #[inline]
+    pub fn sign(root: Hash, sequence: u64, timestamp: Timestamp, key: &SigningKey) -> Self {
+        let signature = key.sign(&Self::message(root, sequence, timestamp));
+
+        Self {
+            root,
+            sequence,
+            timestamp,
+            signature,
+        }
+    }
+
+    /// Checks the signature against the given key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] if the signature doesn't verify.
+    #[inline]
+    pub fn verify(&self, key: &VerifyingKey) -> Result<()> {
+        key.verify_strict(
+            &Self::message(self.root, self.sequence, self.timestamp),
+            &self.signature,
+        )
+        .map_err(|e| Error::InvalidSignature(e.to_string()))
+    }
+
+    fn message(root: Hash, sequence: u64, timestamp: Timestamp) -> Vec<u8> {
+        let mut message = CHECKPOINT_DOMAIN.to_vec();
+        message.extend_from_slice(&root.to_bytes());
+        message.extend_from_slice(&sequence.to_be_bytes());
+        message.extend_from_slice(&timestamp.to_bytes());
+        message
+    }
+}
+
+impl ToBytes for Checkpoint {
+    type Output = Vec<u8>;
+
+    /// Encodes the root, the sequence number as a big-endian `u64`, the timestamp and the
+    /// signature, in that order.
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = Vec::with_capacity(Self::LEN);
+        bytes.extend_from_slice(&self.root.to_bytes());
+        bytes.extend_from_slice(&self.sequence.to_be_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_bytes());
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes
+    }
+}
+
+impl FromBytes for Checkpoint {
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::LEN {
+            return Err(Error::Deserialization(
+                "Invalid length for Checkpoint".to_string(),
+            ));
+        }
+
+        let (root, rest) = bytes.split_at(32);
+        let (sequence, rest) = rest.split_at(8);
+        let (timestamp, signature) = rest.split_at(Timestamp::LEN);
+
+        Ok(Self {
+            root: Hash::from_slice(root)?,
+            sequence: u64::from_be_bytes(sequence.try_into()?),
+            timestamp: Timestamp::from_bytes(timestamp)?,
+            signature: Signature::from_slice(signature)
+                .map_err(|e| Error::Deserialization(e.to_string()))?,
+        })
+    }
+}
+
+/// The keys trusted to author operations, by replica.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeySet(HashMap<ReplicaId, VerifyingKey>);
+
+impl KeySet {
+    /// Creates an empty key set, which trusts no one.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts `key` to author operations on behalf of `replica`, replacing any previous key.
+    #[inline]
+    pub fn insert(&mut self, replica: ReplicaId, key: VerifyingKey) -> Option<VerifyingKey> {
+        self.0.insert(replica, key)
+    }
+
+    /// Stops trusting any key for `replica`.
+    #[inline]
+    pub fn remove(&mut self, replica: ReplicaId) -> Option<VerifyingKey> {
+        self.0.remove(&replica)
+    }
+
+    /// The key trusted for `replica`, if any.
+    #[inline]
+    pub fn get(&self, replica: ReplicaId) -> Option<&VerifyingKey> {
+        self.0.get(&replica)
+    }
+
+    /// Checks that `op` is signed by the key trusted for its author.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] if the author is unknown or the signature doesn't
+    /// verify.
+    #[inline]
+    pub fn verify(&self, op: &AuthenticatedOp) -> Result<()> {
+        let key = self
+            .get(op.replica)
+            .ok_or_else(|| Error::InvalidSignature(format!("unknown replica {}", op.replica)))?;
+
+        op.verify(key)
+    }
+}
+
+impl FromIterator<(ReplicaId, VerifyingKey)> for KeySet {
+    #[inline]
+    fn from_iter<T: IntoIterator<Item = (ReplicaId, VerifyingKey)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<D: TrieHasher + 'static> Trie<D> {
+    /// Applies a signed operation, after checking it against the trusted keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] without modifying the Trie if the operation is not
+    /// signed by the key trusted for its author.
+    #[inline]
+    pub fn apply_authenticated(&mut self, op: &AuthenticatedOp, keys: &KeySet) -> Result<()> {
+        keys.verify(op)?;
+        self.apply(&op.op)
+    }
+
+    /// Signs the current root as the checkpoint `sequence`, timestamped with the system clock.
+    ///
+    /// Sequence numbers are chosen by the signer, and should grow with each checkpoint it
+    /// takes.
+    #[inline]
+    pub fn checkpoint(&self, sequence: u64, key: &SigningKey) -> Checkpoint {
+        Checkpoint::sign(self.root(), sequence, HybridClock::new().tick(), key)
+    }
+
+    /// Checks that `checkpoint` is signed by `key` and vouches for the current root.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSignature`] if the signature doesn't verify, and
+    /// [`Error::InvalidProof`] if the checkpoint is for another root.
+    #[inline]
+    pub fn verify_checkpoint(&self, checkpoint: &Checkpoint, key: &VerifyingKey) -> Result<()> {
+        checkpoint.verify(key)?;
+
+        if checkpoint.root != self.root() {
+            return Err(Error::InvalidProof(format!(
+                "checkpoint for root {}, expected {}",
+                checkpoint.root,
+                self.root()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    fn signed(replica: ReplicaId, seed: u8) -> Result<(AuthenticatedOp, SigningKey)> {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(&[seed], &[replica as u8][..])?;
+
+        Ok((AuthenticatedOp::sign(replica, trie.into_proof(), &key), key))
+    }
+
+    #[test]
+    fn test_rejects_unknown_replicas() -> Result<()> {
+        let (op, _) = signed(1, 1)?;
+        let mut trie = Trie::<Blake2s256>::empty();
+
+        assert!(matches!(
+            trie.apply_authenticated(&op, &KeySet::new()),
+            Err(Error::InvalidSignature(_))
+        ));
+        assert!(trie.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_impersonation() -> Result<()> {
+        let (_, honest) = signed(1, 1)?;
+        let (mut op, _) = signed(2, 2)?;
+        let keys: KeySet = [(1, honest.verifying_key())].into_iter().collect();
+
+        op.replica = 1;
+        assert!(keys.verify(&op).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_tampered_ops() -> Result<()> {
+        let (mut op, key) = signed(1, 1)?;
+        let keys: KeySet = [(1, key.verifying_key())].into_iter().collect();
+        assert!(keys.verify(&op).is_ok());
+
+        let (other, _) = signed(1, 2)?;
+        op.op = other.op;
+        assert!(keys.verify(&op).is_err());
+
+        Ok(())
+    }
+
+    #[proptest]
+    fn test_roundtrip(replica: ReplicaId, seed: u8) {
+        let (op, key) = signed(replica, seed)?;
+        let decoded = AuthenticatedOp::from_bytes(&op.to_bytes())?;
+
+        prop_assert_eq!(&decoded, &op);
+        prop_assert!(decoded.verify(&key.verifying_key()).is_ok());
+    }
+
+    #[proptest]
+    fn test_checkpoint_roundtrip(sequence: u64, timestamp: Timestamp, seed: u8) {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let checkpoint = Checkpoint::sign(Hash::new([seed; 32]), sequence, timestamp, &key);
+        let decoded = Checkpoint::from_bytes(&checkpoint.to_bytes())?;
+
+        prop_assert_eq!(decoded, checkpoint);
+        prop_assert!(decoded.verify(&key.verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_tampered_checkpoints() -> Result<()> {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"value"[..])?;
+
+        let checkpoint = trie.checkpoint(1, &key);
+        trie.verify_checkpoint(&checkpoint, &key.verifying_key())?;
+
+        let other = SigningKey::from_bytes(&[2; 32]);
+        assert!(matches!(
+            trie.verify_checkpoint(&checkpoint, &other.verifying_key()),
+            Err(Error::InvalidSignature(_))
+        ));
+
+        let mut replayed = checkpoint;
+        replayed.sequence += 1;
+        assert!(replayed.verify(&key.verifying_key()).is_err());
+
+        trie.insert(b"other", &b"value"[..])?;
+        assert!(matches!(
+            trie.verify_checkpoint(&checkpoint, &key.verifying_key()),
+            Err(Error::InvalidProof(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,624 @@
+//! SimpleSerialize (SSZ), the encoding of the Ethereum consensus layer.
+//!
+//! Proofs are encoded and merkleized following the consensus specs, so beacon-chain tooling can
+//! decode them, and check their `hash_tree_root` against commitments, with its own SSZ
+//! implementation. The types are described by the following schema:
+//!
+//! ```python
+//! class Timestamp(Container):
+//!     wall: uint64
+//!     logical: uint32
+//!
+//! class Neighbor(Container):
+//!     nibble: uint8
+//!     prefix: ByteList[MAX_PREFIX_LENGTH]
+//!     root: Bytes32
+//!
+//! class Branch(Container):
+//!     skip: uint64
+//!     neighbors: Vector[Bytes32, 4]
+//!
+//! class Fork(Container):
+//!     skip: uint64
+//!     neighbor: Neighbor
+//!
+//! class Leaf(Container):
+//!     skip: uint64
+//!     key: Bytes32
+//!     value: Bytes32
+//!     timestamp: List[Timestamp, 1]
+//!
+//! Step = Union[Branch, Fork, Leaf]
+//! Proof = List[Step, MAX_PROOF_STEPS]
+//! ```
+//!
+//! Optional timestamps are encoded as lists holding at most one element, since unions with a
+//! `None` variant are not supported by most SSZ implementations.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use blake2::Blake2s256;
+//! use mutree::{prelude::*, ssz::SimpleSerialize};
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut trie = Trie::<Blake2s256>::empty();
+//!     trie.insert(b"key", &b"value"[..])?;
+//!
+//!     let bytes = trie.proof().to_ssz()?;
+//!     assert_eq!(Proof::from_ssz(&bytes)?, *trie.proof());
+//!     assert_eq!(trie.proof().hash_tree_root()?.to_bytes().len(), 32);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use alloc::{format, string::ToString, vec, vec::Vec};
+
+use sha2::Sha256;
+
+use crate::prelude::*;
+
+/// The maximum length of the prefix of a [`Neighbor`], in nibbles.
+pub const MAX_PREFIX_LENGTH: usize = 64;
+
+/// The maximum number of steps in a [`Proof`].
+pub const MAX_PROOF_STEPS: u64 = 1 << 32;
+
+/// The length of the offsets pointing to variable-size fields.
+const OFFSET: usize = 4;
+
+/// A type with an SSZ encoding and hash tree root.
+pub trait SimpleSerialize: Sized {
+    /// Encodes the value with SSZ.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if a list is longer than its limit.
+    fn to_ssz(&self) -> Result<Vec<u8>>;
+
+    /// Decodes a value from its SSZ encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the bytes are not a valid encoding, including
+    /// encodings with trailing bytes, or with offsets out of order.
+    fn from_ssz(bytes: &[u8]) -> Result<Self>;
+
+    /// Computes the hash tree root of the value, with SHA-256.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if a list is longer than its limit.
+    fn hash_tree_root(&self) -> Result<Hash>;
+}
+
+/// Merkleizes `chunks` into a binary tree with `limit` leaves, rounded up to a power of two,
+/// padding it with zero chunks.
+///
+/// # Errors
+///
+/// Returns [`Error::Serialization`] if there are more chunks than `limit`.
+#[inline]
+pub fn merkleize(chunks: &[Hash], limit: u64) -> Result<Hash> {
+    if chunks.len() as u64 > limit {
+        return Err(Error::Serialization(format!(
+            "ssz: {} chunks exceed the limit of {limit}",
+            chunks.len()
+        )));
+    }
+
+    let depth = limit.max(1).next_power_of_two().trailing_zeros();
+    let mut layer = chunks.to_vec();
+    let mut zero = Hash::zero();
+
+    for _ in 0..depth {
+        if layer.len() % 2 == 1 {
+            layer.push(zero);
+        }
+
+        layer = layer
+            .chunks(2)
+            .map(|pair| Hash::combine::<Sha256>(&pair[0], &pair[1]))
+            .collect();
+        zero = Hash::combine::<Sha256>(&zero, &zero);
+    }
+
+    Ok(layer.first().copied().unwrap_or(zero))
+}
+
+/// Mixes the length of a list into the root of its elements.
+#[inline]
+pub fn mix_in_length(root: Hash, length: u64) -> Hash {
+    Hash::combine::<Sha256>(&root, &uint(length))
+}
+
+/// Mixes the selector of a union into the root of its value.
+#[inline]
+pub fn mix_in_selector(root: Hash, selector: u8) -> Hash {
+    Hash::combine::<Sha256>(&root, &uint(selector.into()))
+}
+
+/// Packs an unsigned integer into a chunk, as little-endian.
+fn uint(value: u64) -> Hash {
+    let mut chunk = [0u8; 32];
+    chunk[..8].copy_from_slice(&value.to_le_bytes());
+    Hash::from(chunk)
+}
+
+fn offset(bytes: &[u8], at: usize) -> Result<usize> {
+    let offset = bytes
+        .get(at..at + OFFSET)
+        .ok_or_else(|| Error::Deserialization("ssz: truncated offset".to_string()))?;
+
+    Ok(u32::from_le_bytes(offset.try_into()?) as usize)
+}
+
+fn expect_offset(bytes: &[u8], at: usize) -> Result<()> {
+    if offset(bytes, at)? != at + OFFSET {
+        return Err(Error::Deserialization("ssz: unexpected offset".to_string()));
+    }
+
+    Ok(())
+}
+
+fn fixed<const N: usize>(bytes: &[u8], at: usize) -> Result<[u8; N]> {
+    let field = bytes
+        .get(at..at + N)
+        .ok_or_else(|| Error::Deserialization("ssz: truncated field".to_string()))?;
+
+    Ok(field.try_into()?)
+}
+
+fn skip(bytes: &[u8]) -> Result<usize> {
+    Step::decode_skip(u64::from_le_bytes(fixed(bytes, 0)?))
+}
+
+fn exact(bytes: &[u8], len: usize) -> Result<()> {
+    if bytes.len() != len {
+        return Err(Error::Deserialization(format!(
+            "ssz: expected {len} bytes, got {}",
+            bytes.len()
+        )));
+    }
+
+    Ok(())
+}
+
+impl SimpleSerialize for Timestamp {
+    #[inline]
+    fn to_ssz(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(Self::LEN);
+        bytes.extend_from_slice(&self.wall.to_le_bytes());
+        bytes.extend_from_slice(&self.logical.to_le_bytes());
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn from_ssz(bytes: &[u8]) -> Result<Self> {
+        exact(bytes, Self::LEN)?;
+
+        Ok(Self {
+            wall: u64::from_le_bytes(fixed(bytes, 0)?),
+            logical: u32::from_le_bytes(fixed(bytes, 8)?),
+        })
+    }
+
+    #[inline]
+    fn hash_tree_root(&self) -> Result<Hash> {
+        merkleize(&[uint(self.wall), uint(self.logical.into())], 2)
+    }
+}
+
+impl SimpleSerialize for Neighbor {
+    #[inline]
+    fn to_ssz(&self) -> Result<Vec<u8>> {
+        if self.prefix.len() > MAX_PREFIX_LENGTH {
+            return Err(Error::Serialization(format!(
+                "ssz: prefix longer than {MAX_PREFIX_LENGTH} nibbles"
+            )));
+        }
+
+        let mut bytes = Vec::with_capacity(1 + OFFSET + 32 + self.prefix.len());
+        bytes.push(self.nibble);
+        bytes.extend_from_slice(&((1 + OFFSET + 32) as u32).to_le_bytes());
+        bytes.extend_from_slice(self.root.as_ref());
+        bytes.extend_from_slice(&self.prefix);
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn from_ssz(bytes: &[u8]) -> Result<Self> {
+        let nibble = fixed::<1>(bytes, 0)?[0];
+        if offset(bytes, 1)? != 1 + OFFSET + 32 {
+            return Err(Error::Deserialization("ssz: unexpected offset".to_string()));
+        }
+        let root = Hash::from(fixed::<32>(bytes, 1 + OFFSET)?);
+        let prefix = &bytes[1 + OFFSET + 32..];
+
+        if prefix.len() > MAX_PREFIX_LENGTH {
+            return Err(Error::Deserialization(format!(
+                "ssz: prefix longer than {MAX_PREFIX_LENGTH} nibbles"
+            )));
+        }
+
+        Self {
+            nibble,
+            prefix: prefix.to_vec(),
+            root,
+        }
+        .checked()
+    }
+
+    #[inline]
+    fn hash_tree_root(&self) -> Result<Hash> {
+        let chunks: Vec<Hash> = self
+            .prefix
+            .chunks(32)
+            .map(|chunk| {
+                let mut padded = [0u8; 32];
+                padded[..chunk.len()].copy_from_slice(chunk);
+                Hash::from(padded)
+            })
+            .collect();
+        let prefix = mix_in_length(
+            merkleize(&chunks, MAX_PREFIX_LENGTH.div_ceil(32) as u64)?,
+            self.prefix.len() as u64,
+        );
+
+        merkleize(&[uint(self.nibble.into()), prefix, self.root], 3)
+    }
+}
+
+impl SimpleSerialize for Step {
+    /// Encodes the step as a union, with a selector of 0 for branches, 1 for forks and 2 for
+    /// leaves.
+    #[inline]
+    fn to_ssz(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+
+        match self {
+            Step::Branch { skip, neighbors } => {
+                bytes.push(0);
+                bytes.extend_from_slice(&(*skip as u64).to_le_bytes());
+                for neighbor in neighbors {
+                    bytes.extend_from_slice(neighbor.as_ref());
+                }
+            }
+            Step::Fork { skip, neighbor } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(*skip as u64).to_le_bytes());
+                bytes.extend_from_slice(&((8 + OFFSET) as u32).to_le_bytes());
+                bytes.extend(neighbor.to_ssz()?);
+            }
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => {
+                bytes.push(2);
+                bytes.extend_from_slice(&(*skip as u64).to_le_bytes());
+                bytes.extend_from_slice(key.as_ref());
+                bytes.extend_from_slice(value.as_ref());
+                bytes.extend_from_slice(&((8 + 64 + OFFSET) as u32).to_le_bytes());
+                if let Some(timestamp) = timestamp {
+                    bytes.extend(timestamp.to_ssz()?);
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn from_ssz(bytes: &[u8]) -> Result<Self> {
+        let (selector, body) = bytes
+            .split_first()
+            .ok_or_else(|| Error::Deserialization("ssz: empty union".to_string()))?;
+
+        match selector {
+            0 => {
+                exact(body, 8 + 4 * 32)?;
+                let mut neighbors = [Hash::zero(); 4];
+                for (i, neighbor) in neighbors.iter_mut().enumerate() {
+                    *neighbor = Hash::from(fixed::<32>(body, 8 + i * 32)?);
+                }
+
+                Ok(Step::Branch {
+                    skip: skip(body)?,
+                    neighbors,
+                })
+            }
+            1 => {
+                expect_offset(body, 8)?;
+
+                Step::Fork {
+                    skip: skip(body)?,
+                    neighbor: Neighbor::from_ssz(&body[8 + OFFSET..])?,
+                }
+                .checked()
+            }
+            2 => {
+                expect_offset(body, 8 + 64)?;
+                let timestamp = match &body[8 + 64 + OFFSET..] {
+                    [] => None,
+                    timestamp => Some(Timestamp::from_ssz(timestamp)?),
+                };
+
+                Ok(Step::Leaf {
+                    skip: skip(body)?,
+                    key: Hash::from(fixed::<32>(body, 8)?),
+                    value: Hash::from(fixed::<32>(body, 8 + 32)?),
+                    timestamp,
+                })
+            }
+            selector => Err(Error::Deserialization(format!(
+                "ssz: unknown step selector {selector}"
+            ))),
+        }
+    }
+
+    #[inline]
+    fn hash_tree_root(&self) -> Result<Hash> {
+        let (selector, root) = match self {
+            Step::Branch { skip, neighbors } => (
+                0,
+                merkleize(&[uint(*skip as u64), merkleize(neighbors, 4)?], 2)?,
+            ),
+            Step::Fork { skip, neighbor } => (
+                1,
+                merkleize(&[uint(*skip as u64), neighbor.hash_tree_root()?], 2)?,
+            ),
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => {
+                let timestamps = match timestamp {
+                    Some(timestamp) => vec![timestamp.hash_tree_root()?],
+                    None => Vec::new(),
+                };
+                let timestamp = mix_in_length(merkleize(&timestamps, 1)?, timestamps.len() as u64);
+
+                (
+                    2,
+                    merkleize(&[uint(*skip as u64), *key, *value, timestamp], 4)?,
+                )
+            }
+        };
+
+        Ok(mix_in_selector(root, selector))
+    }
+}
+
+impl SimpleSerialize for Proof {
+    #[inline]
+    fn to_ssz(&self) -> Result<Vec<u8>> {
+        if self.len() as u64 > MAX_PROOF_STEPS {
+            return Err(Error::Serialization(format!(
+                "ssz: more than {MAX_PROOF_STEPS} steps"
+            )));
+        }
+
+        let steps = self
+            .iter()
+            .map(SimpleSerialize::to_ssz)
+            .collect::<Result<Vec<_>>>()?;
+        let mut bytes = Vec::new();
+        let mut next = steps.len() * OFFSET;
+
+        for step in &steps {
+            bytes.extend_from_slice(&u32::try_from(next)?.to_le_bytes());
+            next += step.len();
+        }
+        for step in steps {
+            bytes.extend(step);
+        }
+
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn from_ssz(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() {
+            return Ok(Proof::new());
+        }
+
+        let first = offset(bytes, 0)?;
+        if first == 0 || first % OFFSET != 0 || first > bytes.len() {
+            return Err(Error::Deserialization(
+                "ssz: invalid first offset".to_string(),
+            ));
+        }
+
+        let mut offsets = (0..first / OFFSET)
+            .map(|i| offset(bytes, i * OFFSET))
+            .collect::<Result<Vec<_>>>()?;
+        offsets.push(bytes.len());
+
+        offsets
+            .windows(2)
+            .map(|window| match bytes.get(window[0]..window[1]) {
+                Some(step) => Step::from_ssz(step),
+                None => Err(Error::Deserialization(
+                    "ssz: offsets out of order".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Proof::from)
+    }
+
+    #[inline]
+    fn hash_tree_root(&self) -> Result<Hash> {
+        let steps = self
+            .iter()
+            .map(SimpleSerialize::hash_tree_root)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(mix_in_length(
+            merkleize(&steps, MAX_PROOF_STEPS)?,
+            steps.len() as u64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    fn trie(entries: &[(Vec<u8>, Vec<u8>)]) -> Result<Trie<Blake2s256>> {
+        let mut trie = Trie::empty();
+
+        for (key, value) in entries.iter().filter(|(key, _)| !key.is_empty()) {
+            trie.insert(key, &value[..])?;
+        }
+
+        Ok(trie)
+    }
+
+    #[proptest]
+    fn test_proof_roundtrip(entries: Vec<(Vec<u8>, Vec<u8>)>) {
+        let proof = trie(&entries)?.into_proof();
+        prop_assert_eq!(Proof::from_ssz(&proof.to_ssz()?)?, proof);
+    }
+
+    #[proptest]
+    fn test_step_roundtrip(
+        #[strategy(any::<Step>().prop_filter("prefix too long", |step| {
+            !matches!(step, Step::Fork { neighbor, .. } if neighbor.prefix.len() > MAX_PREFIX_LENGTH)
+        }))]
+        step: Step,
+    ) {
+        prop_assert_eq!(Step::from_ssz(&step.to_ssz()?)?, step);
+    }
+
+    #[proptest]
+    fn test_timestamp_roundtrip(timestamp: Timestamp) {
+        prop_assert_eq!(Timestamp::from_ssz(&timestamp.to_ssz()?)?, timestamp);
+    }
+
+    #[proptest]
+    fn test_decoding_never_panics(bytes: Vec<u8>) {
+        let _ = Proof::from_ssz(&bytes);
+        let _ = Step::from_ssz(&bytes);
+    }
+
+    #[test]
+    fn test_zero_hashes() -> Result<()> {
+        // The first zero hashes of the consensus specs: SHA-256 of 64 and 128 zero bytes.
+        assert_eq!(merkleize(&[], 1)?, Hash::zero());
+        assert_eq!(
+            merkleize(&[], 2)?.to_hex(),
+            "f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b"
+        );
+        assert_eq!(
+            merkleize(&[], 4)?.to_hex(),
+            "db56114e00fdd4c1f85c892bf35ac9a89289aaecb1ebd0a96cde606a748b5d71"
+        );
+        assert!(merkleize(&[Hash::zero(); 3], 2).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_layout() -> Result<()> {
+        let step = Step::Leaf {
+            skip: 1,
+            key: Hash::from([0xaa; 32]),
+            value: Hash::from([0xbb; 32]),
+            timestamp: Some(Timestamp::new(2, 3)),
+        };
+        let bytes = Proof::from(vec![step]).to_ssz()?;
+
+        let mut expected = vec![4, 0, 0, 0, 2, 1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend([0xaa; 32]);
+        expected.extend([0xbb; 32]);
+        expected.extend([76, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(bytes, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_malformed_encodings() {
+        let branch = Step::Branch {
+            skip: 0,
+            neighbors: [Hash::zero(); 4],
+        };
+        let mut bytes = branch.to_ssz().unwrap();
+
+        bytes.push(0);
+        assert!(Step::from_ssz(&bytes).is_err());
+        bytes[0] = 3;
+        assert!(Step::from_ssz(&bytes).is_err());
+
+        assert!(Proof::from_ssz(&[8, 0, 0, 0, 4, 0, 0, 0]).is_err());
+        assert!(Proof::from_ssz(&[3, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_forks() -> Result<()> {
+        let fork = |nibble, prefix| Step::Fork {
+            skip: 0,
+            neighbor: Neighbor {
+                nibble,
+                prefix,
+                root: Hash::zero(),
+            },
+        };
+
+        for step in [fork(0x10, vec![]), fork(0, vec![0; 32])] {
+            assert!(matches!(
+                Step::from_ssz(&step.to_ssz()?),
+                Err(Error::Deserialization(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_skips_past_the_maximum() -> Result<()> {
+        let branch = |skip| Step::Branch {
+            skip,
+            neighbors: [Hash::zero(); 4],
+        };
+
+        let bytes = branch(MAX_SKIP).to_ssz()?;
+        assert_eq!(Step::from_ssz(&bytes)?, branch(MAX_SKIP));
+
+        let bytes = branch(MAX_SKIP + 1).to_ssz()?;
+        assert!(matches!(
+            Step::from_ssz(&bytes),
+            Err(Error::Deserialization(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_tree_root_commits_to_every_field() -> Result<()> {
+        let leaf = |timestamp| Step::Leaf {
+            skip: 0,
+            key: Hash::zero(),
+            value: Hash::zero(),
+            timestamp,
+        };
+
+        let none = Proof::from(vec![leaf(None)]).hash_tree_root()?;
+        let some = Proof::from(vec![leaf(Some(Timestamp::default()))]).hash_tree_root()?;
+        let empty = Proof::new().hash_tree_root()?;
+
+        assert_ne!(none, some);
+        assert_ne!(none, empty);
+        assert_eq!(empty, mix_in_length(merkleize(&[], MAX_PROOF_STEPS)?, 0));
+
+        Ok(())
+    }
+}
@@ -1,5 +1,12 @@
 #![allow(clippy::test_attr_in_doctest)]
 
+#[cfg(test)]
+mod conformance;
+#[cfg(test)]
+mod reference;
+pub mod simulation;
+pub mod strategies;
+
 /// Tests properties required for state-based CRDTs (CvRDT).
 ///
 /// This macro generates test cases that verify the following CRDT properties:
@@ -11,9 +18,9 @@
 /// # Example
 ///
 /// ```rust
-/// use mutree::prelude::*;
+/// use mutree::{prelude::*, test_state_crdt_properties};
 ///
-/// #[derive(Debug, Clone, PartialEq, Default)]
+/// #[derive(Debug, Clone, PartialEq, Default, test_strategy::Arbitrary)]
 /// struct Counter(u64);
 ///
 /// impl CvRDT for Counter {
@@ -25,6 +32,7 @@
 ///
 /// // Generates comprehensive CRDT property tests
 /// test_state_crdt_properties!(Counter);
+/// # fn main() {}
 /// ```
 #[macro_export]
 macro_rules! test_state_crdt_properties {
@@ -63,7 +71,7 @@ macro_rules! test_state_crdt_properties {
                 #[test_strategy::proptest(fork = false)]
                 fn test_commutativity(a: $type, b: $type) {
                     let ab = build_state(vec![&a, &b])?;
-                    let ba = build_state(vec![&a, &b])?;
+                    let ba = build_state(vec![&b, &a])?;
 
                     prop_assert_eq!(ab, ba);
                 }
@@ -118,7 +126,7 @@ macro_rules! test_op_crdt_properties_inner {
         #[test_strategy::proptest(fork = false)]
         fn test_commutativity(a: $op_type, b: $op_type) {
             let ab = build_op(vec![&a, &b])?;
-            let ba = build_op(vec![&a, &b])?;
+            let ba = build_op(vec![&b, &a])?;
 
             prop_assert_eq!(ab, ba);
         }
@@ -135,29 +143,30 @@ macro_rules! test_op_crdt_properties_inner {
 ///
 /// Basic usage with same type for state and operations:
 /// ```rust
-/// use mutree::prelude::*;
+/// use mutree::{prelude::*, test_op_crdt_properties};
 ///
-/// #[derive(Debug, Clone, PartialEq, Default)]
+/// #[derive(Debug, Clone, PartialEq, Default, test_strategy::Arbitrary)]
 /// struct Counter(u64);
 ///
 /// impl CmRDT<Counter> for Counter {
 ///     fn apply(&mut self, op: &Counter) -> Result<(), Error> {
-///         self.0 += op.0;
+///         self.0 = self.0.wrapping_add(op.0);
 ///         Ok(())
 ///     }
 /// }
 ///
 /// test_op_crdt_properties!(Counter);
+/// # fn main() {}
 /// ```
 ///
 /// Usage with separate operation type:
 /// ```rust
-/// use mutree::prelude::*;
+/// use mutree::{prelude::*, test_op_crdt_properties};
 ///
 /// #[derive(Debug, Clone, PartialEq, Default)]
 /// struct Counter(u64);
 ///
-/// #[derive(Debug, Clone, PartialEq)]
+/// #[derive(Debug, Clone, PartialEq, test_strategy::Arbitrary)]
 /// enum CounterOp {
 ///     Increment(u64),
 ///     Decrement(u64)
@@ -166,14 +175,15 @@ macro_rules! test_op_crdt_properties_inner {
 /// impl CmRDT<CounterOp> for Counter {
 ///     fn apply(&mut self, op: &CounterOp) -> Result<(), Error> {
 ///         match op {
-///             CounterOp::Increment(n) => self.0 += n,
-///             CounterOp::Decrement(n) => self.0 -= n,
+///             CounterOp::Increment(n) => self.0 = self.0.wrapping_add(*n),
+///             CounterOp::Decrement(n) => self.0 = self.0.wrapping_sub(*n),
 ///         }
 ///         Ok(())
 ///     }
 /// }
 ///
 /// test_op_crdt_properties!(Counter, CounterOp);
+/// # fn main() {}
 /// ```
 #[macro_export]
 macro_rules! test_op_crdt_properties {
@@ -208,9 +218,9 @@ macro_rules! test_op_crdt_properties {
 /// # Example
 ///
 /// ```rust
-/// use mutree::test_to_bytes;
+/// use mutree::{prelude::*, test_to_bytes};
 ///
-/// #[derive(Debug, Clone, PartialEq)]
+/// #[derive(Debug, Clone, PartialEq, Default, test_strategy::Arbitrary)]
 /// struct MyType(Vec<u8>);
 ///
 /// impl ToBytes for MyType {
@@ -227,6 +237,7 @@ macro_rules! test_op_crdt_properties {
 /// }
 ///
 /// test_to_bytes!(MyType);
+/// # fn main() {}
 /// ```
 #[macro_export]
 macro_rules! test_to_bytes {
@@ -285,9 +296,9 @@ macro_rules! test_to_bytes {
 /// # Example
 ///
 /// ```rust
-/// use mutree::test_to_hex;
+/// use mutree::{prelude::*, test_to_hex};
 ///
-/// #[derive(Debug, Clone, PartialEq)]
+/// #[derive(Debug, Clone, PartialEq, test_strategy::Arbitrary)]
 /// struct MyType(Vec<u8>);
 ///
 /// impl ToHex for MyType {
@@ -303,6 +314,7 @@ macro_rules! test_to_bytes {
 /// }
 ///
 /// test_to_hex!(MyType);
+/// # fn main() {}
 /// ```
 #[macro_export]
 macro_rules! test_to_hex {
@@ -0,0 +1,270 @@
+//! Conformance of the byte encodings to the format documented in [`crate::wire`].
+//!
+//! Each fixture is the encoding of a fixed value, written field by field from the
+//! documentation rather than produced by the encoders, at each version of the wire format and
+//! each [`RootFormat`]. A fixture failing means that stored proofs, or replicas running another
+//! release, would no longer read the bytes or agree on the roots: if the change is intended,
+//! it needs a new version instead of new fixtures.
+
+use alloc::vec::Vec;
+
+use crate::{prelude::*, wire::WireFormat};
+
+fn hash(byte: u8) -> Hash {
+    Hash::new([byte; 32])
+}
+
+fn timestamp() -> Timestamp {
+    Timestamp::new(0x0102_0304_0506_0708, 0x090a_0b0c)
+}
+
+fn neighbor() -> Neighbor {
+    Neighbor {
+        nibble: 5,
+        prefix: vec![0xab, 0xcd],
+        root: hash(0x33),
+    }
+}
+
+fn branch() -> Step {
+    Step::Branch {
+        skip: 3,
+        neighbors: [hash(0xaa), Hash::zero(), Hash::zero(), hash(0xbb)],
+    }
+}
+
+fn fork() -> Step {
+    Step::Fork {
+        skip: 1,
+        neighbor: neighbor(),
+    }
+}
+
+fn leaf(timestamp: Option<Timestamp>) -> Step {
+    Step::Leaf {
+        skip: 2,
+        key: hash(0x11),
+        value: hash(0x22),
+        timestamp,
+    }
+}
+
+/// Encodings of wire format version 1.
+mod v1 {
+    pub const TIMESTAMP: &str = concat!("0102030405060708", "090a0b0c");
+    pub const NEIGHBOR: &str = concat!(
+        "05",
+        "abcd",
+        "3333333333333333333333333333333333333333333333333333333333333333"
+    );
+    pub const BRANCH: &str = concat!(
+        "00",
+        "0000000000000003",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+    );
+    pub const FORK: &str = concat!(
+        "01",
+        "0000000000000001",
+        "05",
+        "abcd",
+        "3333333333333333333333333333333333333333333333333333333333333333"
+    );
+    pub const LEAF: &str = concat!(
+        "02",
+        "0000000000000002",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+    );
+    pub const LEAF_WITH_TIMESTAMP: &str = concat!(
+        "02",
+        "0000000000000002",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+        "0102030405060708",
+        "090a0b0c",
+    );
+    /// A branch followed by a trailing byte, which no step accepts.
+    pub const BRANCH_WITH_TRAILING_BYTE: &str = concat!(
+        "00",
+        "0000000000000003",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+        "00",
+    );
+    /// A leaf followed by a branch.
+    pub const PROOF: &str = concat!(
+        "00000002",
+        "00000049",
+        "02",
+        "0000000000000002",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+        "00000089",
+        "00",
+        "0000000000000003",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+    );
+}
+
+/// The bytes each step contributes to a root of [`RootFormat::V1`].
+mod root_v1 {
+    pub const BRANCH: &str = concat!(
+        "02",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+    );
+    pub const FORK: &str = concat!(
+        "ff",
+        "05",
+        "abcd",
+        "3333333333333333333333333333333333333333333333333333333333333333"
+    );
+    pub const LEAF: &str = concat!(
+        "00",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+    );
+    pub const LEAF_WITH_TIMESTAMP: &str = concat!(
+        "00",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+        "0102030405060708",
+        "090a0b0c",
+    );
+    /// The Blake2s-256 root of [`super::v1::PROOF`], which hashes its branch first.
+    pub const ROOT: &str = "8d5de65df760754813623d500b4b44b165596e354cd9a4dd83473362307e6756";
+}
+
+/// The bytes each step contributes to a root of [`RootFormat::V2`].
+mod root_v2 {
+    pub const BRANCH: &str = concat!(
+        "01",
+        "0000000000000003",
+        "09",
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+    );
+    pub const FORK: &str = concat!(
+        "02",
+        "0000000000000001",
+        "05",
+        "00000002",
+        "abcd",
+        "3333333333333333333333333333333333333333333333333333333333333333"
+    );
+    pub const LEAF: &str = concat!(
+        "00",
+        "0000000000000002",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+        "00",
+    );
+    pub const LEAF_WITH_TIMESTAMP: &str = concat!(
+        "00",
+        "0000000000000002",
+        "1111111111111111111111111111111111111111111111111111111111111111",
+        "2222222222222222222222222222222222222222222222222222222222222222",
+        "01",
+        "0102030405060708",
+        "090a0b0c",
+    );
+    /// The Blake2s-256 root of [`super::v1::PROOF`], which hashes its branch first.
+    pub const ROOT: &str = "eb06d8818f7d8a1ea329bc1f78be4dc947689210985a7692fb339cbcf7f56a5b";
+}
+
+/// Checks that `value` encodes to `fixture`, and that `fixture` decodes to `value`.
+fn check<T>(value: &T, fixture: &str)
+where
+    T: ToBytes + FromBytes + PartialEq + core::fmt::Debug,
+{
+    let bytes = hex::decode(fixture).unwrap();
+    assert_eq!(
+        hex::encode(value.to_bytes()),
+        fixture,
+        "encoding of {value:?}"
+    );
+    assert_eq!(
+        &T::from_bytes(&bytes).unwrap(),
+        value,
+        "decoding of {fixture}"
+    );
+}
+
+/// Checks that `step` contributes `fixture` to roots of `format`.
+fn check_commit(step: &Step, format: RootFormat, fixture: &str) {
+    let mut bytes = Vec::new();
+    step.commit(format, |chunk| bytes.extend_from_slice(chunk));
+    assert_eq!(
+        hex::encode(bytes),
+        fixture,
+        "{format:?} commitment of {step:?}"
+    );
+}
+
+#[test]
+fn test_v1_encodings() {
+    check(&timestamp(), v1::TIMESTAMP);
+    check(&neighbor(), v1::NEIGHBOR);
+    check(&branch(), v1::BRANCH);
+    check(&fork(), v1::FORK);
+    check(&leaf(None), v1::LEAF);
+    check(&leaf(Some(timestamp())), v1::LEAF_WITH_TIMESTAMP);
+    check(&Proof::from(vec![leaf(None), branch()]), v1::PROOF);
+}
+
+#[test]
+fn test_v1_rejects_trailing_bytes() {
+    let bytes = hex::decode(v1::BRANCH_WITH_TRAILING_BYTE).unwrap();
+    assert!(Step::from_bytes(&bytes).is_err());
+
+    let proof = ["00000001", "0000008a", v1::BRANCH_WITH_TRAILING_BYTE].concat();
+    assert!(Proof::from_bytes(&hex::decode(proof).unwrap()).is_err());
+}
+
+#[test]
+fn test_v1_wire_prefix() {
+    let proof = Proof::from(vec![leaf(None), branch()]);
+    let wire = proof.to_wire(1).unwrap();
+
+    assert_eq!(hex::encode(&wire), ["0001", v1::PROOF].concat());
+    assert_eq!(Proof::from_wire(&wire).unwrap(), proof);
+}
+
+#[test]
+fn test_root_v1_commitments() {
+    check_commit(&branch(), RootFormat::V1, root_v1::BRANCH);
+    check_commit(&fork(), RootFormat::V1, root_v1::FORK);
+    check_commit(&leaf(None), RootFormat::V1, root_v1::LEAF);
+    check_commit(
+        &leaf(Some(timestamp())),
+        RootFormat::V1,
+        root_v1::LEAF_WITH_TIMESTAMP,
+    );
+
+    let trie = Trie::<blake2::Blake2s256>::from_proof(Proof::from(vec![leaf(None), branch()]));
+    assert_eq!(trie, Trie::try_from(root_v1::ROOT).unwrap());
+}
+
+#[test]
+fn test_root_v2_commitments() {
+    check_commit(&branch(), RootFormat::V2, root_v2::BRANCH);
+    check_commit(&fork(), RootFormat::V2, root_v2::FORK);
+    check_commit(&leaf(None), RootFormat::V2, root_v2::LEAF);
+    check_commit(
+        &leaf(Some(timestamp())),
+        RootFormat::V2,
+        root_v2::LEAF_WITH_TIMESTAMP,
+    );
+
+    let mut trie = Trie::<blake2::Blake2s256>::from_proof(Proof::from(vec![leaf(None), branch()]));
+    trie.set_root_format(RootFormat::V2);
+    assert_eq!(trie, Trie::try_from(root_v2::ROOT).unwrap());
+}
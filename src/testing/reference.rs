@@ -0,0 +1,242 @@
+//! Replays test vectors recorded from a reference implementation of the Merkle Patricia
+//! Forestry, asserting that inserting the same entries yields the same roots.
+//!
+//! The vectors are in the format emitted by `mutree-vectors`: each vector names its digest,
+//! lists the inserted entries in hex, and the root after every insertion. The ones checked in at
+//! `reference/vectors.json` were recorded with `mutree-vectors` itself, so they pin the roots of
+//! this crate, and are replayed by every test run. `MUTREE_REFERENCE_VECTORS` names another file
+//! to replay instead, such as vectors recorded from another implementation.
+//!
+//! Malformed vectors and unknown digests fail the replay. Vectors of known digests without a
+//! matching hash feature are skipped.
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{prelude::*, TrieHasher};
+
+/// A vector, as recorded by the reference implementation.
+#[derive(Debug)]
+struct Vector {
+    digest: String,
+    seed: u64,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    roots: Vec<Hash>,
+}
+
+impl Vector {
+    fn from_json(vector: &Value) -> Result<Self> {
+        let field = |name: &str| {
+            vector
+                .get(name)
+                .ok_or_else(|| Error::Deserialization(format!("vector is missing `{name}`")))
+        };
+        let hex = |value: &Value| {
+            value
+                .as_str()
+                .ok_or_else(|| Error::Deserialization(format!("expected hex, got {value}")))
+                .and_then(|hex| Ok(hex::decode(hex)?))
+        };
+        let array = |name: &str| {
+            field(name)?
+                .as_array()
+                .ok_or_else(|| Error::Deserialization(format!("`{name}` is not an array")))
+        };
+
+        Ok(Self {
+            digest: field("digest")?
+                .as_str()
+                .ok_or_else(|| Error::Deserialization("`digest` is not a string".to_string()))?
+                .to_string(),
+            seed: field("seed")?
+                .as_u64()
+                .ok_or_else(|| Error::Deserialization("`seed` is not an integer".to_string()))?,
+            entries: array("entries")?
+                .iter()
+                .map(|entry| Ok((hex(&entry["key"])?, hex(&entry["value"])?)))
+                .collect::<Result<_>>()?,
+            roots: array("roots")?
+                .iter()
+                .map(|root| Hash::from_bytes(&hex(root)?))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Inserts the entries of the vector, returning the index of the first insertion whose root
+    /// differs from the recorded one.
+    fn replay<D: TrieHasher + 'static>(&self) -> Result<Option<usize>> {
+        let mut trie = Trie::<D>::empty();
+
+        for (i, ((key, value), expected)) in self.entries.iter().zip(&self.roots).enumerate() {
+            trie.insert(key, &value[..])?;
+            if trie.root() != *expected {
+                return Ok(Some(i));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Replays the vector with the digest it names, or returns `None` if its feature is not
+    /// enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the digest is unknown.
+    fn replay_named(&self) -> Result<Option<Option<usize>>> {
+        let replayed = match self.digest.as_str() {
+            "blake2b-256" => self.replay::<blake2::Blake2b<blake2::digest::consts::U32>>(),
+            "blake2s-256" => self.replay::<blake2::Blake2s256>(),
+            #[cfg(feature = "blake3")]
+            "blake3" => self.replay::<blake3::Hasher>(),
+            #[cfg(feature = "sha2")]
+            "sha2-256" => self.replay::<sha2::Sha256>(),
+            #[cfg(feature = "sha3")]
+            "sha3-256" => self.replay::<sha3::Sha3_256>(),
+            #[cfg(feature = "keccak")]
+            "keccak-256" => self.replay::<sha3::Keccak256>(),
+            #[cfg(feature = "ripemd")]
+            "ripemd-160" => self.replay::<ripemd::Ripemd160>(),
+            // Digests whose feature is disabled
+            #[allow(unreachable_patterns)]
+            "blake3" | "sha2-256" | "sha3-256" | "keccak-256" | "ripemd-160" => return Ok(None),
+            digest => {
+                return Err(Error::Deserialization(format!(
+                    "vector {} has an unknown digest `{digest}`",
+                    self.seed
+                )))
+            }
+        };
+
+        replayed.map(Some)
+    }
+}
+
+/// Replays every vector of `vectors`, returning the digest, seed and insertion of each mismatch.
+fn replay_all(vectors: &Value) -> Result<Vec<(String, u64, usize)>> {
+    let vectors = vectors
+        .get("vectors")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::Deserialization("missing `vectors` array".to_string()))?;
+
+    let mut mismatches = Vec::new();
+    for vector in vectors {
+        let vector = Vector::from_json(vector)?;
+        if vector.roots.len() != vector.entries.len() {
+            return Err(Error::Deserialization(format!(
+                "{} vector {} has {} roots for {} entries",
+                vector.digest,
+                vector.seed,
+                vector.roots.len(),
+                vector.entries.len()
+            )));
+        }
+
+        if let Some(Some(i)) = vector.replay_named()? {
+            mismatches.push((vector.digest, vector.seed, i));
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[test]
+    fn test_replays_reference_vectors() -> Result<()> {
+        let path = env::var_os("MUTREE_REFERENCE_VECTORS")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("reference/vectors.json"));
+
+        let vectors: Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+        let mismatches = replay_all(&vectors)?;
+        assert!(
+            mismatches.is_empty(),
+            "roots differ from the reference at (digest, seed, insertion): {mismatches:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_malformed_vectors() {
+        let vectors = |vector: Value| serde_json::json!({ "version": 1, "vectors": [vector] });
+        let valid = serde_json::json!({
+            "digest": "blake2s-256",
+            "seed": 0,
+            "entries": [],
+            "roots": [],
+        });
+        assert!(replay_all(&vectors(valid.clone())).unwrap().is_empty());
+
+        for (field, value) in [
+            ("digest", Value::Null),
+            ("digest", "blake2s-512".into()),
+            ("seed", Value::Null),
+            ("seed", "0".into()),
+            ("entries", Value::Null),
+            ("roots", serde_json::json!(["00"])),
+        ] {
+            let mut vector = valid.clone();
+            vector[field] = value;
+            assert!(replay_all(&vectors(vector)).is_err(), "{field}");
+        }
+        for field in ["digest", "seed", "entries", "roots"] {
+            let mut vector = valid.clone();
+            vector.as_object_mut().unwrap().remove(field);
+            assert!(replay_all(&vectors(vector)).is_err(), "{field}");
+        }
+    }
+
+    #[proptest]
+    fn test_replay_detects_mismatches(
+        #[strategy(vec((vec(any::<u8>(), 1..8), vec(any::<u8>(), 0..8)), 1..8))] entries: Vec<(
+            Vec<u8>,
+            Vec<u8>,
+        )>,
+        #[strategy(0..#entries.len())] tampered: usize,
+    ) {
+        let mut trie = Trie::<blake2::Blake2s256>::empty();
+        let mut roots = Vec::new();
+        for (key, value) in &entries {
+            trie.insert(key, &value[..])?;
+            roots.push(trie.root());
+        }
+
+        let vector = |roots: &[Hash]| {
+            serde_json::json!({
+                "version": 1,
+                "vectors": [{
+                    "digest": "blake2s-256",
+                    "seed": 0,
+                    "entries": entries
+                        .iter()
+                        .map(|(key, value)| serde_json::json!({
+                            "key": hex::encode(key),
+                            "value": hex::encode(value),
+                        }))
+                        .collect::<Vec<_>>(),
+                    "roots": roots.iter().map(ToHex::to_hex).collect::<Vec<_>>(),
+                }],
+            })
+        };
+
+        prop_assert!(replay_all(&vector(&roots))?.is_empty());
+
+        roots[tampered] = Hash::zero();
+        prop_assert_eq!(
+            replay_all(&vector(&roots))?,
+            vec![("blake2s-256".to_string(), 0, tampered)]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Convergence simulation for state-based CRDTs.
+//!
+//! A [`Simulation`] runs a number of in-memory replicas of a [`CvRDT`] through a schedule of
+//! [`Action`]s: local writes, and state messages between replicas that can be lost, reordered,
+//! or blocked by network partitions. Once the schedule is over, [`Simulation::settle`] heals the
+//! network and runs a full round of anti-entropy, after which every replica must have converged
+//! to the same state.
+//!
+//! Most users should reach for the [`test_convergence!`](crate::test_convergence) macro, which
+//! generates random schedules and checks for convergence.
+
+use proptest::{collection::vec, prelude::*};
+
+use crate::prelude::*;
+
+/// A single event in the life of the simulated replicas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action<S> {
+    /// A local write, modelled as merging `state` into `replica`.
+    Write { replica: usize, state: S },
+    /// `from` sends a snapshot of its state to `to`.
+    Send { from: usize, to: usize },
+    /// Delivers an in-flight message, picked by index modulo the number of messages. Picking
+    /// messages out of order models reordering by the network.
+    Deliver { message: usize },
+    /// Loses an in-flight message, picked by index modulo the number of messages.
+    Drop { message: usize },
+    /// Splits the replicas in two groups by the bits of `mask`. Messages crossing groups are
+    /// lost, both when sent and when delivered.
+    Partition { mask: u64 },
+    /// Removes any partition.
+    Heal,
+}
+
+/// A message in flight between two replicas.
+#[derive(Debug, Clone, PartialEq)]
+struct Message<S> {
+    from: usize,
+    to: usize,
+    state: S,
+}
+
+/// A set of in-memory replicas connected by an unreliable network.
+#[derive(Debug, Clone)]
+pub struct Simulation<S> {
+    replicas: Vec<S>,
+    in_flight: Vec<Message<S>>,
+    partition: Option<u64>,
+}
+
+impl<S: CvRDT> Simulation<S> {
+    /// Creates a simulation with `replicas` replicas, all starting from the default state.
+    #[inline]
+    pub fn new(replicas: usize) -> Self {
+        assert!(
+            (1..=64).contains(&replicas),
+            "simulations support between 1 and 64 replicas"
+        );
+
+        Self {
+            replicas: vec![S::default(); replicas],
+            in_flight: Vec::new(),
+            partition: None,
+        }
+    }
+
+    /// The current state of every replica.
+    #[inline]
+    pub fn replicas(&self) -> &[S] {
+        &self.replicas
+    }
+
+    /// The number of messages sent but neither delivered nor lost yet.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    fn is_reachable(&self, from: usize, to: usize) -> bool {
+        self.partition
+            .is_none_or(|mask| (mask >> from) & 1 == (mask >> to) & 1)
+    }
+
+    /// Applies a single action. Replica indices are taken modulo the number of replicas.
+    #[inline]
+    pub fn apply(&mut self, action: Action<S>) -> Result<()> {
+        let n = self.replicas.len();
+
+        match action {
+            Action::Write { replica, state } => self.replicas[replica % n].merge(&state)?,
+            Action::Send { from, to } => {
+                let (from, to) = (from % n, to % n);
+
+                if self.is_reachable(from, to) {
+                    self.in_flight.push(Message {
+                        from,
+                        to,
+                        state: self.replicas[from].clone(),
+                    });
+                }
+            }
+            Action::Deliver { message } if !self.in_flight.is_empty() => {
+                let message = self.in_flight.remove(message % self.in_flight.len());
+
+                if self.is_reachable(message.from, message.to) {
+                    self.replicas[message.to].merge(&message.state)?;
+                }
+            }
+            Action::Drop { message } if !self.in_flight.is_empty() => {
+                self.in_flight.remove(message % self.in_flight.len());
+            }
+            Action::Deliver { .. } | Action::Drop { .. } => {}
+            Action::Partition { mask } => self.partition = Some(mask),
+            Action::Heal => self.partition = None,
+        }
+
+        Ok(())
+    }
+
+    /// Applies every action of a schedule, in order.
+    #[inline]
+    pub fn run(&mut self, schedule: impl IntoIterator<Item = Action<S>>) -> Result<()> {
+        schedule
+            .into_iter()
+            .try_for_each(|action| self.apply(action))
+    }
+
+    /// Heals the network, delivers every message still in flight, and runs a round of
+    /// anti-entropy in which every replica exchanges its state with every other.
+    #[inline]
+    pub fn settle(&mut self) -> Result<()> {
+        self.partition = None;
+
+        while !self.in_flight.is_empty() {
+            self.apply(Action::Deliver { message: 0 })?;
+        }
+
+        let n = self.replicas.len();
+        for from in 1..n {
+            let state = self.replicas[from].clone();
+            self.replicas[0].merge(&state)?;
+        }
+
+        let state = self.replicas[0].clone();
+        for replica in self.replicas.iter_mut().skip(1) {
+            replica.merge(&state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every replica has the same state.
+    #[inline]
+    pub fn is_converged(&self) -> bool {
+        self.replicas.windows(2).all(|pair| pair[0] == pair[1])
+    }
+}
+
+/// Generates schedules of `len` actions for `replicas` replicas, with writes drawn from
+/// `writes`.
+#[inline]
+pub fn schedule<S: std::fmt::Debug + Clone>(
+    replicas: usize,
+    writes: impl Strategy<Value = S> + Clone + 'static,
+    len: usize,
+) -> impl Strategy<Value = Vec<Action<S>>> {
+    let action = prop_oneof![
+        3 => (0..replicas, writes).prop_map(|(replica, state)| Action::Write { replica, state }),
+        4 => (0..replicas, 0..replicas).prop_map(|(from, to)| Action::Send { from, to }),
+        4 => any::<usize>().prop_map(|message| Action::Deliver { message }),
+        1 => any::<usize>().prop_map(|message| Action::Drop { message }),
+        1 => any::<u64>().prop_map(|mask| Action::Partition { mask }),
+        1 => Just(Action::Heal),
+    ];
+
+    vec(action, 0..=len)
+}
+
+/// Tests that replicas of a state-based CRDT converge under unreliable networks.
+///
+/// This macro generates a property test that runs random schedules of writes, message loss,
+/// reordering and partitions through a [`Simulation`], settles it, and asserts every replica
+/// ends up in the same state.
+///
+/// Writes are drawn from `any::<Type>()` by default. Types whose default strategy only
+/// generates trivial states can provide their own.
+///
+/// # Example
+///
+/// ```rust
+/// use mutree::{prelude::*, test_convergence};
+///
+/// #[derive(Debug, Clone, PartialEq, Default, test_strategy::Arbitrary)]
+/// struct Counter(u64);
+///
+/// impl CvRDT for Counter {
+///     fn merge(&mut self, other: &Self) -> Result<(), Error> {
+///         self.0 = std::cmp::max(self.0, other.0);
+///         Ok(())
+///     }
+/// }
+///
+/// test_convergence!(Counter);
+///
+/// // Or, with small counters and eight replicas:
+/// // test_convergence!(Counter, (0..10u64).prop_map(Counter), replicas = 8);
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! test_convergence {
+    ($type:ty) => {
+        $crate::test_convergence!(
+            $type,
+            $crate::__dependencies::proptest::prelude::any::<$type>()
+        );
+    };
+    ($type:ty, $writes:expr) => {
+        $crate::test_convergence!($type, $writes, replicas = 4);
+    };
+    ($type:ty, $writes:expr, replicas = $replicas:expr) => {
+        $crate::__dependencies::paste::paste! {
+            mod [<test_convergence_$type:snake>] {
+                #[allow(unused_imports)]
+                use super::*;
+                #[allow(unused_imports)]
+                use $crate::{
+                    __dependencies::{proptest::prelude::*, test_strategy},
+                    testing::simulation::{schedule, Action, Simulation},
+                };
+
+                #[test_strategy::proptest(fork = false)]
+                fn test_eventual_convergence(
+                    #[strategy(schedule($replicas, $writes, 64))] schedule: Vec<Action<$type>>
+                ) {
+                    let mut simulation = Simulation::<$type>::new($replicas);
+                    simulation.run(schedule)?;
+                    simulation.settle()?;
+
+                    prop_assert!(
+                        simulation.is_converged(),
+                        "replicas did not converge: {:?}",
+                        simulation.replicas()
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use test_strategy::Arbitrary;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Default, Arbitrary)]
+    struct MaxCounter(u64);
+
+    impl CvRDT for MaxCounter {
+        fn merge(&mut self, other: &Self) -> Result<(), Error> {
+            self.0 = self.0.max(other.0);
+            Ok(())
+        }
+    }
+
+    /// Not a CRDT: merging the same state twice counts it twice.
+    #[derive(Debug, Clone, PartialEq, Default, Arbitrary)]
+    struct SumCounter(u64);
+
+    impl CvRDT for SumCounter {
+        fn merge(&mut self, other: &Self) -> Result<(), Error> {
+            self.0 = self.0.wrapping_add(other.0);
+            Ok(())
+        }
+    }
+
+    crate::test_convergence!(MaxCounter);
+
+    #[test]
+    fn test_partitions_block_messages() -> Result<()> {
+        let mut simulation = Simulation::<MaxCounter>::new(2);
+
+        simulation.run([
+            Action::Write {
+                replica: 0,
+                state: MaxCounter(1),
+            },
+            Action::Partition { mask: 0b01 },
+            Action::Send { from: 0, to: 1 },
+        ])?;
+        assert_eq!(simulation.in_flight(), 0);
+
+        simulation.run([
+            Action::Heal,
+            Action::Send { from: 0, to: 1 },
+            Action::Partition { mask: 0b10 },
+            Action::Deliver { message: 0 },
+        ])?;
+        assert_eq!(simulation.replicas()[1], MaxCounter(0));
+
+        simulation.settle()?;
+        assert!(simulation.is_converged());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_divergence() -> Result<()> {
+        let mut simulation = Simulation::<SumCounter>::new(3);
+
+        simulation.run([
+            Action::Write {
+                replica: 1,
+                state: SumCounter(1),
+            },
+            Action::Write {
+                replica: 2,
+                state: SumCounter(2),
+            },
+        ])?;
+        simulation.settle()?;
+
+        assert!(!simulation.is_converged());
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! Proptest strategies for the types of mutree, for downstream property tests.
+//!
+//! The `Arbitrary` implementations of [`Hash`], [`Step`] and [`Proof`] generate any value their
+//! encoding allows, so proofs drawn from them are mostly made of steps no Trie would hold. The
+//! strategies of this module instead build tries the way applications do, by inserting entries,
+//! so their proofs have the shape and size of real ones.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use blake2::Blake2s256;
+//! use mutree::{prelude::*, testing::strategies};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn test_roundtrip(trie in strategies::trie_with_entries::<Blake2s256>(8)) {
+//!         let decoded = Trie::<Blake2s256>::from_bytes(&trie.to_bytes())?;
+//!         prop_assert_eq!(decoded.root(), trie.root());
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+
+use proptest::{
+    collection::{btree_map, vec},
+    prelude::*,
+};
+
+use crate::{prelude::*, TrieHasher};
+
+/// Any hash.
+#[inline]
+pub fn hash() -> impl Strategy<Value = Hash> + Clone {
+    any::<Hash>()
+}
+
+/// Any step of a proof: branches, forks and leaves, with or without a timestamp.
+///
+/// Steps are only well-formed on their own; a proof of them is unlikely to hash to the root of
+/// any Trie. Use [`realistic_proof`] for those.
+#[inline]
+pub fn step() -> impl Strategy<Value = Step> + Clone {
+    any::<Step>()
+}
+
+/// Proofs of tries holding `entries` distinct keys, hashed with `D`.
+#[inline]
+pub fn realistic_proof<D: TrieHasher + 'static>(
+    entries: usize,
+) -> impl Strategy<Value = Proof> + Clone {
+    trie_with_entries::<D>(entries).prop_map(|trie| trie.into_proof())
+}
+
+/// Tries holding `n` distinct keys, of 1 to 16 bytes, with values of up to 32 bytes.
+#[inline]
+pub fn trie_with_entries<D: TrieHasher + 'static>(
+    n: usize,
+) -> impl Strategy<Value = Trie<D>> + Clone {
+    btree_map(vec(any::<u8>(), 1..=16), vec(any::<u8>(), 0..=32), n).prop_map(|entries| {
+        let mut trie = Trie::empty();
+        trie.extend(entries);
+        trie
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_tries_hold_their_entries(
+        #[strategy(0..16usize)] n: usize,
+        #[strategy(trie_with_entries::<Blake2s256>(#n))] trie: Trie<Blake2s256>,
+    ) {
+        let leaves = trie.proof().iter().filter(|step| step.is_leaf()).count();
+        prop_assert_eq!(leaves, n);
+    }
+
+    #[proptest]
+    fn test_proofs_are_sound(#[strategy(realistic_proof::<Blake2s256>(8))] proof: Proof) {
+        let trie = Trie::<Blake2s256>::from_proof(proof.clone());
+        let report = proof.check_soundness::<Blake2s256>(trie.root());
+        prop_assert!(report.is_sound(), "{}", report);
+    }
+
+    #[proptest]
+    fn test_steps_roundtrip(#[strategy(step())] step: Step, #[strategy(hash())] hash: Hash) {
+        prop_assert_eq!(Step::from_bytes(&step.to_bytes())?, step);
+        prop_assert_eq!(Hash::from_bytes(&hash.to_bytes())?, hash);
+    }
+}
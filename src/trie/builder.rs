@@ -0,0 +1,300 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+
+use crate::{prelude::*, TrieHasher};
+
+/// Configures a [`Trie`] option by option, instead of through a constructor for every
+/// combination of them.
+///
+/// The builder starts without a digest, which is chosen with [`TrieBuilder::digest`], unless it
+/// was created by [`Trie::builder`]. Options left unset keep the defaults of [`Trie::empty`].
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = TrieBuilder::new()
+///         .digest::<Blake2s256>()
+///         .root_format(RootFormat::V2)
+///         .limits(EntryLimits {
+///             max_key_len: 8,
+///             max_value_len: 64,
+///         })
+///         .build()?;
+///
+///     trie.insert(b"key", &b"value"[..])?;
+///     assert_eq!(trie.root_format(), RootFormat::V2);
+///     assert!(trie.insert(b"a long key", &b"value"[..]).is_err());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrieBuilder<D = ()> {
+    proof: Option<Proof>,
+    capacity: usize,
+    duplicates: Option<DuplicateLeaves>,
+    root_format: RootFormat,
+    entry_limits: EntryLimits,
+    memory_budget: Option<usize>,
+    value_hasher: Option<Arc<dyn ValueHasher>>,
+    profile: Profile,
+    _phantom: PhantomData<D>,
+}
+
+impl TrieBuilder {
+    /// Starts configuring a Trie, with every option at its default.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            proof: None,
+            capacity: 0,
+            duplicates: None,
+            root_format: RootFormat::default(),
+            entry_limits: EntryLimits::default(),
+            memory_budget: None,
+            value_hasher: None,
+            profile: Profile::default(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Default for TrieBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> TrieBuilder<D> {
+    /// Sets the digest the Trie hashes its keys, values and steps with.
+    #[inline]
+    pub fn digest<E: TrieHasher>(self) -> TrieBuilder<E> {
+        TrieBuilder {
+            proof: self.proof,
+            capacity: self.capacity,
+            duplicates: self.duplicates,
+            root_format: self.root_format,
+            entry_limits: self.entry_limits,
+            memory_budget: self.memory_budget,
+            value_hasher: self.value_hasher,
+            profile: self.profile,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Builds the Trie from an existing proof, like [`Trie::from_proof`], instead of empty.
+    #[inline]
+    pub fn proof(mut self, proof: Proof) -> Self {
+        self.proof = Some(proof);
+        self
+    }
+
+    /// Reserves room for at least `capacity` steps in the proof, like [`Trie::with_capacity`].
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets what is done with leaves of the proof that share a key, like
+    /// [`Trie::from_proof_with`]. Proofs are taken as they are by default.
+    #[inline]
+    pub fn duplicates(mut self, duplicates: DuplicateLeaves) -> Self {
+        self.duplicates = Some(duplicates);
+        self
+    }
+
+    /// Sets the format in which the steps are hashed into the root, like
+    /// [`Trie::set_root_format`].
+    #[inline]
+    pub fn root_format(mut self, format: RootFormat) -> Self {
+        self.root_format = format;
+        self
+    }
+
+    /// Limits the length of the keys and values accepted by inserts, like
+    /// [`Trie::set_entry_limits`].
+    #[inline]
+    pub fn limits(mut self, limits: EntryLimits) -> Self {
+        self.entry_limits = limits;
+        self
+    }
+
+    /// Limits the bytes the Trie may hold, like [`Trie::set_memory_budget`].
+    #[inline]
+    pub fn memory_budget(mut self, budget: usize) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Sets how values are hashed, like [`Trie::set_value_hasher`].
+    #[inline]
+    pub fn value_hasher(mut self, hasher: impl ValueHasher + 'static) -> Self {
+        self.value_hasher = Some(Arc::new(hasher));
+        self
+    }
+
+    /// Sets how strictly the Trie verifies proofs and checks merged states, like
+    /// [`Trie::set_profile`].
+    #[inline]
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+}
+
+impl<D: TrieHasher + 'static> TrieBuilder<D> {
+    /// Builds the configured Trie, hashing its root once, in its final format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] if the proof holds several leaves for a key and
+    /// duplicates are [`DuplicateLeaves::Reject`]ed, and [`Error::MemoryBudgetExceeded`] if
+    /// the Trie already holds more than its memory budget.
+    #[inline]
+    pub fn build(self) -> Result<Trie<D>> {
+        let mut trie = match self.proof {
+            Some(mut proof) => {
+                if let Some(duplicates) = self.duplicates {
+                    Trie::<D>::handle_duplicate_leaves(&mut proof, duplicates)?;
+                }
+                proof.reserve(self.capacity.saturating_sub(proof.len()));
+
+                let mut trie = Trie::from_parts(proof, Hash::zero());
+                trie.root_format = self.root_format;
+                trie.refresh_root();
+                trie
+            }
+            None => {
+                let mut trie = Trie::with_capacity(self.capacity);
+                trie.root_format = self.root_format;
+                trie
+            }
+        };
+        trie.entry_limits = self.entry_limits;
+        trie.memory_budget = self.memory_budget;
+        trie.value_hasher = self.value_hasher;
+        trie.profile = self.profile;
+        trie.check_memory_budget(|| trie.memory_usage().bytes)?;
+
+        Ok(trie)
+    }
+}
+
+impl<D: TrieHasher + 'static> Trie<D> {
+    /// Starts configuring a Trie hashed with `D`, option by option.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let copy = Trie::<Blake2s256>::builder()
+    ///         .proof(trie.proof().clone())
+    ///         .duplicates(DuplicateLeaves::Reject)
+    ///         .build()?;
+    ///     assert_eq!(copy.root(), trie.root());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn builder() -> TrieBuilder<D> {
+        TrieBuilder::new().digest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_matches_constructors(trie: Trie<Blake2s256>, #[strategy(0..64usize)] capacity: usize) {
+        let built = Trie::<Blake2s256>::builder()
+            .proof(trie.proof.clone())
+            .capacity(capacity)
+            .build()?;
+        prop_assert_eq!(&built, &Trie::from_proof(trie.proof.clone()));
+        prop_assert!(built.proof.capacity() >= capacity);
+
+        let built = Trie::<Blake2s256>::builder()
+            .proof(trie.proof.clone())
+            .duplicates(DuplicateLeaves::Resolve)
+            .build()?;
+        prop_assert_eq!(
+            built,
+            Trie::from_proof_with(trie.proof.clone(), DuplicateLeaves::Resolve)?
+        );
+
+        let built = Trie::<Blake2s256>::builder().capacity(capacity).build()?;
+        prop_assert_eq!(&built, &Trie::empty());
+        prop_assert!(built.proof.capacity() >= capacity);
+    }
+
+    #[proptest]
+    fn test_applies_every_option(trie: Trie<Blake2s256>) {
+        let limits = EntryLimits {
+            max_key_len: 4,
+            max_value_len: 4,
+        };
+        let built = TrieBuilder::new()
+            .root_format(RootFormat::V2)
+            .limits(limits)
+            .memory_budget(usize::MAX)
+            .value_hasher(LengthPrefixed)
+            .profile(Profile::Paranoid)
+            .proof(trie.proof.clone())
+            .digest::<Blake2s256>()
+            .build()?;
+
+        let mut expected = Trie::<Blake2s256>::from_proof(trie.proof.clone());
+        expected.set_root_format(RootFormat::V2);
+        prop_assert_eq!(built.root, expected.root);
+        prop_assert_eq!(built.root_format(), RootFormat::V2);
+        prop_assert_eq!(built.entry_limits(), limits);
+        prop_assert_eq!(built.memory_budget(), Some(usize::MAX));
+        prop_assert!(built.value_hasher().is_some());
+        prop_assert_eq!(built.profile(), Profile::Paranoid);
+    }
+
+    #[test]
+    fn test_rejects_invalid_configurations() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"key", &b"1"[..])?;
+        let mut other = Trie::<Blake2s256>::empty();
+        other.insert(b"key", &b"2"[..])?;
+        let mut proof = trie.proof.clone();
+        proof.push(other.proof[0].clone());
+
+        assert!(matches!(
+            Trie::<Blake2s256>::builder()
+                .proof(proof)
+                .duplicates(DuplicateLeaves::Reject)
+                .build(),
+            Err(Error::InvalidProof(_))
+        ));
+        assert!(matches!(
+            Trie::<Blake2s256>::builder()
+                .proof(trie.proof.clone())
+                .memory_budget(0)
+                .build(),
+            Err(Error::MemoryBudgetExceeded { .. })
+        ));
+
+        Ok(())
+    }
+}
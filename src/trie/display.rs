@@ -0,0 +1,217 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::{prelude::*, TrieHasher};
+
+/// The number of nibbles in a key hash.
+const KEY_NIBBLES: usize = 64;
+
+fn nibble(key: &Hash, at: usize) -> u8 {
+    let byte = key[at / 2];
+    if at % 2 == 0 {
+        byte >> 4
+    } else {
+        byte & 0xf
+    }
+}
+
+/// Writes the leaves sharing the first `depth` nibbles of their key, sorted by key, as the
+/// children of a node indented by `indent` levels.
+///
+/// Each child is labelled with the nibbles its leaves share after `depth`, so runs of nibbles
+/// without a fork are shown on a single line.
+fn write_leaves(
+    f: &mut fmt::Formatter<'_>,
+    leaves: &[&Step],
+    depth: usize,
+    indent: usize,
+) -> fmt::Result {
+    let key = |step: &Step| match step {
+        Step::Leaf { key, .. } => *key,
+        _ => Hash::zero(),
+    };
+
+    // Leaves for the same key, as merges can leave, can't be told apart by their path
+    if depth == KEY_NIBBLES {
+        return leaves
+            .iter()
+            .try_for_each(|leaf| write_leaf(f, leaf, indent, ""));
+    }
+
+    for group in leaves.chunk_by(|a, b| nibble(&key(a), depth) == nibble(&key(b), depth)) {
+        let (first, last) = (key(group[0]), key(group[group.len() - 1]));
+        let shared = (depth..KEY_NIBBLES)
+            .take_while(|at| nibble(&first, *at) == nibble(&last, *at))
+            .count();
+        let label: String = (depth..depth + shared.max(1))
+            .map(|at| char::from_digit(nibble(&first, at).into(), 16).unwrap_or('?'))
+            .collect();
+
+        if group.len() == 1 {
+            write_leaf(f, group[0], indent, &label[..1])?;
+        } else {
+            writeln!(f, "{:indent$}{label}", "", indent = indent * 2)?;
+            write_leaves(f, group, depth + shared, indent + 1)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_leaf(f: &mut fmt::Formatter<'_>, leaf: &Step, indent: usize, label: &str) -> fmt::Result {
+    let Step::Leaf {
+        key,
+        value,
+        timestamp,
+        ..
+    } = leaf
+    else {
+        return Ok(());
+    };
+
+    write!(
+        f,
+        "{:indent$}{label}{}leaf {} = {}",
+        "",
+        if label.is_empty() { "" } else { " " },
+        key.short(),
+        value.short(),
+        indent = indent * 2
+    )?;
+    if let Some(timestamp) = timestamp {
+        write!(f, " at {}.{}", timestamp.wall, timestamp.logical)?;
+    }
+    writeln!(f)
+}
+
+impl<D: TrieHasher> fmt::Display for Trie<D> {
+    /// Renders the Trie as a tree of its leaves, indented by the nibbles of their key hashes.
+    ///
+    /// Hashes are truncated to their first bytes, and the branches and forks of proofs built
+    /// elsewhere, which only commit to hashes, are listed after the leaves. The output is meant
+    /// for people and may change between versions; use [`ToBytes`] or [`WireFormat`] to store
+    /// or exchange a Trie.
+    ///
+    /// [`WireFormat`]: crate::wire::WireFormat
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"a", &b"1"[..])?;
+    ///     trie.insert(b"b", &b"2"[..])?;
+    ///
+    ///     let tree = trie.to_string();
+    ///     assert!(tree.starts_with("root "));
+    ///     assert_eq!(tree.matches("leaf").count(), 2);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "root {}", self.root.short())?;
+
+        let (mut leaves, others): (Vec<&Step>, Vec<&Step>) = self
+            .proof
+            .canonical_steps()
+            .into_iter()
+            .partition(|step| step.is_leaf());
+        leaves.sort_by_key(|step| match step {
+            Step::Leaf { key, .. } => *key,
+            _ => Hash::zero(),
+        });
+        write_leaves(f, &leaves, 0, 1)?;
+
+        for step in others {
+            match step {
+                Step::Branch { skip, neighbors } => {
+                    write!(f, "  branch skip {skip}:")?;
+                    for neighbor in neighbors {
+                        write!(f, " {}", neighbor.short())?;
+                    }
+                    writeln!(f)?;
+                }
+                Step::Fork { skip, neighbor } => writeln!(
+                    f,
+                    "  fork skip {skip}: nibble {:x}, prefix {}, root {}",
+                    neighbor.nibble,
+                    hex::encode(&neighbor.prefix),
+                    neighbor.root.short()
+                )?,
+                Step::Leaf { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::{
+        collection::{btree_map, vec},
+        prelude::*,
+    };
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_shows_every_step_once(
+        #[strategy(btree_map(vec(any::<u8>(), 1..8), vec(any::<u8>(), 1..8), 0..32))]
+        entries: alloc::collections::BTreeMap<Vec<u8>, Vec<u8>>,
+    ) {
+        let mut trie = Trie::<Blake2s256>::empty();
+        for (key, value) in &entries {
+            trie.insert(key, &value[..])?;
+        }
+        let tree = trie.to_string();
+
+        for step in trie.proof.iter() {
+            if let Step::Leaf { key, value, .. } = step {
+                let line = format!("leaf {} = {}", key.short(), value.short());
+                prop_assert_eq!(tree.matches(&line[..]).count(), 1);
+            }
+        }
+        for line in tree.lines().skip(1) {
+            let line = line.trim_start();
+            prop_assert!(
+                line.contains("leaf ") || line.chars().all(|c| c.is_ascii_hexdigit()),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_renders_an_indented_tree() -> Result<()> {
+        let key = |hex: &str| Hash::from_hex(hex);
+        let leaf = |key: Hash| Step::Leaf {
+            skip: 0,
+            key,
+            value: Hash::zero(),
+            timestamp: None,
+        };
+        let a = key(&format!("a1{}", "0".repeat(62)))?;
+        let b = key(&format!("a1f{}", "0".repeat(61)))?;
+        let c = key(&format!("3{}", "0".repeat(63)))?;
+
+        let trie = Trie::<Blake2s256>::from_proof(Proof::from(vec![leaf(a), leaf(b), leaf(c)]));
+
+        assert_eq!(
+            trie.to_string(),
+            format!(
+                "root {}\n  3 leaf 30000000… = 00000000…\n  a1\n    0 leaf a1000000… = 00000000…\n    f leaf a1f00000… = 00000000…\n",
+                trie.root.short()
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use alloc::{collections::BTreeSet, format, vec::Vec};
+#[cfg(feature = "std")]
+use std::{collections::HashMap, hash::Hash as StdHash};
+
+use crate::{instrument::operation, prelude::*, TrieHasher};
+
+/// A point in the history of a [`Trie`], which replicas can agree on and prune up to.
+///
+/// An epoch covers the set of steps of the proof at the time it was taken, and records the root
+/// they commit to. Roots hash the steps sorted, so replicas holding the same steps in different
+/// orders, after merging in different orders, cover the same history. Once every peer has
+/// acknowledged the epoch, the covered steps can be replaced by a single summary step with
+/// [`Trie::compact`], bounding the memory used by long-lived replicas.
+///
+/// The covered steps are shared with the proof they were taken from, so taking an epoch doesn't
+/// copy them, but the Trie copies its proof on its next change while the epoch is alive.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"a", &b"1"[..])?;
+///     trie.insert(b"b", &b"2"[..])?;
+///
+///     let epoch = trie.epoch(1);
+///     let mut acks = EpochAcks::new(["alice", "bob"]);
+///     acks.acknowledge(&"alice", 1);
+///     acks.acknowledge(&"bob", 1);
+///
+///     if acks.settled() >= Some(epoch.number) {
+///         trie.compact(&epoch)?;
+///     }
+///
+///     assert_eq!(trie.proof().len(), 1);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Epoch {
+    /// The sequence number of the epoch, chosen by the application
+    pub number: u64,
+    /// The root committed to by the covered steps
+    pub root: Hash,
+    /// The steps covered by the epoch
+    steps: Proof,
+}
+
+impl Epoch {
+    /// The step that replaces the covered history once the epoch is compacted.
+    ///
+    /// It is encoded as a Fork step with an empty prefix, whose neighbor root is the root of the
+    /// compacted history. Fork steps are never merged by path compression, so the summary keeps
+    /// its position at the start of the proof.
+    #[inline]
+    pub fn summary(&self) -> Step {
+        Step::Fork {
+            skip: 0,
+            neighbor: Neighbor {
+                nibble: 0,
+                prefix: Vec::new(),
+                root: self.root,
+            },
+        }
+    }
+
+    /// The steps covered by the epoch, in the order of the proof they were taken from.
+    #[inline]
+    pub fn steps(&self) -> &Proof {
+        &self.steps
+    }
+}
+
+impl<D: TrieHasher + 'static> Trie<D> {
+    /// Takes an epoch covering the whole current proof.
+    #[inline]
+    pub fn epoch(&self, number: u64) -> Epoch {
+        Epoch {
+            number,
+            root: self.root,
+            steps: self.proof.clone(),
+        }
+    }
+
+    /// Replaces the steps covered by `epoch` with a single summary step.
+    ///
+    /// Steps are matched by value, wherever they sit in the proof. Covered steps removed since
+    /// the epoch was taken, such as leaves overwritten by newer writes of their key, or branches
+    /// merged by path compression, are already gone, so every replica holding the same steps
+    /// compacts them alike. Epochs covering no steps leave the Trie unchanged.
+    ///
+    /// Leaves in the compacted history can't be verified anymore, and the root changes to commit
+    /// to the summary instead. Replicas must only compact an epoch acknowledged by all of their
+    /// peers, so they all compact the same history and keep converging.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidState`] if the covered steps don't hash to the root of the epoch,
+    /// or if the proof holds no leaf for a key written in the epoch, having never seen it.
+    #[inline]
+    pub fn compact(&mut self, epoch: &Epoch) -> Result<()> {
+        let mut operation = operation!("compact", epoch = epoch.number);
+
+        if epoch.steps.is_empty() {
+            operation.succeed(self.proof.len());
+            return Ok(());
+        }
+        if Self::calculate_root(&epoch.steps, self.root_format) != epoch.root {
+            return Err(Error::InvalidState(format!(
+                "epoch {} doesn't commit to its steps",
+                epoch.number
+            )));
+        }
+
+        let keys: BTreeSet<&Hash> = self
+            .proof
+            .iter()
+            .filter_map(|step| match step {
+                Step::Leaf { key, .. } => Some(key),
+                _ => None,
+            })
+            .collect();
+        let unseen = epoch
+            .steps
+            .iter()
+            .any(|step| matches!(step, Step::Leaf { key, .. } if !keys.contains(key)));
+        if unseen {
+            return Err(Error::InvalidState(format!(
+                "proof history diverges from epoch {}",
+                epoch.number
+            )));
+        }
+
+        let covered: BTreeSet<&Step> = epoch.steps.iter().collect();
+        let mut steps = Vec::with_capacity(self.proof.len() + 1);
+        steps.push(epoch.summary());
+        steps.extend(
+            self.proof
+                .iter()
+                .filter(|step| !covered.contains(step))
+                .cloned(),
+        );
+
+        self.proof = Proof::from(steps);
+        self.refresh_root();
+
+        operation.succeed(self.proof.len());
+
+        Ok(())
+    }
+}
+
+/// Tracks which epochs have been acknowledged by each peer.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochAcks<P: Eq + StdHash> {
+    acks: HashMap<P, Option<u64>>,
+}
+
+#[cfg(feature = "std")]
+impl<P: Eq + StdHash> EpochAcks<P> {
+    /// Starts tracking the given peers, none of which have acknowledged any epoch yet.
+    #[inline]
+    pub fn new(peers: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            acks: peers.into_iter().map(|peer| (peer, None)).collect(),
+        }
+    }
+
+    /// Records that `peer` acknowledged every epoch up to `number`.
+    ///
+    /// Returns false if the peer is not tracked. Acknowledgements never move backwards.
+    #[inline]
+    pub fn acknowledge(&mut self, peer: &P, number: u64) -> bool {
+        match self.acks.get_mut(peer) {
+            Some(ack) => {
+                *ack = (*ack).max(Some(number));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The latest epoch acknowledged by every peer, if any.
+    #[inline]
+    pub fn settled(&self) -> Option<u64> {
+        self.acks.values().copied().min().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+
+    use super::*;
+
+    type TestTrie = Trie<Blake2s256>;
+
+    #[test]
+    fn test_compact_keeps_later_history() -> Result<()> {
+        let mut trie = TestTrie::empty();
+        trie.insert(b"a", &b"1"[..])?;
+        let epoch = trie.epoch(1);
+        trie.insert(b"b", &b"2"[..])?;
+
+        trie.compact(&epoch)?;
+
+        assert_eq!(trie.proof.len(), 2);
+        assert_eq!(trie.proof[0], epoch.summary());
+        assert!(!trie.verify(b"a", b"1"));
+        assert!(trie.verify(b"b", b"2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicas_compacting_the_same_epoch_converge() -> Result<()> {
+        let mut a = TestTrie::empty();
+        a.insert(b"a", &b"1"[..])?;
+        let mut b = a.clone();
+        let epoch = a.epoch(1);
+
+        a.compact(&epoch)?;
+        b.compact(&epoch)?;
+
+        assert_eq!(a, b);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicas_merging_in_opposite_orders_compact_alike() -> Result<()> {
+        let (mut a, mut b) = (TestTrie::empty(), TestTrie::empty());
+        a.insert(b"a", &b"1"[..])?;
+        b.insert(b"b", &b"2"[..])?;
+        let (a_only, b_only) = (a.clone(), b.clone());
+
+        a.merge(&b_only)?;
+        b.merge(&a_only)?;
+        assert_eq!(a.root(), b.root());
+        assert_ne!(a.proof[..], b.proof[..]);
+
+        let epoch = a.epoch(1);
+        for trie in [&mut a, &mut b] {
+            trie.insert(b"c", &b"3"[..])?;
+            trie.compact(&epoch)?;
+        }
+
+        assert_eq!(a.root(), b.root());
+        assert_eq!(a.proof.len(), 2);
+        assert!(a.verify(b"c", b"3") && b.verify(b"c", b"3"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_skips_overwritten_steps() -> Result<()> {
+        let mut trie = TestTrie::empty();
+        trie.insert(b"a", &b"1"[..])?;
+        trie.insert(b"b", &b"2"[..])?;
+        let epoch = trie.epoch(1);
+
+        trie.insert(b"a", &b"3"[..])?;
+        trie.compact(&epoch)?;
+
+        assert_eq!(trie.proof[0], epoch.summary());
+        assert_eq!(trie.proof.len(), 2);
+        assert!(trie.verify(b"a", b"3"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_rejects_diverging_history() -> Result<()> {
+        let mut a = TestTrie::empty();
+        a.insert(b"a", &b"1"[..])?;
+        let mut b = TestTrie::empty();
+        b.insert(b"b", &b"2"[..])?;
+
+        assert!(matches!(
+            b.compact(&a.epoch(1)),
+            Err(Error::InvalidState(_))
+        ));
+
+        let mut long = a.clone();
+        long.insert(b"c", &b"3"[..])?;
+        assert!(matches!(
+            a.compact(&long.epoch(1)),
+            Err(Error::InvalidState(_))
+        ));
+
+        let mut forged = a.epoch(1);
+        forged.root = Hash::zero();
+        assert!(matches!(a.compact(&forged), Err(Error::InvalidState(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_settled_requires_every_peer() {
+        let mut acks = EpochAcks::new([1, 2]);
+        assert_eq!(acks.settled(), None);
+
+        assert!(acks.acknowledge(&1, 3));
+        assert_eq!(acks.settled(), None);
+
+        assert!(acks.acknowledge(&2, 2));
+        assert_eq!(acks.settled(), Some(2));
+
+        assert!(acks.acknowledge(&2, 1));
+        assert_eq!(acks.settled(), Some(2));
+
+        assert!(!acks.acknowledge(&3, 5));
+    }
+}
@@ -0,0 +1,39 @@
+/// The format in which the steps of a proof are hashed into its root.
+///
+/// Roots of different formats never match, so replicas must agree on the format, like they
+/// agree on the digest. It is not part of the encoding of a Trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RootFormat {
+    /// The original format, where leaves start with `0x00`, forks with `0xff`, and branches
+    /// with the number of their non-empty neighbors.
+    ///
+    /// These markers overlap: a branch without neighbors starts like a leaf, and the
+    /// variable-length prefix of a fork can hold the bytes of the steps following it, so
+    /// different proofs can hash to the same root.
+    #[default]
+    V1,
+    /// Every step starts with a prefix naming its kind, followed by its skip as a big-endian
+    /// `u64`, and fields of variable length are preceded by their length, in the spirit of the
+    /// leaf and node prefixes of RFC 6962:
+    ///
+    /// - Leaves: [`RootFormat::LEAF_PREFIX`], the skip, the key, the value, then `0x00`, or `0x01`
+    ///   and the timestamp.
+    /// - Branches: [`RootFormat::BRANCH_PREFIX`], the skip, a byte with a bit set for each
+    ///   non-empty neighbor, then those neighbors.
+    /// - Forks: [`RootFormat::FORK_PREFIX`], the skip, the nibble, the length of the prefix as a
+    ///   big-endian `u32`, the prefix, then the root.
+    ///
+    /// The bytes of a proof can then only be split into steps in one way.
+    V2,
+}
+
+impl RootFormat {
+    /// The first byte of the leaves hashed with [`RootFormat::V2`].
+    pub const LEAF_PREFIX: u8 = 0x00;
+
+    /// The first byte of the branches hashed with [`RootFormat::V2`].
+    pub const BRANCH_PREFIX: u8 = 0x01;
+
+    /// The first byte of the forks hashed with [`RootFormat::V2`].
+    pub const FORK_PREFIX: u8 = 0x02;
+}
@@ -0,0 +1,293 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::prelude::*;
+
+/// The position of a hash in a [`HashPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HashRef(u32);
+
+/// A table of distinct hashes, each stored once.
+#[derive(Debug, Clone, Default)]
+struct HashPool {
+    hashes: Vec<Hash>,
+    index: BTreeMap<Hash, HashRef>,
+}
+
+impl HashPool {
+    /// Returns the reference to `hash`, adding it to the pool if it's not there yet.
+    fn intern(&mut self, hash: Hash) -> HashRef {
+        if let Some(reference) = self.index.get(&hash) {
+            return *reference;
+        }
+
+        // Proofs holding 2^32 distinct hashes take 128 GiB, so they can't be decoded to reach it
+        #[allow(clippy::expect_used)]
+        let reference = HashRef(
+            u32::try_from(self.hashes.len()).expect("more than 2^32 distinct hashes in a proof"),
+        );
+        self.hashes.push(hash);
+        self.index.insert(hash, reference);
+        reference
+    }
+
+    fn get(&self, reference: HashRef) -> Hash {
+        self.hashes[reference.0 as usize]
+    }
+}
+
+/// A [`Step`] whose hashes are references into the pool of its proof.
+#[derive(Debug, Clone)]
+enum InternedStep {
+    Branch {
+        skip: usize,
+        neighbors: [HashRef; 4],
+    },
+    Fork {
+        skip: usize,
+        nibble: u8,
+        prefix: Vec<u8>,
+        root: HashRef,
+    },
+    Leaf {
+        skip: usize,
+        key: HashRef,
+        value: HashRef,
+        timestamp: Option<Timestamp>,
+    },
+}
+
+/// A [`Proof`] storing each distinct hash once, for replicas holding millions of steps.
+///
+/// Large merged proofs repeat the same hashes many times, the zero hash of empty neighbors
+/// above all. Here, steps refer to their hashes by a 4-byte index into a pool shared by the
+/// whole proof, so a branch step takes 16 bytes of references instead of 128 bytes of hashes.
+/// The pool also keeps an index of its hashes to deduplicate new steps, so interning pays off
+/// when most hashes are repeated.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"key", &b"value"[..])?;
+///
+///     let interned = InternedProof::from(trie.proof());
+///     assert_eq!(interned.len(), trie.proof().len());
+///     assert_eq!(interned.to_proof(), *trie.proof());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InternedProof {
+    pool: HashPool,
+    steps: Vec<InternedStep>,
+}
+
+impl InternedProof {
+    /// Creates an empty proof.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of steps in the proof.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Checks if the proof has no steps.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// The number of distinct hashes stored in the pool.
+    #[inline]
+    pub fn unique_hashes(&self) -> usize {
+        self.pool.hashes.len()
+    }
+
+    /// Appends a step, interning its hashes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the proof would hold more than 2^32 distinct hashes.
+    #[inline]
+    pub fn push(&mut self, step: &Step) {
+        let pool = &mut self.pool;
+        let step = match step {
+            Step::Branch { skip, neighbors } => InternedStep::Branch {
+                skip: *skip,
+                neighbors: neighbors.map(|neighbor| pool.intern(neighbor)),
+            },
+            Step::Fork { skip, neighbor } => InternedStep::Fork {
+                skip: *skip,
+                nibble: neighbor.nibble,
+                prefix: neighbor.prefix.clone(),
+                root: pool.intern(neighbor.root),
+            },
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => InternedStep::Leaf {
+                skip: *skip,
+                key: pool.intern(*key),
+                value: pool.intern(*value),
+                timestamp: *timestamp,
+            },
+        };
+
+        self.steps.push(step);
+    }
+
+    /// Returns the step at `index`, with its hashes resolved.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Step> {
+        self.steps.get(index).map(|step| self.resolve(step))
+    }
+
+    /// Iterates over the steps, with their hashes resolved.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Step> + '_ {
+        self.steps.iter().map(|step| self.resolve(step))
+    }
+
+    /// Resolves every step back into a [`Proof`].
+    #[inline]
+    pub fn to_proof(&self) -> Proof {
+        Proof::from(self.iter().collect::<Vec<_>>())
+    }
+
+    fn resolve(&self, step: &InternedStep) -> Step {
+        match step {
+            InternedStep::Branch { skip, neighbors } => Step::Branch {
+                skip: *skip,
+                neighbors: neighbors.map(|neighbor| self.pool.get(neighbor)),
+            },
+            InternedStep::Fork {
+                skip,
+                nibble,
+                prefix,
+                root,
+            } => Step::Fork {
+                skip: *skip,
+                neighbor: Neighbor {
+                    nibble: *nibble,
+                    prefix: prefix.clone(),
+                    root: self.pool.get(*root),
+                },
+            },
+            InternedStep::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => Step::Leaf {
+                skip: *skip,
+                key: self.pool.get(*key),
+                value: self.pool.get(*value),
+                timestamp: *timestamp,
+            },
+        }
+    }
+}
+
+impl From<&Proof> for InternedProof {
+    #[inline]
+    fn from(proof: &Proof) -> Self {
+        let mut interned = Self::new();
+        interned.extend(proof.iter());
+        interned
+    }
+}
+
+impl From<&InternedProof> for Proof {
+    #[inline]
+    fn from(proof: &InternedProof) -> Self {
+        proof.to_proof()
+    }
+}
+
+impl<'a> Extend<&'a Step> for InternedProof {
+    #[inline]
+    fn extend<I: IntoIterator<Item = &'a Step>>(&mut self, steps: I) {
+        for step in steps {
+            self.push(step);
+        }
+    }
+}
+
+impl PartialEq for InternedProof {
+    /// Proofs are equal if they have the same steps, no matter the order of their pools.
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for InternedProof {}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_roundtrip(#[any(16usize)] proof: Proof) {
+        let interned = InternedProof::from(&proof);
+
+        prop_assert_eq!(interned.len(), proof.len());
+        prop_assert_eq!(interned.to_proof(), proof.clone());
+        prop_assert_eq!(interned.get(proof.len()), None);
+        for (index, step) in proof.iter().enumerate() {
+            prop_assert_eq!(interned.get(index), Some(step.clone()));
+        }
+    }
+
+    #[proptest]
+    fn test_equality_ignores_pool_order(#[any(8usize)] a: Proof, #[any(8usize)] b: Proof) {
+        let mut ab = InternedProof::from(&a);
+        ab.extend(b.iter());
+
+        let mut pooled = InternedProof::from(&b);
+        let mut expected = InternedProof::new();
+        expected.extend(a.iter().chain(b.iter()));
+        pooled.extend(a.iter());
+
+        prop_assert_eq!(&ab, &expected);
+        prop_assert_eq!(
+            ab == pooled,
+            [a.steps(), b.steps()].concat() == [b.steps(), a.steps()].concat()
+        );
+    }
+
+    #[test]
+    fn test_deduplicates_hashes() {
+        let proof = Proof::from(
+            (0..1000)
+                .map(|skip| Step::Branch {
+                    skip,
+                    neighbors: [
+                        Hash::zero(),
+                        Hash::zero(),
+                        Hash::zero(),
+                        Hash::from_array([1; 32]),
+                    ],
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let interned = InternedProof::from(&proof);
+        assert_eq!(interned.unique_hashes(), 2);
+        assert_eq!(interned.to_proof(), proof);
+    }
+}
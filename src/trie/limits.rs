@@ -0,0 +1,64 @@
+use alloc::format;
+
+use crate::prelude::*;
+
+/// The longest keys and values a [`Trie`] accepts, set with [`Trie::set_entry_limits`].
+///
+/// Keys and values are hashed before they are stored, so their length doesn't change the size
+/// of the proof, but hashing them still takes time proportional to it. The limits let replicas
+/// refuse a single oversized insert instead of stalling while hashing it. Values streamed from
+/// a reader are refused as soon as they exceed the limit, without reading them to the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryLimits {
+    /// The length of the longest key accepted, in bytes
+    pub max_key_len: usize,
+    /// The length of the longest value accepted, in bytes
+    pub max_value_len: u64,
+}
+
+impl EntryLimits {
+    /// The longest key accepted by default, 64 KiB.
+    pub const DEFAULT_MAX_KEY_LEN: usize = 64 * 1024;
+
+    /// The longest value accepted by default, 16 GiB.
+    pub const DEFAULT_MAX_VALUE_LEN: u64 = 16 * 1024 * 1024 * 1024;
+
+    /// Checks that `key` is neither empty nor longer than the limit.
+    pub(crate) fn check_key(&self, key: &[u8]) -> Result<()> {
+        if key.is_empty() {
+            return Err(Error::EmptyKeyOrValue);
+        }
+        if key.len() > self.max_key_len {
+            return Err(Error::InvalidOperation(format!(
+                "key of {} bytes exceeds the maximum of {}",
+                key.len(),
+                self.max_key_len
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a value of `len` bytes, or of which `len` bytes were read so far, is not
+    /// longer than the limit.
+    pub(crate) fn check_value_len(&self, len: u64) -> Result<()> {
+        if len > self.max_value_len {
+            return Err(Error::InvalidOperation(format!(
+                "value of at least {len} bytes exceeds the maximum of {}",
+                self.max_value_len
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for EntryLimits {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_key_len: Self::DEFAULT_MAX_KEY_LEN,
+            max_value_len: Self::DEFAULT_MAX_VALUE_LEN,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use alloc::{collections::BTreeSet, sync::Arc};
+use core::mem::size_of;
+
+use crate::prelude::*;
+
+/// The number of entries held by each node of a `BTreeSet`.
+const BTREE_NODE_ENTRIES: usize = 11;
+
+/// An estimate of the memory held by a [`Trie`], as returned by [`Trie::memory_usage`].
+///
+/// Sizes are taken from the capacity of the buffers and the layout of the types, without
+/// counting the bookkeeping of the allocator, so the real usage is somewhat higher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// The number of steps in the proof.
+    pub steps: usize,
+    /// The bytes held by the Trie, including the Trie itself.
+    pub bytes: usize,
+    /// The number of heap allocations holding those bytes.
+    pub allocations: usize,
+}
+
+impl MemoryUsage {
+    /// Estimates the memory held by a Trie of `size` bytes, with `proof`, `applied` and a
+    /// scratch buffer of `scratch` bytes.
+    pub(crate) fn estimate(
+        size: usize,
+        proof: &Proof,
+        applied: &Arc<BTreeSet<Hash>>,
+        scratch: usize,
+    ) -> Self {
+        let (proof_bytes, proof_allocations) = proof.heap_usage();
+        let scratch_allocations = usize::from(scratch > 0);
+        let nodes = applied.len().div_ceil(BTREE_NODE_ENTRIES);
+
+        Self {
+            steps: proof.len(),
+            bytes: size
+                + proof_bytes
+                + 2 * size_of::<usize>()
+                + size_of::<BTreeSet<Hash>>()
+                + nodes * BTREE_NODE_ENTRIES * size_of::<Hash>()
+                + scratch,
+            allocations: 1 + proof_allocations + nodes + scratch_allocations,
+        }
+    }
+}
@@ -1,17 +1,77 @@
 #![allow(clippy::doc_lazy_continuation)]
-
-use std::{io::Read, marker::PhantomData};
-
-use digest::Digest;
+// Proofs are decoded and verified from untrusted input, so nothing on that path may panic
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unimplemented
+    )
+)]
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{marker::PhantomData, str::FromStr};
+
+#[cfg(feature = "std")]
 use proptest::prelude::*;
-
-use crate::prelude::*;
-
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[cfg(feature = "metrics")]
+use crate::metrics::MergeObservation;
+use crate::{instrument::operation, io::Read, prelude::*, TrieHasher};
+
+mod builder;
+#[cfg(not(feature = "redact"))]
+mod display;
+mod epoch;
+mod format;
+mod interned;
+mod limits;
+mod memory;
+mod mode;
 mod neighbor;
+mod profile;
 mod proof;
+mod redact;
+mod soundness;
 mod step;
-
-pub use self::{neighbor::Neighbor, proof::Proof, step::Step};
+mod stream;
+#[cfg(feature = "trace-hashing")]
+mod trace;
+mod value;
+
+#[cfg(feature = "std")]
+pub use self::epoch::EpochAcks;
+pub use self::{
+    builder::TrieBuilder,
+    epoch::Epoch,
+    format::RootFormat,
+    interned::InternedProof,
+    limits::EntryLimits,
+    memory::MemoryUsage,
+    mode::{DuplicateLeaves, VerifyMode},
+    neighbor::Neighbor,
+    profile::Profile,
+    proof::Proof,
+    redact::Redacted,
+    soundness::{SoundnessReport, StepIssue, StepReport},
+    step::{Step, MAX_SKIP},
+    stream::{
+        calculate_root_from_reader,
+        calculate_root_from_reader_with_format,
+        MAX_STREAMED_STEP_LEN,
+    },
+    value::{ChunkedMerkle, LengthPrefixed, PlainValues, ValueHasher},
+};
 
 /// A Merkle-Patricia Trie implementation that provides succinct proofs through an optimized
 /// branch structure using tiny Sparse-Merkle trees.
@@ -32,6 +92,10 @@ pub use self::{neighbor::Neighbor, proof::Proof, step::Step};
 /// - Traditional MPT: ~480 * log₁₆(n) bytes
 /// - Trie: ~130 * log₁₆(n) bytes
 ///
+/// These bounds are the ones of a proof for a single key. [`Trie::verify`] checks keys against
+/// the whole [`Trie::proof`], which grows linearly with n, as recorded by the `proof_size`
+/// benchmark.
+///
 /// # Type Parameters
 ///
 /// * `D` - The digest algorithm implementing the [`Digest`] trait used for hashing operations
@@ -51,17 +115,59 @@ pub use self::{neighbor::Neighbor, proof::Proof, step::Step};
 ///     Ok(())
 /// }
 /// ```
-pub struct Trie<D: Digest> {
-    pub proof: Proof,
-    pub root: Hash,
+pub struct Trie<D: TrieHasher> {
+    /// The steps of the Trie, changed from outside the Trie through [`Trie::proof_mut`] only,
+    /// which forgets `calculated_root`
+    proof: Proof,
+    /// The root the Trie commits to, only set from the proof or by the constructors taking a
+    /// root, so it can't drift from the proof by accident
+    root: Hash,
+    /// The root last calculated from the proof, so verifying an unchanged Trie doesn't hash it
+    /// again. Every step is fed to the same hasher, so digests of single steps couldn't be
+    /// combined into the root after a change, and are not cached
+    calculated_root: Option<Hash>,
+    /// Digests of the operations applied so far, so they are not applied twice. Shared between
+    /// clones like the steps of the proof.
+    applied: Arc<BTreeSet<Hash>>,
+    /// The bytes the Trie may hold before inserts and merges are refused, if any
+    memory_budget: Option<usize>,
+    /// Whether path compression has been applied to the whole proof since it last changed
+    /// otherwise, so inserts only need to compress around the steps they touch
+    compressed: bool,
+    /// The buffer values are read into while hashing them, kept so that frequent small inserts
+    /// don't allocate it every time
+    scratch: Vec<u8>,
+    /// The format in which the steps are hashed into the root
+    root_format: RootFormat,
+    /// The longest keys and values accepted by inserts
+    entry_limits: EntryLimits,
+    /// How values are hashed, if not with a plain digest of their bytes
+    value_hasher: Option<Arc<dyn ValueHasher>>,
+    /// How strictly proofs are verified and merged states checked
+    profile: Profile,
+    #[cfg(feature = "metrics")]
+    metrics: MergeMetrics,
     _phantom: PhantomData<D>,
 }
 
-impl<D: Digest + 'static> Trie<D> {
+/// The number of steps from which the root is calculated in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_ROOT_STEPS: usize = 4096;
+
+/// The number of steps encoded by each task when calculating the root in parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_SEGMENT_STEPS: usize = 1024;
+
+/// The size of the chunks of values hashed in parallel with BLAKE3.
+#[cfg(all(feature = "rayon", feature = "blake3"))]
+const PARALLEL_VALUE_CHUNK: usize = 1 << 20;
+
+impl<D: TrieHasher + 'static> Trie<D> {
     /// Creates a new Trie instance from an existing proof.
     ///
     /// This method calculates the root hash from the provided proof and initializes
-    /// a new Trie structure.
+    /// a new Trie structure. The proof is taken as is, even if it holds several leaves for the
+    /// same key, which [`Trie::from_proof_with`] detects.
     ///
     /// # Arguments
     ///
@@ -82,12 +188,75 @@ impl<D: Digest + 'static> Trie<D> {
     /// ```
     #[inline]
     pub fn from_proof(proof: Proof) -> Self {
-        let root = Self::calculate_root(&proof);
-        Self {
-            proof,
-            root,
-            _phantom: PhantomData,
+        let mut trie = Self::from_parts(proof, Hash::zero());
+        trie.refresh_root();
+        trie
+    }
+
+    /// Creates a new Trie instance from an existing proof, handling leaves that share a key as
+    /// set by `duplicates`.
+    ///
+    /// A proof holding two leaves for the same key commits to both values, so which one
+    /// verifies depends on the mode of verification. [`DuplicateLeaves::Resolve`] keeps only
+    /// the newest leaf of each key, as merges do, and [`DuplicateLeaves::Reject`] refuses the
+    /// proof.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] if the proof holds several leaves for a key and
+    /// `duplicates` is [`DuplicateLeaves::Reject`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut clock = HybridClock::new();
+    ///     let mut a = Trie::<Blake2s256>::empty();
+    ///     a.insert_at(b"key", &b"old"[..], clock.tick_at(1))?;
+    ///     let mut b = Trie::<Blake2s256>::empty();
+    ///     b.insert_at(b"key", &b"new"[..], clock.tick_at(2))?;
+    ///
+    ///     let both = Proof::from(
+    ///         a.proof().iter().chain(b.proof().iter()).cloned().collect::<Vec<_>>(),
+    ///     );
+    ///     assert!(matches!(
+    ///         Trie::<Blake2s256>::from_proof_with(both.clone(), DuplicateLeaves::Reject),
+    ///         Err(Error::InvalidProof(_))
+    ///     ));
+    ///
+    ///     let resolved = Trie::<Blake2s256>::from_proof_with(both, DuplicateLeaves::Resolve)?;
+    ///     assert!(resolved.verify(b"key", b"new"));
+    ///     assert!(!resolved.verify(b"key", b"old"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn from_proof_with(mut proof: Proof, duplicates: DuplicateLeaves) -> Result<Self> {
+        Self::handle_duplicate_leaves(&mut proof, duplicates)?;
+        Ok(Self::from_proof(proof))
+    }
+
+    /// Resolves or rejects the leaves of `proof` sharing a key, as set by `duplicates`.
+    fn handle_duplicate_leaves(proof: &mut Proof, duplicates: DuplicateLeaves) -> Result<()> {
+        match duplicates {
+            DuplicateLeaves::Resolve => Self::resolve_writes(proof),
+            DuplicateLeaves::Reject => {
+                let mut keys = BTreeSet::new();
+                for step in proof.iter() {
+                    if let Step::Leaf { key, .. } = step {
+                        if !keys.insert(key) {
+                            return Err(Error::InvalidProof(format!("several leaves for key {key}")));
+                        }
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
 
     /// Creates a new Trie instance from a root hash.
@@ -120,202 +289,1243 @@ impl<D: Digest + 'static> Trie<D> {
     /// Returns [`Error::InvalidLength`] if the root hash is not exactly 32 bytes
     #[inline]
     pub fn from_root(root: &[u8]) -> Result<Self> {
-        if root.len() != 32 {
-            return Err(Error::InvalidLength);
-        }
-
-        Ok(Self {
-            proof: Proof::new(),
-            root: Hash::from_slice(root),
-            _phantom: PhantomData,
-        })
+        Ok(Self::from_parts(Proof::new(), Hash::from_slice(root)?))
     }
 
     /// Constructs a new empty Trie.
     #[inline]
     pub fn empty() -> Self {
+        Self::from_parts(Proof::new(), Hash::zero())
+    }
+
+    /// Constructs a new empty Trie, with room for at least `capacity` steps in its proof.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// let trie = Trie::<Blake2s256>::with_capacity(1024);
+    /// assert!(trie.proof().capacity() >= 1024);
+    /// assert_eq!(trie.root(), Trie::<Blake2s256>::empty().root());
+    /// ```
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_parts(Proof::with_capacity(capacity), Hash::zero())
+    }
+
+    /// Constructs a Trie from the hashes of its keys and values, as stored in its leaves,
+    /// without hashing the entries again.
+    ///
+    /// Systems keeping the hashes of their entries, like the leaves of a Trie they persisted,
+    /// can rebuild it without reading and hashing every value. Pairs are inserted in order, so
+    /// a later pair for the same key replaces an earlier one, and the root is hashed once at the
+    /// end. The result is the Trie obtained by inserting the entries the hashes come from.
+    ///
+    /// Values hashed with a [`ValueHasher`] are taken as they are, but entries verified or
+    /// inserted afterwards are only hashed the same way once it is set on the Trie.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"a", &b"1"[..])?;
+    ///     trie.insert(b"b", &b"2"[..])?;
+    ///
+    ///     let leaves = trie.proof().iter().filter_map(|step| match step {
+    ///         Step::Leaf { key, value, .. } => Some((*key, *value)),
+    ///         _ => None,
+    ///     });
+    ///     let rebuilt = Trie::<Blake2s256>::from_leaf_hashes(leaves);
+    ///
+    ///     assert_eq!(rebuilt.root(), trie.root());
+    ///     assert!(rebuilt.verify(b"a", b"1"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn from_leaf_hashes(leaves: impl IntoIterator<Item = (Hash, Hash)>) -> Self {
+        let leaves = leaves.into_iter();
+        let mut trie = Self::with_capacity(leaves.size_hint().0);
+        for (key, value) in leaves {
+            trie.insert_to_proof(key, value, None);
+        }
+        // Empty Tries keep the zero root instead of the one of their empty proof
+        if !trie.is_empty() {
+            trie.update_root();
+        }
+
+        trie
+    }
+
+    /// Reserves room for at least `additional` more steps in the proof, so inserting or merging
+    /// that many steps doesn't reallocate it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MemoryBudgetExceeded`] if the reserved steps would exceed the memory
+    /// budget, in which case nothing is reserved.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        let missing = (self.proof.len() + additional).saturating_sub(self.proof.capacity());
+        self.reserve_steps(missing)?;
+        self.proof.reserve(additional);
+
+        Ok(())
+    }
+
+    /// Assembles a Trie from a proof and its root, without checking that they match.
+    fn from_parts(proof: Proof, root: Hash) -> Self {
         Self {
-            proof: Proof::new(),
-            root: Hash::zero(),
+            proof,
+            root,
+            calculated_root: None,
+            applied: Arc::default(),
+            memory_budget: None,
+            compressed: false,
+            scratch: Vec::new(),
+            root_format: RootFormat::default(),
+            entry_limits: EntryLimits::default(),
+            value_hasher: None,
+            profile: Profile::default(),
+            #[cfg(feature = "metrics")]
+            metrics: MergeMetrics::default(),
             _phantom: PhantomData,
         }
     }
 
-    /// Checks if the Trie is empty.
+    /// Returns the counters accumulated over every merge into this Trie.
+    #[cfg(feature = "metrics")]
     #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.proof.is_empty()
+    pub fn merge_metrics(&self) -> &MergeMetrics {
+        &self.metrics
     }
 
-    /// Verifies if a key-value pair exists in the Trie.
+    /// Resets the merge counters, e.g. after they have been exported.
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub fn reset_merge_metrics(&mut self) {
+        self.metrics.reset();
+    }
+
+    /// Estimates the memory held by the Trie: its steps, and the bytes and allocations behind
+    /// them.
     ///
-    /// This method:
-    /// 1. Hashes the key and value using digest algorithm D
-    /// 2. Traverses the proof structure to:
-    ///    - Find a Leaf step matching the key-value hashes
-    ///    - Verify the authenticity of the path using the root hash
+    /// Steps shared with clones of the Trie are counted in full by each of them.
     ///
-    /// The verification process ensures:
-    /// - The key-value pair exists exactly as provided
-    /// - The proof structure is valid and matches the root hash
-    /// - All branch steps have valid Sparse-Merkle Tree structures
+    /// # Examples
     ///
-    /// # Arguments
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
     ///
-    /// * `key` - The key to verify, as a byte slice
-    /// * `value` - The value to verify, as a byte slice
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     let empty = trie.memory_usage();
+    ///     trie.insert(b"key", &b"value"[..])?;
     ///
-    /// # Returns
+    ///     assert_eq!(trie.memory_usage().steps, 1);
+    ///     assert!(trie.memory_usage().bytes > empty.bytes);
     ///
-    /// Returns true if the key-value pair exists and is authenticated by the proof
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::estimate(
+            size_of::<Self>(),
+            &self.proof,
+            &self.applied,
+            self.scratch.capacity(),
+        )
+    }
+
+    /// Limits the bytes the Trie may hold, as estimated by [`Trie::memory_usage`], or removes
+    /// the limit with `None`.
+    ///
+    /// Inserts, merges and applied operations that would take the Trie over its budget fail
+    /// with [`Error::MemoryBudgetExceeded`], leaving the Trie unchanged, so replicas on small
+    /// devices can refuse writes instead of running out of memory. The budget is not checked
+    /// when it is set, so a Trie already over it only refuses to grow.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use mutree::prelude::*;
     /// use blake2::Blake2s256;
-    /// use std::io::Cursor;
     ///
     /// fn main() -> Result<(), Error> {
     ///     let mut trie = Trie::<Blake2s256>::empty();
-    ///     trie.insert(b"key", Cursor::new(b"value"))?;
+    ///     trie.set_memory_budget(Some(trie.memory_usage().bytes));
+    ///
+    ///     assert!(matches!(
+    ///         trie.insert(b"key", &b"value"[..]),
+    ///         Err(Error::MemoryBudgetExceeded { .. })
+    ///     ));
+    ///     assert!(trie.is_empty());
     ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    /// The bytes the Trie may hold, if limited by [`Trie::set_memory_budget`].
+    #[inline]
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Changes the format in which the steps are hashed into the root, and recalculates it.
+    ///
+    /// Tries use [`RootFormat::V1`] by default, to keep the roots of existing replicas and the
+    /// EVM verifier. [`RootFormat::V2`] prefixes every step with its kind and skip, so that no
+    /// two proofs hash to the same root. The root of an empty Trie stays zero in either format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///     let v1 = trie.root();
+    ///
+    ///     trie.set_root_format(RootFormat::V2);
+    ///     assert_ne!(trie.root(), v1);
     ///     assert!(trie.verify(b"key", b"value"));
-    ///     assert!(!trie.verify(b"key", b"wrong_value"));
-    ///     assert!(!trie.verify(b"wrong_key", b"value"));
-    ///     
+    ///
     ///     Ok(())
     /// }
     /// ```
     #[inline]
-    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
-        if self.is_empty() {
-            return false;
+    pub fn set_root_format(&mut self, format: RootFormat) {
+        self.root_format = format;
+        if !self.proof.is_empty() {
+            self.update_root();
         }
-        let key_hash = Hash::digest::<D>(key);
-        let value_hash = Hash::digest::<D>(value);
-
-        // Verify the proof contains the exact key-value pair
-        let contains_pair = self.proof.iter().any(|step| {
-            matches!(step, Step::Leaf { key: leaf_key, value: leaf_value, .. }
-                if *leaf_key == key_hash && *leaf_value == value_hash)
-        });
+    }
 
-        // Verify the root hash matches
-        let calculated_root = Self::calculate_root(&self.proof);
-        contains_pair && calculated_root == self.root
+    /// The format in which the steps are hashed into the root.
+    #[inline]
+    pub fn root_format(&self) -> RootFormat {
+        self.root_format
     }
 
-    /// Inserts a key-value pair into the Merkle-Patricia Trie.
+    /// Limits the length of the keys and values accepted by inserts.
     ///
-    /// This method:
-    /// 1. Hashes the key and value using the digest algorithm D
-    /// 2. Updates the proof structure by:
-    ///    - Adding necessary Branch/Fork/Leaf steps
-    ///    - Removing any existing leaf with the same key
-    ///    - Compressing paths where possible
-    /// 3. Recalculates the root hash
+    /// Inserts of longer keys or values fail with [`Error::InvalidOperation`], leaving the Trie
+    /// unchanged. Merges are not limited, since they carry hashes instead of keys and values.
     ///
-    /// The insertion maintains the following invariants:
-    /// - Branch nodes use a mini Sparse-Merkle Tree requiring only 4 hashes
-    /// - Fork nodes include complete neighbor information
-    /// - Leaf nodes contain the actual key-value pair hashes
+    /// # Examples
     ///
-    /// # Arguments
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
     ///
-    /// * `key` - The key to insert, as a byte slice
-    /// * `value` - The value to insert, as a byte slice
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.set_entry_limits(EntryLimits {
+    ///         max_key_len: 8,
+    ///         max_value_len: 4,
+    ///     });
     ///
-    /// # Returns
+    ///     trie.insert(b"key", &b"1234"[..])?;
+    ///     assert!(matches!(
+    ///         trie.insert(b"key", &b"12345"[..]),
+    ///         Err(Error::InvalidOperation(_))
+    ///     ));
+    ///     assert!(trie.verify(b"key", b"1234"));
     ///
-    /// Returns the hash of the inserted value if successful, or an error if:
-    /// - The key is empty
-    /// - The insertion would violate the trie structure
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_entry_limits(&mut self, limits: EntryLimits) {
+        self.entry_limits = limits;
+    }
+
+    /// The longest keys and values accepted by inserts.
+    #[inline]
+    pub fn entry_limits(&self) -> EntryLimits {
+        self.entry_limits
+    }
+
+    /// Sets how the values of inserted and verified entries are hashed, instead of with a plain
+    /// digest of their bytes.
+    ///
+    /// Leaves already in the Trie keep the hashes of their values, so the value hasher is set
+    /// before inserting, like the digest is chosen before.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use mutree::prelude::*;
     /// use blake2::Blake2s256;
-    /// use std::io::Cursor;
     ///
     /// fn main() -> Result<(), Error> {
     ///     let mut trie = Trie::<Blake2s256>::empty();
-    ///     trie.insert(b"key", Cursor::new(b"value"))?;
-    ///     assert!(trie.verify(b"key", b"value"));
-    ///     
+    ///     trie.set_value_hasher(ChunkedMerkle { chunk_len: 1024 });
+    ///
+    ///     trie.insert(b"key", &vec![7; 4096][..])?;
+    ///     assert!(trie.verify(b"key", &vec![7; 4096]));
+    ///
     ///     Ok(())
     /// }
     /// ```
     #[inline]
-    pub fn insert<R: Read>(&mut self, key: &[u8], value: R) -> Result<Hash, Error> {
-        #[cfg(feature = "blake3")]
-        {
-            if std::any::TypeId::of::<D>() == std::any::TypeId::of::<blake3::Hasher>() {
-                // Use specialized blake3 implementation
-                return self.insert_blake3(key, value);
-            }
-        }
-        // Use default implementation for other hash functions
-        self.insert_default(key, value)
+    pub fn set_value_hasher(&mut self, hasher: impl ValueHasher + 'static) {
+        self.value_hasher = Some(Arc::new(hasher));
     }
 
+    /// How values are hashed, or `None` if they are hashed with a plain digest of their bytes,
+    /// as they are by default.
     #[inline]
-    fn insert_default<R: Read>(&mut self, key: &[u8], mut value: R) -> Result<Hash, Error> {
-        if key.is_empty() {
-            return Err(Error::EmptyKeyOrValue);
-        }
+    pub fn value_hasher(&self) -> Option<&dyn ValueHasher> {
+        self.value_hasher.as_deref()
+    }
 
-        let key_hash = Hash::digest::<D>(key);
-        let mut hasher = D::new();
-        let mut buffer = vec![0u8; 16384]; // 16KB chunks
+    /// Sets how strictly the Trie verifies proofs and checks the states merged into it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut a = Trie::<Blake2s256>::empty();
+    ///     a.insert(b"key", &b"a"[..])?;
+    ///     let mut b = Trie::<Blake2s256>::empty();
+    ///     b.insert(b"key", &b"b"[..])?;
+    ///
+    ///     // A proof holding two values for the same key
+    ///     let mut conflicting = Trie::<Blake2s256>::from_proof(Proof::from(
+    ///         a.proof().iter().chain(b.proof().iter()).cloned().collect::<Vec<_>>(),
+    ///     ));
+    ///     assert!(conflicting.verify(b"key", b"a"));
+    ///
+    ///     conflicting.set_profile(Profile::Paranoid);
+    ///     assert!(!conflicting.verify(b"key", b"a"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = profile;
+    }
 
-        loop {
-            match value.read(&mut buffer) {
-                Ok(0) => break, // EOF
-                Ok(n) => hasher.update(&buffer[..n]),
-                Err(e) => return Err(Error::Unknown(e.to_string())),
-            }
-        }
+    /// How strictly the Trie verifies proofs and checks the states merged into it,
+    /// [`Profile::Permissive`] unless set otherwise.
+    #[inline]
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
 
-        let value_hash = Hash::from_slice(hasher.finalize().as_ref());
-        self.proof = self.insert_to_proof(key_hash, value_hash);
-        self.root = Self::calculate_root(&self.proof);
+    /// The value hasher of the Trie, shared, for hashing entries apart from the Trie.
+    #[cfg(feature = "std")]
+    pub(crate) fn shared_value_hasher(&self) -> Option<Arc<dyn ValueHasher>> {
+        self.value_hasher.clone()
+    }
 
-        Ok(value_hash)
+    /// Hashes `value` with `values`, or with a plain digest of its bytes if there is no value
+    /// hasher.
+    fn hash_value(values: Option<&dyn ValueHasher>, value: &[u8]) -> Hash {
+        match values {
+            Some(values) => values.hash_value(value, &value::digest_parts::<D>),
+            None => Hash::digest::<D>(value),
+        }
     }
 
-    #[cfg(feature = "blake3")]
-    #[inline]
-    fn insert_blake3<R: Read>(&mut self, key: &[u8], mut value: R) -> Result<Hash, Error> {
-        if key.is_empty() {
-            return Err(Error::EmptyKeyOrValue);
+    /// Fails if the Trie has a memory budget, and `used` bytes would exceed it.
+    ///
+    /// The usage is only estimated when there is a budget to check it against.
+    fn check_memory_budget(&self, used: impl FnOnce() -> usize) -> Result<()> {
+        let Some(budget) = self.memory_budget else {
+            return Ok(());
+        };
+
+        let used = used();
+        if used > budget {
+            return Err(Error::MemoryBudgetExceeded { used, budget });
         }
 
-        // Use blake3's optimized hasher for the key
-        let mut key_hasher = blake3::Hasher::new();
-        key_hasher.update(key);
-        let key_hash = Hash::from_slice(key_hasher.finalize().as_ref());
+        Ok(())
+    }
 
-        // Use blake3's streaming hasher for the value
-        let mut value_hasher = blake3::Hasher::new();
-        let mut buffer = vec![0u8; 65536]; // 64KB chunks for better streaming performance
+    /// Fails if adding `steps` steps to the proof would exceed the memory budget.
+    pub(crate) fn reserve_steps(&self, steps: usize) -> Result<()> {
+        self.check_memory_budget(|| self.memory_usage().bytes + steps * size_of::<Step>())
+    }
 
-        loop {
-            match value.read(&mut buffer) {
-                Ok(0) => break,
+    /// Checks if the Trie is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.proof.is_empty()
+    }
+
+    /// Verifies if a key-value pair exists in the Trie.
+    ///
+    /// This method:
+    /// 1. Hashes the key and value using digest algorithm D
+    /// 2. Traverses the proof structure to:
+    ///    - Find a Leaf step matching the key-value hashes
+    ///    - Verify the authenticity of the path using the root hash
+    ///
+    /// The verification process ensures:
+    /// - The key-value pair exists exactly as provided
+    /// - The proof structure is valid and matches the root hash
+    /// - All branch steps have valid Sparse-Merkle Tree structures
+    ///
+    /// The proof is checked in the [`VerifyMode`] of the [`Profile`] of the Trie.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to verify, as a byte slice
+    /// * `value` - The value to verify, as a byte slice
+    ///
+    /// # Returns
+    ///
+    /// Returns true if the key-value pair exists and is authenticated by the proof
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    /// use std::io::Cursor;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", Cursor::new(b"value"))?;
+    ///
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///     assert!(!trie.verify(b"key", b"wrong_value"));
+    ///     assert!(!trie.verify(b"wrong_key", b"value"));
+    ///     
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
+        self.verify_with(key, value, self.profile.verify_mode())
+    }
+
+    /// Verifies a pair inserted with [`Trie::insert_encoded`], like [`Trie::verify`].
+    #[inline]
+    pub fn verify_encoded<K: KeyEncode + ?Sized>(&self, key: &K, value: &[u8]) -> bool {
+        self.verify(&key.encode_key(), value)
+    }
+
+    /// Verifies a key-value pair like [`Trie::verify`], checking the proof as required by
+    /// `mode`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut a = Trie::<Blake2s256>::empty();
+    ///     a.insert(b"key", &b"a"[..])?;
+    ///     let mut b = Trie::<Blake2s256>::empty();
+    ///     b.insert(b"key", &b"b"[..])?;
+    ///
+    ///     // A proof holding two values for the same key
+    ///     let conflicting = Trie::<Blake2s256>::from_proof(Proof::from(
+    ///         a.proof().iter().chain(b.proof().iter()).cloned().collect::<Vec<_>>(),
+    ///     ));
+    ///
+    ///     assert!(conflicting.verify_with(b"key", b"a", VerifyMode::Lenient));
+    ///     assert!(!conflicting.verify_with(b"key", b"a", VerifyMode::Strict));
+    ///     assert!(a.verify_with(b"key", b"a", VerifyMode::Strict));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn verify_with(&self, key: &[u8], value: &[u8], mode: VerifyMode) -> bool {
+        let mut operation = operation!("verify", key, verified);
+
+        if self.is_empty() {
+            operation.record("verified", false);
+            return false;
+        }
+        let key_hash = Hash::digest::<D>(key);
+        operation.record_key(&key_hash);
+        let value_hash = Self::hash_value(self.value_hasher(), value);
+
+        // Verify the proof contains the exact key-value pair
+        let contains_pair = self.proof.iter().any(|step| {
+            matches!(step, Step::Leaf { key: leaf_key, value: leaf_value, .. }
+                if *leaf_key == key_hash && *leaf_value == value_hash)
+        });
+
+        let verified = contains_pair && mode.admits(&self.proof, &key_hash) && self.root_matches();
+
+        #[cfg(feature = "trace-hashing")]
+        trace::verify(key_hash, value_hash, contains_pair, self.root, verified);
+        operation.record("verified", verified);
+        if verified {
+            operation.succeed(self.proof.len());
+        }
+
+        verified
+    }
+
+    /// Verifies a key-value pair like [`Trie::verify`], reporting why it fails.
+    ///
+    /// Unlike [`Trie::verify`], the steps of the proof are also checked with
+    /// [`Step::validate`], so a proof built from out-of-bounds steps fails even if it holds the
+    /// pair and hashes to the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VerifyError::MalformedStep`] with the index of the first invalid step,
+    /// [`VerifyError::LeafNotFound`] if no leaf holds the pair, including when the Trie is
+    /// empty, [`VerifyError::NotStrict`] if the [`VerifyMode`] of the [`Profile`] of the Trie
+    /// rejects the proof, and [`VerifyError::RootMismatch`] if the proof doesn't hash to the
+    /// root.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///     trie.verify_detailed(b"key", b"value")?;
+    ///
+    ///     assert_eq!(
+    ///         trie.verify_detailed(b"key", b"other"),
+    ///         Err(VerifyError::LeafNotFound)
+    ///     );
+    ///
+    ///     // A Trie holding the same proof, but committing to another root
+    ///     let mut forged = Trie::<Blake2s256>::from_root(&[0; 32])?;
+    ///     *forged.proof_mut() = trie.proof().clone();
+    ///     let computed = trie.root();
+    ///     assert_eq!(
+    ///         forged.verify_detailed(b"key", b"value"),
+    ///         Err(VerifyError::RootMismatch { expected: Hash::zero(), computed })
+    ///     );
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn verify_detailed(&self, key: &[u8], value: &[u8]) -> Result<(), VerifyError> {
+        if let Some(index) = self.proof.iter().position(|step| step.validate().is_err()) {
+            return Err(VerifyError::MalformedStep { index });
+        }
+
+        let key_hash = Hash::digest::<D>(key);
+        let value_hash = Self::hash_value(self.value_hasher(), value);
+        let contains_pair = self.proof.iter().any(|step| {
+            matches!(step, Step::Leaf { key: leaf_key, value: leaf_value, .. }
+                if *leaf_key == key_hash && *leaf_value == value_hash)
+        });
+        if !contains_pair {
+            return Err(VerifyError::LeafNotFound);
+        }
+        if !self.profile.verify_mode().admits(&self.proof, &key_hash) {
+            return Err(VerifyError::NotStrict);
+        }
+
+        let computed = self
+            .calculated_root
+            .unwrap_or_else(|| Self::calculate_root(&self.proof, self.root_format));
+        if computed != self.root {
+            return Err(VerifyError::RootMismatch {
+                expected: self.root,
+                computed,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Verifies a key-value pair like [`Trie::verify`], in a time independent of the pair.
+    ///
+    /// Every step of the proof is compared with the pair, without stopping at the first
+    /// matching leaf, and hashes are compared with [`Hash::ct_eq`]. The time taken only depends
+    /// on the steps of the proof, so services answering verifications don't leak which keys
+    /// exist, or where their leaves sit in the proof, through their response times. The proof
+    /// is checked in the [`VerifyMode`] of the [`Profile`] of the Trie, like [`Trie::verify`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     assert!(trie.verify_constant_time(b"key", b"value"));
+    ///     assert!(!trie.verify_constant_time(b"key", b"other"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn verify_constant_time(&self, key: &[u8], value: &[u8]) -> bool {
+        let key_hash = Hash::digest::<D>(key);
+        let value_hash = Self::hash_value(self.value_hasher(), value);
+
+        let (mut contains_pair, mut leaves) = (false, 0usize);
+        for step in self.proof.iter() {
+            if let Step::Leaf {
+                key: leaf_key,
+                value: leaf_value,
+                ..
+            } = step
+            {
+                let same_key = leaf_key.ct_eq(&key_hash);
+                contains_pair |= same_key & leaf_value.ct_eq(&value_hash);
+                leaves += usize::from(same_key);
+            }
+        }
+
+        // Repeated steps don't depend on the pair, so checking them doesn't leak it
+        let mode = self.profile.verify_mode();
+        let admitted = (mode == VerifyMode::Lenient || leaves == 1) & mode.admits_steps(&self.proof);
+
+        let calculated_root = self
+            .calculated_root
+            .unwrap_or_else(|| Self::calculate_root(&self.proof, self.root_format));
+
+        contains_pair & admitted & calculated_root.ct_eq(&self.root) & !self.is_empty()
+    }
+
+    /// Verifies many key-value pairs at once, returning whether each of them exists in the Trie.
+    ///
+    /// The proof is walked and checked against the root once for the whole batch, instead of
+    /// once per pair as done by calling [`Trie::verify`] for each of them, in the same
+    /// [`VerifyMode`]. With the `rayon` feature, the keys and values are also hashed in
+    /// parallel.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"a", &b"1"[..])?;
+    ///     trie.insert(b"b", &b"2"[..])?;
+    ///
+    ///     let pairs: [(&[u8], &[u8]); 3] = [(b"a", b"1"), (b"b", b"1"), (b"b", b"2")];
+    ///     assert_eq!(trie.verify_batch(&pairs), [true, false, true]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn verify_batch(&self, pairs: &[(&[u8], &[u8])]) -> Vec<bool> {
+        let mode = self.profile.verify_mode();
+        if self.is_empty() || !self.root_matches() || !mode.admits_steps(&self.proof) {
+            return vec![false; pairs.len()];
+        }
+
+        // Keys with several leaves, which strict verification rejects
+        let (mut leaves, mut keys, mut conflicting) =
+            (BTreeSet::new(), BTreeSet::new(), BTreeSet::new());
+        for step in self.proof.iter() {
+            if let Step::Leaf { key, value, .. } = step {
+                leaves.insert((*key, *value));
+                if mode == VerifyMode::Strict && !keys.insert(*key) {
+                    conflicting.insert(*key);
+                }
+            }
+        }
+        let values = self.value_hasher();
+        let contains = |(key, value): &(&[u8], &[u8])| {
+            let key = Hash::digest::<D>(key);
+            leaves.contains(&(key, Self::hash_value(values, value))) && !conflicting.contains(&key)
+        };
+
+        #[cfg(feature = "rayon")]
+        let verified = {
+            use rayon::prelude::*;
+
+            pairs.par_iter().map(contains).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let verified = pairs.iter().map(contains).collect();
+
+        verified
+    }
+
+    /// Checks that the proof hashes to the root, hashing it only if it changed since the last
+    /// time.
+    fn root_matches(&self) -> bool {
+        let calculated_root = self
+            .calculated_root
+            .unwrap_or_else(|| Self::calculate_root(&self.proof, self.root_format));
+        calculated_root == self.root
+    }
+
+    /// Recalculates the root from the proof.
+    ///
+    /// Every method changing the proof already does this, so it is only needed after changing
+    /// it through [`Trie::proof_mut`]. Until then, the Trie commits to the root of the previous
+    /// proof, which [`Trie::verify`] checks the changed proof against. The next insertion also
+    /// applies path compression to the whole proof again, instead of only around the steps it
+    /// touches.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut source = Trie::<Blake2s256>::empty();
+    ///     source.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     *trie.proof_mut() = source.proof().clone();
+    ///     trie.refresh_root();
+    ///
+    ///     assert_eq!(trie.root(), source.root());
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn refresh_root(&mut self) {
+        self.compressed = false;
+        self.update_root();
+    }
+
+    /// Recalculates the root after an insertion, which keeps track of path compression itself.
+    fn update_root(&mut self) {
+        let root = Self::calculate_root(&self.proof, self.root_format);
+        self.root = root;
+        self.calculated_root = Some(root);
+    }
+
+    /// Inserts a key-value pair into the Merkle-Patricia Trie.
+    ///
+    /// This method:
+    /// 1. Hashes the key and value using the digest algorithm D
+    /// 2. Updates the proof structure by:
+    ///    - Adding necessary Branch/Fork/Leaf steps
+    ///    - Removing any existing leaf with the same key
+    ///    - Compressing paths where possible
+    /// 3. Recalculates the root hash
+    ///
+    /// The insertion maintains the following invariants:
+    /// - Branch nodes use a mini Sparse-Merkle Tree requiring only 4 hashes
+    /// - Fork nodes include complete neighbor information
+    /// - Leaf nodes contain the actual key-value pair hashes
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to insert, as a byte slice
+    /// * `value` - The value to insert, as a byte slice
+    ///
+    /// # Returns
+    ///
+    /// Returns the hash of the inserted value if successful, or an error if:
+    /// - The key is empty
+    /// - The key or the value is longer than the [entry limits](Trie::set_entry_limits)
+    /// - The insertion would violate the trie structure
+    /// - The insertion would exceed the memory budget of the Trie
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    /// use std::io::Cursor;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", Cursor::new(b"value"))?;
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///     
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn insert<R: Read>(&mut self, key: &[u8], value: R) -> Result<Hash, Error> {
+        let mut operation = operation!("insert", key);
+
+        let (key_hash, value_hash) = Self::hash_entry(
+            key,
+            value,
+            &mut self.scratch,
+            self.entry_limits,
+            self.value_hasher.as_deref(),
+        )?;
+        operation.record_key(&key_hash);
+
+        self.reserve_steps(1)?;
+        self.insert_to_proof(key_hash, value_hash, None);
+        self.update_root();
+
+        operation.succeed(self.proof.len());
+
+        Ok(value_hash)
+    }
+
+    /// Inserts a value under a structured key, encoded with [`KeyEncode`], like
+    /// [`Trie::insert`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Trie::insert`].
+    #[inline]
+    pub fn insert_encoded<K: KeyEncode + ?Sized, R: Read>(
+        &mut self,
+        key: &K,
+        value: R,
+    ) -> Result<Hash, Error> {
+        self.insert(&key.encode_key(), value)
+    }
+
+    /// Inserts a key-value pair written at `timestamp`, following last-writer-wins semantics.
+    ///
+    /// The timestamp is stored in the leaf and committed into the root, so merges between
+    /// replicas keep the newest write for each key, no matter the order they happen in. If the
+    /// Trie already holds a newer write for the same key, the insertion is discarded.
+    ///
+    /// Ties between writes with the same timestamp are broken by the value hash, and leaves
+    /// written through [`Trie::insert`] lose against any timestamped write.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to insert, as a byte slice
+    /// * `value` - The value to insert
+    /// * `timestamp` - When the write happened, usually taken from a [`HybridClock`]
+    ///
+    /// # Returns
+    ///
+    /// Returns the hash of the value, whether or not it was stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the key is empty, [`Error::InvalidOperation`] if
+    /// the key or the value exceeds the entry limits, or [`Error::MemoryBudgetExceeded`] if
+    /// storing the write would exceed the memory budget.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut clock = HybridClock::new();
+    ///     let first = clock.tick_at(100);
+    ///     let second = clock.tick_at(100);
+    ///
+    ///     let mut a = Trie::<Blake2s256>::empty();
+    ///     a.insert_at(b"key", &b"new"[..], second)?;
+    ///
+    ///     let mut b = Trie::<Blake2s256>::empty();
+    ///     b.insert_at(b"key", &b"old"[..], first)?;
+    ///
+    ///     // The newest write wins on both sides
+    ///     b.merge(&a)?;
+    ///     a.merge(&b)?;
+    ///     assert!(a.verify(b"key", b"new"));
+    ///     assert!(!b.verify(b"key", b"old"));
+    ///     assert_eq!(a.root(), b.root());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn insert_at<R: Read>(
+        &mut self,
+        key: &[u8],
+        value: R,
+        timestamp: Timestamp,
+    ) -> Result<Hash, Error> {
+        let mut operation = operation!("insert", key, stale);
+
+        let (key_hash, value_hash) = Self::hash_entry(
+            key,
+            value,
+            &mut self.scratch,
+            self.entry_limits,
+            self.value_hasher.as_deref(),
+        )?;
+        operation.record_key(&key_hash);
+
+        let write = (Some(timestamp), value_hash, 0);
+        let stale = self.proof.iter().any(|step| {
+            matches!(step, Step::Leaf { skip, key, value, timestamp }
+                if *key == key_hash && (*timestamp, *value, *skip) >= write)
+        });
+
+        if !stale {
+            self.reserve_steps(1)?;
+            self.insert_to_proof(key_hash, value_hash, Some(timestamp));
+            self.update_root();
+        }
+
+        operation.record("stale", stale);
+        operation.succeed(self.proof.len());
+
+        Ok(value_hash)
+    }
+
+    /// Inserts a key-value pair, reading the value from an [`AsyncRead`].
+    ///
+    /// The value is streamed and hashed in chunks, as done by [`Trie::insert`], so large values
+    /// can be inserted from files or sockets without blocking the executor or buffering them
+    /// whole.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the key is empty, [`Error::InvalidOperation`] if
+    /// the key or the value exceeds the entry limits, [`Error::Unknown`] if reading the value
+    /// fails, or [`Error::MemoryBudgetExceeded`] if the insertion would exceed the memory
+    /// budget.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     runtime.block_on(trie.insert_async(b"key", &b"value"[..]))?;
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub async fn insert_async<R: AsyncRead + Unpin>(
+        &mut self,
+        key: &[u8],
+        mut value: R,
+    ) -> Result<Hash, Error> {
+        let mut operation = operation!("insert", key);
+
+        let limits = self.entry_limits;
+        limits.check_key(key)?;
+
+        let key_hash = Hash::digest::<D>(key);
+        operation.record_key(&key_hash);
+        let mut hasher = D::new();
+        // Value hashers take whole values, so the value is kept for them
+        let mut bytes = Vec::new();
+        let buffer = Self::scratch_chunk(&mut self.scratch, 16384); // 16KB chunks
+        let mut len = 0;
+
+        loop {
+            match value.read(buffer).await {
+                Ok(0) => break, // EOF
                 Ok(n) => {
-                    value_hasher.update(&buffer[..n]);
+                    len += n as u64;
+                    limits.check_value_len(len)?;
+                    match self.value_hasher {
+                        Some(_) => bytes.extend_from_slice(&buffer[..n]),
+                        None => hasher.update(&buffer[..n]),
+                    }
+                }
+                Err(e) => return Err(Error::Unknown(e.to_string())),
+            }
+        }
+
+        let value_hash = match self.value_hasher() {
+            Some(values) => Self::hash_value(Some(values), &bytes),
+            None => hasher.finalize32(),
+        };
+        self.reserve_steps(1)?;
+        self.insert_to_proof(key_hash, value_hash, None);
+        self.update_root();
+
+        operation.succeed(self.proof.len());
+
+        Ok(value_hash)
+    }
+
+    /// Inserts a key-value pair whose value is the content of the file at `path`.
+    ///
+    /// With the `mmap` feature, the file is memory-mapped and hashed in place, without copying
+    /// it through the buffer used by [`Trie::insert`], which makes inserting files of several
+    /// gigabytes cheaper. The file must not be modified while it is being hashed. Without the
+    /// feature, on platforms without memory maps, or for files that can't be mapped, the file
+    /// is streamed like any other reader. The resulting hash is the same either way.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyKeyOrValue`] if the key is empty, [`Error::InvalidOperation`] if
+    /// the key or the file exceeds the entry limits, [`Error::Unknown`] if the file can't be
+    /// opened or read, or [`Error::MemoryBudgetExceeded`] if the insertion would exceed the
+    /// memory budget.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let path = std::env::temp_dir().join("mutree-insert-file-example");
+    ///     std::fs::write(&path, b"value").unwrap();
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert_file(b"key", &path)?;
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///
+    ///     std::fs::remove_file(&path).unwrap();
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn insert_file<P: AsRef<std::path::Path>>(
+        &mut self,
+        key: &[u8],
+        path: P,
+    ) -> Result<Hash, Error> {
+        let file = std::fs::File::open(path).map_err(|e| Error::Unknown(e.to_string()))?;
+
+        #[cfg(all(feature = "mmap", any(unix, windows)))]
+        if let Some(map) = Self::map_file(&file) {
+            let mut operation = operation!("insert", key);
+
+            let (key_hash, value_hash) =
+                Self::hash_entry_slice(key, &map, self.entry_limits, self.value_hasher())?;
+            operation.record_key(&key_hash);
+
+            self.reserve_steps(1)?;
+            self.insert_to_proof(key_hash, value_hash, None);
+            self.update_root();
+
+            operation.succeed(self.proof.len());
+
+            return Ok(value_hash);
+        }
+
+        self.insert(key, file)
+    }
+
+    /// Maps a file into memory, or returns `None` if it can't be mapped.
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    pub(crate) fn map_file(file: &std::fs::File) -> Option<memmap2::Mmap> {
+        // SAFETY: the map is only read while hashing the value, and callers of `insert_file`
+        // are required not to modify the file meanwhile. Changes from other processes can only
+        // make the hash not match any version of the file, not cause unsoundness in the
+        // hashing of plain bytes.
+        unsafe { memmap2::Mmap::map(file) }.ok()
+    }
+
+    /// Hashes a key-value pair whose value is already in memory, without copying it.
+    pub(crate) fn hash_entry_slice(
+        key: &[u8],
+        value: &[u8],
+        limits: EntryLimits,
+        values: Option<&dyn ValueHasher>,
+    ) -> Result<(Hash, Hash), Error> {
+        limits.check_key(key)?;
+        limits.check_value_len(value.len() as u64)?;
+
+        if values.is_some() {
+            return Ok((Hash::digest::<D>(key), Self::hash_value(values, value)));
+        }
+
+        #[cfg(all(feature = "rayon", feature = "blake3"))]
+        if core::any::TypeId::of::<D>() == core::any::TypeId::of::<blake3::Hasher>() {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_rayon(value);
+
+            return Ok((
+                Hash::digest::<D>(key),
+                Hash::from_digest(blake3::Hasher::finalize(&hasher).as_bytes()),
+            ));
+        }
+
+        let mut hasher = D::new();
+        hasher.update(value);
+
+        Ok((Hash::digest::<D>(key), hasher.finalize32()))
+    }
+
+    /// Hashes a key-value pair, returning the key and value hashes.
+    ///
+    /// The value is read in chunks into `scratch`, which is grown to the chunk size if needed
+    /// and can be reused between calls. Reading stops as soon as the value exceeds `limits`.
+    /// Value hashers take whole values, so values are read into memory first for them.
+    pub(crate) fn hash_entry<R: Read>(
+        key: &[u8],
+        value: R,
+        scratch: &mut Vec<u8>,
+        limits: EntryLimits,
+        values: Option<&dyn ValueHasher>,
+    ) -> Result<(Hash, Hash), Error> {
+        if let Some(values) = values {
+            limits.check_key(key)?;
+
+            let mut bytes = Vec::new();
+            Self::read_chunks(
+                value,
+                Self::scratch_chunk(scratch, 16384),
+                limits,
+                |chunk| bytes.extend_from_slice(chunk),
+            )?;
+
+            return Ok((
+                Hash::digest::<D>(key),
+                Self::hash_value(Some(values), &bytes),
+            ));
+        }
+
+        #[cfg(feature = "blake3")]
+        {
+            if core::any::TypeId::of::<D>() == core::any::TypeId::of::<blake3::Hasher>() {
+                // Use specialized blake3 implementation
+                return Self::hash_entry_blake3(key, value, scratch, limits);
+            }
+        }
+        // Use default implementation for other hash functions
+        Self::hash_entry_default(key, value, scratch, limits)
+    }
+
+    /// Returns the first `len` bytes of `scratch`, growing it first if it is shorter.
+    fn scratch_chunk(scratch: &mut Vec<u8>, len: usize) -> &mut [u8] {
+        if scratch.len() < len {
+            scratch.resize(len, 0);
+        }
+
+        &mut scratch[..len]
+    }
+
+    fn hash_entry_default<R: Read>(
+        key: &[u8],
+        value: R,
+        scratch: &mut Vec<u8>,
+        limits: EntryLimits,
+    ) -> Result<(Hash, Hash), Error> {
+        limits.check_key(key)?;
+
+        let key_hash = Hash::digest::<D>(key);
+        let mut hasher = D::new();
+        let buffer = Self::scratch_chunk(scratch, 16384); // 16KB chunks
+        Self::read_chunks(value, buffer, limits, |chunk| hasher.update(chunk))?;
+
+        Ok((key_hash, hasher.finalize32()))
+    }
+
+    /// Reads `value` to its end through `buffer`, passing the bytes read to `chunk`, and fails
+    /// as soon as it exceeds `limits`.
+    fn read_chunks<R: Read>(
+        mut value: R,
+        buffer: &mut [u8],
+        limits: EntryLimits,
+        mut chunk: impl FnMut(&[u8]),
+    ) -> Result<()> {
+        let mut len = 0;
+
+        loop {
+            match value.read(buffer) {
+                Ok(0) => return Ok(()), // EOF
+                Ok(n) => {
+                    len += n as u64;
+                    limits.check_value_len(len)?;
+                    chunk(&buffer[..n]);
+                }
+                Err(e) => return Err(Error::Unknown(e.to_string())),
+            }
+        }
+    }
+
+    #[cfg(feature = "blake3")]
+    #[cfg_attr(feature = "rayon", allow(unused_variables, clippy::ptr_arg))] // See below
+    fn hash_entry_blake3<R: Read>(
+        key: &[u8],
+        mut value: R,
+        scratch: &mut Vec<u8>,
+        limits: EntryLimits,
+    ) -> Result<(Hash, Hash), Error> {
+        limits.check_key(key)?;
+        let mut len = 0;
+
+        // Use blake3's optimized hasher for the key
+        let mut key_hasher = blake3::Hasher::new();
+        key_hasher.update(key);
+        let key_hash = Hash::from_digest(key_hasher.finalize().as_ref());
+
+        // Use blake3's streaming hasher for the value
+        let mut value_hasher = blake3::Hasher::new();
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            let buffer = Self::scratch_chunk(scratch, 65536); // 64KB chunks for better streaming performance
+
+            loop {
+                match value.read(buffer) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        len += n as u64;
+                        limits.check_value_len(len)?;
+                        value_hasher.update(&buffer[..n]);
+                    }
+                    Err(e) => return Err(Error::Unknown(e.to_string())),
                 }
-                Err(e) => return Err(Error::Unknown(e.to_string())),
             }
         }
 
-        let value_hash = Hash::from_slice(value_hasher.finalize().as_ref());
-        self.proof = self.insert_to_proof(key_hash, value_hash);
-        self.root = Self::calculate_root(&self.proof);
+        // Fill larger chunks, so blake3 has enough input to split between threads. Values this
+        // large dwarf the cost of allocating the buffer, so it isn't kept in the scratch buffer.
+        #[cfg(feature = "rayon")]
+        {
+            let mut buffer = vec![0u8; PARALLEL_VALUE_CHUNK];
+
+            loop {
+                let mut filled = 0;
+                while filled < buffer.len() {
+                    match value.read(&mut buffer[filled..]) {
+                        Ok(0) => break,
+                        Ok(n) => filled += n,
+                        Err(e) => return Err(Error::Unknown(e.to_string())),
+                    }
+                }
+
+                len += filled as u64;
+                limits.check_value_len(len)?;
 
-        Ok(value_hash)
+                value_hasher.update_rayon(&buffer[..filled]);
+
+                if filled < buffer.len() {
+                    break;
+                }
+            }
+        }
+
+        Ok((
+            key_hash,
+            Hash::from_digest(value_hasher.finalize().as_ref()),
+        ))
     }
 
     /// Verifies a proof for a given key and value.
@@ -330,130 +1540,682 @@ impl<D: Digest + 'static> Trie<D> {
         })
     }
 
-    /// Inserts a key-value pair into the proof.
-    fn insert_to_proof(&self, key: Hash, value: Hash) -> Proof {
-        let mut new_proof = self.proof.clone();
+    /// Merges the state of another replica like [`CvRDT::merge`], after checking that its steps
+    /// are structurally valid and that they hash to its root.
+    ///
+    /// [`CvRDT::merge`] takes the union of both proofs unconditionally in
+    /// [`Profile::Permissive`] tries, so a peer sending steps that don't match its root could
+    /// poison this replica. States received from untrusted peers should be merged with this
+    /// method instead, or by tries of a stricter profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] naming the first invalid step, or the expected and
+    /// computed roots if they differ, in which case the Trie is left unchanged. Merging can also
+    /// fail like [`CvRDT::merge`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut other = Trie::<Blake2s256>::empty();
+    ///     other.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let mut forged = Trie::<Blake2s256>::from_root(&[0; 32])?;
+    ///     *forged.proof_mut() = other.proof().clone();
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     assert!(trie.merge_verified(&forged).is_err());
+    ///     assert!(trie.is_empty());
+    ///
+    ///     trie.merge_verified(&other)?;
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn merge_verified(&mut self, other: &Self) -> Result<()> {
+        self.merge_with(other, true)
+    }
+
+    /// Decodes the state of another replica, within the [`DecodeContext`] of the profile of the
+    /// Trie, and merges it like [`CvRDT::merge`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the state is malformed or exceeds the bounds of the
+    /// profile, in which case the Trie is left unchanged. Merging can also fail like
+    /// [`CvRDT::merge`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut other = Trie::<Blake2s256>::empty();
+    ///     other.insert(b"key", &b"value"[..])?;
+    ///     let bytes = other.to_bytes();
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::builder()
+    ///         .profile(Profile::Paranoid)
+    ///         .build()?;
+    ///     trie.merge_from_bytes(&bytes)?;
+    ///     assert!(trie.verify(b"key", b"value"));
+    ///
+    ///     let oversized = vec![0; Profile::PARANOID_MAX_BYTES + 1];
+    ///     assert!(matches!(
+    ///         trie.merge_from_bytes(&oversized),
+    ///         Err(Error::Deserialization(_))
+    ///     ));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn merge_from_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let other = Self::from_bytes_with(bytes, &self.profile.decode_context())?;
+        self.merge(&other)
+    }
+
+    /// Checks that the steps of `self` are structurally valid and that they hash to its root.
+    fn check_state(&self) -> Result<()> {
+        self.proof.validate()?;
+
+        // Empty Tries have the zero root instead of the one of their empty proof
+        let computed = Self::calculate_root(&self.proof, self.root_format);
+        if computed != self.root && !(self.is_empty() && self.root.is_zero()) {
+            return Err(Error::InvalidProof(format!(
+                "root mismatch: expected {}, computed {}",
+                self.root.to_hex(),
+                computed.to_hex()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Merges `other` like [`CvRDT::merge`], checking it first like [`Trie::merge_verified`] if
+    /// `validate` is set.
+    fn merge_with(&mut self, other: &Self, validate: bool) -> Result<()> {
+        let mut operation = operation!("merge", incoming = other.proof.len());
+        if validate {
+            other.check_state()?;
+        }
+
+        #[cfg(feature = "metrics")]
+        let observation = MergeObservation::start(&self.proof, self.root, &other.proof);
+
+        let mut merged_proof = self.proof.clone();
+        for step in other.proof.iter() {
+            if !merged_proof.contains(step) {
+                merged_proof.push(step.clone());
+            }
+        }
+        Self::resolve_writes(&mut merged_proof);
+        self.check_memory_budget(|| {
+            MemoryUsage::estimate(
+                size_of::<Self>(),
+                &merged_proof,
+                &self.applied,
+                self.scratch.capacity(),
+            )
+            .bytes
+        })?;
+
+        self.proof = merged_proof;
+        self.refresh_root();
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(observation, &self.proof, self.root);
+        operation.succeed(self.proof.len());
+
+        Ok(())
+    }
+
+    /// Applies a batch of operations, recomputing the root only once at the end.
+    ///
+    /// Operations are deduplicated by their digest: any operation already applied, either
+    /// earlier in the batch or by a previous call to this method or to
+    /// [`CmRDT::apply`], is skipped.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of operations that were not skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MemoryBudgetExceeded`] if the batch would exceed the memory budget of
+    /// the Trie, in which case none of its operations are applied.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut source = Trie::<Blake2s256>::empty();
+    ///     source.insert(b"a", &b"1"[..])?;
+    ///     let first = source.proof().clone();
+    ///     source.insert(b"b", &b"2"[..])?;
+    ///     let second = source.proof().clone();
+    ///
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     assert_eq!(trie.apply_batch(&[first.clone(), second, first])?, 2);
+    ///     assert!(trie.verify(b"a", b"1"));
+    ///     assert!(trie.verify(b"b", b"2"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn apply_batch(&mut self, ops: &[Proof]) -> Result<usize> {
+        let mut operation = operation!("apply_batch", ops = ops.len());
+
+        let mut known: BTreeSet<Hash> = self
+            .proof
+            .iter()
+            .map(|step| step.hash_bytes::<D>())
+            .collect();
+        let mut digests = BTreeSet::new();
+        let mut pending = Vec::new();
+
+        for op in ops {
+            let digest = op.hash_bytes::<D>();
+            if self.applied.contains(&digest) || !digests.insert(digest) {
+                continue;
+            }
+
+            for step in op.iter() {
+                if known.insert(step.hash_bytes::<D>()) {
+                    pending.push(step.clone());
+                }
+            }
+        }
+
+        self.check_memory_budget(|| {
+            self.memory_usage().bytes
+                + pending.len() * size_of::<Step>()
+                + digests.len() * size_of::<Hash>()
+        })?;
+
+        let applied = digests.len();
+        Arc::make_mut(&mut self.applied).extend(digests);
+        self.proof.extend(pending);
+
+        if applied > 0 {
+            Self::resolve_writes(&mut self.proof);
+            self.refresh_root();
+        }
+
+        operation.succeed(self.proof.len());
+
+        Ok(applied)
+    }
+
+    /// Inserts entries hashed by [`Trie::hash_entry`], recalculating the root once at the end.
+    ///
+    /// The memory budget is checked for the whole batch first, so either every entry is
+    /// inserted or none is.
+    pub(crate) fn insert_hashed(&mut self, entries: &[(Hash, Hash)]) -> Result<()> {
+        self.reserve_steps(entries.len())?;
+        self.proof.reserve(entries.len());
+
+        for (key, value) in entries {
+            self.insert_to_proof(*key, *value, None);
+        }
+        self.update_root();
+
+        Ok(())
+    }
+
+    /// Inserts a key-value pair into the proof, in place.
+    ///
+    /// Hashing the entry is the only fallible part of an insertion, and happens before this is
+    /// called, so the proof is never left half-updated.
+    ///
+    /// When the proof is known to be compressed already, only the steps made adjacent by the
+    /// removal of the previous leaf of the key can be merged, so compression only looks at them.
+    fn insert_to_proof(&mut self, key: Hash, value: Hash, timestamp: Option<Timestamp>) {
+        let is_previous =
+            |step: &Step| matches!(step, Step::Leaf { key: leaf_key, .. } if *leaf_key == key);
+        let previous = self.proof.iter().position(is_previous);
+        let len = self.proof.len();
+
         // Remove any existing leaf with the same key
-        new_proof
-            .retain(|step| !matches!(step, Step::Leaf { key: leaf_key, .. } if *leaf_key == key));
-        new_proof.push(Step::Leaf {
+        self.proof.retain(|step| !is_previous(step));
+        let removed = len - self.proof.len();
+
+        self.proof.push(Step::Leaf {
             skip: 0,
             key,
             value,
+            timestamp,
         });
-        Self::compress_path(&mut new_proof);
-        new_proof
-    }
-
-    /// Applies path compression to the proof.
-    fn compress_path(proof: &mut Proof) {
-        let mut i = 0;
-        while i < proof.len() - 1 {
-            if let (
-                Step::Branch {
-                    skip: skip1,
-                    neighbors: neighbors1,
-                },
-                Step::Branch {
-                    skip: skip2,
-                    neighbors: neighbors2,
-                },
-            ) = (&proof[i], &proof[i + 1])
+
+        match previous {
+            _ if !self.compressed || removed > 1 => self.proof.normalize(),
+            Some(at) => self.proof.compress(at.saturating_sub(1)..at),
+            None => {}
+        }
+        self.compressed = true;
+    }
+
+    /// Keeps only the newest leaf for each key, so the proof behaves as a last-writer-wins map.
+    ///
+    /// Leaves are ordered by timestamp first, with untimestamped leaves being the oldest, then by
+    /// value and skip. Since this is a total order, every replica picks the same winner no matter
+    /// in which order it received the writes.
+    fn resolve_writes(proof: &mut Proof) {
+        let mut newest: BTreeMap<Hash, (Option<Timestamp>, Hash, usize)> = BTreeMap::new();
+
+        for step in proof.iter() {
+            if let Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } = step
             {
-                if neighbors1.iter().filter(|&&n| n != Hash::zero()).count() == 1
-                    && neighbors2.iter().filter(|&&n| n != Hash::zero()).count() == 1
-                {
-                    // Merge the two branch nodes
-                    let new_skip = skip1 + skip2 + 1;
-                    let new_neighbors = *neighbors2;
-                    proof[i] = Step::Branch {
-                        skip: new_skip,
-                        neighbors: new_neighbors,
-                    };
-                    proof.remove(i + 1);
-                } else {
-                    i += 1;
-                }
-            } else {
-                i += 1;
+                let write = (*timestamp, *value, *skip);
+                newest
+                    .entry(*key)
+                    .and_modify(|newest| *newest = (*newest).max(write))
+                    .or_insert(write);
             }
         }
+
+        let mut kept = BTreeSet::new();
+        proof.retain(|step| match step {
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => newest[key] == (*timestamp, *value, *skip) && kept.insert(*key),
+            _ => true,
+        });
     }
 
     /// Calculates the root hash of the Merkle Patricia Trie.
-    fn calculate_root(proof: &Proof) -> Hash {
+    ///
+    /// Steps are hashed sorted by their byte representation instead of in the order in which
+    /// they appear in the proof. Merging appends the steps of the other replica, so replicas that merged the same
+    /// states in different orders hold the same steps in different positions, and must still
+    /// agree on the root to converge.
+    ///
+    /// With the `rayon` feature, large proofs are sorted and encoded in parallel, and hashed in
+    /// parallel too when the digest is BLAKE3. Either way, the root is the same as the one of the
+    /// sequential path.
+    fn calculate_root(proof: &Proof, format: RootFormat) -> Hash {
+        let steps = proof.canonical_steps();
+
+        #[cfg(feature = "trace-hashing")]
+        for (index, step) in steps.iter().enumerate() {
+            trace::step::<D>(index, step, format);
+        }
+
+        #[cfg(feature = "rayon")]
+        let root = if steps.len() >= PARALLEL_ROOT_STEPS {
+            Self::hash_steps_parallel(&steps, format)
+        } else {
+            Self::hash_steps(&steps, format)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let root = Self::hash_steps(&steps, format);
+
+        #[cfg(feature = "trace-hashing")]
+        trace::root(steps.len(), format, root);
+
+        root
+    }
+
+    /// Hashes the bytes every step contributes to the root, in order.
+    ///
+    /// All steps feed a single hasher, so their marker bytes are absorbed into its running
+    /// state like any other byte: there is no per-step hasher whose initial state could be
+    /// precomputed. Gathering the steps in a buffer to update the hasher in larger chunks was
+    /// measured with the `root` benchmark, and is slower than feeding the fields directly,
+    /// since the hashers already buffer their input.
+    fn hash_steps(steps: &[&Step], format: RootFormat) -> Hash {
         let mut hasher = D::new();
-        for step in proof.iter() {
-            match step {
-                Step::Branch { neighbors, .. } => {
-                    // First hash the number of non-zero neighbors
-                    let non_zero = neighbors.iter().filter(|&&n| n != Hash::zero()).count();
-                    hasher.update([non_zero as u8]);
-                    // Then hash each non-zero neighbor in order
-                    for neighbor in neighbors.iter().filter(|&&n| n != Hash::zero()) {
-                        hasher.update(neighbor.as_ref());
-                    }
-                }
-                Step::Fork { neighbor, .. } => {
-                    // Hash fork marker
-                    hasher.update([0xFF]);
-                    // Hash nibble and prefix
-                    hasher.update([neighbor.nibble]);
-                    hasher.update(&neighbor.prefix);
-                    // Hash root
-                    hasher.update(neighbor.root.as_ref());
+        for step in steps {
+            step.commit(format, |bytes| hasher.update(bytes));
+        }
+        hasher.finalize32()
+    }
+
+    /// Hashes the same bytes as [`Trie::hash_steps`], encoding segments of the steps on the
+    /// rayon thread pool.
+    #[cfg(feature = "rayon")]
+    fn hash_steps_parallel(steps: &[&Step], format: RootFormat) -> Hash {
+        use rayon::prelude::*;
+
+        let segments: Vec<Vec<u8>> = steps
+            .par_chunks(PARALLEL_SEGMENT_STEPS)
+            .map(|segment| {
+                let mut bytes = Vec::new();
+                for step in segment {
+                    step.commit(format, |chunk| bytes.extend_from_slice(chunk));
                 }
-                Step::Leaf { key, value, .. } => {
-                    // Hash leaf marker
-                    hasher.update([0x00]);
-                    // Hash key and value
-                    hasher.update(key.as_ref());
-                    hasher.update(value.as_ref());
+                bytes
+            })
+            .collect();
+
+        #[cfg(feature = "blake3")]
+        if core::any::TypeId::of::<D>() == core::any::TypeId::of::<blake3::Hasher>() {
+            // BLAKE3 is a tree hash itself, so it can hash the whole encoding in parallel
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_rayon(&segments.concat());
+            return Hash::from_digest(blake3::Hasher::finalize(&hasher).as_bytes());
+        }
+
+        let mut hasher = D::new();
+        for segment in &segments {
+            hasher.update(segment);
+        }
+        hasher.finalize32()
+    }
+}
+
+/// Clones share the steps of the proof until one of them is mutated, so cloning is O(1).
+impl<D: TrieHasher> Trie<D> {
+    /// Returns the root the Trie commits to.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     assert_eq!(trie.root(), Trie::<Blake2s256>::from_bytes(&trie.to_bytes())?.root());
+    ///     assert_eq!(trie.root_bytes(), <[u8; 32]>::from(trie.root()));
+    ///     assert_eq!(trie.root_hex(), trie.root().to_hex());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    /// Returns the bytes of the root the Trie commits to.
+    #[inline]
+    pub fn root_bytes(&self) -> [u8; 32] {
+        self.root.into()
+    }
+
+    /// Returns the root the Trie commits to, as a lowercase hex string.
+    #[inline]
+    pub fn root_hex(&self) -> String {
+        self.root.to_hex()
+    }
+
+    /// Returns the steps of the Trie.
+    #[inline]
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Returns the steps of the Trie, to change them directly.
+    ///
+    /// The Trie keeps committing to its current root until [`Trie::refresh_root`] is called,
+    /// but forgets the root it calculated from the previous steps, so verifications hash the
+    /// changed steps again and fail until then.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     trie.proof_mut().push(Step::Leaf {
+    ///         skip: 0,
+    ///         key: Hash::digest::<Blake2s256>(b"forged"),
+    ///         value: Hash::digest::<Blake2s256>(b"value"),
+    ///         timestamp: None,
+    ///     });
+    ///     assert!(!trie.verify(b"forged", b"value"));
+    ///
+    ///     trie.refresh_root();
+    ///     assert!(trie.verify(b"forged", b"value"));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn proof_mut(&mut self) -> &mut Proof {
+        self.calculated_root = None;
+        self.compressed = false;
+        &mut self.proof
+    }
+
+    /// Returns the steps of the Trie, consuming it.
+    #[inline]
+    pub fn into_proof(self) -> Proof {
+        self.proof
+    }
+}
+
+impl<D: TrieHasher> Clone for Trie<D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            proof: self.proof.clone(),
+            root: self.root,
+            calculated_root: self.calculated_root,
+            applied: self.applied.clone(),
+            memory_budget: self.memory_budget,
+            compressed: self.compressed,
+            scratch: Vec::new(),
+            root_format: self.root_format,
+            entry_limits: self.entry_limits,
+            value_hasher: self.value_hasher.clone(),
+            profile: self.profile,
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<D: TrieHasher> PartialEq for Trie<D> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
+}
+
+impl<D: TrieHasher> Eq for Trie<D> {}
+
+/// Tries are hashed by their root, like they are compared, so replica states can be
+/// deduplicated in sets or used as keys of maps.
+impl<D: TrieHasher> core::hash::Hash for Trie<D> {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        core::hash::Hash::hash(&self.root, state);
+    }
+}
+
+/// Tries are ordered by their root. The order has no meaning besides being total and
+/// consistent with equality, so tries can be kept in ordered collections.
+impl<D: TrieHasher> PartialOrd for Trie<D> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D: TrieHasher> Ord for Trie<D> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.root.cmp(&other.root)
+    }
+}
+
+#[cfg(not(feature = "redact"))]
+impl<D: TrieHasher> core::fmt::Debug for Trie<D> {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Trie")
+            .field("proof", &self.proof)
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+impl<D: TrieHasher + 'static> Default for Trie<D> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<D: TrieHasher + 'static, K: AsRef<[u8]>, V: AsRef<[u8]>> Extend<(K, V)> for Trie<D> {
+    /// Inserts every pair at once, like the batches of bulk loads, so the proof is compressed
+    /// and the root hashed once for the whole iterator.
+    ///
+    /// [`Extend`] can't report errors, so the pairs [`Trie::insert`] would reject, with an empty
+    /// key or exceeding the entry limits, are skipped. If the memory budget can't hold every
+    /// pair, pairs are inserted in order until it is reached. Call [`Trie::insert`] instead to
+    /// handle these errors.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    /// use blake2::Blake2s256;
+    ///
+    /// let mut trie = Trie::<Blake2s256>::empty();
+    /// trie.extend((0..16u8).map(|i| (vec![i + 1], vec![i])));
+    ///
+    /// assert!(trie.verify(&[1], &[0]));
+    /// assert!(trie.verify(&[16], &[15]));
+    /// ```
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        let (limits, values) = (self.entry_limits, self.value_hasher.clone());
+        let hashes: Vec<_> = entries
+            .into_iter()
+            .filter_map(|(key, value)| {
+                Self::hash_entry_slice(key.as_ref(), value.as_ref(), limits, values.as_deref()).ok()
+            })
+            .collect();
+
+        if self.insert_hashed(&hashes).is_err() {
+            for entry in &hashes {
+                if self.insert_hashed(core::slice::from_ref(entry)).is_err() {
+                    break;
                 }
             }
         }
-        Hash::from_slice(hasher.finalize().as_ref())
     }
 }
 
-impl<D: Digest> Clone for Trie<D> {
+impl<D: TrieHasher> ToBytes for Trie<D> {
+    type Output = Vec<u8>;
+
+    /// Encodes the Trie as its proof. The root is not included, as it is derived from the proof.
     #[inline]
-    fn clone(&self) -> Self {
-        Self {
-            proof: self.proof.clone(),
-            root: self.root,
-            _phantom: PhantomData,
-        }
+    fn to_bytes(&self) -> Self::Output {
+        self.proof.to_bytes()
     }
 }
 
-impl<D: Digest> PartialEq for Trie<D> {
+impl<D: TrieHasher + 'static> FromBytes for Trie<D> {
     #[inline]
-    fn eq(&self, other: &Self) -> bool {
-        self.root == other.root
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        Ok(Self::from_proof(Proof::from_bytes_with(bytes, context)?))
+    }
+}
+
+impl<D: TrieHasher + 'static> FromHex for Trie<D> {
+    #[inline]
+    fn from_hex(input: &str) -> Result<Self> {
+        let bytes = hex::decode(input)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Parses a verifier from the hex string of its root, like [`Trie::from_root`]. Roots are
+/// displayed in hex, so `trie.root().to_string()` converts back to a verifier for `trie`.
+///
+/// Unlike [`FromHex`], which decodes a whole Trie, only the root is read, and the proof is left
+/// empty until it is received.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"key", &b"value"[..])?;
+///
+///     let mut verifier = Trie::<Blake2s256>::try_from(&*trie.root().to_string())?;
+///     assert_eq!(verifier.root(), trie.root());
+///
+///     *verifier.proof_mut() = trie.proof().clone();
+///     assert!(verifier.verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Returns [`Error::Deserialization`] if the string is not hex, and [`Error::InvalidLength`] if it
+/// doesn't encode exactly 32 bytes.
+impl<D: TrieHasher + 'static> FromStr for Trie<D> {
+    type Err = Error;
+
+    #[inline]
+    fn from_str(root: &str) -> Result<Self> {
+        Self::from_root(&hex::decode(root)?)
     }
 }
 
-impl<D: Digest> Eq for Trie<D> {}
+/// Constructs a verifier from the hex string of its root, like [`FromStr`], so it can be
+/// written `Trie::try_from(root)`.
+impl<D: TrieHasher + 'static> TryFrom<&str> for Trie<D> {
+    type Error = Error;
 
-impl<D: Digest> std::fmt::Debug for Trie<D> {
     #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Trie")
-            .field("proof", &self.proof)
-            .field("root", &self.root)
-            .finish()
+    fn try_from(root: &str) -> Result<Self> {
+        root.parse()
     }
 }
 
-impl<D: Digest + 'static> Default for Trie<D> {
+impl<D: TrieHasher> ToHex for Trie<D> {
     #[inline]
-    fn default() -> Self {
-        Self::empty()
+    fn to_hex(&self) -> String {
+        hex::encode(ToBytes::to_bytes(self))
     }
 }
 
-impl<D: Digest + 'static> Arbitrary for Trie<D> {
+#[cfg(feature = "std")]
+impl<D: TrieHasher + 'static> Arbitrary for Trie<D> {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
 
@@ -465,34 +2227,42 @@ impl<D: Digest + 'static> Arbitrary for Trie<D> {
     }
 }
 
-impl<D: Digest + 'static> CvRDT for Trie<D> {
+impl<D: TrieHasher + 'static> CvRDT for Trie<D> {
     #[inline]
     fn merge(&mut self, other: &Self) -> Result<(), Error> {
-        let mut merged_proof = self.proof.clone();
-        for step in other.proof.iter() {
-            if !merged_proof.contains(step) {
-                merged_proof.push(step.clone());
-            }
-        }
-
-        self.proof = merged_proof;
-        self.root = Self::calculate_root(&self.proof);
+        self.merge_with(other, self.profile.validates_merges())
+    }
 
-        Ok(())
+    /// The root already commits to the whole state, so it is used as is.
+    #[inline]
+    fn fingerprint<H: TrieHasher>(&self) -> Hash
+    where
+        Self: ToBytes,
+    {
+        self.root
     }
 }
 
-impl<D: Digest + 'static> CmRDT<Proof> for Trie<D> {
+impl<D: TrieHasher + 'static> CmRDT<Proof> for Trie<D> {
     #[inline]
     fn apply(&mut self, op: &Proof) -> Result<(), Error> {
+        let digest = op.hash_bytes::<D>();
+        if self.applied.contains(&digest) {
+            return Ok(());
+        }
+
         let mpf = Self::from_proof(op.clone());
-        self.merge(&mpf)
+        self.merge(&mpf)?;
+        Arc::make_mut(&mut self.applied).insert(digest);
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::cmp::Ordering;
+
+    use core::cmp::Ordering;
 
     use test_strategy::proptest;
 
@@ -502,6 +2272,8 @@ mod tests {
         not(any(
             feature = "blake2",
             feature = "blake3",
+            feature = "keccak",
+            feature = "ripemd",
             feature = "sha2",
             feature = "sha3"
         )),
@@ -519,11 +2291,26 @@ mod tests {
                     type TrieT = Trie<$digest>;
                     $crate::test_state_crdt_properties!(TrieT);
                     $crate::test_op_crdt_properties!(TrieT, Proof);
+                    $crate::test_to_bytes!(TrieT);
+                    $crate::test_convergence!(TrieT, writes());
+                    $crate::test_trie_invariants!($digest);
 
                     fn non_empty_string() -> impl Strategy<Value = String> {
                         any::<String>().prop_filter("must not be empty", |s| !s.is_empty())
                     }
 
+                    /// Small tries drawn from a small key space, so replicas often write to the
+                    /// same keys.
+                    fn writes() -> impl Strategy<Value = TrieT> + Clone {
+                        vec((vec(0..4u8, 1..=2), vec(any::<u8>(), 0..4)), 1..4).prop_map(|entries| {
+                            let mut trie = TrieT::empty();
+                            for (key, value) in entries {
+                                trie.insert(&key, &value[..]).expect("keys are never empty");
+                            }
+                            trie
+                        })
+                    }
+
                     #[proptest]
                     fn test_verify_proof(
                         mut trie: Trie<$digest>,
@@ -536,6 +2323,117 @@ mod tests {
                             key, value);
                     }
 
+                    #[proptest]
+                    fn test_hash_and_order_follow_the_root(
+                        #[strategy(writes())] a: TrieT,
+                        #[strategy(writes())] b: TrieT,
+                    ) {
+                        use std::collections::{BTreeSet, HashSet};
+
+                        let rebuilt = TrieT::from_proof(a.proof.clone());
+                        let distinct = if a.root == b.root { 1 } else { 2 };
+
+                        let states = [a.clone(), rebuilt, b.clone()];
+                        prop_assert_eq!(HashSet::from(states.clone()).len(), distinct);
+                        prop_assert_eq!(BTreeSet::from(states).len(), distinct);
+                        prop_assert_eq!(a.cmp(&b), a.root.cmp(&b.root));
+                    }
+
+                    #[proptest]
+                    fn test_value_hashers(
+                        #[strategy(vec((vec(any::<u8>(), 1..4), vec(any::<u8>(), 0..64)), 1..8))]
+                        entries: Vec<(Vec<u8>, Vec<u8>)>,
+                    ) {
+                        let build = |configure: &dyn Fn(&mut TrieT)| -> Result<TrieT> {
+                            let mut trie = TrieT::empty();
+                            configure(&mut trie);
+                            for (key, value) in &entries {
+                                trie.insert(key, &value[..])?;
+                            }
+                            Ok(trie)
+                        };
+
+                        let plain = build(&|_| {})?;
+                        let explicit = build(&|trie| trie.set_value_hasher(PlainValues))?;
+                        prop_assert_eq!(&explicit, &plain);
+
+                        let hashers: [&dyn Fn(&mut TrieT); 2] = [
+                            &|trie| trie.set_value_hasher(LengthPrefixed),
+                            &|trie| trie.set_value_hasher(ChunkedMerkle { chunk_len: 8 }),
+                        ];
+                        for configure in hashers {
+                            let trie = build(configure)?;
+                            prop_assert_ne!(&trie, &plain);
+
+                            let mut extended = TrieT::empty();
+                            configure(&mut extended);
+                            extended.extend(entries.iter().cloned());
+                            prop_assert_eq!(&extended, &trie);
+
+                            let (key, value) = entries.last().unwrap();
+                            prop_assert!(trie.verify(key, value));
+                            prop_assert!(!TrieT::from_proof(trie.proof.clone()).verify(key, value));
+                            prop_assert!(trie.verify_detailed(key, value).is_ok());
+                            prop_assert!(trie.verify_constant_time(key, value));
+                            prop_assert_eq!(trie.verify_batch(&[(key, value)]), vec![true]);
+                        }
+                    }
+
+                    #[proptest]
+                    fn test_try_from_hex_root(#[strategy(writes())] trie: TrieT) {
+                        let verifier = TrieT::try_from(&*trie.root().to_string())?;
+                        prop_assert_eq!(&verifier, &trie);
+                        prop_assert!(verifier.is_empty());
+
+                        prop_assert!(TrieT::try_from("00").is_err());
+                        prop_assert!(TrieT::try_from("not hex").is_err());
+                    }
+
+                    #[proptest]
+                    fn test_extend_matches_insert(
+                        mut trie: Trie<$digest>,
+                        #[strategy(vec((vec(any::<u8>(), 0..4), vec(any::<u8>(), 0..8)), 0..16))]
+                        entries: Vec<(Vec<u8>, Vec<u8>)>,
+                    ) {
+                        let mut expected = trie.clone();
+                        for (key, value) in &entries {
+                            // Empty keys are skipped by extend
+                            let _ = expected.insert(key, &value[..]);
+                        }
+
+                        trie.extend(entries);
+                        prop_assert_eq!(trie, expected);
+                    }
+
+                    #[proptest]
+                    fn test_from_leaf_hashes_matches_insert(
+                        #[strategy(vec((vec(any::<u8>(), 1..4), vec(any::<u8>(), 0..8)), 0..16))]
+                        entries: Vec<(Vec<u8>, Vec<u8>)>,
+                    ) {
+                        let mut expected = TrieT::empty();
+                        for (key, value) in &entries {
+                            expected.insert(key, &value[..])?;
+                        }
+
+                        let trie = TrieT::from_leaf_hashes(entries.iter().map(|(key, value)| {
+                            (Hash::digest::<$digest>(key), Hash::digest::<$digest>(value))
+                        }));
+                        prop_assert_eq!(&trie.proof, &expected.proof);
+                        prop_assert_eq!(trie.root(), expected.root());
+                    }
+
+                    #[proptest]
+                    fn test_extend_stops_at_memory_budget(
+                        #[strategy(vec((vec(any::<u8>(), 1..4), vec(any::<u8>(), 0..8)), 1..16))]
+                        entries: Vec<(Vec<u8>, Vec<u8>)>,
+                    ) {
+                        let mut trie = TrieT::empty();
+                        trie.set_memory_budget(Some(trie.memory_usage().bytes));
+
+                        trie.extend(entries);
+                        prop_assert!(trie.is_empty());
+                    }
+
                     #[proptest]
                     fn test_insert(
                         mut trie: Trie<$digest>,
@@ -548,6 +2446,21 @@ mod tests {
                         prop_assert_ne!(trie, original_trie);
                     }
 
+                    #[cfg(feature = "tokio")]
+                    #[proptest]
+                    fn test_insert_async_matches_insert(
+                        mut trie: Trie<$digest>,
+                        #[strategy(non_empty_string())] key: String,
+                        #[strategy(vec(any::<u8>(), 0..40000))] value: Vec<u8>
+                    ) {
+                        let mut expected = trie.clone();
+                        expected.insert(key.as_bytes(), &value[..])?;
+
+                        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+                        runtime.block_on(trie.insert_async(key.as_bytes(), &value[..]))?;
+                        prop_assert_eq!(trie, expected);
+                    }
+
                     #[proptest]
                     fn test_multiple_inserts(
                         mut trie: Trie<$digest>,
@@ -660,21 +2573,678 @@ mod tests {
                         prop_assert!(default_trie.is_empty(), "Default instance should be empty");
                     }
 
+                    #[proptest]
+                    fn test_apply_batch_matches_apply(#[strategy(vec(writes(), 0..4))] writes: Vec<TrieT>) {
+                        let mut ops: Vec<Proof> = writes.into_iter().map(|trie| trie.proof).collect();
+                        ops.extend(ops.clone());
+
+                        let mut batched = TrieT::empty();
+                        batched.apply_batch(&ops)?;
+
+                        let mut sequential = TrieT::empty();
+                        for op in ops.iter() {
+                            sequential.apply(op)?;
+                        }
+
+                        prop_assert_eq!(&batched.proof, &sequential.proof);
+                        prop_assert_eq!(batched.root, sequential.root);
+                        prop_assert_eq!(batched.apply_batch(&ops)?, 0);
+                    }
+
+                    #[test]
+                    fn test_apply_batch_of_nothing() -> Result<()> {
+                        let mut trie = TrieT::empty();
+                        assert_eq!(trie.apply_batch(&[])?, 0);
+                        assert!(trie.is_empty());
+                        assert_eq!(trie.root(), TrieT::empty().root());
+                        Ok(())
+                    }
+
+                    #[proptest]
+                    fn test_from_proof_with_handles_duplicate_leaves(
+                        #[strategy(writes())] a: TrieT,
+                        #[strategy(writes())] b: TrieT,
+                    ) {
+                        prop_assert_eq!(
+                            TrieT::from_proof_with(a.proof.clone(), DuplicateLeaves::Reject)?,
+                            TrieT::from_proof(a.proof.clone())
+                        );
+
+                        let keys = |trie: &TrieT| -> BTreeSet<Hash> {
+                            trie.proof
+                                .iter()
+                                .filter_map(|step| match step {
+                                    Step::Leaf { key, .. } => Some(*key),
+                                    _ => None,
+                                })
+                                .collect()
+                        };
+                        let shared = !keys(&a).is_disjoint(&keys(&b));
+
+                        let both = Proof::from(a.proof.iter().chain(b.proof.iter()).cloned().collect::<Vec<_>>());
+                        prop_assert_eq!(
+                            TrieT::from_proof_with(both.clone(), DuplicateLeaves::Reject).is_err(),
+                            shared
+                        );
+
+                        let resolved = TrieT::from_proof_with(both, DuplicateLeaves::Resolve)?;
+                        prop_assert!(TrieT::from_proof_with(resolved.proof.clone(), DuplicateLeaves::Reject).is_ok());
+                        prop_assert_eq!(keys(&resolved), &keys(&a) | &keys(&b));
+                    }
+
+                    #[proptest]
+                    fn test_merge_keeps_newest_write(
+                        #[strategy(non_empty_string())] key: String,
+                        value1: String,
+                        value2: String,
+                        timestamp1: Timestamp,
+                        timestamp2: Timestamp,
+                    ) {
+                        prop_assume!(timestamp1 != timestamp2);
+
+                        let mut a = TrieT::empty();
+                        a.insert_at(key.as_bytes(), value1.as_bytes(), timestamp1)?;
+                        let mut b = TrieT::empty();
+                        b.insert_at(key.as_bytes(), value2.as_bytes(), timestamp2)?;
+
+                        let (newest, oldest) = if timestamp1 > timestamp2 {
+                            (&value1, &value2)
+                        } else {
+                            (&value2, &value1)
+                        };
+
+                        let mut ab = a.clone();
+                        ab.merge(&b)?;
+                        let mut ba = b.clone();
+                        ba.merge(&a)?;
+
+                        prop_assert_eq!(&ab.proof, &ba.proof);
+                        prop_assert_eq!(ab.root, ba.root);
+                        prop_assert!(ab.verify(key.as_bytes(), newest.as_bytes()));
+                        prop_assert_eq!(ab.verify(key.as_bytes(), oldest.as_bytes()), newest == oldest);
+                    }
+
+                    #[proptest]
+                    fn test_insert_at_discards_stale_writes(
+                        #[strategy(non_empty_string())] key: String,
+                        value1: String,
+                        value2: String,
+                        timestamp1: Timestamp,
+                        timestamp2: Timestamp,
+                    ) {
+                        prop_assume!(timestamp1 > timestamp2);
+
+                        let mut trie = TrieT::empty();
+                        trie.insert_at(key.as_bytes(), value1.as_bytes(), timestamp1)?;
+                        let root = trie.root;
+
+                        trie.insert_at(key.as_bytes(), value2.as_bytes(), timestamp2)?;
+                        prop_assert_eq!(trie.root, root);
+                        prop_assert!(trie.verify(key.as_bytes(), value1.as_bytes()));
+                    }
+
+                    #[proptest]
+                    fn test_timestamp_is_committed_to_root(
+                        #[strategy(non_empty_string())] key: String,
+                        value: String,
+                        timestamp1: Timestamp,
+                        timestamp2: Timestamp,
+                    ) {
+                        prop_assume!(timestamp1 != timestamp2);
+
+                        let mut a = TrieT::empty();
+                        a.insert_at(key.as_bytes(), value.as_bytes(), timestamp1)?;
+                        let mut b = TrieT::empty();
+                        b.insert_at(key.as_bytes(), value.as_bytes(), timestamp2)?;
+
+                        prop_assert_ne!(a.root, b.root);
+                    }
+
+                    #[proptest]
+                    fn test_fingerprint_is_root(#[strategy(writes())] trie: TrieT) {
+                        prop_assert_eq!(trie.fingerprint::<$digest>(), trie.root);
+                    }
+
                     #[proptest]
                     fn test_root_matches_calculated(
                         trie: Trie<$digest>
                     ) {
-                        let calculated_root = Trie::<$digest>::calculate_root(&trie.proof);
+                        let calculated_root = Trie::<$digest>::calculate_root(&trie.proof, RootFormat::V1);
                         prop_assert_eq!(trie.root, calculated_root, "Root should match calculated root");
                     }
 
                     #[proptest]
                     fn test_from_proof_root_calculation(proof: Proof) {
                         let trie = Trie::<$digest>::from_proof(proof.clone());
-                        let calculated_root = Trie::<$digest>::calculate_root(&proof);
+                        let calculated_root = Trie::<$digest>::calculate_root(&proof, RootFormat::V1);
                         prop_assert_eq!(trie.root, calculated_root, "Root should match calculated root after from_proof");
                     }
 
+                    // Large proofs are slow to generate, and spanning a few segments is enough.
+                    #[cfg(feature = "rayon")]
+                    #[proptest(cases = 16)]
+                    fn test_parallel_root_matches_sequential(
+                        #[strategy(vec(any::<Step>(), 0..2500))] steps: Vec<Step>,
+                        #[strategy(prop_oneof![Just(RootFormat::V1), Just(RootFormat::V2)])]
+                        format: RootFormat,
+                    ) {
+                        let proof = Proof::from(steps);
+                        let steps = proof.canonical_steps();
+
+                        prop_assert_eq!(
+                            Trie::<$digest>::hash_steps_parallel(&steps, format),
+                            Trie::<$digest>::hash_steps(&steps, format)
+                        );
+                    }
+
+                    #[proptest]
+                    fn test_verify_batch_matches_verify(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec((vec(0..4u8, 1..=2), vec(any::<u8>(), 0..4)), 0..8))]
+                        pairs: Vec<(Vec<u8>, Vec<u8>)>,
+                        tamper: bool,
+                    ) {
+                        let mut trie = trie;
+                        if tamper {
+                            trie.root = Hash::zero();
+                        }
+
+                        let pairs: Vec<(&[u8], &[u8])> = pairs
+                            .iter()
+                            .map(|(key, value)| (&key[..], &value[..]))
+                            .collect();
+                        let expected: Vec<bool> = pairs
+                            .iter()
+                            .map(|(key, value)| trie.verify(key, value))
+                            .collect();
+
+                        prop_assert_eq!(trie.verify_batch(&pairs), expected);
+                    }
+
+                    #[proptest]
+                    fn test_merge_verified_rejects_forged_states(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(writes())] other: TrieT,
+                        root: Hash,
+                        #[strategy(MAX_SKIP + 1..=usize::MAX)] skip: usize,
+                    ) {
+                        let mut merged = trie.clone();
+                        merged.merge(&other)?;
+                        let mut verified = trie.clone();
+                        verified.merge_verified(&other)?;
+                        prop_assert_eq!(&verified, &merged);
+
+                        prop_assume!(root != other.root);
+                        let mut forged = other.clone();
+                        forged.root = root;
+                        let mut unchanged = trie.clone();
+                        prop_assert!(matches!(
+                            unchanged.merge_verified(&forged),
+                            Err(Error::InvalidProof(_))
+                        ));
+                        prop_assert_eq!(&unchanged, &trie);
+
+                        let mut malformed = other.proof.clone();
+                        malformed.push(Step::Branch { skip, neighbors: [Hash::zero(); 4] });
+                        prop_assert!(matches!(
+                            unchanged.merge_verified(&TrieT::from_proof(malformed)),
+                            Err(Error::InvalidProof(_))
+                        ));
+                        prop_assert!(unchanged.merge_verified(&TrieT::empty()).is_ok());
+                    }
+
+                    #[proptest]
+                    fn test_profiles_apply_to_every_verifier(
+                        #[strategy(vec(any::<u8>(), 1..4))] key: Vec<u8>,
+                        a: Vec<u8>,
+                        b: Vec<u8>,
+                    ) {
+                        prop_assume!(a != b);
+                        let (mut x, mut y) = (TrieT::empty(), TrieT::empty());
+                        x.insert(&key, &a[..])?;
+                        y.insert(&key, &b[..])?;
+
+                        let mut repeated = x.clone();
+                        repeated.proof_mut().push(x.proof()[0].clone());
+                        repeated.refresh_root();
+                        let conflicting = TrieT::from_proof(Proof::from(
+                            x.proof().iter().chain(y.proof().iter()).cloned().collect::<Vec<_>>(),
+                        ));
+
+                        let pair: (&[u8], &[u8]) = (&key, &a);
+                        for mut trie in [repeated, conflicting] {
+                            prop_assert!(trie.verify_detailed(pair.0, pair.1).is_ok());
+                            prop_assert!(trie.verify_constant_time(pair.0, pair.1));
+                            prop_assert_eq!(trie.verify_batch(&[pair]), [true]);
+
+                            trie.set_profile(Profile::Paranoid);
+                            prop_assert!(!trie.verify(pair.0, pair.1));
+                            prop_assert_eq!(
+                                trie.verify_detailed(pair.0, pair.1),
+                                Err(VerifyError::NotStrict)
+                            );
+                            prop_assert!(!trie.verify_constant_time(pair.0, pair.1));
+                            prop_assert_eq!(trie.verify_batch(&[pair]), [false]);
+                        }
+
+                        x.set_profile(Profile::Paranoid);
+                        prop_assert!(x.verify_detailed(pair.0, pair.1).is_ok());
+                        prop_assert!(x.verify_constant_time(pair.0, pair.1));
+                        prop_assert_eq!(x.verify_batch(&[pair]), [true]);
+                    }
+
+                    #[proptest]
+                    fn test_profiles_check_merged_states(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(writes())] other: TrieT,
+                        root: Hash,
+                    ) {
+                        prop_assume!(root != other.root);
+                        let mut forged = other.clone();
+                        forged.root = root;
+
+                        let mut permissive = trie.clone();
+                        permissive.merge(&forged)?;
+                        prop_assert_eq!(permissive.profile(), Profile::Permissive);
+
+                        for profile in [Profile::Standard, Profile::Paranoid] {
+                            let mut strict = TrieT::builder()
+                                .proof(trie.proof.clone())
+                                .profile(profile)
+                                .build()?;
+                            prop_assert!(matches!(
+                                strict.merge(&forged),
+                                Err(Error::InvalidProof(_))
+                            ));
+                            prop_assert_eq!(&strict, &trie);
+
+                            strict.merge_from_bytes(&other.to_bytes())?;
+                            let mut expected = trie.clone();
+                            expected.merge(&other)?;
+                            prop_assert_eq!(&strict, &expected);
+                        }
+                    }
+
+                    #[proptest]
+                    fn test_root_format_v2_separates_colliding_proofs(
+                        #[strategy(vec(any::<u8>(), 0..8))] p: Vec<u8>,
+                        #[strategy(vec(any::<u8>(), 0..8))] q: Vec<u8>,
+                        r: Hash,
+                        s: Hash,
+                        #[strategy(1..=MAX_SKIP)] skip: usize,
+                    ) {
+                        prop_assume!(!r.is_zero());
+                        let fork = |nibble, prefix: Vec<u8>, root| Step::Fork {
+                            skip: 0,
+                            neighbor: Neighbor { nibble, prefix, root },
+                        };
+                        let mut joined = p.clone();
+                        joined.extend_from_slice(r.as_ref());
+                        joined.extend_from_slice(&[0xFF, 0x02]);
+                        joined.extend_from_slice(&q);
+
+                        let branch = |skip, neighbors| Step::Branch { skip, neighbors };
+                        let leaf = |skip| Step::Leaf { skip, key: r, value: s, timestamp: None };
+                        let colliding = [
+                            (
+                                vec![fork(1, p, r), fork(2, q, s)],
+                                vec![fork(1, joined, s)],
+                            ),
+                            (
+                                vec![branch(0, [r, Hash::zero(), Hash::zero(), Hash::zero()])],
+                                vec![branch(0, [Hash::zero(), r, Hash::zero(), Hash::zero()])],
+                            ),
+                            (vec![branch(0, [r; 4])], vec![branch(skip, [r; 4])]),
+                            (vec![leaf(0)], vec![leaf(skip)]),
+                        ];
+
+                        for (left, right) in colliding {
+                            let mut left = TrieT::from_proof(Proof::from(left));
+                            let mut right = TrieT::from_proof(Proof::from(right));
+                            prop_assert_eq!(left.root, right.root);
+
+                            left.set_root_format(RootFormat::V2);
+                            right.set_root_format(RootFormat::V2);
+                            prop_assert_ne!(left.root, right.root);
+                        }
+                    }
+
+                    #[proptest]
+                    fn test_set_root_format_round_trips(#[strategy(writes())] trie: TrieT) {
+                        let mut v2 = trie.clone();
+                        v2.set_root_format(RootFormat::V2);
+                        prop_assert_eq!(v2.root_format(), RootFormat::V2);
+                        prop_assert_eq!(v2.is_empty(), v2.root == trie.root);
+
+                        v2.set_root_format(RootFormat::V1);
+                        prop_assert_eq!(v2.root, trie.root);
+                    }
+
+                    #[proptest]
+                    fn test_verify_detailed_matches_verify(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec(0..4u8, 1..=2))] key: Vec<u8>,
+                        #[strategy(vec(any::<u8>(), 0..4))] value: Vec<u8>,
+                        root: Hash,
+                        tamper: bool,
+                    ) {
+                        let mut trie = trie;
+                        if tamper {
+                            trie.root = root;
+                        }
+
+                        let result = trie.verify_detailed(&key, &value);
+                        prop_assert_eq!(result.is_ok(), trie.verify(&key, &value));
+                        match result {
+                            Err(VerifyError::RootMismatch { expected, computed }) => {
+                                prop_assert!(tamper);
+                                prop_assert_eq!(expected, root);
+                                prop_assert_ne!(computed, root);
+                            }
+                            Err(error) => prop_assert_eq!(error, VerifyError::LeafNotFound),
+                            Ok(()) => {}
+                        }
+
+                        let mut malformed = trie.proof.clone();
+                        malformed.push(Step::Branch { skip: MAX_SKIP + 1, neighbors: [Hash::zero(); 4] });
+                        let index = malformed.len() - 1;
+                        prop_assert_eq!(
+                            TrieT::from_proof(malformed).verify_detailed(&key, &value),
+                            Err(VerifyError::MalformedStep { index })
+                        );
+                    }
+
+                    #[proptest]
+                    fn test_untrusted_proofs_never_panic(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec(any::<(usize, u8)>(), 0..8))] flips: Vec<(usize, u8)>,
+                        #[strategy(vec(any::<u8>(), 0..64))] tail: Vec<u8>,
+                        #[strategy(vec(0..4u8, 1..=2))] key: Vec<u8>,
+                        #[strategy(vec(any::<u8>(), 0..4))] value: Vec<u8>,
+                    ) {
+                        let mut bytes = trie.proof.to_bytes().to_vec();
+                        let len = bytes.len();
+                        for (at, flip) in flips {
+                            if let Some(byte) = bytes.get_mut(at % len.max(1)) {
+                                *byte ^= flip;
+                            }
+                        }
+                        bytes.extend_from_slice(&tail);
+
+                        let _ = calculate_root_from_reader::<$digest, _>(&bytes[..]);
+                        if let Ok(proof) = Proof::from_bytes(&bytes) {
+                            let untrusted = TrieT::from_proof(proof);
+                            let _ = untrusted.verify(&key, &value);
+                            let _ = untrusted.verify_with(&key, &value, VerifyMode::Strict);
+                            let _ = untrusted.verify_detailed(&key, &value);
+                            let _ = untrusted.verify_constant_time(&key, &value);
+                        }
+                    }
+
+                    #[proptest]
+                    fn test_verify_constant_time_matches_verify(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec(0..4u8, 1..=2))] key: Vec<u8>,
+                        #[strategy(vec(any::<u8>(), 0..4))] value: Vec<u8>,
+                        tamper: bool,
+                    ) {
+                        let mut trie = trie;
+                        if tamper {
+                            trie.root = Hash::zero();
+                        }
+
+                        prop_assert_eq!(
+                            trie.verify_constant_time(&key, &value),
+                            trie.verify(&key, &value)
+                        );
+                    }
+
+                    #[proptest]
+                    fn test_incremental_compression_matches_full(
+                        #[strategy(prop_oneof![writes(), any::<TrieT>()])] trie: TrieT,
+                        #[strategy(vec((vec(0..4u8, 1..=2), vec(any::<u8>(), 0..4)), 1..16))]
+                        pairs: Vec<(Vec<u8>, Vec<u8>)>,
+                    ) {
+                        let mut incremental = trie.clone();
+                        let mut full = trie;
+
+                        for (key, value) in &pairs {
+                            incremental.insert(key, &value[..])?;
+
+                            // Refreshing the root makes the next insertion compress everything
+                            full.refresh_root();
+                            full.insert(key, &value[..])?;
+
+                            prop_assert_eq!(&incremental.proof, &full.proof);
+                            prop_assert_eq!(incremental.root, full.root);
+                        }
+                    }
+
+                    #[proptest(cases = 32)]
+                    fn test_insert_file_matches_insert(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+                        value: Vec<u8>,
+                    ) {
+                        let path = std::env::temp_dir().join(format!(
+                            "mutree-test-insert-file-{}-{}",
+                            stringify!($digest),
+                            std::process::id()
+                        ));
+                        std::fs::write(&path, &value).unwrap();
+
+                        let mut expected = trie.clone();
+                        let mut trie = trie;
+                        let inserted = trie.insert_file(&key, &path);
+                        std::fs::remove_file(&path).unwrap();
+
+                        prop_assert_eq!(inserted?, expected.insert(&key, &value[..])?);
+                        prop_assert_eq!(&trie.proof, &expected.proof);
+                        prop_assert_eq!(trie.root, expected.root);
+                        prop_assert!(trie.verify(&key, &value));
+                    }
+
+                    #[proptest]
+                    fn test_entry_limits_refuse_oversized_entries(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(vec(any::<u8>(), 1..16))] key: Vec<u8>,
+                        #[strategy(vec(any::<u8>(), 0..40000))] value: Vec<u8>,
+                        #[strategy(1..16usize)] max_key_len: usize,
+                        #[strategy(0..40000u64)] max_value_len: u64,
+                    ) {
+                        let mut trie = trie;
+                        trie.set_entry_limits(EntryLimits { max_key_len, max_value_len });
+                        let before = trie.clone();
+
+                        let fits = key.len() <= max_key_len && value.len() as u64 <= max_value_len;
+                        match trie.insert(&key, &value[..]) {
+                            Ok(_) => {
+                                prop_assert!(fits);
+                                prop_assert!(trie.verify(&key, &value));
+                            }
+                            Err(error) => {
+                                prop_assert!(!fits);
+                                prop_assert!(matches!(error, Error::InvalidOperation(_)));
+                                prop_assert_eq!(&trie, &before);
+                            }
+                        }
+                    }
+
+                    #[test]
+                    fn test_insert_reuses_scratch_buffer() -> Result<()> {
+                        let mut trie = TrieT::empty();
+                        trie.insert(b"a", &b"1"[..])?;
+                        let scratch = trie.scratch.as_ptr();
+
+                        trie.insert(b"b", &b"2"[..])?;
+                        trie.insert_at(b"c", &b"3"[..], HybridClock::new().tick_at(1))?;
+                        assert_eq!(trie.scratch.as_ptr(), scratch);
+                        assert!(trie.verify(b"a", b"1") && trie.verify(b"b", b"2") && trie.verify(b"c", b"3"));
+
+                        // Clones get their own buffer when they need one
+                        assert!(trie.clone().scratch.is_empty());
+
+                        Ok(())
+                    }
+
+                    #[test]
+                    fn test_insert_compresses_around_replaced_leaf() -> Result<()> {
+                        let single = |skip| Step::Branch {
+                            skip,
+                            neighbors: [Hash::zero(), Hash::zero(), Hash::zero(), Hash::from_array([1; 32])],
+                        };
+                        let leaf = Step::Leaf {
+                            skip: 0,
+                            key: Hash::digest::<$digest>(b"a"),
+                            value: Hash::zero(),
+                            timestamp: None,
+                        };
+
+                        let mut trie = TrieT::from_proof(Proof::from(vec![single(1), leaf, single(2)]));
+                        trie.insert(b"b", &b"1"[..])?;
+                        assert_eq!(trie.proof.len(), 4);
+
+                        // Replacing the leaf makes both branches adjacent
+                        trie.insert(b"a", &b"2"[..])?;
+                        assert_eq!(trie.proof.len(), 3);
+                        assert_eq!(trie.proof[0], single(4));
+                        assert!(trie.verify(b"a", b"2"));
+                        assert!(trie.verify(b"b", b"1"));
+
+                        Ok(())
+                    }
+
+                    #[test]
+                    fn test_insert_does_not_compress_past_max_skip() -> Result<()> {
+                        let single = |skip| Step::Branch {
+                            skip,
+                            neighbors: [Hash::zero(), Hash::zero(), Hash::zero(), Hash::from_array([1; 32])],
+                        };
+
+                        let mut trie = TrieT::from_proof(Proof::from(vec![single(MAX_SKIP), single(usize::MAX)]));
+                        trie.insert(b"a", &b"1"[..])?;
+                        assert_eq!(trie.proof[..2], [single(MAX_SKIP), single(usize::MAX)]);
+
+                        let mut trie = TrieT::from_proof(Proof::from(vec![single(31), single(32)]));
+                        trie.insert(b"a", &b"1"[..])?;
+                        assert_eq!(trie.proof[0], single(MAX_SKIP));
+                        assert!(trie.verify(b"a", b"1"));
+
+                        Ok(())
+                    }
+
+                    #[test]
+                    fn test_reserve_respects_memory_budget() -> Result<()> {
+                        let mut trie = TrieT::with_capacity(4);
+                        trie.set_memory_budget(Some(trie.memory_usage().bytes));
+
+                        // Room that is already there is free
+                        trie.reserve(4)?;
+                        assert!(matches!(
+                            trie.reserve(1024),
+                            Err(Error::MemoryBudgetExceeded { .. })
+                        ));
+                        assert!(trie.proof.capacity() < 1024);
+
+                        trie.set_memory_budget(None);
+                        trie.reserve(1024)?;
+                        assert!(trie.proof.capacity() >= 1024);
+
+                        Ok(())
+                    }
+
+                    #[proptest]
+                    fn test_memory_budget_leaves_trie_unchanged(
+                        #[strategy(writes())] trie: TrieT,
+                        #[strategy(writes())] other: TrieT,
+                        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+                        value: Vec<u8>,
+                    ) {
+                        let mut trie = trie;
+                        let usage = trie.memory_usage();
+                        prop_assert_eq!(usage.steps, trie.proof.len());
+                        trie.set_memory_budget(Some(usage.bytes));
+
+                        let before = trie.clone();
+                        let expect_unchanged = |result: Result<()>, trie: &TrieT| -> Result<(), TestCaseError> {
+                            match result {
+                                Err(Error::MemoryBudgetExceeded { used, budget }) => {
+                                    prop_assert!(used > budget);
+                                    prop_assert_eq!(&trie.proof, &before.proof);
+                                    prop_assert_eq!(trie.root, before.root);
+                                }
+                                result => prop_assert!(result.is_ok()),
+                            }
+                            Ok(())
+                        };
+
+                        let result = trie.insert(&key, &value[..]).map(|_| ());
+                        prop_assert!(result.is_err());
+                        expect_unchanged(result, &trie)?;
+
+                        let result = trie.merge(&other);
+                        expect_unchanged(result, &trie)?;
+
+                        let mut trie = before.clone();
+                        let ops = [other.proof];
+                        let result = trie.apply_batch(&ops).map(|_| ());
+                        let refused = result.is_err();
+                        expect_unchanged(result, &trie)?;
+                        if refused {
+                            trie.set_memory_budget(None);
+                            prop_assert_eq!(trie.apply_batch(&ops)?, 1);
+                        }
+                    }
+
+                    #[proptest]
+                    fn test_verify_checks_refreshed_root(
+                        #[strategy(writes())] mut trie: TrieT,
+                        #[strategy(writes())] other: TrieT,
+                        #[strategy(vec(any::<u8>(), 1..4))] key: Vec<u8>,
+                        value: Vec<u8>,
+                    ) {
+                        prop_assume!(trie.root != other.root);
+                        trie.insert(&key, &value[..])?;
+
+                        let mut tampered = trie.clone();
+                        tampered.root = other.root;
+                        prop_assert!(!tampered.verify(&key, &value));
+
+                        tampered.refresh_root();
+                        prop_assert_eq!(tampered.root, trie.root);
+                        prop_assert!(tampered.verify(&key, &value));
+
+                        let mut replaced = other.clone();
+                        *replaced.proof_mut() = trie.proof().clone();
+                        replaced.refresh_root();
+                        prop_assert_eq!(replaced.root, trie.root);
+                        prop_assert!(replaced.verify(&key, &value));
+                    }
+
+                    #[proptest]
+                    fn test_verify_rejects_changed_proof(
+                        #[strategy(writes())] mut trie: TrieT,
+                        #[strategy(vec(any::<u8>(), 1..4))] key: Vec<u8>,
+                        value: Vec<u8>,
+                    ) {
+                        trie.insert(&key, &value[..])?;
+                        prop_assert!(trie.verify(&key, &value));
+
+                        trie.proof_mut().push(Step::Leaf {
+                            skip: 0,
+                            key: Hash::digest::<$digest>(b"forged"),
+                            value: Hash::digest::<$digest>(b"x"),
+                            timestamp: None,
+                        });
+                        let forged: (&[u8], &[u8]) = (b"forged", b"x");
+
+                        prop_assert!(!trie.verify(forged.0, forged.1));
+                        prop_assert!(!trie.verify_constant_time(forged.0, forged.1));
+                        prop_assert!(trie.verify_detailed(forged.0, forged.1).is_err());
+                        prop_assert_eq!(trie.verify_batch(&[forged]), [false]);
+                        prop_assert!(!trie.verify(&key, &value));
+                    }
+
                     #[proptest]
                     fn test_verify_non_existent(
                         mut trie: Trie<$digest>,
@@ -814,6 +3384,96 @@ mod tests {
     #[cfg(feature = "sha3")]
     generate_mpf_tests!(Sha3_256);
 
+    #[cfg(feature = "keccak")]
+    type Keccak256 = sha3::Keccak256;
+    #[cfg(feature = "keccak")]
+    generate_mpf_tests!(Keccak256);
+
+    #[cfg(feature = "ripemd")]
+    type Ripemd160 = ripemd::Ripemd160;
+    #[cfg(feature = "ripemd")]
+    generate_mpf_tests!(Ripemd160);
+
+    #[cfg(all(feature = "rayon", feature = "blake3"))]
+    #[proptest(cases = 16)]
+    fn test_parallel_blake3_matches_sequential(
+        #[strategy(0..3 * PARALLEL_VALUE_CHUNK)] len: usize,
+        #[strategy(1..100_000usize)] read_len: usize,
+        seed: u8,
+    ) {
+        /// Returns at most `read_len` bytes per read, like a socket.
+        struct Trickle<'a>(&'a [u8], usize);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.1);
+                std::io::Read::read(&mut self.0, &mut buf[..len])
+            }
+        }
+
+        let value: Vec<u8> = (0..len)
+            .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+            .collect();
+        let (_, value_hash) = Trie::<blake3::Hasher>::hash_entry(
+            b"key",
+            Trickle(&value, read_len),
+            &mut Vec::new(),
+            EntryLimits::default(),
+            None,
+        )?;
+
+        prop_assert_eq!(
+            value_hash,
+            Hash::from_digest(blake3::hash(&value).as_bytes())
+        );
+    }
+
+    #[cfg(feature = "blake2")]
+    #[proptest]
+    fn test_verify_does_not_rehash_unchanged_proof(
+        #[strategy(proptest::collection::vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        use std::cell::Cell;
+
+        std::thread_local! {
+            static FINALIZED: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// Blake2s, counting how many hashes are computed.
+        struct Counting(blake2::Blake2s256);
+
+        impl TrieHasher for Counting {
+            fn new() -> Self {
+                Counting(TrieHasher::new())
+            }
+
+            fn update(&mut self, data: &[u8]) {
+                TrieHasher::update(&mut self.0, data);
+            }
+
+            fn finalize32(self) -> Hash {
+                FINALIZED.with(|count| count.set(count.get() + 1));
+                self.0.finalize32()
+            }
+        }
+
+        let mut trie = Trie::<Counting>::empty();
+        trie.insert(&key, &value[..])?;
+
+        // Only the key and value are hashed, not the proof.
+        let before = FINALIZED.with(Cell::get);
+        prop_assert!(trie.verify(&key, &value));
+        prop_assert_eq!(FINALIZED.with(Cell::get) - before, 2);
+
+        // A Trie that doesn't know the root of its proof still checks it.
+        let mut unchecked = Trie::<Counting>::from_root(trie.root.as_ref())?;
+        *unchecked.proof_mut() = trie.proof().clone();
+        let before = FINALIZED.with(Cell::get);
+        prop_assert!(unchecked.verify(&key, &value));
+        prop_assert_eq!(FINALIZED.with(Cell::get) - before, 3);
+    }
+
     #[proptest]
     fn test_merkle_proof_reflexive(proof: Proof) {
         prop_assert_eq!(proof.partial_cmp(&proof), Some(Ordering::Equal));
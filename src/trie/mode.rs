@@ -0,0 +1,62 @@
+use alloc::collections::BTreeSet;
+
+use crate::prelude::*;
+
+/// How strictly a proof is checked by [`Trie::verify_with`].
+///
+/// Proofs merged from several replicas can carry steps unrelated to the verified key, or leaves
+/// holding different values for it. They still commit to the root, so the default mode accepts
+/// them as long as the verified pair is in the proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VerifyMode {
+    /// Accepts any proof committing to the root and holding the verified pair.
+    #[default]
+    Lenient,
+    /// Also rejects proofs holding the same step twice, or more than one leaf for the verified
+    /// key.
+    ///
+    /// Every step of a proof is hashed into the root, so no step is off the path of a key in the
+    /// sense of a hexary trie: extraneous steps are the repeated ones.
+    Strict,
+}
+
+impl VerifyMode {
+    /// Checks that `proof` holds a single leaf for `key` and no repeated step, if required by
+    /// the mode.
+    pub(crate) fn admits(self, proof: &Proof, key: &Hash) -> bool {
+        match self {
+            VerifyMode::Lenient => true,
+            VerifyMode::Strict => {
+                let leaves = proof
+                    .iter()
+                    .filter(
+                        |step| matches!(step, Step::Leaf { key: leaf_key, .. } if leaf_key == key),
+                    )
+                    .count();
+
+                leaves == 1 && self.admits_steps(proof)
+            }
+        }
+    }
+
+    /// Checks that `proof` holds no repeated step, if required by the mode, which doesn't
+    /// depend on the verified key.
+    pub(crate) fn admits_steps(self, proof: &Proof) -> bool {
+        match self {
+            VerifyMode::Lenient => true,
+            VerifyMode::Strict => {
+                let mut seen = BTreeSet::new();
+                proof.iter().all(|step| seen.insert(step.to_bytes_vec()))
+            }
+        }
+    }
+}
+
+/// What [`Trie::from_proof_with`] does with proofs holding several leaves for the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicateLeaves {
+    /// Keeps only the newest leaf of each key, ordered like the writes resolved by merges.
+    Resolve,
+    /// Fails with [`Error::InvalidProof`].
+    Reject,
+}
@@ -1,7 +1,17 @@
+#![cfg_attr(not(test), deny(clippy::indexing_slicing))]
+
+use alloc::{format, string::ToString, vec::Vec};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "std")]
 use test_strategy::Arbitrary;
 
+#[cfg(feature = "std")]
+use super::MAX_SKIP;
 use super::{FromBytes, ToBytes};
 use crate::{
+    decode::DecodeContext,
     error::{Error, Result},
     hash::Hash,
 };
@@ -16,45 +26,176 @@ use crate::{
 ///
 /// This structure is particularly important for Fork steps, where having the complete
 /// neighbor information allows proper verification and reconstruction of the trie.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Arbitrary)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "std", derive(Arbitrary))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedNeighbor"))]
 pub struct Neighbor {
     /// The 4-bit position (0-15) of this neighbor in its parent branch
+    #[cfg_attr(feature = "std", strategy(0..16u8))]
     pub nibble: u8,
     /// The common prefix shared with its siblings, encoded as bytes
+    #[cfg_attr(feature = "serde", serde(with = "prefix"))]
+    #[cfg_attr(
+        feature = "std",
+        strategy(proptest::collection::vec(proptest::prelude::any::<u8>(), 0..=(MAX_SKIP - 1) / 2))
+    )]
     pub prefix: Vec<u8>,
     /// The root hash of this neighbor's subtree
     pub root: Hash,
 }
 
+/// Generates neighbors at a nibble, with prefixes short enough to fit in a key after it.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Neighbor {
+    #[inline]
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let nibble = u.int_in_range(0..=0xf)?;
+        let len = u.int_in_range(0..=(MAX_SKIP - 1) / 2)?;
+        let prefix = (0..len)
+            .map(|_| u.arbitrary())
+            .collect::<arbitrary::Result<_>>()?;
+
+        Ok(Self {
+            nibble,
+            prefix,
+            root: u.arbitrary()?,
+        })
+    }
+}
+
+/// Prefixes serialize as hex strings in human-readable formats, and as byte sequences otherwise.
+#[cfg(feature = "serde")]
+mod prefix {
+    use alloc::string::String;
+
+    use super::*;
+
+    pub fn serialize<S: Serializer>(prefix: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(prefix))
+        } else {
+            prefix.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let prefix = String::deserialize(deserializer)?;
+            hex::decode(prefix).map_err(de::Error::custom)
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+}
+
+/// A [`Neighbor`] as deserialized by serde, before [`Neighbor::checked`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Neighbor")]
+struct UncheckedNeighbor {
+    nibble: u8,
+    #[serde(with = "prefix")]
+    prefix: Vec<u8>,
+    root: Hash,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedNeighbor> for Neighbor {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(neighbor: UncheckedNeighbor) -> Result<Self> {
+        Neighbor {
+            nibble: neighbor.nibble,
+            prefix: neighbor.prefix,
+            root: neighbor.root,
+        }
+        .checked()
+    }
+}
+
+impl Neighbor {
+    /// Checks that a decoded neighbor sits at a nibble, like its byte decoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the nibble is out of range.
+    pub(crate) fn checked(self) -> Result<Self> {
+        if self.nibble > 0xf {
+            return Err(Error::Deserialization(format!(
+                "Neighbor nibble {} is not a nibble",
+                self.nibble
+            )));
+        }
+
+        Ok(self)
+    }
+
+    /// The length of the byte representation of the neighbor.
+    pub(crate) fn encoded_len(&self) -> usize {
+        1 + self.prefix.len() + 32
+    }
+
+    /// Feeds the byte representation of the neighbor to `write`, piece by piece.
+    pub(crate) fn encode(&self, mut write: impl FnMut(&[u8])) {
+        write(&[self.nibble]);
+        write(&self.prefix);
+        write(self.root.as_ref());
+    }
+}
+
 impl ToBytes for Neighbor {
     type Output = Vec<u8>;
 
     #[inline]
     fn to_bytes(&self) -> Self::Output {
-        let mut bytes = vec![self.nibble];
-        bytes.extend_from_slice(&self.prefix);
-        bytes.extend_from_slice(self.root.as_ref());
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.to_bytes_into(&mut bytes);
         bytes
     }
+
+    #[inline]
+    fn to_bytes_into(&self, bytes: &mut Vec<u8>) {
+        self.encode(|chunk| bytes.extend_from_slice(chunk));
+    }
 }
 
 impl FromBytes for Neighbor {
     #[inline]
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.len() < 33 {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+
+        let Some((&nibble, (prefix, root))) = bytes
+            .split_first()
+            .and_then(|(nibble, rest)| Some((nibble, rest.split_last_chunk::<32>()?)))
+        else {
             return Err(Error::Deserialization(
                 "Invalid length for Neighbor".to_string(),
             ));
-        }
+        };
 
-        let nibble = bytes[0];
-        let prefix = bytes[1..bytes.len() - 32].to_vec();
-        let root = Hash::from_slice(&bytes[bytes.len() - 32..]);
+        context.check_prefix(prefix.len())?;
 
-        Ok(Neighbor {
+        Neighbor {
             nibble,
-            prefix,
-            root,
-        })
+            prefix: prefix.to_vec(),
+            root: Hash::new(*root),
+        }
+        .checked()
     }
 }
+
+crate::impl_hex_via_bytes!(Neighbor);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::test_to_hex!(Neighbor);
+}
@@ -0,0 +1,117 @@
+use crate::prelude::*;
+
+/// Presets of how strictly a [`Trie`] treats the proofs and states it is given, set with
+/// [`Trie::set_profile`] or [`TrieBuilder::profile`].
+///
+/// Each profile bundles the [`VerifyMode`] of [`Trie::verify`], whether [`CvRDT::merge`] checks
+/// states like [`Trie::merge_verified`], and the [`DecodeContext`] of
+/// [`Trie::merge_from_bytes`], so applications pick how much they trust their peers instead of
+/// every option. Profiles apply to every verifier of the Trie, including
+/// [`Trie::verify_detailed`], [`Trie::verify_constant_time`] and [`Trie::verify_batch`].
+///
+/// The default is [`Profile::Permissive`], which fresh tries start with, so tries behave as they
+/// did before profiles existed. Applications exchanging states with untrusted peers should pick
+/// [`Profile::Standard`] or stricter.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut other = Trie::<Blake2s256>::empty();
+///     other.insert(b"key", &b"value"[..])?;
+///
+///     let mut forged = Trie::<Blake2s256>::from_root(&[0; 32])?;
+///     *forged.proof_mut() = other.proof().clone();
+///
+///     let mut trie = Trie::<Blake2s256>::builder()
+///         .profile(Profile::Standard)
+///         .build()?;
+///     assert!(trie.merge(&forged).is_err());
+///
+///     trie.merge(&other)?;
+///     assert!(trie.verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Profile {
+    /// Trusts every peer: proofs are verified leniently, merged states are not checked, and
+    /// decoded inputs are unbounded.
+    #[default]
+    Permissive,
+    /// Checks merged states and bounds decoded inputs with [`DecodeContext::default`], but
+    /// verifies proofs leniently, so proofs merged from several replicas still verify.
+    Standard,
+    /// Also verifies proofs strictly, and bounds decoded inputs to the size of the proofs of a
+    /// few thousand keys.
+    Paranoid,
+}
+
+impl Profile {
+    /// The longest input decoded by [`Profile::Paranoid`] tries, 1 MiB.
+    pub const PARANOID_MAX_BYTES: usize = 1024 * 1024;
+
+    /// The most steps decoded by [`Profile::Paranoid`] tries.
+    pub const PARANOID_MAX_STEPS: usize = 1 << 14;
+
+    /// How [`Trie::verify`] checks proofs.
+    #[inline]
+    pub fn verify_mode(self) -> VerifyMode {
+        match self {
+            Profile::Permissive | Profile::Standard => VerifyMode::Lenient,
+            Profile::Paranoid => VerifyMode::Strict,
+        }
+    }
+
+    /// Whether [`CvRDT::merge`] checks the steps and root of merged states, like
+    /// [`Trie::merge_verified`].
+    #[inline]
+    pub fn validates_merges(self) -> bool {
+        !matches!(self, Profile::Permissive)
+    }
+
+    /// The bounds on the inputs decoded by [`Trie::merge_from_bytes`].
+    #[inline]
+    pub fn decode_context(self) -> DecodeContext {
+        match self {
+            Profile::Permissive => DecodeContext::UNBOUNDED,
+            Profile::Standard => DecodeContext::default(),
+            Profile::Paranoid => DecodeContext {
+                max_bytes: Self::PARANOID_MAX_BYTES,
+                max_steps: Self::PARANOID_MAX_STEPS,
+                ..DecodeContext::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_only_tighten() {
+        let profiles = [Profile::Permissive, Profile::Standard, Profile::Paranoid];
+
+        for pair in profiles.windows(2) {
+            let (looser, stricter) = (pair[0].decode_context(), pair[1].decode_context());
+            assert!(stricter.max_bytes <= looser.max_bytes);
+            assert!(stricter.max_steps <= looser.max_steps);
+            assert!(stricter.max_prefix_len <= looser.max_prefix_len);
+            assert!(pair[1].validates_merges() >= pair[0].validates_merges());
+        }
+        assert_eq!(Profile::Paranoid.verify_mode(), VerifyMode::Strict);
+    }
+
+    #[test]
+    fn test_tries_start_with_the_default() {
+        let trie = Trie::<blake2::Blake2s256>::empty();
+        assert_eq!(trie.profile(), Profile::default());
+        let built = Trie::<blake2::Blake2s256>::builder().build().unwrap();
+        assert_eq!(built.profile(), Profile::default());
+    }
+}
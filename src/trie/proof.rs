@@ -1,12 +1,28 @@
-use std::{
+use alloc::{format, string::ToString, sync::Arc, vec::Vec};
+use core::{
     cmp::Ordering,
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Index, Range},
+    slice::SliceIndex,
 };
 
+#[cfg(feature = "std")]
 use proptest::{collection::vec, prelude::*};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use super::Step;
-use crate::prelude::Hash;
+use super::{Step, MAX_SKIP};
+use crate::prelude::*;
+
+/// The number of steps stored inline with the `smallvec` feature, enough for the proofs of most
+/// light clients.
+#[cfg(feature = "smallvec")]
+const INLINE_STEPS: usize = 8;
+
+/// The storage of the steps of a proof.
+#[cfg(feature = "smallvec")]
+type Steps = smallvec::SmallVec<[Step; INLINE_STEPS]>;
+#[cfg(not(feature = "smallvec"))]
+type Steps = Vec<Step>;
 
 /// A complete proof in a Merkle-Patricia Trie.
 ///
@@ -21,8 +37,32 @@ use crate::prelude::Hash;
 /// The proof size is bounded by O(log₁₆(n)) steps, where each step requires at most
 /// 130 bytes (for Branch nodes), significantly improving upon traditional MPT proofs
 /// while maintaining similar verification costs.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct Proof(Vec<Step>);
+///
+/// # Serde
+///
+/// With the `serde` feature, proofs serialize as a sequence of steps. Each step is an enum
+/// variant, indexed in declaration order (`Branch` = 0, `Fork` = 1, `Leaf` = 2), followed by its
+/// fields in declaration order. `skip` is a `u64`, hashes are 32-byte tuples, neighbor prefixes
+/// are byte sequences, and leaf timestamps are options of `{ wall: u64, logical: u32 }`. In
+/// human-readable formats, hashes and prefixes are hex strings instead, and the JSON encoding is
+/// described by [`Proof::json_schema`]. This layout is stable, so in postcard a proof is
+/// the varint step count followed by the steps, with every integer as a varint, and in bincode
+/// the same with fixed-size little-endian integers, `u64` lengths and `u32` variant indices.
+///
+/// # Storage
+///
+/// The steps are shared between clones, so cloning a proof, or a [`Trie`] holding it, is O(1).
+/// Mutating a proof whose steps are shared copies them first, leaving the other clones as they
+/// were.
+///
+/// With the `smallvec` feature, proofs of up to 8 steps are stored inline in that shared
+/// allocation, instead of in a buffer of their own. The API and encodings are the same either
+/// way.
+#[derive(Clone, PartialEq, Eq, Default)]
+#[cfg_attr(not(feature = "redact"), derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Proof(Arc<Steps>);
 
 impl Proof {
     #[inline]
@@ -30,11 +70,65 @@ impl Proof {
         Self::default()
     }
 
+    /// Creates an empty proof with room for at least `capacity` steps, for callers that know
+    /// how many steps they are about to add, like when restoring a snapshot.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Proof(Arc::new(Steps::with_capacity(capacity)))
+    }
+
+    /// The number of steps the proof can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Reserves room for at least `additional` more steps.
+    ///
+    /// Like any other change, this copies the steps first if they are shared with a clone.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.steps_mut().reserve(additional);
+    }
+
     #[inline]
     pub fn steps(&self) -> &[Step] {
         &self.0
     }
 
+    /// The steps, copied first if they are shared with another clone.
+    fn steps_mut(&mut self) -> &mut Steps {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// The bytes and allocations held by the steps: the shared allocation, the buffer of the
+    /// steps if they are not inline, and the prefixes of fork steps.
+    pub(crate) fn heap_usage(&self) -> (usize, usize) {
+        let mut bytes = 2 * size_of::<usize>() + size_of::<Steps>();
+        let mut allocations = 1;
+
+        #[cfg(feature = "smallvec")]
+        let buffered = self.0.spilled();
+        #[cfg(not(feature = "smallvec"))]
+        let buffered = self.0.capacity() > 0;
+
+        if buffered {
+            bytes += self.0.capacity() * size_of::<Step>();
+            allocations += 1;
+        }
+
+        for step in self.iter() {
+            if let Step::Fork { neighbor, .. } = step {
+                if neighbor.prefix.capacity() > 0 {
+                    bytes += neighbor.prefix.capacity();
+                    allocations += 1;
+                }
+            }
+        }
+
+        (bytes, allocations)
+    }
+
     #[inline]
     pub fn iter_steps(&self) -> impl Iterator<Item = &Step> {
         self.0.iter()
@@ -42,14 +136,11 @@ impl Proof {
 
     #[inline]
     pub fn root(&self) -> Hash {
-        if self.is_empty() {
-            return Hash::default();
-        }
-
-        match self.last().unwrap() {
-            Step::Branch { neighbors, .. } => neighbors[0],
-            Step::Fork { neighbor, .. } => neighbor.root,
-            Step::Leaf { value, .. } => *value,
+        match self.last() {
+            None => Hash::default(),
+            Some(Step::Branch { neighbors, .. }) => neighbors[0],
+            Some(Step::Fork { neighbor, .. }) => neighbor.root,
+            Some(Step::Leaf { value, .. }) => *value,
         }
     }
 
@@ -58,18 +149,30 @@ impl Proof {
         self.0.get(index)
     }
 
+    /// Returns the first leaf of the proof, in the order of its steps.
     #[inline]
-    pub fn retain<F>(&mut self, f: F)
+    pub fn first_leaf(&self) -> Option<&Step> {
+        self.iter().find(|step| step.is_leaf())
+    }
+
+    /// Returns the last leaf of the proof, in the order of its steps.
+    #[inline]
+    pub fn last_leaf(&self) -> Option<&Step> {
+        self.iter().rev().find(|step| step.is_leaf())
+    }
+
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
     where
         F: FnMut(&Step) -> bool,
     {
-        self.0.retain(f);
+        self.steps_mut().retain(|step| f(step));
     }
 
     #[inline]
     pub fn remove(&mut self, index: usize) -> Option<Step> {
         if index < self.0.len() {
-            Some(self.0.remove(index))
+            Some(self.steps_mut().remove(index))
         } else {
             None
         }
@@ -77,17 +180,324 @@ impl Proof {
 
     #[inline]
     pub fn push(&mut self, step: Step) {
-        self.0.push(step);
+        self.steps_mut().push(step);
     }
 
     #[inline]
     pub fn extend<I: IntoIterator<Item = Step>>(&mut self, iter: I) {
-        self.0.extend(iter);
+        self.steps_mut().extend(iter);
+    }
+
+    /// Replaces the step at `index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidOperation`] if `index` is past the last step.
+    #[inline]
+    pub fn try_set(&mut self, index: usize, step: Step) -> Result<()> {
+        let len = self.len();
+        match self.steps_mut().get_mut(index) {
+            Some(slot) => {
+                *slot = step;
+                Ok(())
+            }
+            None => Err(Error::InvalidOperation(format!(
+                "step {index} is out of bounds for a proof of {len} steps"
+            ))),
+        }
+    }
+
+    /// Checks that every step of the proof is structurally valid, as done by
+    /// [`Step::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] naming the first invalid step and its invalid field.
+    #[inline]
+    pub fn validate(&self) -> Result<()> {
+        for (index, step) in self.iter().enumerate() {
+            step.validate().map_err(|error| match error {
+                Error::InvalidProof(reason) => Error::InvalidProof(format!("step {index}: {reason}")),
+                error => error,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies path compression to the whole proof, merging adjacent branch steps that have a
+    /// single neighbor each.
+    ///
+    /// Tries do this on every insertion. Proofs changed directly, for instance with
+    /// [`Proof::retain`], can be normalized to get the same steps, including when they are left
+    /// with fewer than two steps, which have nothing to merge.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    ///
+    /// let single = |skip| Step::Branch {
+    ///     skip,
+    ///     neighbors: [Hash::zero(), Hash::zero(), Hash::zero(), Hash::from_array([1; 32])],
+    /// };
+    ///
+    /// let mut proof = Proof::from(vec![single(1), single(2)]);
+    /// proof.normalize();
+    /// assert_eq!(proof[..], [single(4)]);
+    ///
+    /// proof.retain(|_| false);
+    /// proof.normalize();
+    /// assert!(proof.is_empty());
+    /// ```
+    #[inline]
+    pub fn normalize(&mut self) {
+        self.compress(0..usize::MAX);
+    }
+
+    /// Applies path compression to the proof, merging the steps starting at the positions in
+    /// `range` with the ones following them.
+    pub(crate) fn compress(&mut self, range: Range<usize>) {
+        let mut i = range.start;
+        while i < range.end && i + 1 < self.len() {
+            if let (
+                Step::Branch {
+                    skip: skip1,
+                    neighbors: neighbors1,
+                },
+                Step::Branch {
+                    skip: skip2,
+                    neighbors: neighbors2,
+                },
+            ) = (&self[i], &self[i + 1])
+            {
+                if neighbors1.iter().filter(|n| !n.is_zero()).count() == 1
+                    && neighbors2.iter().filter(|n| !n.is_zero()).count() == 1
+                {
+                    // Merge the two branch nodes, unless they skip more nibbles than a key has
+                    match skip1
+                        .checked_add(*skip2)
+                        .and_then(|skip| skip.checked_add(1))
+                        .filter(|&skip| skip <= MAX_SKIP)
+                    {
+                        Some(new_skip) => {
+                            let new_neighbors = *neighbors2;
+                            self.steps_mut()[i] = Step::Branch {
+                                skip: new_skip,
+                                neighbors: new_neighbors,
+                            };
+                            self.remove(i + 1);
+                        }
+                        None => i += 1,
+                    }
+                } else {
+                    i += 1;
+                }
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// The JSON Schema of proofs encoded in JSON through their serde implementation, so clients
+    /// in other languages can validate their encoders against it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    ///
+    /// assert!(Proof::json_schema().contains("\"title\": \"Proof\""));
+    /// ```
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn json_schema() -> &'static str {
+        include_str!("proof.schema.json")
+    }
+
+    /// Encodes the proof in MessagePack.
+    ///
+    /// Structs are encoded as arrays holding their fields in declaration order, and enum
+    /// variants as single-entry maps from their name to their fields, so the layout doesn't
+    /// depend on field names, matching the one expected by rmp-serde on the other end:
+    ///
+    /// ```text
+    /// Proof     = [Step, ...]
+    /// Step      = {"Branch": [skip, [Hash; 4]]}
+    ///           | {"Fork": [skip, [nibble, [u8, ...], Hash]]}
+    ///           | {"Leaf": [skip, key, value, nil | [wall, logical]]}
+    /// Hash      = [u8; 32]
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the encoder fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let bytes = trie.proof().to_msgpack()?;
+    ///     assert_eq!(Proof::from_msgpack(&bytes)?, *trie.proof());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "msgpack")]
+    #[inline]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(self).map_err(|error| Error::Serialization(error.to_string()))
+    }
+
+    /// Decodes a proof encoded in MessagePack by [`Proof::to_msgpack`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] if the bytes are not a MessagePack-encoded proof.
+    #[cfg(feature = "msgpack")]
+    #[inline]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self> {
+        rmp_serde::from_slice(bytes).map_err(|error| Error::Deserialization(error.to_string()))
     }
 
+    /// Sorts proofs in their [`Ord`] order, which compares them without encoding them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mutree::prelude::*;
+    ///
+    /// let long = Proof::from(vec![Step::default(), Step::default()]);
+    /// let short = Proof::from(vec![Step::default()]);
+    ///
+    /// let mut proofs = vec![long.clone(), short.clone(), Proof::new()];
+    /// Proof::sort(&mut proofs);
+    /// assert_eq!(proofs, [Proof::new(), short, long]);
+    /// ```
     #[inline]
-    pub fn set(&mut self, index: usize, step: Step) {
-        self.0[index] = step;
+    pub fn sort(proofs: &mut [Proof]) {
+        proofs.sort();
+    }
+
+    /// Sorts the steps in the order in which they are hashed into the root.
+    ///
+    /// The root doesn't depend on the order of the steps, so this doesn't change it, but proofs
+    /// in this order can have their root calculated while they are read, by
+    /// [`calculate_root_from_reader`].
+    #[inline]
+    pub fn canonicalize(&mut self) {
+        self.steps_mut().sort_unstable();
+    }
+
+    /// Decodes a proof persisted by releases that encoded the skip of steps as a `usize`,
+    /// before it was fixed to a `u64` on every platform.
+    ///
+    /// Those encodings are the current one on 64-bit targets, but 32-bit targets, such as
+    /// `wasm32`, wrote skips on 4 bytes. Inputs that decode in the current format are taken as
+    /// such, and the others are decoded with 4-byte skips, so proofs of unknown origin can be
+    /// migrated alike. Encoding the result with [`ToBytes::to_bytes`] upgrades them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`], for the current format, if the input decodes in
+    /// neither format.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     // A leaf written by a 32-bit target: its kind, then a 4-byte skip
+    ///     let step = trie.proof()[0].to_bytes();
+    ///     let mut narrow = [&1u32.to_be_bytes()[..], &(step.len() as u32 - 4).to_be_bytes()].concat();
+    ///     narrow.push(step[0]);
+    ///     narrow.extend_from_slice(&step[5..]);
+    ///
+    ///     assert!(Proof::from_bytes(&narrow).is_err());
+    ///     assert_eq!(Proof::migrate_from_v0(&narrow)?, *trie.proof());
+    ///     assert_eq!(Proof::migrate_from_v0(&trie.proof().to_bytes())?, *trie.proof());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn migrate_from_v0(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes(bytes).or_else(|error| Self::from_narrow_v0(bytes).map_err(|_| error))
+    }
+
+    /// Decodes a proof whose steps have 4-byte skips, by widening them to the current format.
+    #[deny(clippy::indexing_slicing)]
+    fn from_narrow_v0(bytes: &[u8]) -> Result<Self> {
+        let truncated = || Error::Deserialization("Truncated Proof".to_string());
+        let (count, mut rest) = bytes.split_first_chunk().ok_or_else(truncated)?;
+
+        let count = u32::from_be_bytes(*count) as usize;
+        let mut steps = Steps::with_capacity(count.min(rest.len() / 4));
+        let mut widened = Vec::new();
+        for _ in 0..count {
+            let Some(((kind, step), tail)) = rest
+                .split_first_chunk()
+                .and_then(|(len, tail)| tail.split_at_checked(u32::from_be_bytes(*len) as usize))
+                .and_then(|(step, tail)| Some((step.split_first()?, tail)))
+            else {
+                return Err(truncated());
+            };
+
+            // A big-endian `u32` is widened to a `u64` by prefixing it with zeros
+            widened.clear();
+            widened.push(*kind);
+            widened.extend_from_slice(&[0; 4]);
+            widened.extend_from_slice(step);
+            steps.push(Step::from_bytes(&widened)?);
+            rest = tail;
+        }
+
+        if !rest.is_empty() {
+            return Err(Error::Deserialization(
+                "Trailing bytes after Proof".to_string(),
+            ));
+        }
+
+        Ok(Proof(Arc::new(steps)))
+    }
+
+    /// Encodes the proof like 32-bit targets did before skips were fixed to a `u64`.
+    #[cfg(test)]
+    pub(crate) fn to_narrow_v0_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.len() as u32).to_be_bytes().to_vec();
+        for step in self.iter() {
+            let step = step.to_bytes();
+            bytes.extend_from_slice(&(step.len() as u32 - 4).to_be_bytes());
+            bytes.push(step[0]);
+            bytes.extend_from_slice(&step[5..]);
+        }
+        bytes
+    }
+
+    /// The steps in the order in which they are hashed into the root.
+    ///
+    /// Steps are sorted by their byte representation, a total order, so the parallel sort of
+    /// the `rayon` feature yields the same order as the sequential one.
+    pub(crate) fn canonical_steps(&self) -> Vec<&Step> {
+        let mut steps: Vec<&Step> = self.iter().collect();
+
+        #[cfg(feature = "rayon")]
+        rayon::slice::ParallelSliceMut::par_sort_unstable(&mut steps[..]);
+        #[cfg(not(feature = "rayon"))]
+        steps.sort_unstable();
+
+        steps
     }
 }
 
@@ -103,37 +513,54 @@ impl Deref for Proof {
 impl DerefMut for Proof {
     #[inline(always)]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        self.steps_mut()
+    }
+}
+
+/// Indexes and slices the steps of the proof, panicking if the index is out of bounds, like
+/// slices do. Use [`Proof::get`] for a checked lookup, and [`Proof::try_set`] to replace a
+/// step.
+impl<I: SliceIndex<[Step]>> Index<I> for Proof {
+    type Output = I::Output;
+
+    #[inline(always)]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.0[index]
     }
 }
 
 impl From<Vec<Step>> for Proof {
     #[inline(always)]
     fn from(steps: Vec<Step>) -> Self {
-        Proof(steps)
+        Proof(Arc::new(Steps::from(steps)))
     }
 }
 
 impl From<Proof> for Vec<Step> {
     #[inline(always)]
     fn from(proof: Proof) -> Self {
-        proof.0
+        let steps = Arc::unwrap_or_clone(proof.0);
+
+        #[cfg(feature = "smallvec")]
+        return steps.into_vec();
+        #[cfg(not(feature = "smallvec"))]
+        steps
     }
 }
 
 impl IntoIterator for Proof {
     type Item = Step;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        Vec::from(self).into_iter()
     }
 }
 
 impl<'a> IntoIterator for &'a Proof {
     type Item = &'a Step;
-    type IntoIter = std::slice::Iter<'a, Step>;
+    type IntoIter = core::slice::Iter<'a, Step>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -143,7 +570,7 @@ impl<'a> IntoIterator for &'a Proof {
 
 impl<'a> IntoIterator for &'a mut Proof {
     type Item = &'a mut Step;
-    type IntoIter = std::slice::IterMut<'a, Step>;
+    type IntoIter = core::slice::IterMut<'a, Step>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -154,31 +581,112 @@ impl<'a> IntoIterator for &'a mut Proof {
 impl PartialOrd for Proof {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        // Compare the lengths of the proof vectors first
-        match self.len().partial_cmp(&other.len()) {
-            Some(Ordering::Equal) => {}
-            ord => return ord,
+        Some(self.cmp(other))
+    }
+}
+
+/// Proofs are ordered by their byte representation, which is a total order. Since it starts with
+/// the step count, shorter proofs come first.
+///
+/// Comparisons walk the steps instead of encoding them, comparing their lengths, which prefix
+/// them, and then the steps themselves, whose order matches their bytes.
+impl Ord for Proof {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return Ordering::Equal;
         }
 
-        // Use iterators instead of cloning
-        self.iter()
-            .zip(other.iter())
-            .try_fold(Ordering::Equal, |acc, (a, b)| {
-                match (acc, a.partial_cmp(b)?) {
-                    (Ordering::Equal, ord) => Some(ord),
-                    (ord, _) => Some(ord),
-                }
-            })
+        self.len().cmp(&other.len()).then_with(|| {
+            let steps = other.iter().map(|step| (step.encoded_len(), step));
+            self.iter()
+                .map(|step| (step.encoded_len(), step))
+                .cmp(steps)
+        })
+    }
+}
+
+impl ToBytes for Proof {
+    type Output = Vec<u8>;
+
+    /// Encodes the proof as a big-endian `u32` step count, followed by each step prefixed by its
+    /// length as a big-endian `u32`.
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let len = 4 + self
+            .iter()
+            .map(|step| 4 + step.encoded_len())
+            .sum::<usize>();
+        let mut bytes = Vec::with_capacity(len);
+        self.to_bytes_into(&mut bytes);
+        bytes
+    }
+
+    #[inline]
+    fn to_bytes_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&(self.len() as u32).to_be_bytes());
+        for step in self.iter() {
+            bytes.extend_from_slice(&(step.encoded_len() as u32).to_be_bytes());
+            step.to_bytes_into(bytes);
+        }
+    }
+}
+
+#[deny(clippy::indexing_slicing)]
+impl FromBytes for Proof {
+    #[inline]
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+
+        let Some((count, mut rest)) = bytes.split_first_chunk() else {
+            return Err(Error::Deserialization(
+                "Invalid length for Proof".to_string(),
+            ));
+        };
+
+        let count = u32::from_be_bytes(*count) as usize;
+        context.check_steps(count)?;
+        // Every step takes at least 4 bytes for its length prefix, so a larger count can only
+        // come from a malformed input and must not drive the allocation.
+        let mut steps = Steps::with_capacity(count.min(rest.len() / 4));
+
+        for _ in 0..count {
+            let Some((step, tail)) = rest
+                .split_first_chunk()
+                .and_then(|(len, tail)| tail.split_at_checked(u32::from_be_bytes(*len) as usize))
+            else {
+                return Err(Error::Deserialization("Truncated Proof".to_string()));
+            };
+
+            steps.push(Step::from_bytes_with(step, context)?);
+            rest = tail;
+        }
+
+        if !rest.is_empty() {
+            return Err(Error::Deserialization(
+                "Trailing bytes after Proof".to_string(),
+            ));
+        }
+
+        Ok(Proof(Arc::new(steps)))
     }
 }
 
+crate::impl_hex_via_bytes!(Proof);
+
+#[cfg(feature = "std")]
 impl Arbitrary for Proof {
     type Parameters = usize;
     type Strategy = BoxedStrategy<Self>;
 
     #[inline]
     fn arbitrary_with(depth: Self::Parameters) -> Self::Strategy {
-        vec(any::<Step>(), 0..=depth).prop_map(Proof).boxed()
+        vec(any::<Step>(), 0..=depth).prop_map(Proof::from).boxed()
     }
 }
 
@@ -188,13 +696,270 @@ mod tests {
 
     use super::*;
 
+    #[proptest]
+    fn test_order_matches_bytes(#[any(4usize)] a: Proof, #[any(4usize)] b: Proof) {
+        prop_assert_eq!(a.cmp(&b), a.to_bytes().cmp(&b.to_bytes()));
+        prop_assert_eq!(a.cmp(&b).is_eq(), a == b);
+    }
+
+    #[proptest]
+    fn test_sort_matches_ord(#[strategy(vec(any_with::<Proof>(4), 0..16))] proofs: Vec<Proof>) {
+        let mut sorted = proofs.clone();
+        Proof::sort(&mut sorted);
+
+        let mut expected = proofs;
+        expected.sort();
+        prop_assert_eq!(sorted, expected);
+    }
+
+    #[proptest]
+    fn test_migrates_narrow_v0_proofs(
+        #[strategy(crate::testing::strategies::realistic_proof::<blake2::Blake2s256>(8))]
+        proof: Proof,
+    ) {
+        // Proofs of entries hold leaves, whose lengths tell the two formats apart
+        let narrow = proof.to_narrow_v0_bytes();
+        prop_assert!(Proof::from_bytes(&narrow).is_err());
+        prop_assert_eq!(Proof::migrate_from_v0(&narrow)?, proof.clone());
+        prop_assert_eq!(Proof::migrate_from_v0(&proof.to_bytes())?, proof.clone());
+
+        prop_assert!(Proof::migrate_from_v0(&narrow[..narrow.len() - 1]).is_err());
+    }
+
+    #[proptest]
+    fn test_first_and_last_leaf(#[any(8usize)] proof: Proof) {
+        let leaves: Vec<&Step> = proof.iter().filter(|step| step.is_leaf()).collect();
+        prop_assert_eq!(proof.first_leaf(), leaves.first().copied());
+        prop_assert_eq!(proof.last_leaf(), leaves.last().copied());
+    }
+
+    #[proptest]
+    fn test_index_matches_steps(
+        #[any(8usize)] proof: Proof,
+        #[strategy(0..=#proof.len())] end: usize,
+        #[strategy(0..=#end)] start: usize,
+    ) {
+        prop_assert_eq!(&proof[start..end], &proof.steps()[start..end]);
+        prop_assert_eq!(&proof[start..], &proof.steps()[start..]);
+        if start < end {
+            prop_assert_eq!(&proof[start], &proof.steps()[start]);
+        }
+    }
+
+    #[proptest]
+    fn test_clones_share_steps_until_mutated(#[any(8usize)] proof: Proof, step: Step) {
+        let mut clone = proof.clone();
+        prop_assert!(Arc::ptr_eq(&proof.0, &clone.0));
+
+        clone.push(step.clone());
+        prop_assert!(!Arc::ptr_eq(&proof.0, &clone.0));
+        prop_assert_eq!(clone.len(), proof.len() + 1);
+        prop_assert_eq!(&clone[..proof.len()], &proof[..]);
+        prop_assert_eq!(clone.last(), Some(&step));
+
+        // Unshared steps are mutated in place
+        let steps = Arc::as_ptr(&clone.0);
+        clone.try_set(0, step.clone())?;
+        prop_assert_eq!(Arc::as_ptr(&clone.0), steps);
+        prop_assert!(matches!(
+            clone.try_set(clone.len(), step),
+            Err(Error::InvalidOperation(_))
+        ));
+    }
+
+    #[proptest]
+    fn test_reserve(#[any(8usize)] proof: Proof, #[strategy(0..64usize)] additional: usize) {
+        let mut reserved = Proof::with_capacity(proof.len());
+        prop_assert!(reserved.capacity() >= proof.len());
+        reserved.extend(proof.iter().cloned());
+
+        let clone = reserved.clone();
+        reserved.reserve(additional);
+        prop_assert!(reserved.capacity() >= proof.len() + additional);
+        prop_assert_eq!(&reserved, &proof);
+        prop_assert_eq!(&clone, &proof);
+    }
+
+    #[proptest]
+    fn test_normalize_is_idempotent(#[any(8usize)] proof: Proof, keep: Vec<bool>) {
+        let mut keep = keep.into_iter();
+        let mut proof = proof;
+        proof.retain(|_| keep.next().unwrap_or(false));
+
+        let mut normalized = proof.clone();
+        normalized.normalize();
+        prop_assert!(normalized.len() <= proof.len());
+
+        let mut again = normalized.clone();
+        again.normalize();
+        prop_assert_eq!(again, normalized);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[proptest]
+    fn test_short_proofs_are_inline(#[any(16usize)] proof: Proof) {
+        let decoded = Proof::from_bytes(&proof.to_bytes())?;
+
+        prop_assert_eq!(decoded.0.spilled(), proof.len() > INLINE_STEPS);
+        prop_assert_eq!(Vec::from(decoded.clone()), proof.steps().to_vec());
+        prop_assert_eq!(
+            decoded.into_iter().collect::<Vec<_>>(),
+            proof.steps().to_vec()
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[proptest]
+    fn test_fuzzer_inputs_roundtrip(data: Vec<u8>) {
+        use arbitrary::Unstructured;
+
+        if let Ok(proof) = <Proof as arbitrary::Arbitrary>::arbitrary(&mut Unstructured::new(&data)) {
+            prop_assert_eq!(Proof::from_bytes(&proof.to_bytes())?, proof);
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_fuzzer_branch_roundtrip() -> Result<()> {
+        use arbitrary::Unstructured;
+
+        let data = [19, 0, 0, 0, 86, 0, 16];
+        let proof = <Proof as arbitrary::Arbitrary>::arbitrary(&mut Unstructured::new(&data))
+            .expect("the data holds a branch");
+        assert_eq!(Proof::from_bytes(&proof.to_bytes())?, proof);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde_tests {
+        use test_strategy::proptest;
+
+        use super::*;
+
+        fn leaf() -> Proof {
+            Proof::from(vec![Step::Leaf {
+                skip: 3,
+                key: Hash::from_array([1; 32]),
+                value: Hash::from_array([2; 32]),
+                timestamp: None,
+            }])
+        }
+
+        /// Forks rejected by the byte decoder: off a nibble, and too long for a key.
+        fn invalid_forks() -> [Proof; 2] {
+            let fork = |nibble, prefix| {
+                Proof::from(vec![Step::Fork {
+                    skip: 0,
+                    neighbor: Neighbor {
+                        nibble,
+                        prefix,
+                        root: Hash::zero(),
+                    },
+                }])
+            };
+
+            [fork(0x10, vec![]), fork(0, vec![0; 32])]
+        }
+
+        #[test]
+        fn test_rejects_invalid_forks() {
+            for proof in invalid_forks() {
+                let json = serde_json::to_string(&proof).unwrap();
+                assert!(serde_json::from_str::<Proof>(&json).is_err(), "{json}");
+
+                let bytes = postcard::to_allocvec(&proof).unwrap();
+                assert!(postcard::from_bytes::<Proof>(&bytes).is_err());
+
+                let bytes = bincode::serialize(&proof).unwrap();
+                assert!(bincode::deserialize::<Proof>(&bytes).is_err());
+            }
+        }
+
+        #[test]
+        fn test_postcard_layout() {
+            let mut expected = vec![1, 2, 3];
+            expected.extend([1; 32]);
+            expected.extend([2; 32]);
+            expected.push(0);
+
+            assert_eq!(postcard::to_allocvec(&leaf()).unwrap(), expected);
+        }
+
+        #[test]
+        fn test_bincode_layout() {
+            let mut expected = vec![1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0];
+            expected.extend([1; 32]);
+            expected.extend([2; 32]);
+            expected.push(0);
+
+            assert_eq!(bincode::serialize(&leaf()).unwrap(), expected);
+        }
+
+        #[proptest]
+        fn test_json_matches_schema(#[any(8usize)] proof: Proof) {
+            let schema: serde_json::Value = serde_json::from_str(Proof::json_schema())?;
+            let validator = jsonschema::validator_for(&schema)?;
+            let json = serde_json::to_value(&proof)?;
+
+            prop_assert!(validator.is_valid(&json), "{}", json);
+            prop_assert_eq!(serde_json::from_value::<Proof>(json)?, proof);
+        }
+
+        #[proptest]
+        fn test_postcard_roundtrip(#[any(8usize)] proof: Proof) {
+            let bytes = postcard::to_allocvec(&proof)?;
+            prop_assert_eq!(postcard::from_bytes::<Proof>(&bytes)?, proof);
+        }
+
+        #[cfg(feature = "msgpack")]
+        #[test]
+        fn test_msgpack_layout() -> Result<()> {
+            // [{"Leaf": [3, [1; 32], [2; 32], nil]}]
+            let mut expected = vec![
+                0x91, 0x81, 0xa4, b'L', b'e', b'a', b'f', 0x94, 3, 0xdc, 0, 32,
+            ];
+            expected.extend([1; 32]);
+            expected.extend([0xdc, 0, 32]);
+            expected.extend([2; 32]);
+            expected.push(0xc0);
+
+            assert_eq!(leaf().to_msgpack()?, expected);
+
+            Ok(())
+        }
+
+        #[cfg(feature = "msgpack")]
+        #[test]
+        fn test_msgpack_rejects_invalid_forks() -> Result<()> {
+            for proof in invalid_forks() {
+                assert!(matches!(
+                    Proof::from_msgpack(&proof.to_msgpack()?),
+                    Err(Error::Deserialization(_))
+                ));
+            }
+
+            Ok(())
+        }
+
+        #[cfg(feature = "msgpack")]
+        #[proptest]
+        fn test_msgpack_roundtrip(#[any(8usize)] proof: Proof) {
+            prop_assert_eq!(Proof::from_msgpack(&proof.to_msgpack()?)?, proof);
+        }
+
+        #[proptest]
+        fn test_bincode_roundtrip(#[any(8usize)] proof: Proof) {
+            let bytes = bincode::serialize(&proof)?;
+            prop_assert_eq!(bincode::deserialize::<Proof>(&bytes)?, proof);
+        }
+    }
+
     #[cfg(feature = "blake3")]
     /// Tests an optimization that is possible on blake3 because the hash function itself is
     /// incremental.
     mod blake3_tests {
         use std::io::Cursor;
 
-        
         use crate::prelude::*;
 
         #[test]
@@ -269,4 +1034,21 @@ mod tests {
         proof.push(step);
         prop_assert!(!proof.is_empty());
     }
+
+    #[proptest]
+    fn test_roundtrip_with_steps(#[any(8usize)] proof: Proof) {
+        prop_assert_eq!(Proof::from_bytes(&proof.to_bytes())?, proof);
+    }
+
+    #[proptest]
+    fn test_from_bytes_rejects_trailing_bytes(
+        #[any(8usize)] proof: Proof,
+        #[strategy(1..16usize)] extra: usize,
+    ) {
+        let mut bytes = proof.to_bytes();
+        bytes.extend(vec![0u8; extra]);
+        prop_assert!(Proof::from_bytes(&bytes).is_err());
+    }
+
+    crate::test_to_bytes!(Proof);
 }
@@ -0,0 +1,179 @@
+use core::fmt;
+
+use crate::{prelude::*, TrieHasher};
+
+/// A summary of a [`Trie`] or [`Proof`] that can be logged without revealing its entries,
+/// returned by [`Trie::redacted`] and [`Proof::redacted`].
+///
+/// Keys and values are only stored as hashes, but the same pair always hashes the same way, so
+/// logged leaves link payments across logs and replicas. The summary only holds the root, which
+/// replicas publish anyway, and the number of steps. With the `redact` feature, the [`Debug`]
+/// of tries, proofs and steps, and the [`Display`] of tries, are redacted too, so logging them
+/// by accident doesn't leak their leaves either.
+///
+/// [`Debug`]: fmt::Debug
+/// [`Display`]: fmt::Display
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"key", &b"value"[..])?;
+///
+///     let redacted = trie.redacted();
+///     assert_eq!(redacted.to_string(), format!("Trie root={} steps=1", trie.root()));
+///     assert_eq!(redacted.steps(), 1);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Redacted {
+    name: &'static str,
+    root: Option<Hash>,
+    steps: usize,
+}
+
+impl Redacted {
+    /// The root of the Trie, or `None` for proofs, whose root depends on the digest.
+    #[inline]
+    pub fn root(&self) -> Option<Hash> {
+        self.root
+    }
+
+    /// The number of steps of the proof.
+    #[inline]
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+}
+
+impl fmt::Debug for Redacted {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut summary = f.debug_struct(self.name);
+        if let Some(root) = &self.root {
+            summary.field("root", root);
+        }
+        summary.field("steps", &self.steps).finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Redacted {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(root) = &self.root {
+            write!(f, " root={root}")?;
+        }
+        write!(f, " steps={}", self.steps)
+    }
+}
+
+impl<D: TrieHasher> Trie<D> {
+    /// Summarizes the Trie by its root and number of steps, for logs.
+    #[inline]
+    pub fn redacted(&self) -> Redacted {
+        Redacted {
+            name: "Trie",
+            root: Some(self.root),
+            steps: self.proof.len(),
+        }
+    }
+}
+
+impl Proof {
+    /// Summarizes the proof by its number of steps, for logs.
+    #[inline]
+    pub fn redacted(&self) -> Redacted {
+        Redacted {
+            name: "Proof",
+            root: None,
+            steps: self.len(),
+        }
+    }
+}
+
+#[cfg(feature = "redact")]
+impl<D: TrieHasher> fmt::Debug for Trie<D> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.redacted(), f)
+    }
+}
+
+#[cfg(feature = "redact")]
+impl<D: TrieHasher> fmt::Display for Trie<D> {
+    /// Displays the Trie redacted, like [`Trie::redacted`].
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.redacted(), f)
+    }
+}
+
+#[cfg(feature = "redact")]
+impl fmt::Debug for Proof {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.redacted(), f)
+    }
+}
+
+#[cfg(feature = "redact")]
+impl fmt::Debug for Step {
+    /// Shows the kind and skip of the step, without its hashes.
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, skip) = match self {
+            Step::Branch { skip, .. } => ("Branch", skip),
+            Step::Fork { skip, .. } => ("Fork", skip),
+            Step::Leaf { skip, .. } => ("Leaf", skip),
+        };
+        f.debug_struct(name)
+            .field("skip", skip)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_hides_leaves(
+        #[strategy(crate::testing::strategies::trie_with_entries::<Blake2s256>(4))] trie: Trie<
+            Blake2s256,
+        >,
+    ) {
+        let mut logs = vec![
+            format!("{:?}", trie.redacted()),
+            format!("{}", trie.redacted()),
+            format!("{:?}", trie.proof.redacted()),
+        ];
+        if cfg!(feature = "redact") {
+            logs.push(format!("{trie:?} {trie} {:?}", trie.proof));
+        }
+
+        for step in trie.proof.iter() {
+            if let Step::Leaf { key, value, .. } = step {
+                for log in &logs {
+                    prop_assert!(!log.contains(&key.to_hex()[..8]), "{}", log);
+                    prop_assert!(!log.contains(&value.to_hex()[..8]), "{}", log);
+                }
+            }
+        }
+        let steps = format!("steps: {}", trie.proof.len());
+        prop_assert!(logs[0].contains(&trie.root().to_hex()));
+        prop_assert!(logs[2].contains(&steps), "{}", logs[2]);
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{prelude::*, TrieHasher};
+
+/// What is wrong with a step of a proof, as found by [`Proof::check_soundness`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepIssue {
+    /// A field of the step is out of the bounds of a valid Trie.
+    Malformed(String),
+    /// The step is a copy of the one at index `first`.
+    Repeated { first: usize },
+    /// The step is a leaf for the same key as the one at index `first`, with another value.
+    DuplicateLeaf { first: usize },
+}
+
+impl fmt::Display for StepIssue {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepIssue::Malformed(reason) => write!(f, "malformed: {reason}"),
+            StepIssue::Repeated { first } => write!(f, "repeats step {first}"),
+            StepIssue::DuplicateLeaf { first } => write!(f, "holds the key of leaf {first}"),
+        }
+    }
+}
+
+/// The check of a single step of a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    /// The index of the step in the proof
+    pub index: usize,
+    /// The digest of the bytes the step contributes to the root, on their own
+    pub commitment: Hash,
+    /// What is wrong with the step, if anything
+    pub issue: Option<StepIssue>,
+}
+
+/// The outcome of [`Proof::check_soundness`], step by step.
+///
+/// Displaying it lists the unsound steps and the roots, one per line, for logs of CI gates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoundnessReport {
+    /// The checks of the steps, in the order of the proof
+    pub steps: Vec<StepReport>,
+    /// The root the proof was expected to hash to
+    pub expected_root: Hash,
+    /// The root the proof hashes to
+    pub computed_root: Hash,
+}
+
+impl SoundnessReport {
+    /// Whether every step is sound and the proof hashes to the expected root.
+    #[inline]
+    pub fn is_sound(&self) -> bool {
+        self.root_matches() && self.issues().next().is_none()
+    }
+
+    /// Whether the proof hashes to the expected root.
+    #[inline]
+    pub fn root_matches(&self) -> bool {
+        self.expected_root == self.computed_root
+    }
+
+    /// The steps with an issue.
+    #[inline]
+    pub fn issues(&self) -> impl Iterator<Item = &StepReport> {
+        self.steps.iter().filter(|step| step.issue.is_some())
+    }
+}
+
+impl fmt::Display for SoundnessReport {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in self.issues() {
+            if let Some(issue) = &step.issue {
+                writeln!(f, "step {}: {issue}", step.index)?;
+            }
+        }
+
+        write!(
+            f,
+            "expected root {}, computed {}",
+            self.expected_root, self.computed_root
+        )
+    }
+}
+
+impl Proof {
+    /// Replays the proof, checking each of its steps and that it hashes to `root`, like
+    /// [`Proof::check_soundness_with_format`] with the default [`RootFormat`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use blake2::Blake2s256;
+    /// use mutree::prelude::*;
+    ///
+    /// fn main() -> Result<(), Error> {
+    ///     let mut trie = Trie::<Blake2s256>::empty();
+    ///     trie.insert(b"key", &b"value"[..])?;
+    ///
+    ///     let report = trie.proof().check_soundness::<Blake2s256>(trie.root());
+    ///     assert!(report.is_sound());
+    ///
+    ///     // A proof produced elsewhere, which holds a step twice
+    ///     let mut proof = trie.proof().clone();
+    ///     proof.push(proof[0].clone());
+    ///
+    ///     let report = proof.check_soundness::<Blake2s256>(trie.root());
+    ///     assert!(!report.is_sound());
+    ///     assert_eq!(report.issues().count(), 1);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn check_soundness<D: TrieHasher + 'static>(&self, root: Hash) -> SoundnessReport {
+        self.check_soundness_with_format::<D>(root, RootFormat::default())
+    }
+
+    /// Replays the proof, checking each of its steps and that it hashes to `root` in `format`.
+    ///
+    /// Every step is checked against the bounds of a valid Trie, and against the steps before
+    /// it: a proof of a single Trie holds no step twice, and no two leaves for the same key.
+    /// Branches and forks commit to their neighbors by hash, so their consistency is only
+    /// established by the root, which is calculated from the steps and compared with `root`.
+    ///
+    /// Unlike [`Trie::from_proof`], this never fails or stops at the first issue, so proofs
+    /// produced by other systems can be checked as a whole, for instance in CI.
+    #[inline]
+    pub fn check_soundness_with_format<D: TrieHasher + 'static>(
+        &self,
+        root: Hash,
+        format: RootFormat,
+    ) -> SoundnessReport {
+        let mut seen = BTreeMap::new();
+        let mut leaves = BTreeMap::new();
+
+        let steps = self
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                let key = match step {
+                    Step::Leaf { key, .. } => Some(key),
+                    _ => None,
+                };
+
+                let issue = if let Err(error) = step.validate() {
+                    Some(StepIssue::Malformed(error.to_string()))
+                } else if let Some(&first) = seen.get(&step.to_bytes_vec()) {
+                    Some(StepIssue::Repeated { first })
+                } else if let Some(&first) = key.and_then(|key| leaves.get(key)) {
+                    Some(StepIssue::DuplicateLeaf { first })
+                } else {
+                    None
+                };
+
+                seen.entry(step.to_bytes_vec()).or_insert(index);
+                if let Some(key) = key {
+                    leaves.entry(key).or_insert(index);
+                }
+
+                StepReport {
+                    index,
+                    commitment: step.commitment::<D>(format).1,
+                    issue,
+                }
+            })
+            .collect();
+
+        SoundnessReport {
+            steps,
+            expected_root: root,
+            computed_root: Trie::<D>::calculate_root(self, format),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::{
+        collection::{btree_map, vec},
+        prelude::*,
+    };
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_tries_are_sound(
+        #[strategy(btree_map(vec(any::<u8>(), 1..8), vec(any::<u8>(), 1..8), 1..16))]
+        entries: BTreeMap<Vec<u8>, Vec<u8>>,
+    ) {
+        let mut trie = Trie::<Blake2s256>::empty();
+        for (key, value) in &entries {
+            trie.insert(key, &value[..])?;
+        }
+
+        let report = trie.proof.check_soundness::<Blake2s256>(trie.root);
+
+        prop_assert!(report.is_sound(), "{report}");
+        prop_assert_eq!(report.steps.len(), trie.proof.len());
+    }
+
+    #[test]
+    fn test_reports_every_issue() -> Result<()> {
+        let mut trie = Trie::<Blake2s256>::empty();
+        trie.insert(b"a", &b"1"[..])?;
+        trie.insert(b"b", &b"2"[..])?;
+        let mut other = Trie::<Blake2s256>::empty();
+        other.insert(b"a", &b"3"[..])?;
+
+        let mut proof = trie.proof.clone();
+        let repeated = proof.len();
+        proof.push(proof[0].clone());
+        let duplicate = proof.len();
+        proof.push(other.proof[0].clone());
+        let malformed = proof.len();
+        proof.push(Step::Leaf {
+            skip: MAX_SKIP + 1,
+            key: Hash::digest::<Blake2s256>(b"c"),
+            value: Hash::digest::<Blake2s256>(b"4"),
+            timestamp: None,
+        });
+
+        let report = proof.check_soundness::<Blake2s256>(trie.root);
+        assert!(!report.root_matches());
+        assert_eq!(
+            report.issues().map(|step| step.index).collect::<Vec<_>>(),
+            [repeated, duplicate, malformed]
+        );
+        assert_eq!(
+            report.steps[repeated].issue,
+            Some(StepIssue::Repeated { first: 0 })
+        );
+        assert!(matches!(
+            report.steps[duplicate].issue,
+            Some(StepIssue::DuplicateLeaf { .. })
+        ));
+        assert!(matches!(
+            report.steps[malformed].issue,
+            Some(StepIssue::Malformed(_))
+        ));
+
+        Ok(())
+    }
+}
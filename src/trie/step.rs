@@ -1,8 +1,28 @@
+#![cfg_attr(not(test), deny(clippy::indexing_slicing))]
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::cmp::Ordering;
 
+#[cfg(feature = "std")]
 use proptest::{array::uniform4, prelude::*};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, TrieHasher};
 
-use crate::prelude::*;
+/// The largest `skip` of a step: the number of nibbles in a 32-byte hash.
+///
+/// Decoding rejects steps skipping more nibbles than a key has, and path compression never
+/// merges branches past it.
+pub const MAX_SKIP: usize = 64;
+
+/// The length of the `skip` of an encoded step, a big-endian `u64` on every platform, so
+/// encodings don't depend on the width of `usize`.
+const SKIP_LEN: usize = 8;
 
 /// A single step in a Merkle-Patricia Trie proof.
 ///
@@ -27,7 +47,10 @@ use crate::prelude::*;
 ///
 /// This structure reduces the proof size from 15*32=480 bytes to just 4*32=130 bytes
 /// per branch step while maintaining security through the Merkle Tree structure.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "redact"), derive(Debug))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedStep"))]
 pub enum Step {
     /// A branch node with multiple children, using an optimized 4-level Sparse-Merkle Tree
     /// representation requiring only 4 hashes instead of up to 15.
@@ -48,7 +71,60 @@ pub enum Step {
     ///
     /// The `skip` value indicates the length of the common prefix at this level.
     /// The `key` and `value` are the hashes of the original key-value pair.
-    Leaf { skip: usize, key: Hash, value: Hash },
+    /// The optional `timestamp` records when the value was written, so replicas can agree on the
+    /// newest write for each key. It is committed into the leaf hash when present.
+    Leaf {
+        skip: usize,
+        key: Hash,
+        value: Hash,
+        timestamp: Option<Timestamp>,
+    },
+}
+
+/// A [`Step`] as deserialized by serde, before [`Step::checked`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Step")]
+enum UncheckedStep {
+    Branch {
+        skip: usize,
+        neighbors: [Hash; 4],
+    },
+    Fork {
+        skip: usize,
+        neighbor: Neighbor,
+    },
+    Leaf {
+        skip: usize,
+        key: Hash,
+        value: Hash,
+        timestamp: Option<Timestamp>,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedStep> for Step {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(step: UncheckedStep) -> Result<Self> {
+        match step {
+            UncheckedStep::Branch { skip, neighbors } => Step::Branch { skip, neighbors },
+            UncheckedStep::Fork { skip, neighbor } => Step::Fork { skip, neighbor },
+            UncheckedStep::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            },
+        }
+        .checked()
+    }
 }
 
 impl Step {
@@ -66,108 +142,374 @@ impl Step {
     pub fn is_fork(&self) -> bool {
         matches!(self, Self::Fork { .. })
     }
-}
 
-impl ToBytes for Step {
-    type Output = Vec<u8>;
+    /// The byte marking the kind of the step in its byte representation.
+    fn kind(&self) -> u8 {
+        match self {
+            Step::Branch { .. } => 0,
+            Step::Fork { .. } => 1,
+            Step::Leaf { .. } => 2,
+        }
+    }
 
+    /// The skip of the step, whatever its kind.
     #[inline]
-    fn to_bytes(&self) -> Self::Output {
+    pub fn skip(&self) -> usize {
+        match self {
+            Step::Branch { skip, .. } | Step::Fork { skip, .. } | Step::Leaf { skip, .. } => *skip,
+        }
+    }
+
+    /// Checks that the step is structurally valid: it skips at most [`MAX_SKIP`] nibbles, and
+    /// the neighbor of a fork sits at a nibble of its parent, with a prefix that fits in a key
+    /// after the nibbles skipped by the fork and the nibble itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidProof`] describing the first invalid field.
+    #[inline]
+    pub fn validate(&self) -> Result<()> {
+        self.check().map_err(Error::InvalidProof)
+    }
+
+    /// Checks a step decoded by a codec other than [`FromBytes`] like [`Step::validate`], so
+    /// every decoder rejects the steps rejected by the byte decoder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Deserialization`] describing the first invalid field.
+    #[cfg(any(feature = "serde", feature = "grpc", feature = "ssz"))]
+    pub(crate) fn checked(self) -> Result<Self> {
+        self.check().map_err(Error::Deserialization)?;
+        Ok(self)
+    }
+
+    fn check(&self) -> core::result::Result<(), String> {
+        if self.skip() > MAX_SKIP {
+            return Err(format!(
+                "skip of {} nibbles exceeds the maximum of {MAX_SKIP}",
+                self.skip()
+            ));
+        }
+
+        if let Step::Fork { skip, neighbor } = self {
+            check_fork(*skip, neighbor)?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts a decoded `skip` to a `usize`, rejecting values past [`MAX_SKIP`] like the byte
+    /// decoder, for the other codecs.
+    pub(crate) fn decode_skip(skip: u64) -> Result<usize> {
+        if skip > MAX_SKIP as u64 {
+            return Err(Error::Deserialization(format!(
+                "Skip of {skip} nibbles exceeds the maximum of {MAX_SKIP}"
+            )));
+        }
+
+        Ok(skip as usize)
+    }
+
+    /// The length of the byte representation of the step.
+    pub(crate) fn encoded_len(&self) -> usize {
+        1 + SKIP_LEN
+            + match self {
+                Step::Branch { .. } => 4 * 32,
+                Step::Fork { neighbor, .. } => neighbor.encoded_len(),
+                Step::Leaf { timestamp, .. } => 64 + timestamp.map_or(0, |_| Timestamp::LEN),
+            }
+    }
+
+    /// Feeds the byte representation of the step to `write`, piece by piece.
+    pub(crate) fn encode(&self, mut write: impl FnMut(&[u8])) {
         match self {
             Step::Branch { skip, neighbors } => {
-                let mut bytes = Vec::with_capacity(1 + std::mem::size_of::<usize>() + 32 * 4);
-                bytes.push(0u8); // 0 indicates Branch
-                bytes.extend_from_slice(&skip.to_be_bytes());
+                write(&[0u8]); // 0 indicates Branch
+                write(&(*skip as u64).to_be_bytes());
                 for neighbor in neighbors {
-                    bytes.extend_from_slice(neighbor.as_ref());
+                    write(neighbor.as_ref());
                 }
-                bytes
             }
             Step::Fork { skip, neighbor } => {
-                let mut bytes = vec![1u8]; // 1 indicates Fork
-                bytes.extend_from_slice(&skip.to_be_bytes());
-                bytes.extend(neighbor.to_bytes());
-                bytes
+                write(&[1u8]); // 1 indicates Fork
+                write(&(*skip as u64).to_be_bytes());
+                neighbor.encode(write);
             }
-            Step::Leaf { skip, key, value } => {
-                let mut bytes = vec![2u8]; // 2 indicates Leaf
-                bytes.extend_from_slice(&skip.to_be_bytes());
-                bytes.extend_from_slice(key.as_ref());
-                bytes.extend_from_slice(value.as_ref());
-                bytes
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => {
+                write(&[2u8]); // 2 indicates Leaf
+                write(&(*skip as u64).to_be_bytes());
+                write(key.as_ref());
+                write(value.as_ref());
+                if let Some(timestamp) = timestamp {
+                    write(&timestamp.to_bytes());
+                }
+            }
+        }
+    }
+
+    /// Feeds the bytes this step contributes to a root of the given format to `update`.
+    pub(crate) fn commit(&self, format: RootFormat, mut update: impl FnMut(&[u8])) {
+        match format {
+            RootFormat::V1 => self.commit_v1(update),
+            RootFormat::V2 => self.commit_v2(&mut update),
+        }
+    }
+
+    /// Hashes the bytes the step contributes to roots of `format` on their own, returning their
+    /// length and digest.
+    pub(crate) fn commitment<D: TrieHasher>(&self, format: RootFormat) -> (usize, Hash) {
+        let mut hasher = D::new();
+        let mut len = 0;
+        self.commit(format, |bytes| {
+            len += bytes.len();
+            hasher.update(bytes);
+        });
+
+        (len, hasher.finalize32())
+    }
+
+    fn commit_v1(&self, mut update: impl FnMut(&[u8])) {
+        match self {
+            Step::Branch { neighbors, .. } => {
+                // First hash the number of non-zero neighbors
+                let non_zero = neighbors.iter().filter(|n| !n.is_zero()).count();
+                update(&[non_zero as u8]);
+                // Then hash each non-zero neighbor in order
+                for neighbor in neighbors.iter().filter(|n| !n.is_zero()) {
+                    update(neighbor.as_ref());
+                }
+            }
+            Step::Fork { neighbor, .. } => {
+                // Hash fork marker
+                update(&[0xFF]);
+                // Hash nibble and prefix
+                update(&[neighbor.nibble]);
+                update(&neighbor.prefix);
+                // Hash root
+                update(neighbor.root.as_ref());
+            }
+            Step::Leaf {
+                key,
+                value,
+                timestamp,
+                ..
+            } => {
+                // Hash leaf marker
+                update(&[0x00]);
+                // Hash key and value
+                update(key.as_ref());
+                update(value.as_ref());
+                // Hash the timestamp, if the leaf has one
+                if let Some(timestamp) = timestamp {
+                    update(&timestamp.to_bytes());
+                }
+            }
+        }
+    }
+
+    fn commit_v2(&self, update: &mut impl FnMut(&[u8])) {
+        match self {
+            Step::Branch { skip, neighbors } => {
+                // The positions of the non-zero neighbors, so they can't be shifted around
+                let present = neighbors
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, n)| !n.is_zero())
+                    .fold(0u8, |bits, (i, _)| bits | (1 << i));
+                update(&[RootFormat::BRANCH_PREFIX]);
+                update(&(*skip as u64).to_be_bytes());
+                update(&[present]);
+                for neighbor in neighbors.iter().filter(|n| !n.is_zero()) {
+                    update(neighbor.as_ref());
+                }
+            }
+            Step::Fork { skip, neighbor } => {
+                update(&[RootFormat::FORK_PREFIX]);
+                update(&(*skip as u64).to_be_bytes());
+                update(&[neighbor.nibble]);
+                update(&(neighbor.prefix.len() as u32).to_be_bytes());
+                update(&neighbor.prefix);
+                update(neighbor.root.as_ref());
+            }
+            Step::Leaf {
+                skip,
+                key,
+                value,
+                timestamp,
+            } => {
+                update(&[RootFormat::LEAF_PREFIX]);
+                update(&(*skip as u64).to_be_bytes());
+                update(key.as_ref());
+                update(value.as_ref());
+                match timestamp {
+                    Some(timestamp) => {
+                        update(&[0x01]);
+                        update(&timestamp.to_bytes());
+                    }
+                    None => update(&[0x00]),
+                }
             }
         }
     }
 }
 
+impl ToBytes for Step {
+    type Output = Vec<u8>;
+
+    #[inline]
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.to_bytes_into(&mut bytes);
+        bytes
+    }
+
+    #[inline]
+    fn to_bytes_into(&self, bytes: &mut Vec<u8>) {
+        self.encode(|chunk| bytes.extend_from_slice(chunk));
+    }
+}
+
 impl FromBytes for Step {
     #[inline]
     fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        if bytes.is_empty() {
+        Self::from_bytes_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    #[inline]
+    fn from_bytes_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        context.check_bytes(bytes.len())?;
+
+        let Some((&kind, rest)) = bytes.split_first() else {
             return Err(Error::Deserialization("Empty input".to_string()));
-        }
+        };
+        let invalid_length =
+            |kind: &str| Error::Deserialization(format!("Invalid length for {kind}"));
 
-        match bytes[0] {
+        match kind {
             0 => {
                 // Branch
-                if bytes.len() < 1 + std::mem::size_of::<usize>() + 4 * 32 {
-                    return Err(Error::Deserialization(
-                        "Invalid length for Branch".to_string(),
-                    ));
-                }
-                let skip = usize::from_be_bytes(
-                    bytes[1..1 + std::mem::size_of::<usize>()]
-                        .try_into()
-                        .unwrap(),
-                );
+                let (skip, mut rest) = rest
+                    .split_first_chunk()
+                    .ok_or_else(|| invalid_length("Branch"))?;
                 let mut neighbors = [Hash::default(); 4];
-                for (i, neighbor) in neighbors.iter_mut().enumerate() {
-                    let start = 1 + std::mem::size_of::<usize>() + i * 32;
-                    *neighbor = Hash::from_slice(&bytes[start..start + 32]);
+                for neighbor in neighbors.iter_mut() {
+                    let (hash, tail) = rest
+                        .split_first_chunk::<32>()
+                        .ok_or_else(|| invalid_length("Branch"))?;
+                    *neighbor = Hash::new(*hash);
+                    rest = tail;
+                }
+                if !rest.is_empty() {
+                    return Err(invalid_length("Branch"));
                 }
+                let skip = decode_skip(skip)?;
                 Ok(Step::Branch { skip, neighbors })
             }
             1 => {
                 // Fork
-                if bytes.len() < 1 + std::mem::size_of::<usize>() + 33 {
-                    return Err(Error::Deserialization(
-                        "Invalid length for Fork".to_string(),
-                    ));
-                }
-                let skip = usize::from_be_bytes(
-                    bytes[1..1 + std::mem::size_of::<usize>()]
-                        .try_into()
-                        .unwrap(),
-                );
-                let neighbor = Neighbor::from_bytes(&bytes[1 + std::mem::size_of::<usize>()..])?;
+                let (skip, rest) = rest
+                    .split_first_chunk()
+                    .filter(|(_, rest)| rest.len() >= 33)
+                    .ok_or_else(|| invalid_length("Fork"))?;
+                let skip = decode_skip(skip)?;
+                let neighbor = Neighbor::from_bytes_with(rest, context)?;
+                check_fork(skip, &neighbor).map_err(Error::Deserialization)?;
                 Ok(Step::Fork { skip, neighbor })
             }
             2 => {
                 // Leaf
-                if bytes.len() < 1 + std::mem::size_of::<usize>() + 64 {
-                    return Err(Error::Deserialization(
-                        "Invalid length for Leaf".to_string(),
-                    ));
-                }
-                let skip = usize::from_be_bytes(
-                    bytes[1..1 + std::mem::size_of::<usize>()]
-                        .try_into()
-                        .unwrap(),
-                );
-                let key = Hash::from_slice(
-                    &bytes[1 + std::mem::size_of::<usize>()..1 + std::mem::size_of::<usize>() + 32],
-                );
-                let value = Hash::from_slice(
-                    &bytes[1 + std::mem::size_of::<usize>() + 32
-                        ..1 + std::mem::size_of::<usize>() + 64],
-                );
-                Ok(Step::Leaf { skip, key, value })
+                let (skip, rest) = rest
+                    .split_first_chunk()
+                    .ok_or_else(|| invalid_length("Leaf"))?;
+                let (key, rest) = rest
+                    .split_first_chunk::<32>()
+                    .ok_or_else(|| invalid_length("Leaf"))?;
+                let (value, rest) = rest
+                    .split_first_chunk::<32>()
+                    .ok_or_else(|| invalid_length("Leaf"))?;
+                let skip = decode_skip(skip)?;
+                let (key, value) = (Hash::new(*key), Hash::new(*value));
+                let timestamp = match rest {
+                    [] => None,
+                    rest => Some(Timestamp::from_bytes(rest)?),
+                };
+                Ok(Step::Leaf {
+                    skip,
+                    key,
+                    value,
+                    timestamp,
+                })
             }
             _ => Err(Error::Deserialization("Invalid Step type".to_string())),
         }
     }
 }
 
+/// Decodes the `skip` of a step, rejecting values past [`MAX_SKIP`].
+fn decode_skip(bytes: &[u8; SKIP_LEN]) -> Result<usize> {
+    Step::decode_skip(u64::from_be_bytes(*bytes))
+}
+
+/// Checks that the neighbor of a fork skipping `skip` nibbles sits at a nibble of its parent,
+/// and that the skipped nibbles, the nibble and the prefix, two nibbles per byte, fit in a key.
+///
+/// Fork neighbors are hashed without delimiters in [`RootFormat::V1`], so out-of-range nibbles
+/// and oversized prefixes would let crafted forks share their bytes with other steps.
+fn check_fork(skip: usize, neighbor: &Neighbor) -> core::result::Result<(), String> {
+    if neighbor.nibble > 0xf {
+        return Err(format!(
+            "neighbor nibble {} is not a nibble",
+            neighbor.nibble
+        ));
+    }
+
+    let nibbles = skip
+        .saturating_add(1)
+        .saturating_add(neighbor.prefix.len().saturating_mul(2));
+    if nibbles > MAX_SKIP {
+        return Err(format!(
+            "fork skipping {skip} nibbles, with a neighbor prefix of {} bytes, spans {nibbles} \
+             nibbles, more than a key",
+            neighbor.prefix.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Generates valid steps only, so fuzzers exercise the Trie rather than the decoders.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Step {
+    #[inline]
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=2u8)? {
+            0 => Step::Branch {
+                skip: u.int_in_range(0..=MAX_SKIP)?,
+                neighbors: u.arbitrary()?,
+            },
+            1 => {
+                let neighbor: Neighbor = u.arbitrary()?;
+                // The skipped nibbles, the nibble and the prefix fit in a key together
+                let skip = u.int_in_range(0..=MAX_SKIP - 1 - 2 * neighbor.prefix.len())?;
+                Step::Fork { skip, neighbor }
+            }
+            _ => Step::Leaf {
+                skip: u.int_in_range(0..=MAX_SKIP)?,
+                key: u.arbitrary()?,
+                value: u.arbitrary()?,
+                timestamp: u.arbitrary()?,
+            },
+        })
+    }
+}
+
+#[cfg(feature = "std")]
 impl Arbitrary for Step {
     type Parameters = ();
     type Strategy = BoxedStrategy<Self>;
@@ -175,12 +517,27 @@ impl Arbitrary for Step {
     #[inline]
     fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
         prop_oneof![
-            (any::<usize>(), uniform4(any::<Hash>()))
+            (0..=MAX_SKIP, uniform4(any::<Hash>()))
                 .prop_map(|(skip, neighbors)| Step::Branch { skip, neighbors }),
-            (any::<usize>(), any::<Neighbor>())
+            any::<Neighbor>()
+                .prop_flat_map(|neighbor| {
+                    // The skipped nibbles, the nibble and the prefix fit in a key together
+                    let max_skip = MAX_SKIP - 1 - 2 * neighbor.prefix.len();
+                    (0..=max_skip, Just(neighbor))
+                })
                 .prop_map(|(skip, neighbor)| Step::Fork { skip, neighbor }),
-            (any::<usize>(), any::<Hash>(), any::<Hash>())
-                .prop_map(|(skip, key, value)| Step::Leaf { skip, key, value })
+            (
+                0..=MAX_SKIP,
+                any::<Hash>(),
+                any::<Hash>(),
+                any::<Option<Timestamp>>()
+            )
+                .prop_map(|(skip, key, value, timestamp)| Step::Leaf {
+                    skip,
+                    key,
+                    value,
+                    timestamp
+                })
         ]
         .boxed()
     }
@@ -188,7 +545,19 @@ impl Arbitrary for Step {
 
 impl PartialOrd for Step {
     #[inline]
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Steps are ordered by their byte representation, which is a total order: by kind, branches
+/// first and leaves last, then by skip, then by their fields in the order they are encoded.
+///
+/// Comparisons walk the fields instead of encoding the steps, so sorting the steps of a proof
+/// to hash them into its root doesn't allocate.
+impl Ord for Step {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
             (
                 Step::Branch {
@@ -199,10 +568,7 @@ impl PartialOrd for Step {
                     skip: s2,
                     neighbors: n2,
                 },
-            ) => match s1.partial_cmp(s2) {
-                Some(Ordering::Equal) => n1.partial_cmp(n2),
-                ord => ord,
-            },
+            ) => (s1, n1).cmp(&(s2, n2)),
             (
                 Step::Fork {
                     skip: s1,
@@ -212,33 +578,26 @@ impl PartialOrd for Step {
                     skip: s2,
                     neighbor: n2,
                 },
-            ) => match s1.partial_cmp(s2) {
-                Some(Ordering::Equal) => n1.partial_cmp(n2),
-                ord => ord,
-            },
+            ) => (s1, n1.nibble).cmp(&(s2, n2.nibble)).then_with(|| {
+                // The prefix is not length-prefixed, so it runs into the root
+                let a = n1.prefix.iter().chain(n1.root.as_ref());
+                a.cmp(n2.prefix.iter().chain(n2.root.as_ref()))
+            }),
             (
                 Step::Leaf {
                     skip: s1,
                     key: k1,
                     value: v1,
+                    timestamp: t1,
                 },
                 Step::Leaf {
                     skip: s2,
                     key: k2,
                     value: v2,
+                    timestamp: t2,
                 },
-            ) => match s1.partial_cmp(s2) {
-                Some(Ordering::Equal) => match k1.partial_cmp(k2) {
-                    Some(Ordering::Equal) => v1.partial_cmp(v2),
-                    ord => ord,
-                },
-                ord => ord,
-            },
-            // Define an arbitrary order between different Step variants
-            (Step::Branch { .. }, _) => Some(Ordering::Less),
-            (_, Step::Branch { .. }) => Some(Ordering::Greater),
-            (Step::Fork { .. }, Step::Leaf { .. }) => Some(Ordering::Less),
-            (Step::Leaf { .. }, Step::Fork { .. }) => Some(Ordering::Greater),
+            ) => (s1, k1, v1, t1).cmp(&(s2, k2, v2, t2)),
+            _ => self.kind().cmp(&other.kind()),
         }
     }
 }
@@ -253,45 +612,103 @@ impl Default for Step {
     }
 }
 
-impl std::hash::Hash for Step {
+impl core::hash::Hash for Step {
     #[inline]
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.to_bytes().hash(state);
+    /// Hashes the byte representation, length first, without allocating it.
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.encoded_len());
+        self.encode(|chunk| state.write(chunk));
     }
 }
 
-impl FromHex for Step {
-    #[inline]
-    fn from_hex(input: &str) -> Result<Self> {
-        let bytes = hex::decode(input)?;
-        Self::from_bytes(&bytes)
+crate::impl_hex_via_bytes!(Step);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::test_to_bytes!(Step);
+
+    #[test_strategy::proptest]
+    fn test_order_matches_bytes(a: Step, b: Step) {
+        prop_assert_eq!(a.cmp(&b), a.to_bytes().cmp(&b.to_bytes()));
+        prop_assert_eq!(a.cmp(&a.clone()), Ordering::Equal);
     }
-}
 
-impl ToHex for Step {
-    #[inline]
-    fn to_hex(&self) -> String {
-        hex::encode(ToBytes::to_bytes(self))
+    #[test_strategy::proptest]
+    fn test_fork_order_matches_bytes(a: Neighbor, mut b: Neighbor) {
+        // Forks at the same nibble, which only differ by their prefix and root
+        b.nibble = a.nibble;
+        let (a, b) = (
+            Step::Fork {
+                skip: 0,
+                neighbor: a,
+            },
+            Step::Fork {
+                skip: 0,
+                neighbor: b,
+            },
+        );
+        prop_assert_eq!(a.cmp(&b), a.to_bytes().cmp(&b.to_bytes()));
     }
-}
 
-impl std::fmt::LowerHex for Step {
-    #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", hex::encode(self.to_bytes()))
+    #[test_strategy::proptest]
+    fn test_to_bytes_into_appends(step: Step, prefix: Vec<u8>) {
+        let bytes = step.to_bytes();
+        prop_assert_eq!(bytes.len(), step.encoded_len());
+        prop_assert_eq!(bytes.capacity(), bytes.len());
+
+        let mut buffer = prefix.clone();
+        step.to_bytes_into(&mut buffer);
+        prop_assert_eq!(&buffer[..prefix.len()], &prefix[..]);
+        prop_assert_eq!(&buffer[prefix.len()..], &bytes[..]);
     }
-}
 
-impl std::fmt::UpperHex for Step {
-    #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", hex::encode_upper(self.to_bytes()))
+    #[test_strategy::proptest]
+    fn test_from_bytes_rejects_large_skips(
+        step: Step,
+        #[strategy(MAX_SKIP + 1..=usize::MAX)] skip: usize,
+    ) {
+        prop_assert_eq!(Step::from_bytes(&step.to_bytes())?, step.clone());
+
+        let mut bytes = step.to_bytes();
+        bytes[1..1 + SKIP_LEN].copy_from_slice(&(skip as u64).to_be_bytes());
+        prop_assert!(matches!(
+            Step::from_bytes(&bytes),
+            Err(Error::Deserialization(_))
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test_strategy::proptest]
+    fn test_rejects_malicious_forks(
+        step: Step,
+        #[strategy(0x10..=u8::MAX)] nibble: u8,
+        #[strategy(0..=MAX_SKIP)] skip: usize,
+        #[strategy(0..=MAX_SKIP / 2)] prefix_len: usize,
+    ) {
+        prop_assert!(step.validate().is_ok());
 
-    crate::test_to_bytes!(Step);
+        let fork = |nibble, skip, prefix_len| Step::Fork {
+            skip,
+            neighbor: Neighbor {
+                nibble,
+                prefix: vec![0xab; prefix_len],
+                root: Hash::zero(),
+            },
+        };
+
+        // Neighbors out of the 16 nibbles of a branch
+        let step = fork(nibble, 0, 0);
+        prop_assert!(matches!(step.validate(), Err(Error::InvalidProof(_))));
+        prop_assert!(matches!(
+            Step::from_bytes(&step.to_bytes()),
+            Err(Error::Deserialization(_))
+        ));
+
+        // Skips and prefixes spanning more nibbles than a key together
+        let step = fork(0xf, skip, prefix_len);
+        let fits = skip + 1 + 2 * prefix_len <= MAX_SKIP;
+        prop_assert_eq!(step.validate().is_ok(), fits);
+        prop_assert_eq!(Step::from_bytes(&step.to_bytes()).is_ok(), fits);
+    }
 }
@@ -0,0 +1,209 @@
+#![cfg_attr(not(test), deny(clippy::indexing_slicing))]
+
+use alloc::{string::ToString, vec::Vec};
+use core::cmp::Ordering;
+
+use crate::{io::Read, prelude::*, TrieHasher};
+
+/// The longest step accepted by [`calculate_root_from_reader`], bounding the memory it uses.
+pub const MAX_STREAMED_STEP_LEN: usize = 64 * 1024;
+
+/// Calculates the root of a proof encoded by `Proof::to_bytes`, while reading it.
+///
+/// Only the step being read and the one before it are kept in memory, so servers can check the
+/// root of large proof uploads in constant memory, without decoding them into a [`Proof`] first.
+/// This requires the steps to be encoded in the order in which they are hashed, which
+/// [`Proof::canonicalize`] puts them in before encoding. The root is the same as the one of a
+/// [`Trie`] built from the proof.
+///
+/// # Errors
+///
+/// Returns [`Error::Deserialization`] if the proof is malformed, if its steps are not in
+/// canonical order, or if one of them is longer than [`MAX_STREAMED_STEP_LEN`], and
+/// [`Error::Unknown`] if reading fails.
+///
+/// # Examples
+///
+/// ```rust
+/// use mutree::prelude::*;
+/// use blake2::Blake2s256;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.insert(b"a", &b"1"[..])?;
+///     trie.insert(b"b", &b"2"[..])?;
+///
+///     let mut proof = trie.proof().clone();
+///     proof.canonicalize();
+///     let upload = proof.to_bytes();
+///
+///     let root = calculate_root_from_reader::<Blake2s256, _>(&upload[..])?;
+///     assert_eq!(root, trie.root());
+///
+///     Ok(())
+/// }
+/// ```
+#[inline]
+pub fn calculate_root_from_reader<D: TrieHasher, R: Read>(reader: R) -> Result<Hash> {
+    calculate_root_from_reader_with_format::<D, R>(reader, RootFormat::default())
+}
+
+/// Calculates the root of a proof encoded by `Proof::to_bytes` while reading it, like
+/// [`calculate_root_from_reader`], hashing its steps in `format`.
+///
+/// # Errors
+///
+/// Returns the same errors as [`calculate_root_from_reader`].
+#[inline]
+pub fn calculate_root_from_reader_with_format<D: TrieHasher, R: Read>(
+    mut reader: R,
+    format: RootFormat,
+) -> Result<Hash> {
+    let mut hasher = D::new();
+    let mut buffer = Vec::new();
+    let mut previous: Option<Step> = None;
+
+    let count = read_u32(&mut reader)?;
+    #[cfg_attr(not(feature = "trace-hashing"), allow(unused_variables))]
+    for index in 0..count {
+        let len = read_u32(&mut reader)? as usize;
+        if len > MAX_STREAMED_STEP_LEN {
+            return Err(Error::Deserialization(
+                "Step too long to be streamed".to_string(),
+            ));
+        }
+
+        buffer.resize(len, 0);
+        read_exact(&mut reader, &mut buffer)?;
+        let step = Step::from_bytes(&buffer)?;
+
+        if let Some(previous) = &previous {
+            if previous.partial_cmp(&step) == Some(Ordering::Greater) {
+                return Err(Error::Deserialization(
+                    "Proof steps are not in canonical order".to_string(),
+                ));
+            }
+        }
+
+        #[cfg(feature = "trace-hashing")]
+        super::trace::step::<D>(index as usize, &step, format);
+
+        step.commit(format, |bytes| hasher.update(bytes));
+        previous = Some(step);
+    }
+
+    if read(&mut reader, &mut [0])? != 0 {
+        return Err(Error::Deserialization(
+            "Trailing bytes after Proof".to_string(),
+        ));
+    }
+
+    let root = hasher.finalize32();
+
+    #[cfg(feature = "trace-hashing")]
+    super::trace::root(count as usize, format, root);
+
+    Ok(root)
+}
+
+fn read<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    reader
+        .read(buf)
+        .map_err(|error| Error::Unknown(error.to_string()))
+}
+
+fn read_exact<R: Read>(reader: &mut R, mut buf: &mut [u8]) -> Result<()> {
+    while !buf.is_empty() {
+        match read(reader, buf)? {
+            0 => return Err(Error::Deserialization("Truncated Proof".to_string())),
+            n => {
+                buf = core::mem::take(&mut buf).get_mut(n..).ok_or_else(|| {
+                    Error::Unknown("Reader returned more bytes than requested".to_string())
+                })?
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    read_exact(reader, &mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    /// Returns one byte per read, to exercise partial reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            std::io::Read::read(&mut self.0, &mut buf[..len])
+        }
+    }
+
+    #[proptest]
+    fn test_matches_trie_root(#[any(16usize)] proof: Proof) {
+        let trie = Trie::<Blake2s256>::from_proof(proof.clone());
+
+        let mut proof = proof;
+        proof.canonicalize();
+        prop_assert_eq!(
+            Trie::<Blake2s256>::from_proof(proof.clone()).root,
+            trie.root
+        );
+
+        let bytes = proof.to_bytes();
+        prop_assert_eq!(
+            calculate_root_from_reader::<Blake2s256, _>(&bytes[..])?,
+            trie.root
+        );
+        prop_assert_eq!(
+            calculate_root_from_reader::<Blake2s256, _>(Trickle(&bytes))?,
+            trie.root
+        );
+    }
+
+    #[proptest]
+    fn test_rejects_unsorted_steps(#[any(16usize)] proof: Proof) {
+        let mut sorted = proof.clone();
+        sorted.canonicalize();
+
+        let result = calculate_root_from_reader::<Blake2s256, _>(&proof.to_bytes()[..]);
+        prop_assert_eq!(result.is_ok(), proof == sorted);
+    }
+
+    #[proptest]
+    fn test_rejects_malformed_proofs(#[any(8usize)] proof: Proof, cut: usize, extra: u8) {
+        let mut proof = proof;
+        proof.canonicalize();
+        let bytes = proof.to_bytes();
+
+        let truncated = &bytes[..cut % bytes.len()];
+        prop_assert!(calculate_root_from_reader::<Blake2s256, _>(truncated).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(extra);
+        prop_assert!(calculate_root_from_reader::<Blake2s256, _>(&trailing[..]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_long_steps() {
+        let mut bytes = vec![0, 0, 0, 1];
+        bytes.extend_from_slice(&(MAX_STREAMED_STEP_LEN as u32 + 1).to_be_bytes());
+
+        assert!(matches!(
+            calculate_root_from_reader::<Blake2s256, _>(&bytes[..]),
+            Err(Error::Deserialization(_))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Structured `tracing` events for every hash computed while calculating and verifying roots,
+//! emitted with the `trace-hashing` feature.
+//!
+//! Every step is hashed into a single running hasher, so there is no intermediate hash to
+//! compare with another implementation. Each step event carries the digest of the bytes the step
+//! contributed instead, which is enough to find the first step two implementations encode
+//! differently. Events use the `mutree::hashing` target at the `TRACE` level.
+
+use crate::{prelude::*, TrieHasher};
+
+/// The target of the events, to filter them from the rest of the application.
+pub(crate) const TARGET: &str = "mutree::hashing";
+
+/// Traces the bytes `step`, at `index` in canonical order, contributes to a root of `format`.
+pub(crate) fn step<D: TrieHasher>(index: usize, step: &Step, format: RootFormat) {
+    if !tracing::enabled!(target: TARGET, tracing::Level::TRACE) {
+        return;
+    }
+
+    let (input_len, output) = step.commitment::<D>(format);
+
+    tracing::trace!(
+        target: TARGET,
+        index,
+        variant = variant(step),
+        ?format,
+        input_len,
+        %output,
+        "hashed step",
+    );
+}
+
+/// Traces the root of `steps` steps, hashed in `format`.
+pub(crate) fn root(steps: usize, format: RootFormat, root: Hash) {
+    tracing::trace!(target: TARGET, steps, ?format, %root, "hashed root");
+}
+
+/// Traces the verification of the pair hashed to `key` and `value` against `root`.
+pub(crate) fn verify(key: Hash, value: Hash, contains_pair: bool, root: Hash, verified: bool) {
+    tracing::trace!(
+        target: TARGET,
+        %key,
+        %value,
+        contains_pair,
+        %root,
+        verified,
+        "verified pair",
+    );
+}
+
+fn variant(step: &Step) -> &'static str {
+    match step {
+        Step::Branch { .. } => "branch",
+        Step::Fork { .. } => "fork",
+        Step::Leaf { .. } => "leaf",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        string::{String, ToString},
+        sync::{Arc, Mutex},
+    };
+
+    use blake2::Blake2s256;
+    use tracing::{
+        field::{Field, Visit},
+        span,
+        subscriber::with_default,
+        Event,
+        Metadata,
+        Subscriber,
+    };
+
+    use super::*;
+
+    /// Collects the fields of every event, formatted as `name=value`.
+    #[derive(Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<Vec<String>>>>);
+
+    struct Fields(Vec<String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            self.0.push(format!("{}={value:?}", field.name()));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push(format!("{}={value}", field.name()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == TARGET
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(Vec::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn test_traces_every_step_and_the_root() -> Result<()> {
+        let recorder = Recorder::default();
+
+        let trie = with_default(recorder.clone(), || {
+            let mut trie = Trie::<Blake2s256>::empty();
+            trie.insert(b"a", &b"1"[..])?;
+            trie.insert(b"b", &b"2"[..])?;
+            recorder.0.lock().unwrap().clear();
+
+            trie.refresh_root();
+            assert!(trie.verify(b"a", b"1"));
+            Ok::<_, Error>(trie)
+        })?;
+
+        let events = recorder.0.lock().unwrap();
+        let (root, steps) = events
+            .iter()
+            .position(|fields| fields.contains(&"message=hashed root".to_string()))
+            .map(|at| (&events[at], &events[..at]))
+            .unwrap();
+
+        assert_eq!(steps.len(), trie.proof.len());
+        for (index, (fields, step)) in steps.iter().zip(trie.proof.canonical_steps()).enumerate() {
+            let mut bytes = Vec::new();
+            step.commit(RootFormat::V1, |chunk| bytes.extend_from_slice(chunk));
+
+            assert!(fields.contains(&format!("index={index}")));
+            assert!(fields.contains(&format!("variant={}", variant(step))));
+            assert!(fields.contains(&format!("input_len={}", bytes.len())));
+            assert!(fields.contains(&format!("output={}", Hash::digest::<Blake2s256>(&bytes))));
+        }
+        assert!(root.contains(&format!("root={}", trie.root)));
+        assert!(events
+            .last()
+            .unwrap()
+            .contains(&"verified=true".to_string()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::{prelude::*, TrieHasher};
+
+/// How a [`Trie`] hashes the values of its entries, set with [`Trie::set_value_hasher`].
+///
+/// Tries hash values with a single digest of their bytes by default. Other hashers commit to
+/// values in ways that can later be checked piece by piece, like the root of a Merkle tree of
+/// their chunks, or bind their length. Values hashed differently never verify, so replicas must
+/// agree on the value hasher, like they agree on the digest. It is not part of the encoding of
+/// a Trie.
+///
+/// Implementations hash whole values, with `digest` hashing the concatenation of byte strings
+/// with the digest of the Trie. Values inserted from a reader are read into memory before being
+/// hashed by a value hasher, within the [`EntryLimits`] of the Trie.
+///
+/// # Examples
+///
+/// ```rust
+/// use blake2::Blake2s256;
+/// use mutree::prelude::*;
+///
+/// /// Hashes values twice, standing in for a domain-specific commitment.
+/// #[derive(Debug)]
+/// struct Twice;
+///
+/// impl ValueHasher for Twice {
+///     fn hash_value(&self, value: &[u8], digest: &dyn Fn(&[&[u8]]) -> Hash) -> Hash {
+///         digest(&[digest(&[value]).as_ref()])
+///     }
+/// }
+///
+/// fn main() -> Result<(), Error> {
+///     let mut trie = Trie::<Blake2s256>::empty();
+///     trie.set_value_hasher(Twice);
+///     trie.insert(b"key", &b"value"[..])?;
+///
+///     assert!(trie.verify(b"key", b"value"));
+///     assert!(!Trie::<Blake2s256>::from_proof(trie.proof().clone()).verify(b"key", b"value"));
+///
+///     Ok(())
+/// }
+/// ```
+pub trait ValueHasher: Debug + Send + Sync {
+    /// Hashes `value`, with `digest` hashing the concatenation of byte strings.
+    fn hash_value(&self, value: &[u8], digest: &dyn Fn(&[&[u8]]) -> Hash) -> Hash;
+}
+
+/// Hashes values with a single digest of their bytes, like tries do by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PlainValues;
+
+impl ValueHasher for PlainValues {
+    #[inline]
+    fn hash_value(&self, value: &[u8], digest: &dyn Fn(&[&[u8]]) -> Hash) -> Hash {
+        digest(&[value])
+    }
+}
+
+/// Hashes values after their length, as a big-endian `u64`, so the hash commits to the length
+/// of the value before any of its bytes are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LengthPrefixed;
+
+impl ValueHasher for LengthPrefixed {
+    #[inline]
+    fn hash_value(&self, value: &[u8], digest: &dyn Fn(&[&[u8]]) -> Hash) -> Hash {
+        digest(&[&(value.len() as u64).to_be_bytes(), value])
+    }
+}
+
+/// Hashes values as the root of a binary Merkle tree of their chunks, so a chunk can later be
+/// checked against the hash of the value without the rest of it.
+///
+/// Chunks of `chunk_len` bytes, the last one possibly shorter, are hashed after
+/// [`ChunkedMerkle::LEAF_PREFIX`], and pairs of nodes after [`ChunkedMerkle::NODE_PREFIX`], in
+/// the spirit of RFC 6962. The last node of an odd level is carried to the next one unchanged.
+/// Empty values are a single empty chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkedMerkle {
+    /// The length of the chunks, in bytes
+    pub chunk_len: usize,
+}
+
+impl ChunkedMerkle {
+    /// The first byte of the chunks hashed into the tree.
+    pub const LEAF_PREFIX: u8 = 0x00;
+
+    /// The first byte of the pairs of nodes hashed into the tree.
+    pub const NODE_PREFIX: u8 = 0x01;
+
+    /// The length of the chunks by default, 64 KiB.
+    pub const DEFAULT_CHUNK_LEN: usize = 64 * 1024;
+}
+
+impl Default for ChunkedMerkle {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            chunk_len: Self::DEFAULT_CHUNK_LEN,
+        }
+    }
+}
+
+impl ValueHasher for ChunkedMerkle {
+    #[inline]
+    fn hash_value(&self, value: &[u8], digest: &dyn Fn(&[&[u8]]) -> Hash) -> Hash {
+        let mut level: Vec<Hash> = value
+            .chunks(self.chunk_len.max(1))
+            .map(|chunk| digest(&[&[Self::LEAF_PREFIX], chunk]))
+            .collect();
+        if level.is_empty() {
+            level.push(digest(&[&[Self::LEAF_PREFIX]]));
+        }
+
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => digest(&[&[Self::NODE_PREFIX], left.as_ref(), right.as_ref()]),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        level[0]
+    }
+}
+
+/// Hashes the concatenation of `parts` with `D`, as the `digest` given to value hashers.
+pub(crate) fn digest_parts<D: TrieHasher>(parts: &[&[u8]]) -> Hash {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize32()
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::Blake2s256;
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_plain_values_match_the_digest(#[strategy(vec(any::<u8>(), 0..256))] value: Vec<u8>) {
+        prop_assert_eq!(
+            PlainValues.hash_value(&value, &digest_parts::<Blake2s256>),
+            Hash::digest::<Blake2s256>(&value)
+        );
+    }
+
+    #[proptest]
+    fn test_chunked_merkle_commits_to_every_chunk(
+        #[strategy(vec(any::<u8>(), 1..256))] value: Vec<u8>,
+        #[strategy(1..16usize)] chunk_len: usize,
+        #[strategy(0..#value.len())] flipped: usize,
+    ) {
+        let hasher = ChunkedMerkle { chunk_len };
+        let hash = hasher.hash_value(&value, &digest_parts::<Blake2s256>);
+
+        let mut other = value.clone();
+        other[flipped] ^= 1;
+        prop_assert_ne!(hash, hasher.hash_value(&other, &digest_parts::<Blake2s256>));
+
+        // A single chunk is the root of its tree
+        let whole = ChunkedMerkle {
+            chunk_len: value.len(),
+        };
+        let mut leaf = vec![ChunkedMerkle::LEAF_PREFIX];
+        leaf.extend_from_slice(&value);
+        prop_assert_eq!(
+            whole.hash_value(&value, &digest_parts::<Blake2s256>),
+            Hash::digest::<Blake2s256>(&leaf)
+        );
+    }
+
+    #[test]
+    fn test_chunked_merkle_tree_shape() {
+        let digest = &digest_parts::<Blake2s256>;
+        let leaf = |chunk: &[u8]| digest(&[&[ChunkedMerkle::LEAF_PREFIX], chunk]);
+        let node = |left: Hash, right: Hash| {
+            digest(&[&[ChunkedMerkle::NODE_PREFIX], left.as_ref(), right.as_ref()])
+        };
+
+        let hasher = ChunkedMerkle { chunk_len: 2 };
+        assert_eq!(
+            hasher.hash_value(b"aabbc", digest),
+            node(node(leaf(b"aa"), leaf(b"bb")), leaf(b"c"))
+        );
+        assert_eq!(hasher.hash_value(b"", digest), leaf(b""));
+        assert_ne!(
+            LengthPrefixed.hash_value(b"", digest),
+            PlainValues.hash_value(b"", digest)
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! WebAssembly bindings, for verifying proofs in the browser with the same code as the backend.
+//!
+//! The bindings fix the digest to Blake2b-256, the one used by the on-chain Merkle Patricia
+//! Forestry validators. Proofs cross the boundary in their byte encoding, as `Uint8Array`s.
+//!
+//! ```js
+//! import { Trie, verify } from "mutree";
+//!
+//! const trie = new Trie();
+//! trie.insert(key, value);
+//!
+//! verify(trie.root(), key, value, trie.proof());
+//! ```
+
+// Exported functions are never inlined, and `#[inline]` on them is a warning on wasm targets.
+#![allow(clippy::missing_inline_in_public_items)]
+
+use alloc::vec::Vec;
+
+use blake2::{digest::consts::U32, Blake2b};
+use wasm_bindgen::prelude::*;
+
+use crate::prelude::*;
+
+/// The digest used by the bindings.
+pub type Blake2b256 = Blake2b<U32>;
+
+/// Checks that `proof` commits to `root`, and proves that `key` is set to `value`.
+///
+/// # Errors
+///
+/// Fails if `root` is not 32 bytes long, or if `proof` can't be decoded.
+#[wasm_bindgen]
+pub fn verify(root: &[u8], key: &[u8], value: &[u8], proof: &[u8]) -> Result<bool, JsError> {
+    Ok(verify_bytes(root, key, value, proof, VerifyMode::Lenient)?)
+}
+
+/// Checks like [`verify`], also rejecting proofs that repeat a step or hold more than one leaf
+/// for `key`.
+///
+/// # Errors
+///
+/// Fails if `root` is not 32 bytes long, or if `proof` can't be decoded.
+#[wasm_bindgen(js_name = verifyStrict)]
+pub fn verify_strict(root: &[u8], key: &[u8], value: &[u8], proof: &[u8]) -> Result<bool, JsError> {
+    Ok(verify_bytes(root, key, value, proof, VerifyMode::Strict)?)
+}
+
+fn verify_bytes(
+    root: &[u8],
+    key: &[u8],
+    value: &[u8],
+    proof: &[u8],
+    mode: VerifyMode,
+) -> Result<bool> {
+    let root = Hash::from_bytes(root)?;
+    let trie = Trie::<Blake2b256>::from_proof(Proof::from_bytes(proof)?);
+
+    Ok(trie.root() == root && trie.verify_with(key, value, mode))
+}
+
+/// A Trie handle for JavaScript.
+#[wasm_bindgen(js_name = Trie)]
+#[derive(Debug, Clone, Default)]
+pub struct WasmTrie {
+    inner: Trie<Blake2b256>,
+}
+
+#[wasm_bindgen(js_class = Trie)]
+impl WasmTrie {
+    /// Creates an empty Trie.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes a Trie from the bytes returned by [`WasmTrie::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if the bytes are not a valid proof.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmTrie, JsError> {
+        Ok(Self {
+            inner: Trie::from_bytes(bytes)?,
+        })
+    }
+
+    /// Encodes the Trie, as its proof.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    /// Inserts a key-value pair, returning the hash of the value.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key is empty.
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.inner.insert(key, value)?.to_bytes_vec())
+    }
+
+    /// Checks if the Trie proves that `key` is set to `value`.
+    pub fn verify(&self, key: &[u8], value: &[u8]) -> bool {
+        self.inner.verify(key, value)
+    }
+
+    /// Merges another Trie into this one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the merge itself fails.
+    pub fn merge(&mut self, other: &WasmTrie) -> Result<(), JsError> {
+        Ok(self.inner.merge(&other.inner)?)
+    }
+
+    /// The root of the Trie.
+    pub fn root(&self) -> Vec<u8> {
+        self.inner.root().to_bytes_vec()
+    }
+
+    /// The proof of the Trie, in the format expected by [`verify`].
+    pub fn proof(&self) -> Vec<u8> {
+        self.inner.proof().to_bytes()
+    }
+
+    /// Checks if the Trie has no entries.
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[proptest]
+    fn test_verify_matches_trie(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+        other: Vec<u8>,
+    ) {
+        let mut trie = WasmTrie::new();
+        trie.inner.insert(&key, &value[..])?;
+
+        prop_assert!(verify_bytes(
+            &trie.root(),
+            &key,
+            &value,
+            &trie.proof(),
+            VerifyMode::Lenient
+        )?);
+        prop_assert_eq!(
+            verify_bytes(
+                &trie.root(),
+                &key,
+                &other,
+                &trie.proof(),
+                VerifyMode::Lenient
+            )?,
+            value == other
+        );
+    }
+
+    #[proptest]
+    fn test_verify_rejects_other_roots(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+        root: Hash,
+    ) {
+        let mut trie = WasmTrie::new();
+        trie.inner.insert(&key, &value[..])?;
+        prop_assume!(root != trie.inner.root());
+
+        prop_assert!(!verify_bytes(
+            root.as_ref(),
+            &key,
+            &value,
+            &trie.proof(),
+            VerifyMode::Lenient
+        )?);
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_input() {
+        assert!(verify_bytes(
+            &[0; 31],
+            b"key",
+            b"value",
+            &Proof::new().to_bytes(),
+            VerifyMode::Lenient
+        )
+        .is_err());
+        assert!(verify_bytes(&[0; 32], b"key", b"value", &[1, 2, 3], VerifyMode::Lenient).is_err());
+    }
+
+    #[proptest]
+    fn test_verify_strict_rejects_conflicting_leaves(
+        #[strategy(vec(any::<u8>(), 1..32))] key: Vec<u8>,
+        value: Vec<u8>,
+        other: Vec<u8>,
+    ) {
+        prop_assume!(value != other);
+
+        let mut trie = WasmTrie::new();
+        trie.inner.insert(&key, &value[..])?;
+        prop_assert!(verify_bytes(
+            &trie.root(),
+            &key,
+            &value,
+            &trie.proof(),
+            VerifyMode::Strict
+        )?);
+
+        let mut conflicting = trie.inner.clone();
+        conflicting.proof_mut().push(Step::Leaf {
+            skip: 0,
+            key: Hash::digest::<Blake2b256>(&key),
+            value: Hash::digest::<Blake2b256>(&other),
+            timestamp: None,
+        });
+        conflicting.refresh_root();
+        let proof = conflicting.proof().to_bytes();
+
+        prop_assert!(verify_bytes(
+            conflicting.root().as_ref(),
+            &key,
+            &value,
+            &proof,
+            VerifyMode::Lenient
+        )?);
+        prop_assert!(!verify_bytes(
+            conflicting.root().as_ref(),
+            &key,
+            &value,
+            &proof,
+            VerifyMode::Strict
+        )?);
+    }
+}
@@ -0,0 +1,198 @@
+//! Versioning of the byte representations exchanged between replicas.
+//!
+//! The byte representations of [`Step`], [`Proof`] and, with the `replication` feature,
+//! `StateBundle`, form the wire format of the crate. Its version is bumped whenever one of them
+//! changes in a way older decoders can't read, and decoders keep supporting the versions listed
+//! by [`supported_versions`], so clusters can be upgraded one node at a time:
+//!
+//! 1. Peers exchange their [`supported_versions`] when connecting, and each side calls
+//!    [`negotiate`] to settle on the newest version both understand.
+//! 2. Messages are encoded with [`WireFormat::to_wire`] at the negotiated version, which prefixes
+//!    them with the version they use.
+//! 3. [`WireFormat::from_wire`] reads the prefix and decodes the rest accordingly, failing with
+//!    [`Error::UnsupportedWireVersion`] instead of misinterpreting the payload.
+//!
+//! # Versions
+//!
+//! - `1`: the initial format, below. Integers are big-endian on every platform.
+//!
+//! | Value       | Encoding                                                                    |
+//! |-------------|-----------------------------------------------------------------------------|
+//! | `Hash`      | its 32 bytes                                                                |
+//! | `Timestamp` | the seconds as a `u64`, then the nanoseconds as a `u32`                     |
+//! | `Neighbor`  | the nibble as a byte, the prefix, then the root                             |
+//! | Branch      | `0x00`, the skip as a `u64`, then its 4 neighbors                           |
+//! | Fork        | `0x01`, the skip as a `u64`, then the neighbor                              |
+//! | Leaf        | `0x02`, the skip as a `u64`, the key, the value, then the timestamp if any  |
+//! | `Proof`     | the step count as a `u32`, then each step prefixed by its length as a `u32` |
+//! | Bundle      | the root, then the proof                                                    |
+//!
+//! The messages of [`WireFormat::to_wire`] prefix these with the version as a `u16`. The
+//! `testing` module checks them against fixtures written from this table.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use blake2::Blake2s256;
+//! use mutree::{
+//!     prelude::*,
+//!     wire::{negotiate, WireFormat, WIRE_VERSION},
+//! };
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut trie = Trie::<Blake2s256>::empty();
+//!     trie.insert(b"key", &b"value"[..])?;
+//!
+//!     // A peer running a newer release, that also speaks our version.
+//!     let version = negotiate(&[WIRE_VERSION, WIRE_VERSION + 1]).unwrap();
+//!     assert_eq!(version, WIRE_VERSION);
+//!
+//!     let bytes = trie.proof().to_wire(version)?;
+//!     assert_eq!(Proof::from_wire(&bytes)?, *trie.proof());
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use alloc::{string::ToString, vec::Vec};
+
+use crate::prelude::*;
+
+/// The version of the wire format produced by this release.
+pub const WIRE_VERSION: u16 = 1;
+
+/// The versions of the wire format this release can decode, oldest first.
+const SUPPORTED_VERSIONS: &[u16] = &[1];
+
+/// The versions of the wire format this release can encode and decode, oldest first.
+#[inline]
+pub fn supported_versions() -> &'static [u16] {
+    SUPPORTED_VERSIONS
+}
+
+/// Picks the newest version of the wire format supported by both this release and a peer, or
+/// `None` if they have none in common.
+#[inline]
+pub fn negotiate(peer_versions: &[u16]) -> Option<u16> {
+    SUPPORTED_VERSIONS
+        .iter()
+        .rev()
+        .find(|version| peer_versions.contains(version))
+        .copied()
+}
+
+/// A type exchanged between replicas, encoded with a version prefix.
+///
+/// Versioned messages are a big-endian `u16` version, followed by the byte representation of
+/// the value at that version.
+pub trait WireFormat: ToBytes + FromBytes {
+    /// Encodes the value at `version`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedWireVersion`] if this release can't encode `version`.
+    #[inline]
+    fn to_wire(&self, version: u16) -> Result<Vec<u8>> {
+        check(version)?;
+
+        let mut bytes = version.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.to_bytes().as_ref());
+        Ok(bytes)
+    }
+
+    /// Decodes a value encoded by [`WireFormat::to_wire`], at any supported version.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedWireVersion`] if the value was encoded at a version this
+    /// release can't decode, and [`Error::Deserialization`] if it is malformed.
+    #[inline]
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        Self::from_wire_with(bytes, &DecodeContext::UNBOUNDED)
+    }
+
+    /// Decodes a value encoded by [`WireFormat::to_wire`] like [`WireFormat::from_wire`],
+    /// rejecting payloads past the bounds of `context`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`WireFormat::from_wire`], and [`Error::Deserialization`] if the
+    /// payload exceeds a bound of `context`.
+    #[inline]
+    fn from_wire_with(bytes: &[u8], context: &DecodeContext) -> Result<Self> {
+        let (version, payload) = bytes
+            .split_first_chunk::<2>()
+            .ok_or_else(|| Error::Deserialization("Missing wire version".to_string()))?;
+        check(u16::from_be_bytes(*version))?;
+
+        Self::from_bytes_with(payload, context)
+    }
+}
+
+fn check(version: u16) -> Result<()> {
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        return Err(Error::UnsupportedWireVersion(version));
+    }
+
+    Ok(())
+}
+
+impl WireFormat for Step {}
+
+impl WireFormat for Proof {}
+
+#[cfg(feature = "replication")]
+impl WireFormat for crate::replication::StateBundle {}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use test_strategy::proptest;
+
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&[WIRE_VERSION]), Some(WIRE_VERSION));
+        assert_eq!(negotiate(&[0, WIRE_VERSION, u16::MAX]), Some(WIRE_VERSION));
+        assert_eq!(negotiate(&[u16::MAX]), None);
+        assert_eq!(negotiate(&[]), None);
+    }
+
+    #[test]
+    fn test_current_version_is_supported() {
+        assert_eq!(supported_versions().last(), Some(&WIRE_VERSION));
+    }
+
+    #[proptest]
+    fn test_roundtrip(#[any(8usize)] proof: Proof) {
+        prop_assert_eq!(Proof::from_wire(&proof.to_wire(WIRE_VERSION)?)?, proof);
+    }
+
+    #[proptest]
+    fn test_version_prefix(#[any(8usize)] proof: Proof) {
+        let bytes = proof.to_wire(WIRE_VERSION)?;
+
+        prop_assert_eq!(&bytes[..2], &WIRE_VERSION.to_be_bytes()[..]);
+        prop_assert_eq!(&bytes[2..], &proof.to_bytes()[..]);
+    }
+
+    #[test]
+    fn test_rejects_unsupported_versions() {
+        let proof = Proof::new();
+
+        assert!(matches!(
+            proof.to_wire(u16::MAX),
+            Err(Error::UnsupportedWireVersion(u16::MAX))
+        ));
+
+        let mut bytes = proof.to_wire(WIRE_VERSION).unwrap();
+        bytes[..2].copy_from_slice(&u16::MAX.to_be_bytes());
+        assert!(matches!(
+            Proof::from_wire(&bytes),
+            Err(Error::UnsupportedWireVersion(u16::MAX))
+        ));
+
+        assert!(Proof::from_wire(&[0]).is_err());
+    }
+}